@@ -1,7 +1,7 @@
 use tline::prelude::*;
 use tline::fdtd::*;
 
-use std::f32::consts::PI;
+use tline::consts::PI;
 
 fn main() {
     let capacitance = 400e-12; // [F / m]
@@ -19,7 +19,14 @@ fn main() {
         inductance_fn: |_| inductance / 2.0,
         kinetic_inductance_fn: |_| inductance / 2.0,
         critical_current_fn: |_| critical_current,
-    });
+        resistance_fn: |_| 0.0,
+        nonlinear_resistance_fn: |_| 0.0,
+        flux_flow_resistance_fn: |_| 0.0,
+        bias_current_fn: |_| 0.0,
+        max_iterations: 10,
+        tolerance: 1e-5,
+    })
+    .unwrap();
 
     let sim_params = tline.calculate_simulation_parameters(2.0);
 
@@ -27,8 +34,8 @@ fn main() {
         solver: FdtdSolver::new(FdtdSolverDescriptor {
             tline,
             source: Box::new(components::MatchedVSource {
-                source_fn: |t| {
-                    f32::sin(2.0*PI * 4e8 * t)
+                source_fn: |t: Float| {
+                    Float::sin(2.0*PI * 4e8 * t)
                 },
                 inductance,
                 capacitance,
@@ -66,7 +73,16 @@ fn main() {
             filename: "data/ki_tline.h5",
             save_type: SaveType::End,
             overwrite: true,
+            save_warnings: false,
+            save_energy: false,
+            save_voltages: true,
+            save_currents: true,
+            fallback_filename: None,
+            format: SaveFormat::Hdf5,
+            swmr: false,
         }),
+        checkpoint_settings: None,
+        steady_state: None,
     })
     .unwrap();
 
@@ -79,7 +95,16 @@ fn main() {
             filename: "data/ki_tline.h5",
             save_type: SaveType::Full,
             overwrite: false,
+            save_warnings: true,
+            save_energy: false,
+            save_voltages: true,
+            save_currents: true,
+            fallback_filename: None,
+            format: SaveFormat::Hdf5,
+            swmr: false,
         }),
+        checkpoint_settings: None,
+        steady_state: None,
     })
     .unwrap();
 }
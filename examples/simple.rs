@@ -1,7 +1,7 @@
 use tline::prelude::*;
 use tline::fdtd::*;
 
-use std::f32::consts::PI;
+use tline::consts::PI;
 
 fn main() {
     let capacitance = 400e-12; // [F / m]
@@ -27,8 +27,8 @@ fn main() {
         solver: FdtdSolver::new(FdtdSolverDescriptor {
             tline,
             source: Box::new(components::MatchedVSource {
-                source_fn: |t| {
-                    f32::sin(2.0*PI *4e8 * t)
+                source_fn: |t: Float| {
+                    Float::sin(2.0*PI *4e8 * t)
                 },
                 inductance,
                 capacitance,
@@ -65,7 +65,16 @@ fn main() {
             filename: "data/simple_tline.h5",
             save_type: SaveType::End,
             overwrite: true,
+            save_warnings: false,
+            save_energy: false,
+            save_voltages: true,
+            save_currents: true,
+            fallback_filename: None,
+            format: SaveFormat::Hdf5,
+            swmr: false,
         }),
+        checkpoint_settings: None,
+        steady_state: None,
     })
     .unwrap();
 
@@ -78,7 +87,16 @@ fn main() {
             filename: "data/simple_tline.h5",
             save_type: SaveType::Full,
             overwrite: false,
+            save_warnings: false,
+            save_energy: false,
+            save_voltages: true,
+            save_currents: true,
+            fallback_filename: None,
+            format: SaveFormat::Hdf5,
+            swmr: false,
         }),
+        checkpoint_settings: None,
+        steady_state: None,
     })
     .unwrap();
 }
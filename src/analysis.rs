@@ -0,0 +1,14 @@
+//! Post-processing of simulation results.
+
+pub mod abcd;
+pub mod dispersion;
+pub mod distortion;
+pub mod ensemble;
+pub mod eye;
+pub mod parametric;
+pub mod quanta;
+pub mod ripple;
+pub mod shock;
+pub mod smith;
+pub mod spectrum;
+pub mod tdr;
@@ -0,0 +1,82 @@
+//! Frequency domain responses of linear lines, from cascaded ABCD matrices.
+
+use std::f64::consts::PI;
+
+use num_complex::Complex;
+
+use crate::{Float, FrequencyResponse};
+use crate::fdtd::components::LinearLineDescriptor;
+
+/// The ABCD matrix of a two port, as `[[A, B], [C, D]]`.
+type Abcd = [[Complex<f64>; 2]; 2];
+
+/// Calculates the transmission and reflection of the line described by `line` at each of
+/// `frequencies`, between ports of `reference_impedance`.
+///
+/// Each cell of the line is a uniform section with the parameters at its center, the same ones
+/// `LinearLine::new` would use, and the sections are cascaded exactly. This gives the response of
+/// the continuous line, so time domain runs of the same line should approach it as their cells get
+/// shorter, and differences that don't shrink point at something other than numerical dispersion.
+pub fn abcd_response<Fc, Fl, Fr, Fg>(
+    line: &LinearLineDescriptor<Fc, Fl, Fr, Fg>,
+    frequencies: &[Float],
+    reference_impedance: Float,
+) -> FrequencyResponse
+where
+    Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+    Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float,
+{
+    let delta_z = line.length / (line.npoints as Float);
+    let cells = (0..line.npoints)
+        .map(|n| {
+            let z = (n as Float + 0.5)*delta_z;
+            let values = [
+                (line.capacitance_fn)(z), (line.inductance_fn)(z),
+                (line.resistance_fn)(z), (line.conductance_fn)(z),
+            ];
+            values.map(|value| (value*delta_z) as f64)
+        })
+        .collect::<Vec<_>>();
+
+    let z0 = reference_impedance as f64;
+    let mut response = FrequencyResponse {
+        frequencies: frequencies.to_vec(),
+        transmission: Vec::with_capacity(frequencies.len()),
+        reflection: Vec::with_capacity(frequencies.len()),
+    };
+    for &frequency in frequencies {
+        let omega = 2.0*PI*(frequency as f64);
+        let matrix = cells.iter()
+            .map(|&[cap, ind, res, cond]| {
+                section(Complex::new(res, omega*ind), Complex::new(cond, omega*cap))
+            })
+            .fold(identity(), |total, section| multiply(&total, &section));
+
+        let [[a, b], [c, d]] = matrix;
+        let denominator = a + b/z0 + c*z0 + d;
+        let to_float = |value: Complex<f64>| Complex::new(value.re as Float, value.im as Float);
+        response.transmission.push(to_float(Complex::new(2.0, 0.0) / denominator));
+        response.reflection.push(to_float((a + b/z0 - c*z0 - d) / denominator));
+    }
+
+    response
+}
+
+/// The ABCD matrix of a uniform section with a total series impedance `series` and a total shunt
+/// admittance `shunt`.
+fn section(series: Complex<f64>, shunt: Complex<f64>) -> Abcd {
+    let x = (series*shunt).sqrt();
+    // `sinh(x) / x`, which goes to one for short or lossless DC sections
+    let sinhc = if x.norm() < 1e-8 { Complex::new(1.0, 0.0) } else { x.sinh() / x };
+
+    [[x.cosh(), series*sinhc], [shunt*sinhc, x.cosh()]]
+}
+
+fn identity() -> Abcd {
+    let (one, zero) = (Complex::new(1.0, 0.0), Complex::new(0.0, 0.0));
+    [[one, zero], [zero, one]]
+}
+
+fn multiply(left: &Abcd, right: &Abcd) -> Abcd {
+    [0, 1].map(|row| [0, 1].map(|col| left[row][0]*right[0][col] + left[row][1]*right[1][col]))
+}
@@ -0,0 +1,126 @@
+//! Group delay and dispersion of a line from a broadband pulse.
+
+use std::f64::consts::PI;
+
+use num_complex::Complex;
+
+use crate::{Error, Float, SimulationParameters};
+use crate::analysis::spectrum::fft;
+
+/// Describes how dispersion should be extracted.
+pub struct DispersionDescriptor {
+    /// The lowest frequency of the band of interest.
+    pub min_frequency: Float,
+    /// The highest frequency of the band of interest.
+    pub max_frequency: Float,
+    /// The distance between the input and output points, used to convert delays into velocities.
+    pub length: Float,
+}
+
+/// The dispersion of a line within a band.
+#[derive(Clone, Debug)]
+pub struct Dispersion {
+    /// The frequencies within the band of interest.
+    pub frequencies: Vec<Float>,
+    /// The transmission at each frequency.
+    pub transmission: Vec<Complex<Float>>,
+    /// The unwrapped phase of the transmission at each frequency, in radians.
+    pub phase: Vec<Float>,
+    /// The phase delay at each frequency.
+    pub phase_delay: Vec<Float>,
+    /// The group delay at each frequency.
+    pub group_delay: Vec<Float>,
+    /// The phase velocity at each frequency.
+    pub phase_velocity: Vec<Float>,
+    /// The group velocity at each frequency.
+    pub group_velocity: Vec<Float>,
+}
+
+/// Extracts the phase and group delay between `input` and `output` waveforms of a pulse run.
+///
+/// The waveforms are usually the `start` and `end` port voltages, and the pulse should cover the
+/// whole band and have died out by the end of both. The phase is unwrapped across the band, and its
+/// offset is chosen so the phase delay matches the group delay at the lowest frequency, which
+/// assumes the line is nearly dispersionless there.
+pub fn dispersion(
+    input: ndarray::ArrayView1<Float>,
+    output: ndarray::ArrayView1<Float>,
+    desc: DispersionDescriptor,
+    sim_params: &SimulationParameters,
+) -> Result<Dispersion, Error> {
+    if output.len() != input.len() {
+        return Err(Error::BadAnalysisInput {
+            array_name: "Output".to_string(),
+            input_length: output.len(),
+            expected_length: input.len(),
+        })
+    }
+    let n = input.len();
+    let to_complex = |w: ndarray::ArrayView1<Float>| {
+        w.iter().map(|&w| Complex::new(w, 0.0)).collect::<Vec<_>>()
+    };
+    let x = fft(to_complex(input));
+    let y = fft(to_complex(output));
+
+    let delta_f = ((n as Float) * sim_params.delta_t).recip();
+    let band = (1..=(n/2))
+        .filter(|&k| {
+            let f = (k as Float) * delta_f;
+            f >= desc.min_frequency && f <= desc.max_frequency
+        })
+        .collect::<Vec<_>>();
+    let frequencies = band.iter().map(|&k| (k as f64) * (delta_f as f64)).collect::<Vec<_>>();
+    let transmission = band.iter().map(|&k| y[k] / x[k]).collect::<Vec<_>>();
+
+    // unwrap the phase lag, so it increases with frequency on a causal line
+    let mut phase = Vec::<f64>::with_capacity(band.len());
+    for value in &transmission {
+        let wrapped = -(value.arg() as f64);
+        let unwrapped = match phase.last() {
+            Some(&previous) => previous + (wrapped - previous + PI).rem_euclid(2.0*PI) - PI,
+            None => wrapped,
+        };
+        phase.push(unwrapped);
+    }
+
+    // group delay from the phase slope, with one sided differences at the band edges
+    let group_delay = (0..phase.len())
+        .map(|i: usize| {
+            let (low, high) = (i.saturating_sub(1), (i + 1).min(phase.len() - 1));
+            if high > low {
+                (phase[high] - phase[low]) / (2.0*PI*(frequencies[high] - frequencies[low]))
+            } else {
+                0.0
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // remove the unknown multiple of 2π by matching the phase and group delays at the band start
+    if let (Some(&first_phase), Some(&first_delay), Some(&first_frequency)) =
+        (phase.first(), group_delay.first(), frequencies.first())
+    {
+        let turns = ((2.0*PI*first_frequency*first_delay - first_phase) / (2.0*PI)).round();
+        for value in &mut phase {
+            *value += 2.0*PI*turns;
+        }
+    }
+    let phase_delay = phase.iter()
+        .zip(&frequencies)
+        .map(|(&phase, &frequency)| phase / (2.0*PI*frequency))
+        .collect::<Vec<_>>();
+
+    let length = desc.length as f64;
+    let to_float = |values: &[f64]| values.iter().map(|&value| value as Float).collect::<Vec<_>>();
+    let to_velocities = |delays: &[f64]| {
+        delays.iter().map(|&delay| (length / delay) as Float).collect::<Vec<_>>()
+    };
+    Ok(Dispersion {
+        frequencies: to_float(&frequencies),
+        transmission,
+        phase: to_float(&phase),
+        phase_velocity: to_velocities(&phase_delay),
+        group_velocity: to_velocities(&group_delay),
+        phase_delay: to_float(&phase_delay),
+        group_delay: to_float(&group_delay),
+    })
+}
@@ -0,0 +1,106 @@
+//! Harmonic and intermodulation distortion under a two-tone drive.
+
+use crate::{Float, SimulationParameters};
+use crate::analysis::spectrum::phasor;
+
+/// Describes a two-tone distortion measurement.
+pub struct DistortionDescriptor {
+    /// The frequencies of the two drive tones.
+    pub tones: [Float; 2],
+    /// The highest harmonic of each tone to measure.
+    pub max_harmonic: usize,
+    /// The impedance that voltages are converted into powers with.
+    pub impedance: Float,
+}
+
+/// The power at a harmonic of a drive tone.
+#[derive(Copy, Clone, Debug)]
+pub struct Harmonic {
+    /// The index of the drive tone.
+    pub tone: usize,
+    /// The multiple of the drive tone frequency.
+    pub order: usize,
+    /// The frequency of the harmonic.
+    pub frequency: Float,
+    /// The output power at the harmonic, in dBm.
+    pub power: Float,
+}
+
+/// The distortion of a two-tone drive between the input and output of a line.
+#[derive(Clone, Debug)]
+pub struct Distortion {
+    /// The input power of each tone, in dBm.
+    pub input_powers: [Float; 2],
+    /// The output power of each tone, in dBm.
+    pub output_powers: [Float; 2],
+    /// The gain of each tone, in dB.
+    pub gains: [Float; 2],
+    /// The harmonics of each tone from the second up to the highest measured.
+    pub harmonics: Vec<Harmonic>,
+    /// The frequencies of the third order products `2f1 - f2` and `2f2 - f1`.
+    pub im3_frequencies: [Float; 2],
+    /// The output power of each third order product, in dBm.
+    pub im3_powers: [Float; 2],
+    /// The mean third order product power relative to the mean output tone power, in dBc.
+    pub im3: Float,
+    /// The output referred third order intercept point, in dBm.
+    pub oip3: Float,
+    /// The input referred third order intercept point, in dBm.
+    pub iip3: Float,
+}
+
+/// Converts the amplitude of a voltage tone into its power in dBm across `impedance`.
+fn dbm(amplitude: Float, impedance: Float) -> Float {
+    10.0 * (amplitude*amplitude / (2.0*impedance) / 1e-3).log10()
+}
+
+/// Measures harmonics and third order intermodulation from `input` and `output` voltages.
+///
+/// The waveforms are usually the `start` and `end` port voltages of a run driven by two equal
+/// tones. They should span an integer number of periods of every measured frequency and only
+/// hold steady state data. The intercept points are extrapolated from a single drive level, so
+/// the drive should be weak enough for the products to follow their small signal slopes.
+pub fn distortion(
+    input: ndarray::ArrayView1<Float>,
+    output: ndarray::ArrayView1<Float>,
+    desc: DistortionDescriptor,
+    sim_params: &SimulationParameters,
+) -> Distortion {
+    let power = |waveform: ndarray::ArrayView1<Float>, frequency| {
+        dbm(phasor(waveform, frequency, sim_params).norm(), desc.impedance)
+    };
+    let [f1, f2] = desc.tones;
+
+    let input_powers = desc.tones.map(|frequency| power(input, frequency));
+    let output_powers = desc.tones.map(|frequency| power(output, frequency));
+    let gains = [output_powers[0] - input_powers[0], output_powers[1] - input_powers[1]];
+
+    let harmonics = (0..2)
+        .flat_map(|tone| (2..=desc.max_harmonic).map(move |order| (tone, order)))
+        .map(|(tone, order)| {
+            let frequency = (order as Float)*desc.tones[tone];
+            Harmonic { tone, order, frequency, power: power(output, frequency) }
+        })
+        .collect();
+
+    let im3_frequencies = [2.0*f1 - f2, 2.0*f2 - f1];
+    let im3_powers = im3_frequencies.map(|frequency| power(output, frequency));
+
+    // third order products rise 3 dB for each 1 dB of drive, so the gap to the tones closes 2 dB
+    // for each dB and they meet half the separation above the tone power
+    let mean = |values: [Float; 2]| (values[0] + values[1]) / 2.0;
+    let im3 = mean(im3_powers) - mean(output_powers);
+    let oip3 = mean(output_powers) - im3/2.0;
+
+    Distortion {
+        input_powers,
+        output_powers,
+        gains,
+        harmonics,
+        im3_frequencies,
+        im3_powers,
+        im3,
+        oip3,
+        iip3: oip3 - mean(gains),
+    }
+}
@@ -0,0 +1,135 @@
+//! Statistics across the output files of many runs.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::{Error, Float};
+
+/// The mean and sample variance of a dataset across an ensemble of runs.
+#[derive(Clone, Debug)]
+pub struct DatasetStatistics {
+    /// The mean of each element.
+    pub mean: ndarray::ArrayD<Float>,
+    /// The sample variance of each element.
+    pub variance: ndarray::ArrayD<Float>,
+}
+
+/// Statistics of datasets across an ensemble of runs.
+#[derive(Clone, Debug)]
+pub struct EnsembleSummary {
+    /// The number of runs averaged.
+    pub nruns: usize,
+    /// The statistics of each dataset, by path.
+    pub datasets: BTreeMap<String, DatasetStatistics>,
+    /// The time step of the first run, if it was saved.
+    pub time_step: Option<Float>,
+    /// The length step of the first run, if it was saved.
+    pub length_step: Option<Float>,
+}
+
+/// Averages `datasets`, such as `"end/voltages"`, across the output files of many runs.
+///
+/// Each dataset must have the same shape in every file. Files are read one at a time, so the
+/// ensemble can be larger than memory.
+pub fn ensemble_average<P: AsRef<Path>>(
+    filenames: &[P],
+    datasets: &[&str],
+) -> Result<EnsembleSummary, Error> {
+    let mut summary = EnsembleSummary {
+        nruns: 0,
+        datasets: BTreeMap::new(),
+        time_step: None,
+        length_step: None,
+    };
+    // running mean and sum of squared deviations of each dataset
+    let mut accumulators = BTreeMap::<&str, (ndarray::ArrayD<f64>, ndarray::ArrayD<f64>)>::new();
+
+    for filename in filenames {
+        let file = hdf5::File::open(filename)?;
+        if summary.nruns == 0 {
+            summary.time_step = file.attr("time_step").and_then(|attr| attr.read_scalar()).ok();
+            summary.length_step = file.attr("length_step").and_then(|attr| attr.read_scalar()).ok();
+        }
+        summary.nruns += 1;
+        let count = summary.nruns as f64;
+
+        for &path in datasets {
+            let values = file.dataset(path)?.read::<Float, ndarray::IxDyn>()?.mapv(|x| x as f64);
+            let (mean, m2) = accumulators.entry(path).or_insert_with(|| (
+                ndarray::ArrayD::zeros(values.shape()),
+                ndarray::ArrayD::zeros(values.shape()),
+            ));
+            if values.shape() != mean.shape() {
+                return Err(Error::BadAnalysisInput {
+                    array_name: path.to_string(),
+                    input_length: values.len(),
+                    expected_length: mean.len(),
+                })
+            }
+
+            ndarray::Zip::from(mean)
+                .and(m2)
+                .and(&values)
+                .for_each(|mean, m2, &x| {
+                    let delta = x - *mean;
+                    *mean += delta / count;
+                    *m2 += delta * (x - *mean);
+                });
+        }
+    }
+
+    let denominator = (summary.nruns.max(2) - 1) as f64;
+    summary.datasets = accumulators.into_iter()
+        .map(|(path, (mean, m2))| (path.to_string(), DatasetStatistics {
+            mean: mean.mapv(|x| x as Float),
+            variance: m2.mapv(|x| (x / denominator) as Float),
+        }))
+        .collect();
+
+    Ok(summary)
+}
+
+impl EnsembleSummary {
+    /// Writes the summary to an HDF5 file, with `mean` and `variance` datasets for each path.
+    pub fn write<P: AsRef<Path>>(&self, filename: P) -> Result<(), Error> {
+        let file = hdf5::File::create(filename)?;
+        file.new_attr::<u64>()
+            .shape(hdf5::Extents::Scalar)
+            .create("nruns")?
+            .write_scalar(&(self.nruns as u64))?;
+        for (name, value) in [("time_step", self.time_step), ("length_step", self.length_step)] {
+            if let Some(value) = value {
+                file.new_attr::<Float>()
+                    .shape(hdf5::Extents::Scalar)
+                    .create(name)?
+                    .write_scalar(&value)?;
+            }
+        }
+
+        for (path, statistics) in &self.datasets {
+            let mut group = file.group("/")?;
+            for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+                group = match group.group(segment) {
+                    Ok(existing) => existing,
+                    Err(_) => group.create_group(segment)?,
+                };
+            }
+            for (name, values) in [("mean", &statistics.mean), ("variance", &statistics.variance)] {
+                match *values.shape() {
+                    [rows] => group.new_dataset::<Float>().shape(rows).create(name)?.write(values)?,
+                    [rows, cols] => {
+                        group.new_dataset::<Float>().shape((rows, cols)).create(name)?.write(values)?
+                    },
+                    _ => return Err(Error::BadAnalysisInput {
+                        array_name: path.clone(),
+                        input_length: values.ndim(),
+                        expected_length: 2,
+                    }),
+                }
+            }
+        }
+        file.close()?;
+
+        Ok(())
+    }
+}
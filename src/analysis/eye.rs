@@ -0,0 +1,150 @@
+//! Eye diagrams of digital signals.
+
+use crate::{Float, SimulationParameters};
+
+/// Describes how an eye diagram should be folded and binned.
+pub struct EyeDescriptor {
+    /// The duration of each symbol.
+    pub symbol_period: Float,
+    /// The time of the first symbol boundary, before which the waveform is ignored.
+    pub offset: Float,
+    /// The number of time bins across one symbol period.
+    pub time_bins: usize,
+    /// The number of voltage bins between the lowest and highest folded voltages.
+    pub voltage_bins: usize,
+}
+
+/// An eye diagram folded from a waveform.
+#[derive(Clone, Debug)]
+pub struct EyeDiagram {
+    /// The time of the center of each time bin, relative to the symbol boundary.
+    pub times: Vec<Float>,
+    /// The voltage of the center of each voltage bin.
+    pub voltages: Vec<Float>,
+    /// The number of samples in each bin, indexed as `[time, voltage]`.
+    pub histogram: ndarray::Array2<u64>,
+    /// The decision threshold, halfway between the lowest and highest folded voltages.
+    pub threshold: Float,
+    /// The vertical opening of the eye in each time bin, or zero where edges cross the threshold.
+    pub openings: Vec<Float>,
+    /// The vertical opening of the eye at `eye_center`.
+    pub eye_height: Float,
+    /// The middle of the eye, relative to the symbol boundary.
+    pub eye_center: Float,
+    /// The largest interval between threshold crossings within a symbol period.
+    pub eye_width: Float,
+}
+
+/// Folds `waveform` by the symbol period into an eye diagram and measures its opening.
+///
+/// The eye spans the largest interval between folded threshold crossings, so its width shrinks
+/// with jitter. Samples at or above the threshold are treated as ones and the rest as zeros, so
+/// the opening of each time bin is the gap between its lowest one and highest zero. The waveform
+/// should be many symbols long, and `offset` should skip any start up transient.
+pub fn eye_diagram(
+    waveform: ndarray::ArrayView1<Float>,
+    desc: EyeDescriptor,
+    sim_params: &SimulationParameters,
+) -> EyeDiagram {
+    let time_bins = desc.time_bins.max(1);
+    let voltage_bins = desc.voltage_bins.max(1);
+    let symbol_period = desc.symbol_period as f64;
+
+    // fold samples after the offset by their phase within a symbol
+    let folded = waveform.iter()
+        .enumerate()
+        .filter_map(|(n, &v)| {
+            let t = (n as f64) * (sim_params.delta_t as f64) - (desc.offset as f64);
+            if t < 0.0 || symbol_period <= 0.0 {
+                return None;
+            }
+            let bin = ((t / symbol_period).fract() * (time_bins as f64)) as usize;
+            Some((bin.min(time_bins - 1), v))
+        })
+        .collect::<Vec<_>>();
+
+    let low = folded.iter().fold(Float::INFINITY, |accum, &(_, v)| accum.min(v));
+    let high = folded.iter().fold(Float::NEG_INFINITY, |accum, &(_, v)| accum.max(v));
+    let (low, high) = if high >= low { (low, high) } else { (0.0, 0.0) };
+    let threshold = (low + high) / 2.0;
+    let voltage_step = (high - low) / (voltage_bins as Float);
+
+    let mut histogram = ndarray::Array2::zeros((time_bins, voltage_bins));
+    let mut lowest_ones = vec![Float::INFINITY; time_bins];
+    let mut highest_zeros = vec![Float::NEG_INFINITY; time_bins];
+    for &(bin, v) in &folded {
+        let voltage_bin = if voltage_step > 0.0 {
+            (((v - low) / voltage_step) as usize).min(voltage_bins - 1)
+        } else {
+            0
+        };
+        histogram[[bin, voltage_bin]] += 1;
+
+        if v >= threshold {
+            lowest_ones[bin] = lowest_ones[bin].min(v);
+        } else {
+            highest_zeros[bin] = highest_zeros[bin].max(v);
+        }
+    }
+
+    // fold the threshold crossings, interpolated between samples, by their phase
+    let first = if symbol_period > 0.0 {
+        ((desc.offset / sim_params.delta_t).max(0.0).ceil() as usize).min(waveform.len())
+    } else {
+        waveform.len()
+    };
+    let mut crossings = waveform.slice(ndarray::s![first..])
+        .windows(2)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, pair)| (pair[0] >= threshold) != (pair[1] >= threshold))
+        .map(|(n, pair)| {
+            let fraction = ((threshold - pair[0]) / (pair[1] - pair[0])) as f64;
+            let t = ((first + n) as f64 + fraction) * (sim_params.delta_t as f64)
+                - (desc.offset as f64);
+            (t / symbol_period).rem_euclid(1.0)
+        })
+        .collect::<Vec<_>>();
+    crossings.sort_by(|a, b| a.total_cmp(b));
+
+    // the eye is the largest gap between crossings, which may wrap around the symbol boundary
+    let (gap_start, gap) = match (crossings.first(), crossings.last()) {
+        (Some(&first), Some(&last)) => crossings.windows(2)
+            .map(|pair| (pair[0], pair[1] - pair[0]))
+            .fold((last, 1.0 - last + first), |accum, item| {
+                if accum.1 >= item.1 { accum } else { item }
+            }),
+        _ => (0.0, 1.0),
+    };
+    let is_open = |bin: usize| {
+        let phase = ((bin as f64 + 0.5) / (time_bins as f64) - gap_start).rem_euclid(1.0);
+        phase > 0.0 && phase < gap
+    };
+
+    // bins in the crossing region or missing either level have no opening
+    let openings = lowest_ones.iter()
+        .zip(&highest_zeros)
+        .enumerate()
+        .map(|(bin, (&one, &zero))| {
+            if is_open(bin) && one.is_finite() && zero.is_finite() {
+                (one - zero).max(0.0)
+            } else {
+                0.0
+            }
+        })
+        .collect::<Vec<_>>();
+    let center_phase = (gap_start + gap/2.0).rem_euclid(1.0);
+    let center = ((center_phase * (time_bins as f64)) as usize).min(time_bins - 1);
+
+    let time_step = desc.symbol_period / (time_bins as Float);
+    EyeDiagram {
+        times: (0..time_bins).map(|k| (k as Float + 0.5) * time_step).collect(),
+        voltages: (0..voltage_bins).map(|k| low + (k as Float + 0.5) * voltage_step).collect(),
+        histogram,
+        threshold,
+        eye_height: openings[center],
+        openings,
+        eye_center: (center_phase as Float) * desc.symbol_period,
+        eye_width: (gap as Float) * desc.symbol_period,
+    }
+}
@@ -0,0 +1,170 @@
+//! Pump, signal, and idler power tracking for parametric amplifiers.
+
+use crate::{Float, SimulationParameters};
+use crate::analysis::quanta::photon_flux;
+use crate::analysis::spectrum::phasor;
+
+/// The tone frequencies of a parametric amplifier run.
+#[derive(Copy, Clone, Debug)]
+pub struct Tones {
+    /// The pump frequency.
+    pub pump: Float,
+    /// The signal frequency.
+    pub signal: Float,
+    /// The idler frequency.
+    pub idler: Float,
+}
+
+/// The time averaged power carried by each tone at a point along the line.
+#[derive(Copy, Clone, Debug)]
+pub struct TonePowers {
+    /// The physical position the powers were measured at.
+    pub z: Float,
+    /// The power in the pump tone.
+    pub pump: Float,
+    /// The power in the signal tone.
+    pub signal: Float,
+    /// The power in the idler tone.
+    pub idler: Float,
+}
+
+/// Summarizes energy transfer between an input and an output point.
+#[derive(Copy, Clone, Debug)]
+pub struct ConversionMetrics {
+    /// The fraction of input pump power missing at the output.
+    pub pump_depletion: Float,
+    /// Signal and idler photons generated per input pump photon.
+    ///
+    /// This is at most 1 for four-wave mixing and at most 2 for three-wave mixing.
+    pub conversion_efficiency: Float,
+    /// The ratio of output to input signal power.
+    pub signal_gain: Float,
+}
+
+/// Measures the tone powers from voltage and current waveforms at the same point.
+///
+/// The power in each tone is `Re(V I*) / 2`, where `V` and `I` are the tone phasors.
+pub fn tone_powers(
+    voltage: ndarray::ArrayView1<Float>,
+    current: ndarray::ArrayView1<Float>,
+    z: Float,
+    tones: &Tones,
+    sim_params: &SimulationParameters,
+) -> TonePowers {
+    let power = |frequency| {
+        let v = phasor(voltage, frequency, sim_params);
+        let i = phasor(current, frequency, sim_params);
+        (v * i.conj()).re / 2.0
+    };
+
+    TonePowers {
+        z,
+        pump: power(tones.pump),
+        signal: power(tones.signal),
+        idler: power(tones.idler),
+    }
+}
+
+/// Measures the tone powers along the line from full voltage and current data.
+///
+/// Both arrays are indexed as `[time, node]`, as saved in the `full` group. Every `stride`-th
+/// interior voltage node is measured, using the mean of its neighboring currents.
+pub fn tone_powers_along_line(
+    voltages: ndarray::ArrayView2<Float>,
+    currents: ndarray::ArrayView2<Float>,
+    tones: &Tones,
+    stride: usize,
+    sim_params: &SimulationParameters,
+) -> Vec<TonePowers> {
+    (1..currents.ncols())
+        .step_by(stride.max(1))
+        .map(|node| {
+            let current = (&currents.column(node-1) + &currents.column(node)) / 2.0;
+            let z = (node as Float - 0.5) * sim_params.delta_z;
+            tone_powers(voltages.column(node), current.view(), z, tones, sim_params)
+        })
+        .collect()
+}
+
+/// Computes pump depletion and photon conversion efficiency between two points.
+pub fn conversion_metrics(
+    input: &TonePowers,
+    output: &TonePowers,
+    tones: &Tones,
+) -> ConversionMetrics {
+    let generated = photon_flux(output.signal - input.signal, tones.signal)
+        + photon_flux(output.idler - input.idler, tones.idler);
+
+    ConversionMetrics {
+        pump_depletion: 1.0 - output.pump / input.pump,
+        conversion_efficiency: generated / photon_flux(input.pump, tones.pump),
+        signal_gain: output.signal / input.signal,
+    }
+}
+
+/// The mixing process of a parametric amplifier, which sets the idler frequency.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Mixing {
+    /// Four-wave mixing, as in a Kerr-like kinetic inductance line, with `2fp = fs + fi`.
+    FourWave,
+    /// Three-wave mixing, as in a DC-biased line, with `fp = fs + fi`.
+    ThreeWave,
+}
+
+impl Mixing {
+    /// Gets the tones of a pump and signal, with the idler they generate.
+    pub fn tones(&self, pump: Float, signal: Float) -> Tones {
+        let idler = match *self {
+            Mixing::FourWave => 2.0*pump - signal,
+            Mixing::ThreeWave => pump - signal,
+        };
+
+        Tones { pump, signal, idler }
+    }
+}
+
+/// The gain of a parametric amplifier at a single signal frequency.
+#[derive(Copy, Clone, Debug)]
+pub struct GainPoint {
+    /// The tones the gain was measured at.
+    pub tones: Tones,
+    /// The ratio of output to input signal power.
+    pub gain: Float,
+    /// The signal gain in dB.
+    pub gain_db: Float,
+    /// The idler power at the output.
+    pub idler_power: Float,
+    /// The ratio of output idler power to input signal power.
+    pub conversion_gain: Float,
+}
+
+/// Measures the signal gain and idler power at each signal frequency between two ports.
+///
+/// Each port is a pair of voltage and current waveforms, usually from the `start` and `end`
+/// groups of a saved run, and every signal tone must be present in the same run. The waveforms
+/// should span an integer number of periods of every tone, and only hold steady state data.
+pub fn gain_profile(
+    input: (ndarray::ArrayView1<Float>, ndarray::ArrayView1<Float>),
+    output: (ndarray::ArrayView1<Float>, ndarray::ArrayView1<Float>),
+    pump: Float,
+    signals: &[Float],
+    mixing: Mixing,
+    sim_params: &SimulationParameters,
+) -> Vec<GainPoint> {
+    signals.iter()
+        .map(|&signal| {
+            let tones = mixing.tones(pump, signal);
+            let input_powers = tone_powers(input.0, input.1, 0.0, &tones, sim_params);
+            let output_powers = tone_powers(output.0, output.1, 0.0, &tones, sim_params);
+            let gain = output_powers.signal / input_powers.signal;
+
+            GainPoint {
+                tones,
+                gain,
+                gain_db: 10.0 * gain.log10(),
+                idler_power: output_powers.idler,
+                conversion_gain: output_powers.idler / input_powers.signal,
+            }
+        })
+        .collect()
+}
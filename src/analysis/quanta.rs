@@ -0,0 +1,66 @@
+//! Conversions between classical powers and photon numbers.
+
+use crate::Float;
+
+/// Planck's constant [J s].
+pub const PLANCK: Float = 6.626_07e-34;
+/// Boltzmann's constant [J / K].
+pub const BOLTZMANN: Float = 1.380_649e-23;
+/// The vacuum fluctuations of a single mode, in quanta.
+pub const VACUUM_QUANTA: Float = 0.5;
+
+/// A signal expressed in quanta per mode.
+#[derive(Copy, Clone, Debug)]
+pub struct QuantaMetrics {
+    /// The signal photons per second per hertz of bandwidth.
+    pub signal: Float,
+    /// The mean thermal occupation of the mode.
+    pub thermal: Float,
+    /// The ratio of signal quanta to thermal plus vacuum quanta.
+    pub snr: Float,
+}
+
+/// Converts a power at `frequency` into photons per second.
+pub fn photon_flux(power: Float, frequency: Float) -> Float {
+    power / (PLANCK * frequency)
+}
+
+/// Converts a power spread over `bandwidth` into photons per mode.
+pub fn photons_per_mode(power: Float, frequency: Float, bandwidth: Float) -> Float {
+    photon_flux(power, frequency) / bandwidth
+}
+
+/// Computes the Bose-Einstein occupation of a mode at `frequency` and `temperature`.
+pub fn thermal_occupation(frequency: Float, temperature: Float) -> Float {
+    (PLANCK * frequency / (BOLTZMANN * temperature)).exp_m1().recip()
+}
+
+/// Converts a number of quanta into the equivalent noise temperature at `frequency`.
+///
+/// This is the inverse of `thermal_occupation`.
+pub fn noise_temperature(quanta: Float, frequency: Float) -> Float {
+    PLANCK * frequency / (BOLTZMANN * quanta.recip().ln_1p())
+}
+
+/// Converts a noise temperature into the equivalent number of quanta at `frequency`.
+pub fn noise_quanta(temperature: Float, frequency: Float) -> Float {
+    thermal_occupation(frequency, temperature)
+}
+
+/// Expresses a signal of `power` in `bandwidth` in quanta, relative to the thermal and vacuum
+/// noise of a mode at `temperature`.
+pub fn quanta_metrics(
+    power: Float,
+    frequency: Float,
+    bandwidth: Float,
+    temperature: Float,
+) -> QuantaMetrics {
+    let signal = photons_per_mode(power, frequency, bandwidth);
+    let thermal = thermal_occupation(frequency, temperature);
+
+    QuantaMetrics {
+        signal,
+        thermal,
+        snr: signal / (thermal + VACUUM_QUANTA),
+    }
+}
@@ -0,0 +1,162 @@
+//! Transmission ripple caused by reflections between impedance discontinuities.
+
+use std::cmp::min;
+
+use num_complex::Complex;
+
+use crate::{Error, Float, SimulationParameters};
+use crate::analysis::spectrum::{fft, ifft};
+
+/// Describes how transmission ripple should be analyzed.
+pub struct RippleDescriptor {
+    /// The lowest frequency of the band of interest.
+    pub min_frequency: Float,
+    /// The highest frequency of the band of interest.
+    pub max_frequency: Float,
+    /// The width of the time gate around the main arrival.
+    pub gate_width: Float,
+    /// The smallest echo amplitude to report, relative to the main peak.
+    pub threshold: Float,
+    /// The phase velocity used to convert echo delays into distances.
+    pub phase_velocity: Float,
+}
+
+/// A reflection identified in the impulse response.
+#[derive(Copy, Clone, Debug)]
+pub struct Echo {
+    /// The delay of the echo after the main peak.
+    pub delay: Float,
+    /// The amplitude of the echo relative to the main peak.
+    pub amplitude: Float,
+    /// The ripple period in frequency caused by the echo.
+    pub ripple_period: Float,
+    /// The separation of the discontinuities responsible for the echo.
+    pub separation: Float,
+}
+
+/// The result of a ripple analysis.
+#[derive(Clone, Debug)]
+pub struct RippleAnalysis {
+    /// The frequencies within the band of interest.
+    pub frequencies: Vec<Float>,
+    /// The transmission at each frequency.
+    pub transmission: Vec<Complex<Float>>,
+    /// The transmission at each frequency with all echoes gated out.
+    pub gated_transmission: Vec<Complex<Float>>,
+    /// The peak to peak transmission ripple in the band, in dB.
+    pub ripple_db: Float,
+    /// The peak to peak transmission ripple with all echoes gated out, in dB.
+    pub gated_ripple_db: Float,
+    /// The echoes found after the main peak, in order of delay.
+    pub echoes: Vec<Echo>,
+}
+
+/// Analyzes the transmission ripple between `input` and `output` waveforms of a broadband run.
+///
+/// The impulse response is recovered from the in-band transmission and the echoes following its
+/// main peak are identified. The transmission is then recomputed from only the main arrival of
+/// the output, so the ripple attributable to reflections is the difference between `ripple_db`
+/// and `gated_ripple_db`. The input should be a single pulse shorter than `gate_width`.
+///
+/// Frequencies where the input has no content are left out of the band.
+pub fn ripple(
+    input: ndarray::ArrayView1<Float>,
+    output: ndarray::ArrayView1<Float>,
+    desc: RippleDescriptor,
+    sim_params: &SimulationParameters,
+) -> Result<RippleAnalysis, Error> {
+    if output.len() != input.len() {
+        return Err(Error::BadAnalysisInput {
+            array_name: "Output".to_string(),
+            input_length: output.len(),
+            expected_length: input.len(),
+        })
+    }
+    let n = input.len();
+    if n == 0 {
+        return Err(Error::BadParameter("the waveforms are empty".to_string()))
+    }
+    let to_complex = |w: ndarray::ArrayView1<Float>| {
+        w.iter().map(|&w| Complex::new(w, 0.0)).collect::<Vec<_>>()
+    };
+    let x = fft(to_complex(input));
+    let y = fft(to_complex(output));
+
+    // calculate in band transmission, keeping the spectrum hermitian, and skip any frequencies
+    // missing from the input
+    let delta_f = ((n as Float) * sim_params.delta_t).recip();
+    let band = (1..=(n/2))
+        .filter(|&k| {
+            let f = (k as Float) * delta_f;
+            f >= desc.min_frequency && f <= desc.max_frequency && x[k].norm() > 0.0
+        })
+        .collect::<Vec<_>>();
+    let mut transfer = vec![Complex::new(0.0, 0.0); n];
+    for &k in &band {
+        transfer[k] = y[k] / x[k];
+        transfer[n-k] = transfer[k].conj();
+    }
+
+    // recover impulse response
+    let impulse = ifft(transfer.clone())
+        .into_iter()
+        .map(|h| h.re / (n as Float))
+        .collect::<Vec<_>>();
+    let (main, main_amplitude) = impulse.iter()
+        .map(|h| h.abs())
+        .enumerate()
+        .fold((0, 0.0), |accum, item| if accum.1 >= item.1 { accum } else { item });
+
+    // find echoes as local maxima outside of the gate
+    let half_gate = ((desc.gate_width / 2.0) / sim_params.delta_t).ceil() as usize;
+    let echoes = ((main + half_gate + 1)..n)
+        .filter(|&i| {
+            let amplitude = impulse[i].abs();
+            amplitude >= desc.threshold * main_amplitude
+                && ((i - half_gate)..min(i + half_gate + 1, n))
+                    .all(|j| impulse[j].abs() <= amplitude)
+        })
+        .map(|i| {
+            let delay = ((i - main) as Float) * sim_params.delta_t;
+            Echo {
+                delay,
+                amplitude: impulse[i].abs() / main_amplitude,
+                ripple_period: delay.recip(),
+                separation: delay * desc.phase_velocity / 2.0,
+            }
+        })
+        .collect();
+
+    // gate out everything but the main arrival of the input pulse
+    let (input_peak, _) = input.iter()
+        .map(|x| x.abs())
+        .enumerate()
+        .fold((0, 0.0), |accum, item| if accum.1 >= item.1 { accum } else { item });
+    let arrival = (input_peak + main) % n;
+    let y_gated = fft(
+        output.iter()
+            .enumerate()
+            .map(|(i, &y)| Complex::new(if i.abs_diff(arrival) <= half_gate { y } else { 0.0 }, 0.0))
+            .collect()
+    );
+
+    let transmission = band.iter().map(|&k| transfer[k]).collect::<Vec<_>>();
+    let gated_transmission = band.iter().map(|&k| y_gated[k] / x[k]).collect::<Vec<_>>();
+
+    Ok(RippleAnalysis {
+        frequencies: band.iter().map(|&k| (k as Float) * delta_f).collect(),
+        ripple_db: peak_to_peak_db(&transmission),
+        gated_ripple_db: peak_to_peak_db(&gated_transmission),
+        transmission,
+        gated_transmission,
+        echoes,
+    })
+}
+
+/// Computes the peak to peak magnitude variation of `values` in dB.
+fn peak_to_peak_db(values: &[Complex<Float>]) -> Float {
+    let (low, high) = values.iter()
+        .map(|value| 20.0 * value.norm().log10())
+        .fold((Float::INFINITY, Float::NEG_INFINITY), |(low, high), db| (low.min(db), high.max(db)));
+    if high >= low { high - low } else { 0.0 }
+}
@@ -0,0 +1,100 @@
+//! Edge steepening metrics for shock-forming lines.
+
+use crate::{Float, SimulationParameters};
+
+/// Which edge of a waveform to measure.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Edge {
+    /// A low to high transition.
+    Rising,
+    /// A high to low transition.
+    Falling,
+}
+
+/// The edge transition time at a single point along the line.
+#[derive(Copy, Clone, Debug)]
+pub struct SteepeningPoint {
+    /// The voltage node index the waveform was taken from.
+    pub node: usize,
+    /// The physical position of the node along the line.
+    pub z: Float,
+    /// The 10-90% transition time of the edge.
+    pub transition_time: Float,
+    /// The transition time of the first measured node divided by `transition_time`.
+    pub compression: Float,
+}
+
+/// Measures the 10-90% transition time of the first `edge` in `waveform`.
+///
+/// The 10% and 90% levels are taken relative to the minimum and maximum of the waveform,
+/// and crossings are linearly interpolated between samples. Returns `None` if no such edge
+/// is found.
+pub fn transition_time(
+    waveform: ndarray::ArrayView1<Float>,
+    edge: Edge,
+    sim_params: &SimulationParameters,
+) -> Option<Float> {
+    let low = waveform.fold(Float::INFINITY, |accum, &item| accum.min(item));
+    let high = waveform.fold(Float::NEG_INFINITY, |accum, &item| accum.max(item));
+    if high <= low {
+        return None;
+    }
+    let level_10 = low + 0.1*(high - low);
+    let level_90 = low + 0.9*(high - low);
+
+    let (start, end) = match edge {
+        Edge::Rising => {
+            let start = crossing(waveform, level_10, 0, edge)?;
+            (start, crossing(waveform, level_90, start.floor() as usize, edge)?)
+        },
+        Edge::Falling => {
+            let start = crossing(waveform, level_90, 0, edge)?;
+            (start, crossing(waveform, level_10, start.floor() as usize, edge)?)
+        },
+    };
+
+    Some((end - start) * sim_params.delta_t)
+}
+
+/// Measures edge transition time versus position from full voltage data.
+///
+/// `voltages` is indexed as `[time, node]`, as saved in the `full/voltages` dataset. Every
+/// `stride`-th node is measured, and nodes without a detectable edge are skipped.
+pub fn steepening_curve(
+    voltages: ndarray::ArrayView2<Float>,
+    edge: Edge,
+    stride: usize,
+    sim_params: &SimulationParameters,
+) -> Vec<SteepeningPoint> {
+    let mut points = Vec::new();
+    let mut reference = None;
+
+    for node in (0..voltages.ncols()).step_by(stride.max(1)) {
+        if let Some(transition_time) = transition_time(voltages.column(node), edge, sim_params) {
+            let reference = *reference.get_or_insert(transition_time);
+            points.push(SteepeningPoint {
+                node,
+                z: (node as Float - 0.5) * sim_params.delta_z,
+                transition_time,
+                compression: reference / transition_time,
+            });
+        }
+    }
+
+    points
+}
+
+/// Finds the fractional sample index where `waveform` first crosses `level` after `start`.
+fn crossing(
+    waveform: ndarray::ArrayView1<Float>,
+    level: Float,
+    start: usize,
+    edge: Edge,
+) -> Option<Float> {
+    (start..waveform.len().saturating_sub(1))
+        .find(|&i| match edge {
+            Edge::Rising => waveform[i] < level && waveform[i+1] >= level,
+            Edge::Falling => waveform[i] > level && waveform[i+1] <= level,
+        })
+        .map(|i| i as Float + (level - waveform[i]) / (waveform[i+1] - waveform[i]))
+}
@@ -0,0 +1,78 @@
+//! Input impedance traces for Smith chart workflows.
+
+use std::io::Write;
+use std::path::Path;
+
+use num_complex::Complex;
+
+use crate::{Error, Float, SimulationParameters};
+use crate::analysis::spectrum::spectrum;
+
+/// The input impedance and reflection coefficient of a line over a band of frequencies.
+#[derive(Clone, Debug)]
+pub struct SmithTrace {
+    /// The frequencies within the band of interest.
+    pub frequencies: Vec<Float>,
+    /// The input impedance at each frequency.
+    pub impedance: Vec<Complex<Float>>,
+    /// The reflection coefficient at each frequency, normalized to `reference_impedance`.
+    pub reflection: Vec<Complex<Float>>,
+    /// The impedance the reflection coefficient is normalized to.
+    pub reference_impedance: Float,
+}
+
+/// Computes the input impedance of a line from the input `voltage` and `current` of a broadband run.
+///
+/// The excitation should have significant energy at every frequency between `min_frequency` and
+/// `max_frequency`.
+pub fn input_impedance(
+    voltage: ndarray::ArrayView1<Float>,
+    current: ndarray::ArrayView1<Float>,
+    reference_impedance: Float,
+    min_frequency: Float,
+    max_frequency: Float,
+    sim_params: &SimulationParameters,
+) -> Result<SmithTrace, Error> {
+    if current.len() != voltage.len() {
+        return Err(Error::BadAnalysisInput {
+            array_name: "Current".to_string(),
+            input_length: current.len(),
+            expected_length: voltage.len(),
+        })
+    }
+    let voltage_spectrum = spectrum(voltage, sim_params);
+    let current_spectrum = spectrum(current, sim_params);
+
+    let mut trace = SmithTrace {
+        frequencies: Vec::new(),
+        impedance: Vec::new(),
+        reflection: Vec::new(),
+        reference_impedance,
+    };
+    for (k, &frequency) in voltage_spectrum.frequencies.iter().enumerate() {
+        if frequency < min_frequency || frequency > max_frequency {
+            continue
+        }
+        let impedance = voltage_spectrum.values[k] / current_spectrum.values[k];
+        trace.frequencies.push(frequency);
+        trace.impedance.push(impedance);
+        trace.reflection.push((impedance - reference_impedance) / (impedance + reference_impedance));
+    }
+
+    Ok(trace)
+}
+
+impl SmithTrace {
+    /// Writes the reflection coefficient trace as a one port Touchstone (`.s1p`) file.
+    pub fn write_touchstone<P: AsRef<Path>>(&self, filename: P) -> Result<(), Error> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(filename)?);
+        writeln!(file, "! Input reflection coefficient")?;
+        writeln!(file, "# HZ S RI R {}", self.reference_impedance)?;
+        for (frequency, reflection) in self.frequencies.iter().zip(&self.reflection) {
+            writeln!(file, "{:e} {:e} {:e}", frequency, reflection.re, reflection.im)?;
+        }
+        file.flush()?;
+
+        Ok(())
+    }
+}
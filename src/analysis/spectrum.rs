@@ -0,0 +1,73 @@
+//! Spectral estimates of simulated waveforms.
+
+use num_complex::Complex;
+
+use crate::{Float, SimulationParameters};
+
+/// Computes the complex amplitude of the `frequency` component of `waveform`.
+///
+/// A waveform `A cos(2πft + φ)` gives `A e^(iφ)`, with `t` measured from the first sample. The
+/// estimate is exact when the waveform spans an integer number of periods.
+pub fn phasor(
+    waveform: ndarray::ArrayView1<Float>,
+    frequency: Float,
+    sim_params: &SimulationParameters,
+) -> Complex<Float> {
+    let omega = 2.0 * std::f64::consts::PI * (frequency as f64) * (sim_params.delta_t as f64);
+    let sum = waveform.iter()
+        .enumerate()
+        .fold(Complex::<f64>::new(0.0, 0.0), |accum, (n, &w)| {
+            accum + Complex::from_polar(w as f64, -omega * (n as f64))
+        });
+    let amplitude = sum * 2.0 / (waveform.len().max(1) as f64);
+
+    Complex::new(amplitude.re as Float, amplitude.im as Float)
+}
+
+/// A one-sided spectrum of a real waveform.
+#[derive(Clone, Debug)]
+pub struct Spectrum {
+    /// The frequency of each bin.
+    pub frequencies: Vec<Float>,
+    /// The complex amplitude of each bin, scaled to match `phasor`.
+    pub values: Vec<Complex<Float>>,
+}
+
+/// Computes the one-sided spectrum of `waveform` with a fast Fourier transform.
+pub fn spectrum(
+    waveform: ndarray::ArrayView1<Float>,
+    sim_params: &SimulationParameters,
+) -> Spectrum {
+    let n = waveform.len();
+    let buffer = fft(waveform.iter().map(|&w| Complex::new(w, 0.0)).collect());
+
+    let nbins = n/2 + 1;
+    Spectrum {
+        frequencies: (0..nbins)
+            .map(|k| (k as Float) / ((n as Float) * sim_params.delta_t))
+            .collect(),
+        values: buffer.into_iter()
+            .take(nbins)
+            .enumerate()
+            .map(|(k, value)| {
+                if k == 0 || 2*k == n { value / (n as Float) } else { value * 2.0 / (n as Float) }
+            })
+            .collect(),
+    }
+}
+
+/// Computes the unnormalized forward transform of `buffer`.
+pub(crate) fn fft(mut buffer: Vec<Complex<Float>>) -> Vec<Complex<Float>> {
+    rustfft::FftPlanner::new()
+        .plan_fft_forward(buffer.len())
+        .process(&mut buffer);
+    buffer
+}
+
+/// Computes the unnormalized inverse transform of `buffer`.
+pub(crate) fn ifft(mut buffer: Vec<Complex<Float>>) -> Vec<Complex<Float>> {
+    rustfft::FftPlanner::new()
+        .plan_fft_inverse(buffer.len())
+        .process(&mut buffer);
+    buffer
+}
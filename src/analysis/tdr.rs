@@ -0,0 +1,68 @@
+//! Time domain reflectometry of impedance profiles.
+
+use crate::{Float, SimulationParameters};
+
+/// Describes the step source of a time domain reflectometry run.
+pub struct TdrDescriptor {
+    /// The voltage of the incident step at the start port, before any reflections return.
+    ///
+    /// This is half the open circuit source voltage when the source is matched.
+    pub step_amplitude: Float,
+    /// The time the incident step reaches the start port.
+    pub step_time: Float,
+    /// The impedance of the source, which reflections are measured against.
+    pub reference_impedance: Float,
+    /// The phase velocity used to convert round trip delays into distances.
+    pub phase_velocity: Float,
+}
+
+/// An impedance profile recovered from a step response.
+#[derive(Clone, Debug)]
+pub struct TdrProfile {
+    /// The round trip delay of each sample after the step.
+    pub delays: Vec<Float>,
+    /// The distance along the line of each sample.
+    pub distances: Vec<Float>,
+    /// The index of the voltage nearest to each distance.
+    pub indices: Vec<usize>,
+    /// The reflection coefficient of each sample.
+    pub reflection: Vec<Float>,
+    /// The impedance of each sample.
+    pub impedance: Vec<Float>,
+}
+
+/// Converts the start port voltage response to a step into an impedance versus distance profile.
+///
+/// Each sample after the step is mapped to the distance a reflection would travel out and back in
+/// its delay, with `Z = Z0 (1 + ρ) / (1 - ρ)`. Multiple reflections and losses aren't corrected
+/// for, so the profile is most accurate for small discontinuities near the start of the line.
+///
+/// `voltage` holds each time step after the initial state of a run from time zero, as saved or
+/// returned by `run_collect`, so sample `n` is at time `(n + 1) Δt`.
+pub fn tdr(
+    voltage: ndarray::ArrayView1<Float>,
+    desc: TdrDescriptor,
+    sim_params: &SimulationParameters,
+) -> TdrProfile {
+    let first = ((desc.step_time / sim_params.delta_t).max(0.0).ceil() as usize).saturating_sub(1);
+    let delays = (first..voltage.len())
+        .map(|n| ((n + 1) as Float)*sim_params.delta_t - desc.step_time)
+        .collect::<Vec<_>>();
+    let distances = delays.iter()
+        .map(|&delay| delay*desc.phase_velocity / 2.0)
+        .collect::<Vec<_>>();
+    let reflection = voltage.iter()
+        .skip(first)
+        .map(|&v| (v - desc.step_amplitude) / desc.step_amplitude)
+        .collect::<Vec<_>>();
+
+    TdrProfile {
+        indices: distances.iter().map(|&z| sim_params.voltage_index(z)).collect(),
+        impedance: reflection.iter()
+            .map(|&rho| desc.reference_impedance*(1.0 + rho) / (1.0 - rho))
+            .collect(),
+        delays,
+        distances,
+        reflection,
+    }
+}
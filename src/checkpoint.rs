@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::{units, Error, Float, SimulationSnapshot};
+
+/// When and where rolling checkpoints should be written.
+///
+/// Checkpoints are full `SimulationSnapshot`s, so components with internal state, such as random
+/// number generators, are restored exactly as long as they include that state in their snapshots.
+/// Registered controllers and probes keep state that isn't part of checkpoints, so runs with them
+/// can't be resumed.
+#[derive(Debug)]
+pub struct CheckpointSettings<P: AsRef<Path>> {
+    /// The directory that checkpoint files are written to.
+    pub directory: P,
+    /// Write a checkpoint after this many time steps, if set.
+    pub every_steps: Option<usize>,
+    /// Write a checkpoint after this much wall clock time, if set.
+    ///
+    /// This is only checked between compute chunks.
+    pub every_duration: Option<Duration>,
+    /// How many of the most recent checkpoints to keep.
+    pub keep: usize,
+}
+
+/// The rows of each dataset of an HDF5 save that a run started saving at.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct SaveOffsets {
+    pub(crate) end: usize,
+    pub(crate) full: usize,
+    pub(crate) taps: usize,
+    /// The offsets of the channels that were in the file before the run, by name.
+    pub(crate) channels: BTreeMap<String, usize>,
+}
+
+/// How far a run had got when a checkpoint was written.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunProgress {
+    /// The number of time steps of the whole run.
+    pub nsteps: usize,
+    /// The number of time steps of the run taken before the checkpoint.
+    pub completed: usize,
+    /// The simulated time at the start of the run.
+    pub start_time: Float,
+    /// Where the run was saving to in its HDF5 file, if it was.
+    pub(crate) save_offsets: Option<SaveOffsets>,
+}
+
+impl RunProgress {
+    /// Writes the progress to an HDF5 group.
+    fn write(&self, group: &hdf5::Group) -> Result<(), Error> {
+        write_count(group, "nsteps", self.nsteps)?;
+        write_count(group, "completed", self.completed)?;
+        group.new_attr::<Float>()
+            .shape(hdf5::Extents::Scalar)
+            .create("start_time")?
+            .write_scalar(&self.start_time)?;
+        units::label_attr(group, "start_time", "s")?;
+
+        if let Some(ref offsets) = self.save_offsets {
+            let offsets_group = group.create_group("save_offsets")?;
+            write_count(&offsets_group, "end", offsets.end)?;
+            write_count(&offsets_group, "full", offsets.full)?;
+            write_count(&offsets_group, "taps", offsets.taps)?;
+            let channels_group = offsets_group.create_group("channels")?;
+            for (name, &offset) in &offsets.channels {
+                write_count(&channels_group, name, offset)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads progress from an HDF5 group.
+    fn read(group: &hdf5::Group) -> Result<Self, Error> {
+        let save_offsets = match group.group("save_offsets") {
+            Ok(offsets_group) => {
+                let channels_group = offsets_group.group("channels")?;
+                let mut channels = BTreeMap::new();
+                for name in channels_group.attr_names()? {
+                    let offset = read_count(&channels_group, &name)?;
+                    channels.insert(name, offset);
+                }
+
+                Some(SaveOffsets {
+                    end: read_count(&offsets_group, "end")?,
+                    full: read_count(&offsets_group, "full")?,
+                    taps: read_count(&offsets_group, "taps")?,
+                    channels,
+                })
+            },
+            Err(_) => None,
+        };
+
+        Ok(Self {
+            nsteps: read_count(group, "nsteps")?,
+            completed: read_count(group, "completed")?,
+            start_time: group.attr("start_time")?.read_scalar::<Float>()?,
+            save_offsets,
+        })
+    }
+}
+
+/// A checkpoint written during a run, which the run can be resumed from.
+#[derive(Clone)]
+pub struct Checkpoint {
+    /// The configuration and state of the simulation at the checkpoint.
+    pub snapshot: SimulationSnapshot,
+    /// How far the run had got.
+    pub progress: RunProgress,
+}
+
+impl Checkpoint {
+    /// Loads the most recent checkpoint in `directory`, if there is one.
+    pub fn latest<P: AsRef<Path>>(directory: P) -> Result<Option<Self>, Error> {
+        match list_checkpoints(directory.as_ref())?.last() {
+            Some((_, filename)) => {
+                let file = hdf5::File::open(filename)?;
+                Ok(Some(Self {
+                    snapshot: SimulationSnapshot::read(&file)?,
+                    progress: RunProgress::read(&file.group("run")?)?,
+                }))
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+/// Tracks when the next checkpoint is due during a run.
+pub(crate) struct Checkpointer<'a, P: AsRef<Path>> {
+    settings: &'a CheckpointSettings<P>,
+    last_time: Instant,
+    steps: usize,
+}
+
+impl<'a, P: AsRef<Path>> Checkpointer<'a, P> {
+    pub(crate) fn new(settings: &'a CheckpointSettings<P>) -> Result<Self, Error> {
+        std::fs::create_dir_all(&settings.directory)?;
+
+        Ok(Self {
+            settings,
+            last_time: Instant::now(),
+            steps: 0,
+        })
+    }
+
+    /// The number of time steps that can be taken before a checkpoint is due.
+    pub(crate) fn steps_remaining(&self) -> usize {
+        self.settings.every_steps
+            .map(|every_steps| every_steps.max(1).saturating_sub(self.steps))
+            .unwrap_or(usize::MAX)
+    }
+
+    /// Records that `nsteps` time steps were taken and returns whether a checkpoint is due.
+    pub(crate) fn advance(&mut self, nsteps: usize) -> bool {
+        self.steps += nsteps;
+
+        self.steps_remaining() == 0 || self.settings.every_duration
+            .is_some_and(|every_duration| self.last_time.elapsed() >= every_duration)
+    }
+
+    /// Writes a checkpoint and removes any beyond the most recent `keep`.
+    pub(crate) fn write(
+        &mut self,
+        snapshot: &SimulationSnapshot,
+        progress: &RunProgress,
+    ) -> Result<(), Error> {
+        let directory = self.settings.directory.as_ref();
+        let checkpoints = list_checkpoints(directory)?;
+        let next_index = checkpoints.last().map_or(0, |(index, _)| index + 1);
+
+        // write to a temporary file first so a crash never leaves a partial checkpoint
+        let filename = directory.join(format!("checkpoint_{:06}.h5", next_index));
+        let temp_filename = filename.with_extension("h5.tmp");
+        let file = hdf5::File::create(&temp_filename)?;
+        snapshot.write(&file)?;
+        progress.write(&file.create_group("run")?)?;
+        file.close()?;
+        std::fs::rename(&temp_filename, &filename)?;
+
+        let nremove = (checkpoints.len() + 1).saturating_sub(self.settings.keep.max(1));
+        for (_, old_filename) in checkpoints.iter().take(nremove) {
+            std::fs::remove_file(old_filename)?;
+        }
+
+        self.steps = 0;
+        self.last_time = Instant::now();
+
+        Ok(())
+    }
+}
+
+/// Lists the checkpoint files in `directory`, ordered from oldest to newest.
+fn list_checkpoints(directory: &Path) -> Result<Vec<(usize, PathBuf)>, Error> {
+    if !directory.exists() {
+        return Ok(Vec::new())
+    }
+
+    let mut checkpoints = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let filename = entry?.path();
+        let index = filename.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("checkpoint_"))
+            .and_then(|name| name.strip_suffix(".h5"))
+            .and_then(|index| index.parse::<usize>().ok());
+        if let Some(index) = index {
+            checkpoints.push((index, filename));
+        }
+    }
+    checkpoints.sort();
+
+    Ok(checkpoints)
+}
+
+/// Writes a count as an attribute.
+fn write_count(group: &hdf5::Group, name: &str, count: usize) -> Result<(), Error> {
+    group.new_attr::<u64>()
+        .shape(hdf5::Extents::Scalar)
+        .create(name)?
+        .write_scalar(&(count as u64))?;
+    Ok(())
+}
+
+/// Reads a count from an attribute.
+fn read_count(group: &hdf5::Group, name: &str) -> Result<usize, Error> {
+    Ok(group.attr(name)?.read_scalar::<u64>()? as usize)
+}
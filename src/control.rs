@@ -0,0 +1,142 @@
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use crate::{Float, SimulationParameters, SimulationState};
+
+/// An atomic integer with the bits of a `Float`.
+#[cfg(not(feature = "f64"))]
+type AtomicBits = std::sync::atomic::AtomicU32;
+#[cfg(feature = "f64")]
+type AtomicBits = std::sync::atomic::AtomicU64;
+
+/// A shared value that controllers can adjust while a simulation runs.
+///
+/// Clones refer to the same value, so a clone can be captured by a source function while another
+/// is held by a `Controller`.
+#[derive(Clone, Debug)]
+pub struct Parameter(Arc<AtomicBits>);
+
+impl Parameter {
+    /// Creates a new `Parameter` instance.
+    #[inline]
+    pub fn new(value: Float) -> Self {
+        Self(Arc::new(AtomicBits::new(value.to_bits())))
+    }
+
+    /// Gets the current value.
+    #[inline]
+    pub fn get(&self) -> Float {
+        Float::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Sets a new value.
+    #[inline]
+    pub fn set(&self, value: Float) {
+        self.0.store(value.to_bits(), Ordering::Relaxed)
+    }
+}
+
+/// The data a `Controller` observes after each control interval.
+///
+/// Intervals longer than the compute chunk size only include the data of the last chunk.
+pub struct Observation<'a> {
+    /// The state at the end of the interval.
+    pub state: &'a SimulationState,
+    /// The voltages of each point along the line for each time step of the interval.
+    pub voltages: ndarray::ArrayView2<'a, Float>,
+    /// The currents of each point along the line for each time step of the interval.
+    pub currents: ndarray::ArrayView2<'a, Float>,
+    /// The parameters of the simulation.
+    pub sim_params: &'a SimulationParameters,
+    /// The simulated time since the last observation, which covers the whole interval even when
+    /// only its last chunk is included.
+    pub elapsed: Float,
+}
+
+/// Adjusts simulation parameters based on the observed state.
+pub trait Controller {
+    /// Observes the last control interval and updates any controlled parameters.
+    fn update(&mut self, observation: &Observation);
+}
+
+/// Describes a `PidController`.
+pub struct PidControllerDescriptor<F: FnMut(&Observation) -> Float> {
+    /// The parameter to adjust.
+    pub parameter: Parameter,
+    /// Measures the controlled quantity from an observation.
+    pub measure: F,
+    /// The target value of the measured quantity.
+    pub setpoint: Float,
+    /// The proportional gain.
+    pub proportional_gain: Float,
+    /// The integral gain, per unit of simulated time.
+    pub integral_gain: Float,
+    /// The derivative gain, in units of simulated time.
+    pub derivative_gain: Float,
+}
+
+/// Keeps a measured quantity at a setpoint by adjusting a `Parameter`.
+pub struct PidController<F: FnMut(&Observation) -> Float> {
+    parameter: Parameter,
+    measure: F,
+    setpoint: Float,
+    proportional_gain: Float,
+    integral_gain: Float,
+    derivative_gain: Float,
+    initial_value: Float,
+    integral: Float,
+    last_error: Option<Float>,
+}
+
+impl<F: FnMut(&Observation) -> Float> PidController<F> {
+    /// Creates a new `PidController` instance.
+    ///
+    /// The value of the parameter at creation is used as the controller's bias.
+    #[inline]
+    pub fn new(desc: PidControllerDescriptor<F>) -> Self {
+        Self {
+            initial_value: desc.parameter.get(),
+            parameter: desc.parameter,
+            measure: desc.measure,
+            setpoint: desc.setpoint,
+            proportional_gain: desc.proportional_gain,
+            integral_gain: desc.integral_gain,
+            derivative_gain: desc.derivative_gain,
+            integral: 0.0,
+            last_error: None,
+        }
+    }
+}
+
+impl<F: FnMut(&Observation) -> Float> Controller for PidController<F> {
+    fn update(&mut self, observation: &Observation) {
+        let error = self.setpoint - (self.measure)(observation);
+        let delta_t = observation.elapsed;
+
+        self.integral += error * delta_t;
+        let derivative = self.last_error
+            .map_or(0.0, |last_error| (error - last_error) / delta_t);
+        self.last_error = Some(error);
+
+        self.parameter.set(
+            self.initial_value
+                + self.proportional_gain*error
+                + self.integral_gain*self.integral
+                + self.derivative_gain*derivative
+        );
+    }
+}
+
+/// A `Controller` registered with a simulation.
+pub(crate) struct RegisteredController {
+    pub(crate) controller: Box<dyn Controller>,
+    pub(crate) interval: usize,
+    pub(crate) steps: usize,
+}
+
+impl RegisteredController {
+    /// The number of time steps that can be taken before an update is due.
+    pub(crate) fn steps_remaining(&self) -> usize {
+        self.interval.max(1).saturating_sub(self.steps)
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::{ComputeOutput, Error, Float};
+
+/// The header of the file holding the end points.
+const END_HEADER: &str = "time,start_voltage,start_current,end_voltage,end_current";
+
+/// Gets the path of the file holding the `quantity` of every point, next to `filename`.
+pub(crate) fn full_path(filename: &Path, quantity: &str) -> PathBuf {
+    let stem = filename.file_stem().unwrap_or_default().to_string_lossy();
+    filename.with_file_name(format!("{}_{}.csv", stem, quantity))
+}
+
+/// Creates the files of a save with their headers, keeping existing files unless `overwrite`.
+///
+/// `full` sets whether the voltages and currents of every point are saved, in that order.
+pub(crate) fn create(
+    filename: &Path,
+    full: [bool; 2],
+    overwrite: bool,
+    total_points: usize,
+) -> Result<(), Error> {
+    let mut files = vec![(filename.to_path_buf(), END_HEADER.to_string())];
+    if full[0] {
+        files.push((full_path(filename, "voltages"), full_header("voltage", total_points + 1)));
+    }
+    if full[1] {
+        files.push((full_path(filename, "currents"), full_header("current", total_points)));
+    }
+
+    for (path, header) in files {
+        if overwrite || !path.exists() {
+            let mut file = std::fs::File::create(&path)?;
+            writeln!(file, "{}", header)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Appends the `niters` time steps of a computed chunk, whose initial row is at `start_time`.
+pub(crate) fn write_chunk(
+    filename: &Path,
+    data: &ComputeOutput,
+    niters: usize,
+    full: [bool; 2],
+    start_time: Float,
+    delta_t: Float,
+) -> Result<(), Error> {
+    let ComputeOutput { ref voltages, ref currents, .. } = *data;
+    let time = |row: usize| start_time + (row as Float)*delta_t;
+
+    let mut file = append(filename)?;
+    let last = voltages.ncols() - 1;
+    for row in 1..=niters {
+        writeln!(
+            file,
+            "{:e},{:e},{:e},{:e},{:e}",
+            time(row),
+            voltages[[row, 0]],
+            currents[[row, 0]],
+            voltages[[row, last]],
+            currents[[row, last - 1]],
+        )?;
+    }
+    file.flush()?;
+
+    let quantities = [("voltages", voltages), ("currents", currents)];
+    for ((quantity, values), saved) in quantities.into_iter().zip(full) {
+        if saved {
+            let mut file = append(&full_path(filename, quantity))?;
+            for row in 1..=niters {
+                write!(file, "{:e}", time(row))?;
+                for value in values.row(row) {
+                    write!(file, ",{:e}", value)?;
+                }
+                writeln!(file)?;
+            }
+            file.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Gets the header of a file holding a quantity of every point.
+fn full_header(name: &str, len: usize) -> String {
+    std::iter::once("time".to_string())
+        .chain((0..len).map(|index| format!("{}_{}", name, index)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Opens a file for appending rows.
+fn append(path: &Path) -> Result<std::io::BufWriter<std::fs::File>, Error> {
+    let file = std::fs::OpenOptions::new().append(true).open(path)?;
+    Ok(std::io::BufWriter::new(file))
+}
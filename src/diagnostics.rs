@@ -0,0 +1,95 @@
+use std::cell::RefCell;
+
+use hdf5::types::VarLenUnicode;
+
+use crate::{units, Error, Float};
+
+/// A non-fatal problem noticed during a run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    /// What raised the warning, such as a component name.
+    pub source: String,
+    /// A description of the problem.
+    pub message: String,
+    /// The simulated time of the step that first raised the warning.
+    pub first_time: Float,
+    /// How many times the warning was raised.
+    pub count: usize,
+}
+
+/// Collects the warnings raised on the current thread.
+#[derive(Default)]
+struct Collector {
+    time: Float,
+    warnings: Vec<Warning>,
+}
+
+thread_local! {
+    static COLLECTOR: RefCell<Collector> = RefCell::new(Collector::default());
+}
+
+/// Raises a warning from `source` during a run.
+///
+/// Warnings with the same source and message are collected once and counted, so messages should
+/// not include values that change from step to step.
+pub fn warn(source: &str, message: &str) {
+    COLLECTOR.with(|collector| {
+        let mut collector = collector.borrow_mut();
+        let time = collector.time;
+        match collector.warnings.iter_mut()
+            .find(|warning| warning.source == source && warning.message == message)
+        {
+            Some(warning) => warning.count += 1,
+            None => collector.warnings.push(Warning {
+                source: source.to_string(),
+                message: message.to_string(),
+                first_time: time,
+                count: 1,
+            }),
+        }
+    });
+}
+
+/// Sets the simulated time that newly raised warnings are attributed to.
+pub(crate) fn set_time(time: Float) {
+    COLLECTOR.with(|collector| collector.borrow_mut().time = time);
+}
+
+/// Takes all warnings raised since the last call.
+pub(crate) fn take_warnings() -> Vec<Warning> {
+    COLLECTOR.with(|collector| std::mem::take(&mut collector.borrow_mut().warnings))
+}
+
+/// Writes warnings to the `warnings` group of an HDF5 file, replacing any already there.
+pub(crate) fn write_warnings(file: &hdf5::File, warnings: &[Warning]) -> Result<(), Error> {
+    if file.link_exists("warnings") {
+        file.unlink("warnings")?;
+    }
+    let group = file.create_group("warnings")?;
+
+    // strings stored in HDF5 can't contain null characters
+    let to_unicode = |text: &str| text.replace('\0', "").parse::<VarLenUnicode>().unwrap_or_default();
+    let sources = warnings.iter()
+        .map(|warning| to_unicode(&warning.source))
+        .collect::<ndarray::Array1<_>>();
+    let messages = warnings.iter()
+        .map(|warning| to_unicode(&warning.message))
+        .collect::<ndarray::Array1<_>>();
+    let first_times = warnings.iter()
+        .map(|warning| warning.first_time)
+        .collect::<ndarray::Array1<_>>();
+    let counts = warnings.iter()
+        .map(|warning| warning.count as u64)
+        .collect::<ndarray::Array1<_>>();
+
+    group.new_dataset::<VarLenUnicode>().shape(warnings.len()).create("sources")?.write(&sources)?;
+    group.new_dataset::<VarLenUnicode>().shape(warnings.len()).create("messages")?.write(&messages)?;
+    let first_times_dataset = group.new_dataset::<Float>()
+        .shape(warnings.len())
+        .create("first_times")?;
+    first_times_dataset.write(&first_times)?;
+    units::label_dataset(&first_times_dataset, "s", "time first raised", "warning")?;
+    group.new_dataset::<u64>().shape(warnings.len()).create("counts")?.write(&counts)?;
+
+    Ok(())
+}
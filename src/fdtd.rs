@@ -1,69 +1,304 @@
+pub mod adjoint;
 pub mod components;
+pub mod disorder;
+pub mod grid;
+pub mod loading;
+pub mod taper;
+pub mod waveform;
 
+#[cfg(feature = "cuda")]
+mod cuda_solver;
 mod fdtd_solver;
+#[cfg(feature = "gpu")]
+mod gpu_solver;
+mod lax_wendroff;
+mod moving_window;
+mod multiconductor;
+mod spectral_solver;
 
+#[cfg(feature = "cuda")]
+pub use cuda_solver::{CudaFdtdSolver, CudaFdtdSolverDescriptor, CudaLine};
 pub use fdtd_solver::{FdtdSolver, FdtdSolverDescriptor};
+#[cfg(feature = "gpu")]
+pub use gpu_solver::{GpuFdtdSolver, GpuFdtdSolverDescriptor};
+pub use lax_wendroff::{LaxWendroffSolver, LaxWendroffSolverDescriptor};
+pub use moving_window::{MovingWindowDescriptor, MovingWindowSolver};
+pub use multiconductor::{
+    MulticonductorLine, MulticonductorLineDescriptor, MulticonductorSolver,
+    MulticonductorSolverDescriptor,
+};
+pub use spectral_solver::{SpectralSolver, SpectralSolverDescriptor};
 
-use crate::SimulationParameters;
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+
+/// The small signal parameters per unit length of a cell of a line.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LineParameters {
+    pub inductance: Float,
+    pub capacitance: Float,
+    pub resistance: Float,
+    pub conductance: Float,
+}
 
 /// Describes the behavior of the main simulated line.
 pub trait TransmissionLine: Component {
     fn npoints(&self) -> usize;
-    fn length(&self) -> f32;
-    fn max_phase_velocity(&self) -> f32;
-    fn calculate_simulation_parameters(&self, courant: f32) -> SimulationParameters {
-        let delta_z = self.length() / (self.npoints() as f32);
+    fn length(&self) -> Float;
+    fn max_phase_velocity(&self) -> Float;
+
+    /// The fastest phase velocity within `cells`.
+    ///
+    /// Defaults to the fastest phase velocity anywhere on the line.
+    fn max_phase_velocity_in(&self, _cells: std::ops::Range<usize>) -> Float {
+        self.max_phase_velocity()
+    }
+
+    /// The small signal parameters per unit length at the start of the line, z = 0, which a
+    /// source must share to match it.
+    ///
+    /// Defaults to none, for lines that a source with fixed parameters can't match.
+    fn port_parameters(&self) -> Option<LineParameters> {
+        None
+    }
+
+    fn calculate_simulation_parameters(&self, courant: Float) -> SimulationParameters {
+        let delta_z = self.length() / (self.npoints() as Float);
         let delta_t = delta_z / (courant * self.max_phase_velocity());
 
         SimulationParameters { delta_z, delta_t }
     }
+
+    /// Prepares the line for the time step starting at `time`.
+    ///
+    /// This is called before any voltages or currents of the step are calculated, so lines with
+    /// time dependent parameters can update them.
+    fn begin_step(&mut self, _time: Float, _sim_params: &SimulationParameters) {}
+
+    /// The name, width, and SI units of each additional output channel of the line.
+    fn channels(&self) -> Vec<(String, usize, &'static str)> {
+        Vec::new()
+    }
+
+    /// Records the current value of each additional output channel, in the order of `channels`.
+    fn record_channels(&self, _rows: &mut [ndarray::ArrayViewMut1<Float>]) {}
+
+    /// Captures the configuration of the line.
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new(std::any::type_name::<Self>())
+    }
 }
 
 /// Defines the voltage and current response of a circuit element.
 pub trait Component {
+    /// The number of neighboring points on each side used by the updates.
+    ///
+    /// With a width of `w`, `last_currs` and `last_volts` hold the `2w` values centered on the
+    /// updated point, so the adjacent pair is at `w - 1` and `w`. Points beyond the ends of the
+    /// line repeat the end values, or wrap around on periodic lines.
+    fn stencil_width(&self) -> usize {
+        1
+    }
+
     fn next_voltage(
         &self,
-        next_volt: &mut f32,
-        last_volt: f32,
-        last_currs: ndarray::ArrayView1<f32>,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
         index: usize,
         sim_info: &SimulationParameters,
     );
 
     fn next_current(
         &self,
-        next_curr: &mut f32,
-        last_volts: ndarray::ArrayView1<f32>,
-        last_curr: f32,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
         index: usize,
         sim_info: &SimulationParameters,
     );
+
+    /// The DC voltage across the series element of point `index` when carrying `current`.
+    fn dc_series_voltage(
+        &self,
+        _current: Float,
+        _index: usize,
+        _sim_params: &SimulationParameters,
+    ) -> Float {
+        0.0
+    }
+
+    /// The DC current through the shunt element of point `index` at `voltage`.
+    fn dc_shunt_current(
+        &self,
+        _voltage: Float,
+        _index: usize,
+        _sim_params: &SimulationParameters,
+    ) -> Float {
+        0.0
+    }
+
+    /// The state the updates of point `index` keep between time steps, such as the currents of
+    /// internal branches, so that wrappers probing the updates can put it back. A point at rest
+    /// has a state of all zeros.
+    fn point_state(&self, _index: usize) -> Vec<Float> {
+        Vec::new()
+    }
+
+    /// Puts back the state of point `index` from `point_state`.
+    fn restore_point_state(&self, _index: usize, _state: &[Float]) {}
 }
 
 /// Generates a voltage output at the start of a transmission line.
 pub trait VSource {
+    /// Steps the voltage of the source node, once per time step, so sources can keep state such
+    /// as filters, random number generators, or feedback.
     fn next_voltage(
-        &self,
-        t: f32,
-        last_volt: f32,
-        last_curr: f32,
+        &mut self,
+        t: Float,
+        last_volt: Float,
+        last_curr: Float,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        self.next_voltage_scaled(1.0, t, last_volt, last_curr, sim_params)
+    }
+    fn generate(&self, time: Float) -> Float;
+
+    /// Steps the voltage of the source node as `next_voltage`, with the generated voltage scaled
+    /// by `gain`, so wrappers such as `EnvelopedVSource` can shape any source in time.
+    fn next_voltage_scaled(
+        &mut self,
+        gain: Float,
+        t: Float,
+        last_volt: Float,
+        last_curr: Float,
         sim_params: &SimulationParameters,
-    ) -> f32;
-    fn generate(&self, time: f32) -> f32;
+    ) -> Float;
+
+    /// The time that stepping the source from `t` generates its voltage at, which wrappers
+    /// scaling the voltage evaluate their gain at.
+    ///
+    /// Defaults to `t`.
+    fn generation_time(&self, t: Float, _sim_params: &SimulationParameters) -> Float {
+        t
+    }
+
+    /// The DC voltage of the source node when biased at `bias` and supplying `current` to the line.
+    ///
+    /// Defaults to an ideal voltage source.
+    fn dc_voltage(&self, bias: Float, _current: Float, _sim_params: &SimulationParameters) -> Float {
+        bias
+    }
+
+    /// Captures the configuration of the source.
+    ///
+    /// The generated waveform itself is not captured.
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new(std::any::type_name::<Self>())
+    }
 }
 
 /// Handles end of line boundary conditions, representing a physical terminator.
 pub trait Terminator {
     fn next_voltage(
         &self,
-        last_volt: f32,
-        last_curr: f32,
+        last_volt: Float,
+        last_curr: Float,
         sim_params: &SimulationParameters,
-    ) -> f32;
+    ) -> Float;
     fn next_current(
         &self,
-        last_volts: ndarray::ArrayView1<f32>,
-        last_curr: f32,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
         sim_params: &SimulationParameters,
-    ) -> f32;
+    ) -> Float;
+
+    /// A DC state of the end node, as its voltage and the current flowing into it.
+    ///
+    /// The DC states of a linear terminator form a line through zero, parameterized by `scale`.
+    /// Defaults to an open circuit.
+    fn dc_end(&self, scale: Float, _sim_params: &SimulationParameters) -> (Float, Float) {
+        (scale, 0.0)
+    }
+
+    /// The DC voltage across the series element feeding the end node when carrying `current`.
+    fn dc_series_voltage(&self, _current: Float, _sim_params: &SimulationParameters) -> Float {
+        0.0
+    }
+
+    /// Captures the configuration of the terminator.
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new(std::any::type_name::<Self>())
+    }
+}
+
+/// A relative deviation of line parameters along a line, such as a `Disorder` or a
+/// `PeriodicLoading`.
+pub trait Perturbation: Clone {
+    /// Gets the relative deviation at position `z`.
+    fn deviation(&self, z: Float) -> Float;
+
+    /// Perturbs a parameter function for use in line descriptors.
+    ///
+    /// The returned function evaluates to `f(z) * (1 + deviation(z))`.
+    fn apply<F: Fn(Float) -> Float>(&self, f: F) -> impl Fn(Float) -> Float + use<Self, F> {
+        let perturbation = self.clone();
+        move |z| f(z) * (1.0 + perturbation.deviation(z))
+    }
+
+    /// Perturbs a parameter function the opposite way to `apply`, for use in line descriptors.
+    ///
+    /// The returned function evaluates to `f(z) / (1 + deviation(z))`. Applying this to the
+    /// inductance and `apply` to the capacitance changes the impedance while keeping the phase
+    /// velocity, as a change in width does.
+    fn apply_inverse<F>(&self, f: F) -> impl Fn(Float) -> Float + use<Self, F>
+    where
+        F: Fn(Float) -> Float,
+    {
+        let perturbation = self.clone();
+        move |z| f(z) / (1.0 + perturbation.deviation(z))
+    }
+}
+
+/// Fails at the first non-finite voltage or current, so a run that blows up stops right away.
+///
+/// The first rows of `voltages` and `currents` are at time step `first_step`, counted from time
+/// zero, and the cell of the error indexes the voltages or currents of a state.
+pub(crate) fn check_finite(
+    voltages: ndarray::ArrayView2<Float>,
+    currents: ndarray::ArrayView2<Float>,
+    first_step: usize,
+) -> Result<(), Error> {
+    let rows = voltages.rows().into_iter().zip(currents.rows()).enumerate();
+    for (row, (voltages, currents)) in rows {
+        let quantities = [("voltage", voltages), ("current", currents)];
+        for (quantity, values) in quantities {
+            if let Some(cell) = values.iter().position(|value| !value.is_finite()) {
+                return Err(Error::Diverged {
+                    quantity: quantity.to_string(),
+                    step: first_step + row,
+                    cell,
+                })
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The coefficients of a semi-implicit update `x' = decay*x + gain*drive`.
+#[derive(Copy, Clone)]
+pub(crate) struct Update {
+    pub(crate) decay: Float,
+    pub(crate) gain: Float,
+}
+
+impl Update {
+    /// The update of a reactance `reactance` with a loss of `loss` on each side of the step.
+    pub(crate) fn new(reactance: Float, loss: Float) -> Self {
+        let gain = (reactance + loss).recip();
+        Self {
+            decay: gain * (reactance - loss),
+            gain,
+        }
+    }
 }
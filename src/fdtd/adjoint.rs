@@ -0,0 +1,218 @@
+//! Gradients of objectives with respect to the per cell parameters of a linear line.
+//!
+//! The forward run mirrors an `FdtdSolver` driving a `LinearLine` from a `MatchedVSource` into a
+//! `MatchedTerminator`. A single backward pass of the discrete adjoint then gives the gradient of
+//! the objective with respect to every cell parameter at once.
+
+use crate::{Float, SimulationParameters, SimulationState};
+use crate::fdtd::{Update, VSource};
+use crate::fdtd::components::{MatchedTerminator, MatchedVSource};
+use crate::fdtd::waveform::Waveform;
+
+/// The value of an objective and its gradient with respect to a run.
+pub struct Objective {
+    /// The value of the objective.
+    pub value: Float,
+    /// The gradient with respect to each voltage, with the shape of the run's voltages.
+    pub voltage_gradient: ndarray::Array2<Float>,
+    /// The gradient with respect to each current, with the shape of the run's currents.
+    pub current_gradient: ndarray::Array2<Float>,
+}
+
+/// Describes a sensitivity computation.
+pub struct AdjointDescriptor<Fs, Fo>
+where
+    Fs: Waveform,
+    Fo: Fn(ndarray::ArrayView2<Float>, ndarray::ArrayView2<Float>) -> Objective,
+{
+    /// The capacitance per unit length of each cell.
+    pub capacitances: Vec<Float>,
+    /// The inductance per unit length of each cell.
+    pub inductances: Vec<Float>,
+    /// The resistance per unit length of each cell.
+    pub resistances: Vec<Float>,
+    /// The conductance per unit length of each cell.
+    pub conductances: Vec<Float>,
+    /// The source driving the start of the line.
+    pub source: MatchedVSource<Fs>,
+    /// The terminator at the end of the line.
+    pub terminator: MatchedTerminator,
+    /// The parameters of the simulation.
+    pub sim_params: SimulationParameters,
+    /// The state the run starts in, or `None` to start at rest.
+    pub init_state: Option<SimulationState>,
+    /// The number of time steps to run.
+    pub nsteps: usize,
+    /// Evaluates the objective from the voltages and currents of the run.
+    ///
+    /// Like `ComputeOutput`, the arrays have one row for the initial state and each time step.
+    pub objective: Fo,
+}
+
+/// The gradients of an objective with respect to the parameters of each cell.
+#[derive(Clone, Debug)]
+pub struct Sensitivities {
+    /// The value of the objective.
+    pub objective: Float,
+    /// The gradient with respect to the capacitance per unit length of each cell.
+    pub capacitance: Vec<Float>,
+    /// The gradient with respect to the inductance per unit length of each cell.
+    pub inductance: Vec<Float>,
+    /// The gradient with respect to the resistance per unit length of each cell.
+    pub resistance: Vec<Float>,
+    /// The gradient with respect to the conductance per unit length of each cell.
+    pub conductance: Vec<Float>,
+}
+
+/// Runs the line forward and computes the sensitivities of the objective with a backward pass.
+///
+/// The whole run is kept in memory for the backward pass.
+pub fn sensitivities<Fs, Fo>(desc: AdjointDescriptor<Fs, Fo>) -> Sensitivities
+where
+    Fs: Waveform,
+    Fo: Fn(ndarray::ArrayView2<Float>, ndarray::ArrayView2<Float>) -> Objective,
+{
+    let npoints = desc.capacitances.len();
+    let nsteps = desc.nsteps;
+    let SimulationParameters { delta_z, delta_t } = desc.sim_params;
+    let d_ratio = delta_z / delta_t;
+    let (source, terminator) = (&desc.source, &desc.terminator);
+
+    // the updates of each line point, which also give the derivatives with respect to parameters
+    let volt_updates = (0..npoints)
+        .map(|n| Update::new(d_ratio*desc.capacitances[n], delta_z*desc.conductances[n]/2.0))
+        .collect::<Vec<_>>();
+    let curr_updates = (0..npoints)
+        .map(|n| Update::new(d_ratio*desc.inductances[n], delta_z*desc.resistances[n]/2.0))
+        .collect::<Vec<_>>();
+    let source_resistance = delta_z*source.resistance
+        + Float::sqrt(source.inductance / source.capacitance);
+    let source_curr_update = Update::new(d_ratio*source.inductance, source_resistance/2.0);
+    let source_volt_update = Update::new(
+        d_ratio*source.capacitance,
+        delta_z*source.conductance/2.0,
+    );
+    let load_conductance = delta_z*terminator.conductance
+        + Float::sqrt(terminator.capacitance / terminator.inductance);
+    let end_volt_update = Update::new(d_ratio*terminator.capacitance, load_conductance/2.0);
+    let end_curr_update = Update::new(
+        d_ratio*terminator.inductance,
+        delta_z*terminator.resistance/2.0,
+    );
+
+    // run forward, exactly as the solver does
+    let mut voltages = ndarray::Array2::<Float>::zeros((nsteps + 1, npoints + 2));
+    let mut currents = ndarray::Array2::<Float>::zeros((nsteps + 1, npoints + 1));
+    let start_time = desc.init_state.as_ref().map_or(0.0, |state| state.time);
+    if let Some(ref state) = desc.init_state {
+        voltages.row_mut(0).assign(&state.voltages);
+        currents.row_mut(0).assign(&state.currents);
+    }
+    for t_index in 0..nsteps {
+        let t = (t_index as Float)*delta_t + start_time;
+        let last_volts = voltages.row(t_index).to_owned();
+        let last_currs = currents.row(t_index).to_owned();
+
+        let source_curr = source_curr_update.decay*last_currs[0]
+            + source_curr_update.gain*(source.generate(t) - last_volts[0]);
+        voltages[[t_index+1, 0]] = source_volt_update.decay*last_volts[0]
+            + source_volt_update.gain*(source_curr - last_currs[0]);
+        for k in 1..=npoints {
+            let update = volt_updates[k-1];
+            voltages[[t_index+1, k]] = update.decay*last_volts[k]
+                + update.gain*(last_currs[k-1] - last_currs[k]);
+        }
+        voltages[[t_index+1, npoints+1]] = end_volt_update.decay*last_volts[npoints+1]
+            + end_volt_update.gain*last_currs[npoints];
+
+        for n in 0..=npoints {
+            let update = if n < npoints { curr_updates[n] } else { end_curr_update };
+            currents[[t_index+1, n]] = update.decay*last_currs[n]
+                + update.gain*(voltages[[t_index+1, n]] - voltages[[t_index+1, n+1]]);
+        }
+    }
+
+    let objective = (desc.objective)(voltages.view(), currents.view());
+
+    // the derivatives of an update's coefficients with respect to its reactance and loss, per unit
+    // of the reactance and loss coefficients
+    let reactance_derivatives = |update: Update| {
+        (update.gain*(1.0 - update.decay), -update.gain.powi(2))
+    };
+    let loss_derivatives = |update: Update| {
+        (-update.gain*(1.0 + update.decay), -update.gain.powi(2))
+    };
+
+    // propagate the adjoint state backwards through each step
+    let mut volt_adjoint = objective.voltage_gradient.row(nsteps).mapv(|x| x as f64);
+    let mut curr_adjoint = objective.current_gradient.row(nsteps).mapv(|x| x as f64);
+    let mut gradients = [(); 4].map(|_| vec![0.0f64; npoints]);
+    for t_index in (0..nsteps).rev() {
+        let (last_volts, last_currs) = (voltages.row(t_index), currents.row(t_index));
+        let next_volts = voltages.row(t_index+1);
+        let mut last_volt_adjoint = ndarray::Array1::<f64>::zeros(npoints + 2);
+        let mut last_curr_adjoint = ndarray::Array1::<f64>::zeros(npoints + 1);
+
+        // currents, which depend on the last currents and the next voltages
+        for n in 0..=npoints {
+            let update = if n < npoints { curr_updates[n] } else { end_curr_update };
+            let adjoint = curr_adjoint[n];
+            let drive = next_volts[n] - next_volts[n+1];
+            last_curr_adjoint[n] += (update.decay as f64) * adjoint;
+            volt_adjoint[n] += (update.gain as f64) * adjoint;
+            volt_adjoint[n+1] -= (update.gain as f64) * adjoint;
+
+            if n < npoints {
+                let (d_decay, d_gain) = reactance_derivatives(update);
+                let sensitivity = (d_decay*last_currs[n] + d_gain*drive) as f64 * adjoint;
+                gradients[1][n] += sensitivity * (d_ratio as f64);
+                let (d_decay, d_gain) = loss_derivatives(update);
+                let sensitivity = (d_decay*last_currs[n] + d_gain*drive) as f64 * adjoint;
+                gradients[2][n] += sensitivity * (delta_z as f64) / 2.0;
+            }
+        }
+
+        // voltages, which depend on the last voltages and currents
+        let adjoint = volt_adjoint[0];
+        last_volt_adjoint[0] += ((source_volt_update.decay
+            - source_volt_update.gain*source_curr_update.gain) as f64) * adjoint;
+        last_curr_adjoint[0] += (source_volt_update.gain*(source_curr_update.decay - 1.0)) as f64
+            * adjoint;
+        for k in 1..=npoints {
+            let update = volt_updates[k-1];
+            let adjoint = volt_adjoint[k];
+            let drive = last_currs[k-1] - last_currs[k];
+            last_volt_adjoint[k] += (update.decay as f64) * adjoint;
+            last_curr_adjoint[k-1] += (update.gain as f64) * adjoint;
+            last_curr_adjoint[k] -= (update.gain as f64) * adjoint;
+
+            let (d_decay, d_gain) = reactance_derivatives(update);
+            let sensitivity = (d_decay*last_volts[k] + d_gain*drive) as f64 * adjoint;
+            gradients[0][k-1] += sensitivity * (d_ratio as f64);
+            let (d_decay, d_gain) = loss_derivatives(update);
+            let sensitivity = (d_decay*last_volts[k] + d_gain*drive) as f64 * adjoint;
+            gradients[3][k-1] += sensitivity * (delta_z as f64) / 2.0;
+        }
+        let adjoint = volt_adjoint[npoints+1];
+        last_volt_adjoint[npoints+1] += (end_volt_update.decay as f64) * adjoint;
+        last_curr_adjoint[npoints] += (end_volt_update.gain as f64) * adjoint;
+
+        // the initial state is fixed, so only later states gain the objective's direct dependence
+        if t_index > 0 {
+            last_volt_adjoint += &objective.voltage_gradient.row(t_index).mapv(|x| x as f64);
+            last_curr_adjoint += &objective.current_gradient.row(t_index).mapv(|x| x as f64);
+        }
+        volt_adjoint = last_volt_adjoint;
+        curr_adjoint = last_curr_adjoint;
+    }
+
+    let [capacitance, inductance, resistance, conductance] = gradients
+        .map(|gradient| gradient.into_iter().map(|x| x as Float).collect::<Vec<_>>());
+    Sensitivities {
+        objective: objective.value,
+        capacitance,
+        inductance,
+        resistance,
+        conductance,
+    }
+}
@@ -1,11 +1,47 @@
 //! Circuit components.
 
+mod antenna;
+mod cascaded_line;
+mod crlh_line;
+mod diode_line;
+mod dispersive_line;
+mod enveloped_vsource;
+mod ferrite_line;
 mod linear_line;
+mod lumped_line;
 mod ki_line;
+mod recording;
+mod resonator_line;
+mod skin_effect_line;
+mod tabulated_line;
 mod vsource;
 mod terminator;
+mod thermal_ki_line;
+mod thevenin_vsource;
+mod time_varying_line;
+mod two_fluid_line;
+mod varactor_line;
+mod wall;
 
+pub use antenna::{AntennaTerminator, AntennaTerminatorDescriptor};
+pub use cascaded_line::{CascadedLine, CascadedLineDescriptor};
+pub use crlh_line::{CrlhLine, CrlhLineDescriptor};
+pub use diode_line::{Diode, DiodeLine, DiodeLineDescriptor};
+pub use dispersive_line::{DielectricPole, DispersiveLine, DispersiveLineDescriptor};
+pub use enveloped_vsource::{Envelope, EnvelopedVSource};
+pub use ferrite_line::{FerriteLine, FerriteLineDescriptor};
 pub use linear_line::{LinearLine, LinearLineDescriptor};
-pub use ki_line::{KiLine, KiLineDescriptor};
+pub use lumped_line::{LumpedElement, LumpedLine};
+pub use ki_line::{KiLine, KiLineDescriptor, KiNonlinearity};
+pub use recording::{LoadRecord, LoadRecorder, RecordingTerminator};
+pub use resonator_line::{Resonator, ResonatorLine, ResonatorLineDescriptor};
+pub use skin_effect_line::{SkinEffectLine, SkinEffectLineDescriptor};
+pub use tabulated_line::{TableInterpolation, TabulatedLine, TabulatedLineDescriptor};
 pub use terminator::{MatchedTerminator};
+pub use thermal_ki_line::{ThermalKiLine, ThermalKiLineDescriptor};
+pub use thevenin_vsource::{TheveninVSource, TheveninVSourceDescriptor};
+pub use time_varying_line::{TimeVaryingLine, TimeVaryingLineDescriptor};
+pub use two_fluid_line::{TwoFluidLine, TwoFluidLineDescriptor};
+pub use varactor_line::{VaractorLine, VaractorLineDescriptor};
 pub use vsource::{MatchedVSource};
+pub use wall::{ElectricWall, MagneticWall};
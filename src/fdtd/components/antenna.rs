@@ -0,0 +1,211 @@
+use std::cell::Cell;
+
+use num_complex::Complex;
+
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::Terminator;
+
+/// Describes an `AntennaTerminator`.
+///
+/// The antenna is modeled by a series loss resistance, inductance, and capacitance feeding a
+/// shunt inductance in parallel with the radiation resistance. The resulting radiation resistance
+/// grows as `ω²` at low frequencies and saturates at `radiation_resistance`, as for an electrically
+/// small antenna, while the series elements shape the reactance and the resonance.
+pub struct AntennaTerminatorDescriptor {
+    /// The inductance per unit length of the line feeding the antenna.
+    pub inductance: Float,
+    /// The capacitance per unit length of the line feeding the antenna.
+    pub capacitance: Float,
+    /// The resistance per unit length of the line feeding the antenna.
+    pub resistance: Float,
+    /// The conductance per unit length of the line feeding the antenna.
+    pub conductance: Float,
+    /// The ohmic loss resistance of the antenna.
+    pub loss_resistance: Float,
+    /// The series inductance of the antenna.
+    pub series_inductance: Float,
+    /// The series capacitance of the antenna, or infinity for none.
+    pub series_capacitance: Float,
+    /// The shunt inductance in parallel with the radiation resistance.
+    pub shunt_inductance: Float,
+    /// The high frequency limit of the radiation resistance.
+    pub radiation_resistance: Float,
+}
+
+/// Terminates the line with a radiating antenna.
+pub struct AntennaTerminator {
+    inductance: Float,
+    capacitance: Float,
+    resistance: Float,
+    conductance: Float,
+    loss_resistance: Float,
+    series_inductance: Float,
+    series_capacitance: Float,
+    shunt_inductance: Float,
+    radiation_resistance: Float,
+    // series current, series capacitor charge, and shunt inductor current
+    state: Cell<[Float; 3]>,
+}
+
+impl AntennaTerminator {
+    /// Creates a new `AntennaTerminator` instance.
+    #[inline]
+    pub fn new(desc: AntennaTerminatorDescriptor) -> Self {
+        Self {
+            inductance: desc.inductance,
+            capacitance: desc.capacitance,
+            resistance: desc.resistance,
+            conductance: desc.conductance,
+            loss_resistance: desc.loss_resistance,
+            series_inductance: desc.series_inductance,
+            series_capacitance: desc.series_capacitance,
+            shunt_inductance: desc.shunt_inductance,
+            radiation_resistance: desc.radiation_resistance,
+            state: Cell::new([0.0; 3]),
+        }
+    }
+
+    /// Rebuilds an `AntennaTerminator` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("AntennaTerminator")?;
+        let state = snapshot.get("state")?;
+        if state.len() != 3 {
+            return Err(Error::BadSnapshot("AntennaTerminator state has the wrong length".to_string()))
+        }
+
+        let antenna = Self::new(AntennaTerminatorDescriptor {
+            inductance: snapshot.get_scalar("inductance")?,
+            capacitance: snapshot.get_scalar("capacitance")?,
+            resistance: snapshot.get_scalar("resistance")?,
+            conductance: snapshot.get_scalar("conductance")?,
+            loss_resistance: snapshot.get_scalar("loss_resistance")?,
+            series_inductance: snapshot.get_scalar("series_inductance")?,
+            series_capacitance: snapshot.get_scalar("series_capacitance")?,
+            shunt_inductance: snapshot.get_scalar("shunt_inductance")?,
+            radiation_resistance: snapshot.get_scalar("radiation_resistance")?,
+        });
+        antenna.state.set([state[0], state[1], state[2]]);
+
+        Ok(antenna)
+    }
+
+    /// Calculates the input impedance of the antenna at `frequency`.
+    pub fn impedance(&self, frequency: Float) -> Complex<Float> {
+        let jw = Complex::new(0.0, 2.0 * crate::consts::PI * frequency);
+        let shunt = jw*self.shunt_inductance*self.radiation_resistance
+            / (jw*self.shunt_inductance + self.radiation_resistance);
+        let series_cap = if self.series_capacitance.is_finite() {
+            (jw*self.series_capacitance).inv()
+        } else {
+            Complex::new(0.0, 0.0)
+        };
+
+        self.loss_resistance + jw*self.series_inductance + series_cap + shunt
+    }
+
+    /// Gets the power currently radiated by the antenna.
+    pub fn radiated_power(&self) -> Float {
+        let [series_curr, _, shunt_curr] = self.state.get();
+        self.radiation_resistance * (series_curr - shunt_curr).powi(2)
+    }
+}
+
+impl Terminator for AntennaTerminator {
+    fn next_voltage(
+        &self,
+        last_volt: Float,
+        last_curr: Float,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        let delta_t = sim_params.delta_t as f64;
+        let half_t = delta_t / 2.0;
+        let r_loss = self.loss_resistance as f64;
+        let r_rad = self.radiation_resistance as f64;
+        let elastance = (self.series_capacitance as f64).recip();
+
+        // E dx/dt = A x + b v, for x = (series current, series charge, shunt current)
+        let e = [self.series_inductance as f64, 1.0, self.shunt_inductance as f64];
+        let a = [
+            [-(r_loss + r_rad), -elastance, r_rad],
+            [1.0, 0.0, 0.0],
+            [r_rad, 0.0, -r_rad],
+        ];
+        let implicit = |sign: f64| {
+            let mut m = [[0.0; 3]; 3];
+            for i in 0..3 {
+                for j in 0..3 {
+                    m[i][j] = sign * half_t * a[i][j] + if i == j { e[i] } else { 0.0 };
+                }
+            }
+            m
+        };
+        let (lhs, rhs) = (implicit(-1.0), implicit(1.0));
+
+        // trapezoidal update, split into a part independent of and a part linear in the next voltage
+        let last_state = self.state.get().map(|x| x as f64);
+        let mut history = [0.0; 3];
+        for i in 0..3 {
+            history[i] = (0..3).map(|j| rhs[i][j] * last_state[j]).sum::<f64>();
+        }
+        history[0] += half_t * (last_volt as f64);
+        let fixed = solve3(lhs, history);
+        let linear = solve3(lhs, [half_t, 0.0, 0.0]);
+
+        // solve the node equation with the antenna current averaged over the step
+        let delta_z = sim_params.delta_z as f64;
+        let node_cap = delta_z * (self.capacitance as f64) / delta_t;
+        let node_cond = delta_z * (self.conductance as f64) / 2.0;
+        let next_volt = (node_cap + node_cond + linear[0] / 2.0).recip()
+            * ( (node_cap - node_cond) * (last_volt as f64) + (last_curr as f64)
+                - (fixed[0] + last_state[0]) / 2.0 );
+
+        self.state.set([0, 1, 2].map(|i| (fixed[i] + linear[i]*next_volt) as Float));
+
+        next_volt as Float
+    }
+
+    fn next_current(
+        &self,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+
+        (d_ratio*self.inductance + sim_params.delta_z*self.resistance/2.0).recip()
+            *  ( (d_ratio*self.inductance - sim_params.delta_z*self.resistance/2.0) * last_curr
+                + (last_volts[0] - last_volts[1]) )
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("AntennaTerminator")
+            .with("inductance", vec![self.inductance])
+            .with("capacitance", vec![self.capacitance])
+            .with("resistance", vec![self.resistance])
+            .with("conductance", vec![self.conductance])
+            .with("loss_resistance", vec![self.loss_resistance])
+            .with("series_inductance", vec![self.series_inductance])
+            .with("series_capacitance", vec![self.series_capacitance])
+            .with("shunt_inductance", vec![self.shunt_inductance])
+            .with("radiation_resistance", vec![self.radiation_resistance])
+            .with("state", self.state.get().to_vec())
+    }
+}
+
+/// Solves a 3x3 linear system with Cramer's rule.
+fn solve3(m: [[f64; 3]; 3], b: [f64; 3]) -> [f64; 3] {
+    let det = |m: [[f64; 3]; 3]| {
+        m[0][0]*(m[1][1]*m[2][2] - m[1][2]*m[2][1])
+            - m[0][1]*(m[1][0]*m[2][2] - m[1][2]*m[2][0])
+            + m[0][2]*(m[1][0]*m[2][1] - m[1][1]*m[2][0])
+    };
+    let total = det(m);
+
+    [0, 1, 2].map(|col| {
+        let mut replaced = m;
+        for row in 0..3 {
+            replaced[row][col] = b[row];
+        }
+        det(replaced) / total
+    })
+}
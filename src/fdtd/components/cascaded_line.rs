@@ -0,0 +1,201 @@
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+
+/// Describes a `CascadedLine`.
+pub struct CascadedLineDescriptor {
+    /// The lines joined end to end, from the start of the line.
+    pub segments: Vec<Box<dyn TransmissionLine>>,
+}
+
+/// Several lines joined end to end, such as a `KiLine` between two `LinearLine` leads.
+///
+/// Each cell is updated by the segment containing it, with the index of the cell within that
+/// segment, so every segment must have the same cell length. The current joining two segments is
+/// updated by the segment after the junction, as its first current. Segments with wide stencils
+/// see the values of their neighbors near a junction.
+pub struct CascadedLine {
+    segments: Vec<Box<dyn TransmissionLine>>,
+    /// The index of the first cell of each segment.
+    offsets: Vec<usize>,
+    /// The widest stencil of any segment.
+    width: usize,
+    npoints: usize,
+    length: Float,
+}
+impl CascadedLine {
+    pub fn new(desc: CascadedLineDescriptor) -> Result<Self, Error> {
+        let first = desc.segments.first()
+            .ok_or_else(|| Error::BadGrid("a cascaded line needs a segment".to_string()))?;
+        let delta_z = first.length() / (first.npoints() as Float);
+        let mut offsets = Vec::new();
+        let (mut npoints, mut length) = (0, 0.0);
+        for (k, segment) in desc.segments.iter().enumerate() {
+            let segment_delta_z = segment.length() / (segment.npoints() as Float);
+            if (segment_delta_z - delta_z).abs() > 1e-6 * delta_z {
+                return Err(Error::BadGrid(format!(
+                    "segment {} has cells of length {} instead of {}", k, segment_delta_z, delta_z,
+                )))
+            }
+            offsets.push(npoints);
+            npoints += segment.npoints();
+            length += segment.length();
+        }
+
+        Ok(Self {
+            width: desc.segments.iter().map(|segment| segment.stencil_width().max(1)).max()
+                .unwrap_or(1),
+            segments: desc.segments,
+            offsets,
+            npoints,
+            length,
+        })
+    }
+
+    /// The segments of the line, from the start of the line.
+    pub fn segments(&self) -> &[Box<dyn TransmissionLine>] {
+        &self.segments
+    }
+
+    /// The segment containing cell `index`, and the index of the cell within it.
+    #[inline]
+    fn locate(&self, index: usize) -> (&dyn TransmissionLine, usize) {
+        let segment = self.offsets.partition_point(|&offset| offset <= index) - 1;
+        (self.segments[segment].as_ref(), index - self.offsets[segment])
+    }
+
+    /// Narrows a stencil of the whole line to the width of `segment`.
+    #[inline]
+    fn narrow<'a>(
+        &self,
+        stencil: ndarray::ArrayView1<'a, Float>,
+        segment: &dyn TransmissionLine,
+    ) -> ndarray::ArrayView1<'a, Float> {
+        let margin = self.width - segment.stencil_width().max(1);
+        stencil.slice_move(ndarray::s![margin..(stencil.len() - margin)])
+    }
+}
+impl Component for CascadedLine {
+    fn stencil_width(&self) -> usize {
+        self.width
+    }
+    #[inline]
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let (segment, local) = self.locate(index);
+        let last_currs = self.narrow(last_currs, segment);
+        segment.next_voltage(next_volt, last_volt, last_currs, local, sim_params);
+    }
+    #[inline]
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let (segment, local) = self.locate(index);
+        let last_volts = self.narrow(last_volts, segment);
+        segment.next_current(next_curr, last_volts, last_curr, local, sim_params);
+    }
+    #[inline]
+    fn dc_series_voltage(
+        &self,
+        current: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        let (segment, local) = self.locate(index);
+        segment.dc_series_voltage(current, local, sim_params)
+    }
+    #[inline]
+    fn dc_shunt_current(
+        &self,
+        voltage: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        let (segment, local) = self.locate(index);
+        segment.dc_shunt_current(voltage, local, sim_params)
+    }
+    fn point_state(&self, index: usize) -> Vec<Float> {
+        let (segment, local) = self.locate(index);
+        segment.point_state(local)
+    }
+    fn restore_point_state(&self, index: usize, state: &[Float]) {
+        let (segment, local) = self.locate(index);
+        segment.restore_point_state(local, state)
+    }
+}
+impl TransmissionLine for CascadedLine {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.npoints
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.length
+    }
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.max_phase_velocity_in(0..self.npoints)
+    }
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        self.segments.iter().zip(&self.offsets)
+            .filter_map(|(segment, &offset)| {
+                let start = cells.start.max(offset) - offset;
+                let end = cells.end.min(offset + segment.npoints()).saturating_sub(offset);
+                (start < end).then(|| segment.max_phase_velocity_in(start..end))
+            })
+            .fold(0.0, |accum, item| if accum >= item { accum } else { item })
+    }
+    /// The parameters of the first segment.
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        self.segments[0].port_parameters()
+    }
+    fn begin_step(&mut self, time: Float, sim_params: &SimulationParameters) {
+        for segment in &mut self.segments {
+            segment.begin_step(time, sim_params);
+        }
+    }
+    /// The channels of every segment, with the names prefixed by `segment_<k>_`.
+    fn channels(&self) -> Vec<(String, usize, &'static str)> {
+        self.segments.iter().enumerate()
+            .flat_map(|(k, segment)| {
+                segment.channels().into_iter()
+                    .map(move |(name, width, units)| {
+                        (format!("segment_{}_{}", k, name), width, units)
+                    })
+            })
+            .collect()
+    }
+    fn record_channels(&self, rows: &mut [ndarray::ArrayViewMut1<Float>]) {
+        let mut rest = rows;
+        for segment in &self.segments {
+            let (own, others) = rest.split_at_mut(segment.channels().len());
+            segment.record_channels(own);
+            rest = others;
+        }
+    }
+    /// The parameters of every segment, with the names prefixed by `segment_<k>_`.
+    ///
+    /// The kinds of the segments are not captured.
+    fn snapshot(&self) -> ComponentSnapshot {
+        let mut snapshot = ComponentSnapshot::new("CascadedLine")
+            .with("length", vec![self.length])
+            .with("offsets", self.offsets.iter().map(|&offset| offset as Float).collect());
+        for (k, segment) in self.segments.iter().enumerate() {
+            for (name, values) in segment.snapshot().parameters {
+                snapshot = snapshot.with(&format!("segment_{}_{}", k, name), values);
+            }
+        }
+        snapshot
+    }
+}
@@ -0,0 +1,195 @@
+use std::cell::Cell;
+
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+
+/// Describes a `CrlhLine`.
+///
+/// The left handed elements are given in the distributed form, so a cell of length `Δz` has a
+/// series capacitance of `series_capacitance_fn(z) / Δz` and a shunt inductance of
+/// `shunt_inductance_fn(z) / Δz`. An infinite value removes the element.
+pub struct CrlhLineDescriptor<
+    Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+    Fsc: Fn(Float) -> Float, Fsl: Fn(Float) -> Float,
+>{
+    pub length: Float,
+    pub npoints: usize,
+    /// The right handed shunt capacitance per unit length.
+    pub capacitance_fn: Fc,
+    /// The right handed series inductance per unit length.
+    pub inductance_fn: Fl,
+    /// The left handed series capacitance times unit length.
+    pub series_capacitance_fn: Fsc,
+    /// The left handed shunt inductance times unit length.
+    pub shunt_inductance_fn: Fsl,
+}
+
+/// A composite right/left handed transmission line.
+///
+/// Each cell is a right handed line cell loaded by a series capacitor and a shunt inductor, giving
+/// backward wave propagation below the transition frequency.
+pub struct CrlhLine {
+    cap: Vec<Float>,
+    ind: Vec<Float>,
+    series_cap: Vec<Float>,
+    shunt_ind: Vec<Float>,
+    // series capacitor charge at each current and shunt inductor current at each voltage
+    charge: Vec<Cell<Float>>,
+    shunt_curr: Vec<Cell<Float>>,
+    npoints: usize,
+    length: Float,
+}
+impl CrlhLine {
+    pub fn new<
+        Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+        Fsc: Fn(Float) -> Float, Fsl: Fn(Float) -> Float,
+    >(
+        desc: CrlhLineDescriptor<Fc, Fl, Fsc, Fsl>,
+    ) -> Self {
+        let delta_z = desc.length / (desc.npoints as Float);
+
+        Self {
+            cap: (0..desc.npoints)
+                .map(|n| { (desc.capacitance_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            ind: (0..desc.npoints)
+                .map(|n| { (desc.inductance_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            series_cap: (0..desc.npoints)
+                .map(|n| { (desc.series_capacitance_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            shunt_ind: (0..desc.npoints)
+                .map(|n| { (desc.shunt_inductance_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            charge: (0..desc.npoints).map(|_| Cell::new(0.0)).collect(),
+            shunt_curr: (0..desc.npoints).map(|_| Cell::new(0.0)).collect(),
+            npoints: desc.npoints,
+            length: desc.length,
+        }
+    }
+
+    /// Rebuilds a `CrlhLine` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("CrlhLine")?;
+        let cap = snapshot.get("capacitance")?.clone();
+        let npoints = cap.len();
+        let per_cell = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != npoints {
+                return Err(Error::BadSnapshot(format!("CrlhLine {} has the wrong length", name)))
+            }
+            Ok(values.clone())
+        };
+
+        Ok(Self {
+            ind: per_cell("inductance")?,
+            series_cap: per_cell("series_capacitance")?,
+            shunt_ind: per_cell("shunt_inductance")?,
+            charge: per_cell("charge")?.into_iter().map(Cell::new).collect(),
+            shunt_curr: per_cell("shunt_current")?.into_iter().map(Cell::new).collect(),
+            cap,
+            npoints,
+            length: snapshot.get_scalar("length")?,
+        })
+    }
+}
+impl Component for CrlhLine {
+    #[inline]
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+        // change in shunt inductor current per volt, averaged over the step
+        let shunt = sim_params.delta_t * sim_params.delta_z / self.shunt_ind[index] / 2.0;
+        let last_shunt_curr = self.shunt_curr[index].get();
+
+        *next_volt = (d_ratio*self.cap[index] + shunt/2.0).recip()
+            * ( (d_ratio*self.cap[index] - shunt/2.0) * last_volt
+                + (last_currs[0] - last_currs[1]) - last_shunt_curr );
+        self.shunt_curr[index].set(last_shunt_curr + shunt * (last_volt + *next_volt));
+    }
+    #[inline]
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+        let elastance = sim_params.delta_z / self.series_cap[index];
+        let last_charge = self.charge[index].get();
+
+        *next_curr = (d_ratio*self.ind[index] + elastance*sim_params.delta_t/4.0).recip()
+            * ( (d_ratio*self.ind[index] - elastance*sim_params.delta_t/4.0) * last_curr
+                + (last_volts[0] - last_volts[1]) - elastance*last_charge );
+        self.charge[index].set(last_charge + sim_params.delta_t * (last_curr + *next_curr) / 2.0);
+    }
+    fn point_state(&self, index: usize) -> Vec<Float> {
+        vec![self.charge[index].get(), self.shunt_curr[index].get()]
+    }
+    fn restore_point_state(&self, index: usize, state: &[Float]) {
+        self.charge[index].set(state[0]);
+        self.shunt_curr[index].set(state[1]);
+    }
+}
+impl TransmissionLine for CrlhLine {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.npoints
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.length
+    }
+    /// The high frequency, right handed phase velocity.
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.ind.iter().zip(self.cap.iter())
+            .map(|(ind, cap)| Float::sqrt(ind * cap).recip())
+            .reduce(|accum, item| if accum >= item { accum } else { item })
+            .unwrap()
+    }
+    /// The right handed parameters when the line is balanced at the start, where they give its
+    /// impedance at every frequency, or none otherwise.
+    fn port_parameters(&self) -> Option<LineParameters> {
+        let right = self.ind[0] / self.cap[0];
+        let left = self.shunt_ind[0] / self.series_cap[0];
+        if (right - left).abs() > 1e-6 * right {
+            return None
+        }
+        Some(LineParameters {
+            inductance: self.ind[0],
+            capacitance: self.cap[0],
+            resistance: 0.0,
+            conductance: 0.0,
+        })
+    }
+    fn channels(&self) -> Vec<(String, usize, &'static str)> {
+        vec![
+            ("charge".to_string(), self.npoints, "C"),
+            ("shunt_current".to_string(), self.npoints, "A"),
+        ]
+    }
+    fn record_channels(&self, rows: &mut [ndarray::ArrayViewMut1<Float>]) {
+        rows[0].iter_mut().zip(&self.charge).for_each(|(value, charge)| *value = charge.get());
+        rows[1].iter_mut().zip(&self.shunt_curr).for_each(|(value, curr)| *value = curr.get());
+    }
+    #[inline]
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("CrlhLine")
+            .with("length", vec![self.length])
+            .with("capacitance", self.cap.clone())
+            .with("inductance", self.ind.clone())
+            .with("series_capacitance", self.series_cap.clone())
+            .with("shunt_inductance", self.shunt_ind.clone())
+            .with("charge", self.charge.iter().map(Cell::get).collect())
+            .with("shunt_current", self.shunt_curr.iter().map(Cell::get).collect())
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{warn, ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+
+/// The forward bias, as a fraction of the junction potential, past which the junction capacitance
+/// is extrapolated linearly instead of diverging.
+const FORWARD_COEFFICIENT: Float = 0.5;
+
+/// The parameters of a diode connected from the line to ground, with its anode on the line.
+#[derive(Copy, Clone, Debug)]
+pub struct Diode {
+    /// The reverse saturation current.
+    pub saturation_current: Float,
+    /// The ideality factor times the thermal voltage.
+    pub emission_voltage: Float,
+    /// The junction capacitance at zero bias.
+    pub junction_capacitance: Float,
+    /// The built in potential of the junction.
+    pub junction_potential: Float,
+    /// The grading coefficient of the junction, 1/2 for abrupt junctions.
+    pub grading: Float,
+}
+impl Diode {
+    /// The current through the diode at `voltage`, and its derivative.
+    fn current(&self, voltage: Float) -> (Float, Float) {
+        let exponential = Float::exp(voltage / self.emission_voltage);
+        (
+            self.saturation_current * (exponential - 1.0),
+            self.saturation_current * exponential / self.emission_voltage,
+        )
+    }
+
+    /// The junction capacitance at `voltage`, and its derivative.
+    fn capacitance(&self, voltage: Float) -> (Float, Float) {
+        let Diode { junction_capacitance: cj0, junction_potential: phi, grading: m, .. } = *self;
+        if voltage < FORWARD_COEFFICIENT*phi {
+            let depletion = 1.0 - voltage/phi;
+            (cj0*depletion.powf(-m), cj0*m/phi*depletion.powf(-m - 1.0))
+        } else {
+            let scale = cj0*(1.0 - FORWARD_COEFFICIENT).powf(-1.0 - m);
+            (scale*(1.0 - FORWARD_COEFFICIENT*(1.0 + m) + m*voltage/phi), scale*m/phi)
+        }
+    }
+}
+
+/// Describes a `DiodeLine`.
+pub struct DiodeLineDescriptor<Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float> {
+    pub length: Float,
+    pub npoints: usize,
+    /// The capacitance per unit length of the unloaded line.
+    pub capacitance_fn: Fc,
+    /// The inductance per unit length of the unloaded line.
+    pub inductance_fn: Fl,
+    /// The distance between diodes, with the first half of this from the start of the line.
+    pub diode_spacing: Float,
+    pub diode: Diode,
+    /// The most Newton iterations taken to solve for each voltage at a diode.
+    ///
+    /// Lines need at least one.
+    pub max_iterations: usize,
+    /// The step size, in volts, at which an iteration has converged.
+    pub tolerance: Float,
+}
+
+/// A nonlinear transmission line periodically loaded by shunt diodes.
+///
+/// The voltage dependent junction capacitance makes higher voltages travel at different speeds,
+/// which steepens edges into shock waves or breaks pulses into solitons, with the diode spacing
+/// setting the cutoff that limits how steep they get. Forward biased diodes also conduct, with an
+/// exponential I-V curve.
+///
+/// Each diode sits on the voltage of the cell containing its position, so the spacing should span
+/// several cells.
+pub struct DiodeLine {
+    cap: Vec<Float>,
+    ind: Vec<Float>,
+    diodes: Vec<bool>,
+    diode: Diode,
+    npoints: usize,
+    length: Float,
+    max_iterations: usize,
+    tolerance: Float,
+}
+impl DiodeLine {
+    pub fn new<Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float>(
+        desc: DiodeLineDescriptor<Fc, Fl>,
+    ) -> Result<Self, Error> {
+        if desc.max_iterations == 0 {
+            let message = "DiodeLine needs at least one Newton iteration";
+            return Err(Error::BadParameter(message.to_string()))
+        }
+        if desc.npoints == 0 {
+            return Err(Error::BadGrid("a diode line needs a cell".to_string()))
+        }
+        let delta_z = desc.length / (desc.npoints as Float);
+        let mut diodes = vec![false; desc.npoints];
+        let mut position = desc.diode_spacing / 2.0;
+        while desc.diode_spacing > 0.0 && position < desc.length {
+            diodes[((position / delta_z) as usize).min(desc.npoints - 1)] = true;
+            position += desc.diode_spacing;
+        }
+
+        Ok(Self {
+            cap: (0..desc.npoints)
+                .map(|n| { (desc.capacitance_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            ind: (0..desc.npoints)
+                .map(|n| { (desc.inductance_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            diodes,
+            diode: desc.diode,
+            npoints: desc.npoints,
+            length: desc.length,
+            max_iterations: desc.max_iterations,
+            tolerance: desc.tolerance,
+        })
+    }
+
+    /// Rebuilds a `DiodeLine` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("DiodeLine")?;
+        let cap = snapshot.get("capacitance")?.clone();
+        let npoints = cap.len();
+        let per_cell = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != npoints {
+                let message = format!("DiodeLine {} has the wrong length", name);
+                return Err(Error::BadSnapshot(message))
+            }
+            Ok(values.clone())
+        };
+
+        Ok(Self {
+            ind: per_cell("inductance")?,
+            diodes: per_cell("diodes")?.into_iter().map(|diode| diode != 0.0).collect(),
+            diode: Diode {
+                saturation_current: snapshot.get_scalar("saturation_current")?,
+                emission_voltage: snapshot.get_scalar("emission_voltage")?,
+                junction_capacitance: snapshot.get_scalar("junction_capacitance")?,
+                junction_potential: snapshot.get_scalar("junction_potential")?,
+                grading: snapshot.get_scalar("grading")?,
+            },
+            cap,
+            npoints,
+            length: snapshot.get_scalar("length")?,
+            max_iterations: snapshot.get_scalar("max_iterations")? as usize,
+            tolerance: snapshot.get_scalar("tolerance")?,
+        })
+    }
+}
+impl Component for DiodeLine {
+    #[inline]
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+        let line_cap = d_ratio*self.cap[index];
+        let drive = last_currs[0] - last_currs[1];
+        if !self.diodes[index] {
+            *next_volt = last_volt + drive / line_cap;
+            return
+        }
+
+        // solve `(Δz/Δt) C (V' - V) + Cj((V + V')/2) (V' - V) / Δt + (Id(V) + Id(V'))/2 = ΔI`,
+        // limiting forward steps so the exponential doesn't overflow
+        let (last_diode_curr, _) = self.diode.current(last_volt);
+        let mut next_guess = last_volt;
+        let mut converged = false;
+        for _ in 0..self.max_iterations {
+            let this_guess = next_guess;
+            let change = this_guess - last_volt;
+            let (junction_cap, junction_slope) = self.diode.capacitance(last_volt + change/2.0);
+            let (diode_curr, diode_slope) = self.diode.current(this_guess);
+
+            let residual = line_cap*change + junction_cap*change/sim_params.delta_t
+                + (last_diode_curr + diode_curr)/2.0 - drive;
+            let derivative = line_cap
+                + (junction_cap + junction_slope*change/2.0)/sim_params.delta_t
+                + diode_slope/2.0;
+            // a few emission voltages per iteration once forward biased
+            let max_step = 4.0*self.diode.emission_voltage + (-this_guess).max(0.0);
+            let step = (-residual / derivative).min(max_step);
+            next_guess = this_guess + step;
+            if step.abs() <= self.tolerance {
+                converged = true;
+                break;
+            }
+        }
+
+        if !converged {
+            warn("DiodeLine", "Newton iteration did not converge");
+        }
+
+        *next_volt = next_guess;
+    }
+    #[inline]
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+
+        *next_curr = last_curr + (last_volts[0] - last_volts[1]) / (d_ratio*self.ind[index]);
+    }
+    #[inline]
+    fn dc_shunt_current(
+        &self,
+        voltage: Float,
+        index: usize,
+        _sim_params: &SimulationParameters,
+    ) -> Float {
+        if self.diodes[index] { self.diode.current(voltage).0 } else { 0.0 }
+    }
+}
+impl TransmissionLine for DiodeLine {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.npoints
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.length
+    }
+    /// The fastest phase velocity of the unloaded line, which the diodes only slow down.
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.max_phase_velocity_in(0..self.npoints)
+    }
+    #[inline]
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        self.ind[cells.clone()].iter().zip(&self.cap[cells])
+            .map(|(ind, cap)| Float::sqrt(ind * cap).recip())
+            .fold(0.0, |accum, item| if accum >= item { accum } else { item })
+    }
+    /// The parameters of the unloaded line.
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        Some(LineParameters {
+            inductance: self.ind[0],
+            capacitance: self.cap[0],
+            resistance: 0.0,
+            conductance: 0.0,
+        })
+    }
+    #[inline]
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("DiodeLine")
+            .with("length", vec![self.length])
+            .with("capacitance", self.cap.clone())
+            .with("inductance", self.ind.clone())
+            .with("diodes", self.diodes.iter().map(|&diode| diode as u8 as Float).collect())
+            .with("saturation_current", vec![self.diode.saturation_current])
+            .with("emission_voltage", vec![self.diode.emission_voltage])
+            .with("junction_capacitance", vec![self.diode.junction_capacitance])
+            .with("junction_potential", vec![self.diode.junction_potential])
+            .with("grading", vec![self.diode.grading])
+            .with("max_iterations", vec![self.max_iterations as Float])
+            .with("tolerance", vec![self.tolerance])
+    }
+}
@@ -0,0 +1,311 @@
+use std::cell::Cell;
+
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+
+/// A pole of the shunt capacitance of a `DispersiveLine`.
+#[derive(Copy, Clone, Debug)]
+pub enum DielectricPole {
+    /// A relaxation, adding `ΔC / (1 + jω τ)` to the capacitance.
+    Debye {
+        /// The capacitance per unit length `ΔC` added well below the relaxation.
+        capacitance: Float,
+        /// The relaxation time `τ`.
+        relaxation_time: Float,
+    },
+    /// A resonance, adding `ΔC ω0² / (ω0² + jω γ - ω²)` to the capacitance.
+    Lorentz {
+        /// The capacitance per unit length `ΔC` added well below the resonance.
+        capacitance: Float,
+        /// The resonance frequency `ω0 / 2π`.
+        resonance_frequency: Float,
+        /// The damping rate `γ`.
+        damping: Float,
+    },
+}
+
+/// A pole as a series resistor, inductor, and capacitor per unit length from the line to ground,
+/// with the charge on its capacitor and the current through it.
+struct Branch {
+    res: Float,
+    ind: Float,
+    cap: Float,
+    charge: Cell<Float>,
+    current: Cell<Float>,
+}
+impl Branch {
+    fn new(pole: DielectricPole) -> Self {
+        let (res, ind, cap) = match pole {
+            DielectricPole::Debye { capacitance, relaxation_time } => {
+                (relaxation_time / capacitance, 0.0, capacitance)
+            },
+            DielectricPole::Lorentz { capacitance, resonance_frequency, damping } => {
+                let ind = (capacitance * (2.0*crate::consts::PI*resonance_frequency).powi(2))
+                    .recip();
+                (damping*ind, ind, capacitance)
+            },
+        };
+        Self { res, ind, cap, charge: Cell::new(0.0), current: Cell::new(0.0) }
+    }
+
+    /// The impedance relating the average branch current over a step to the average voltage,
+    /// with the trapezoidal rule.
+    #[inline]
+    fn step_impedance(&self, delta_t: Float) -> Float {
+        2.0*self.ind/delta_t + self.res + delta_t/(2.0*self.cap)
+    }
+
+    /// The voltage that, subtracted from the average line voltage, drives the average current.
+    #[inline]
+    fn step_offset(&self, delta_t: Float) -> Float {
+        self.charge.get()/self.cap - 2.0*self.ind*self.current.get()/delta_t
+    }
+}
+
+/// Describes a `DispersiveLine`.
+pub struct DispersiveLineDescriptor<
+    Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+    Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float,
+    Fp: Fn(Float) -> Vec<DielectricPole>,
+>{
+    pub length: Float,
+    pub npoints: usize,
+    /// The capacitance per unit length well above every pole.
+    pub capacitance_fn: Fc,
+    pub inductance_fn: Fl,
+    pub resistance_fn: Fr,
+    pub conductance_fn: Fg,
+    /// The poles of the dielectric at each point.
+    pub poles_fn: Fp,
+}
+
+/// A linear line with a dispersive dielectric, whose shunt capacitance has Debye and Lorentz
+/// poles, as in the materials of circuit boards and cables.
+///
+/// Each pole is a series RLC branch from the line to ground, whose charge and current are stepped
+/// together with the voltage by the trapezoidal rule. The branch states start from zero and change
+/// with every voltage update.
+pub struct DispersiveLine {
+    cap: Vec<Float>,
+    ind: Vec<Float>,
+    res: Vec<Float>,
+    cond: Vec<Float>,
+    branches: Vec<Vec<Branch>>,
+    npoints: usize,
+    length: Float,
+}
+impl DispersiveLine {
+    pub fn new<
+        Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+        Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float,
+        Fp: Fn(Float) -> Vec<DielectricPole>,
+    >(
+        desc: DispersiveLineDescriptor<Fc, Fl, Fr, Fg, Fp>,
+    ) -> Self {
+        let delta_z = desc.length / (desc.npoints as Float);
+        let positions = (0..desc.npoints)
+            .map(|n| (n as Float + 0.5) * delta_z)
+            .collect::<Vec<_>>();
+
+        Self {
+            cap: positions.iter().map(|&z| (desc.capacitance_fn)(z)).collect(),
+            ind: positions.iter().map(|&z| (desc.inductance_fn)(z)).collect(),
+            res: positions.iter().map(|&z| (desc.resistance_fn)(z)).collect(),
+            cond: positions.iter().map(|&z| (desc.conductance_fn)(z)).collect(),
+            branches: positions.iter()
+                .map(|&z| (desc.poles_fn)(z).into_iter().map(Branch::new).collect())
+                .collect(),
+            npoints: desc.npoints,
+            length: desc.length,
+        }
+    }
+
+    /// Rebuilds a `DispersiveLine` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("DispersiveLine")?;
+        let cap = snapshot.get("capacitance")?.clone();
+        let npoints = cap.len();
+        let per_cell = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != npoints {
+                let message = format!("DispersiveLine {} has the wrong length", name);
+                return Err(Error::BadSnapshot(message))
+            }
+            Ok(values.clone())
+        };
+        let counts = per_cell("pole_counts")?;
+        let total = counts.iter().sum::<Float>() as usize;
+        let per_pole = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != total {
+                let message = format!("DispersiveLine {} has the wrong length", name);
+                return Err(Error::BadSnapshot(message))
+            }
+            Ok(values.clone())
+        };
+        let (pole_res, pole_ind, pole_cap, pole_charge, pole_curr) = (
+            per_pole("pole_resistance")?,
+            per_pole("pole_inductance")?,
+            per_pole("pole_capacitance")?,
+            per_pole("pole_charge")?,
+            per_pole("pole_current")?,
+        );
+        let mut first = 0;
+        let branches = counts.iter()
+            .map(|&count| {
+                let poles = first..(first + count as usize);
+                first = poles.end;
+                poles
+                    .map(|k| Branch {
+                        res: pole_res[k],
+                        ind: pole_ind[k],
+                        cap: pole_cap[k],
+                        charge: Cell::new(pole_charge[k]),
+                        current: Cell::new(pole_curr[k]),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(Self {
+            ind: per_cell("inductance")?,
+            res: per_cell("resistance")?,
+            cond: per_cell("conductance")?,
+            cap,
+            branches,
+            npoints,
+            length: snapshot.get_scalar("length")?,
+        })
+    }
+}
+impl Component for DispersiveLine {
+    #[inline]
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let SimulationParameters { delta_z, delta_t } = *sim_params;
+        let d_ratio = delta_z / delta_t;
+        let branches = &self.branches[index];
+
+        // each branch carries `(V̄ - offset) / impedance` on average over the step, where `V̄` is
+        // the average voltage
+        let (mut cond, mut drive) = (self.cond[index], last_currs[0] - last_currs[1]);
+        for branch in branches {
+            let impedance = branch.step_impedance(delta_t);
+            cond += impedance.recip();
+            drive += delta_z*branch.step_offset(delta_t) / impedance;
+        }
+
+        *next_volt = (d_ratio*self.cap[index] + delta_z*cond/2.0).recip()
+            * ( (d_ratio*self.cap[index] - delta_z*cond/2.0) * last_volt + drive );
+
+        let average = (last_volt + *next_volt) / 2.0;
+        for branch in branches {
+            let current = (average - branch.step_offset(delta_t)) / branch.step_impedance(delta_t);
+            branch.charge.set(branch.charge.get() + delta_t*current);
+            branch.current.set(2.0*current - branch.current.get());
+        }
+    }
+    #[inline]
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+
+        *next_curr = (d_ratio*self.ind[index] + sim_params.delta_z*self.res[index]/2.0).recip()
+            *  ( (d_ratio*self.ind[index] - sim_params.delta_z*self.res[index]/2.0) * last_curr
+                + (last_volts[0] - last_volts[1]) );
+    }
+    #[inline]
+    fn dc_series_voltage(
+        &self,
+        current: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        sim_params.delta_z*self.res[index] * current
+    }
+    /// The DC current through the conductance, since the poles block DC.
+    #[inline]
+    fn dc_shunt_current(
+        &self,
+        voltage: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        sim_params.delta_z*self.cond[index] * voltage
+    }
+    fn point_state(&self, index: usize) -> Vec<Float> {
+        self.branches[index].iter()
+            .flat_map(|branch| [branch.charge.get(), branch.current.get()])
+            .collect()
+    }
+    fn restore_point_state(&self, index: usize, state: &[Float]) {
+        for (branch, values) in self.branches[index].iter().zip(state.chunks(2)) {
+            branch.charge.set(values[0]);
+            branch.current.set(values[1]);
+        }
+    }
+}
+impl TransmissionLine for DispersiveLine {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.npoints
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.length
+    }
+    /// The fastest phase velocity with the capacitance above every pole, which the poles only slow
+    /// down.
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.max_phase_velocity_in(0..self.npoints)
+    }
+    #[inline]
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        self.ind[cells.clone()].iter().zip(&self.cap[cells])
+            .map(|(ind, cap)| Float::sqrt(ind * cap).recip())
+            .fold(0.0, |accum, item| if accum >= item { accum } else { item })
+    }
+    /// The parameters well above every pole of the dielectric.
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        Some(LineParameters {
+            inductance: self.ind[0],
+            capacitance: self.cap[0],
+            resistance: self.res[0],
+            conductance: self.cond[0],
+        })
+    }
+    /// The parameters of the line, with each pole as a series branch and its state in the order of
+    /// the cells.
+    #[inline]
+    fn snapshot(&self) -> ComponentSnapshot {
+        let poles = |value: fn(&Branch) -> Float| {
+            self.branches.iter().flatten().map(value).collect()
+        };
+        ComponentSnapshot::new("DispersiveLine")
+            .with("length", vec![self.length])
+            .with("capacitance", self.cap.clone())
+            .with("inductance", self.ind.clone())
+            .with("resistance", self.res.clone())
+            .with("conductance", self.cond.clone())
+            .with("pole_counts", self.branches.iter().map(|poles| poles.len() as Float).collect())
+            .with("pole_resistance", poles(|branch| branch.res))
+            .with("pole_inductance", poles(|branch| branch.ind))
+            .with("pole_capacitance", poles(|branch| branch.cap))
+            .with("pole_charge", poles(|branch| branch.charge.get()))
+            .with("pole_current", poles(|branch| branch.current.get()))
+    }
+}
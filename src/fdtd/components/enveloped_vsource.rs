@@ -0,0 +1,130 @@
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::VSource;
+
+/// The shape in time of an `EnvelopedVSource`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Envelope {
+    /// Silent before `start`, then rising by a raised cosine to full output over `rise_time`.
+    RaisedCosine { start: Float, rise_time: Float },
+    /// Silent before `start`, then rising linearly to full output over `rise_time`.
+    Linear { start: Float, rise_time: Float },
+    /// Full output from `start` until `stop`, and silent otherwise.
+    Gate { start: Float, stop: Float },
+    /// Full output until `start`, then decaying exponentially with time constant `decay_time`.
+    ExponentialDecay { start: Float, decay_time: Float },
+}
+impl Envelope {
+    /// The gain of the envelope at `time`.
+    pub fn gain(&self, time: Float) -> Float {
+        match *self {
+            Envelope::RaisedCosine { start, rise_time } => {
+                if time <= start {
+                    0.0
+                } else if time >= start + rise_time {
+                    1.0
+                } else {
+                    (1.0 - Float::cos(crate::consts::PI * (time - start) / rise_time)) / 2.0
+                }
+            },
+            Envelope::Linear { start, rise_time } => {
+                if time <= start {
+                    0.0
+                } else if time >= start + rise_time {
+                    1.0
+                } else {
+                    (time - start) / rise_time
+                }
+            },
+            Envelope::Gate { start, stop } => {
+                if time >= start && time < stop { 1.0 } else { 0.0 }
+            },
+            Envelope::ExponentialDecay { start, decay_time } => {
+                if time <= start { 1.0 } else { Float::exp(-(time - start) / decay_time) }
+            },
+        }
+    }
+
+    /// The kind, start, and time of the envelope, as recorded in snapshots.
+    fn parameters(&self) -> [Float; 3] {
+        match *self {
+            Envelope::RaisedCosine { start, rise_time } => [0.0, start, rise_time],
+            Envelope::Gate { start, stop } => [1.0, start, stop],
+            Envelope::ExponentialDecay { start, decay_time } => [2.0, start, decay_time],
+            Envelope::Linear { start, rise_time } => [3.0, start, rise_time],
+        }
+    }
+}
+
+/// Shapes the output of any voltage source in time by an envelope, such as a smooth turn on that
+/// avoids the spectral splatter of a source switched on abruptly.
+///
+/// The envelope scales the generated voltage, leaving the internal impedance of the source
+/// untouched, so the source still absorbs reflections while silent. Envelopes can be nested, and
+/// their gains multiply.
+pub struct EnvelopedVSource<S: VSource> {
+    pub source: S,
+    pub envelope: Envelope,
+}
+impl<S: VSource> EnvelopedVSource<S> {
+    /// Creates a new `EnvelopedVSource` instance.
+    #[inline]
+    pub fn new(source: S, envelope: Envelope) -> Self {
+        Self { source, envelope }
+    }
+
+    /// Rebuilds an `EnvelopedVSource` from its snapshot and the wrapped source, which can be
+    /// rebuilt from `snapshot.nested("source", kind)`.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot, source: S) -> Result<Self, Error> {
+        snapshot.expect_kind("EnvelopedVSource")?;
+        let start = snapshot.get_scalar("envelope_start")?;
+        let time = snapshot.get_scalar("envelope_time")?;
+        let envelope = match snapshot.get_scalar("envelope_kind")? as usize {
+            0 => Envelope::RaisedCosine { start, rise_time: time },
+            1 => Envelope::Gate { start, stop: time },
+            2 => Envelope::ExponentialDecay { start, decay_time: time },
+            3 => Envelope::Linear { start, rise_time: time },
+            other => {
+                let message = format!("EnvelopedVSource has no envelope {}", other);
+                return Err(Error::BadSnapshot(message))
+            },
+        };
+
+        Ok(Self { source, envelope })
+    }
+}
+impl<S: VSource> VSource for EnvelopedVSource<S> {
+    fn next_voltage_scaled(
+        &mut self,
+        gain: Float,
+        t: Float,
+        last_volt: Float,
+        last_curr: Float,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        // the envelope scales the voltage at the time the wrapped source generates it
+        let gain = gain * self.envelope.gain(self.source.generation_time(t, sim_params));
+        self.source.next_voltage_scaled(gain, t, last_volt, last_curr, sim_params)
+    }
+
+    fn generate(&self, time: Float) -> Float {
+        self.envelope.gain(time) * self.source.generate(time)
+    }
+
+    fn generation_time(&self, t: Float, sim_params: &SimulationParameters) -> Float {
+        self.source.generation_time(t, sim_params)
+    }
+
+    fn dc_voltage(&self, bias: Float, current: Float, sim_params: &SimulationParameters) -> Float {
+        self.source.dc_voltage(bias, current, sim_params)
+    }
+
+    /// The envelope, with the parameters of the wrapped source prefixed by `source_`.
+    fn snapshot(&self) -> ComponentSnapshot {
+        let [kind, start, time] = self.envelope.parameters();
+        ComponentSnapshot::new("EnvelopedVSource")
+            .with("envelope_kind", vec![kind])
+            .with("envelope_start", vec![start])
+            .with("envelope_time", vec![time])
+            .with_nested("source", self.source.snapshot())
+    }
+}
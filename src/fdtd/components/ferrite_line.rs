@@ -0,0 +1,251 @@
+use crate::{warn, ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+
+/// Describes a `FerriteLine`.
+pub struct FerriteLineDescriptor<
+    Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+    Fs: Fn(Float) -> Float, Fi: Fn(Float) -> Float,
+    Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float,
+>{
+    pub length: Float,
+    pub npoints: usize,
+    pub capacitance_fn: Fc,
+    /// The inductance per unit length with no current.
+    pub inductance_fn: Fl,
+    /// The inductance per unit length left once the ferrite saturates, which must be positive.
+    pub saturated_inductance_fn: Fs,
+    /// The current at which the ferrite saturates, which must be positive.
+    pub saturation_current_fn: Fi,
+    pub resistance_fn: Fr,
+    pub conductance_fn: Fg,
+    /// The most Newton iterations taken to solve for each current.
+    ///
+    /// Lines need at least one.
+    pub max_iterations: usize,
+    /// The step size, in amps, at which an iteration has converged.
+    pub tolerance: Float,
+}
+
+/// A line loaded by ferrite, with a series inductance that falls as the ferrite saturates.
+///
+/// The flux per unit length is `Ls I + (L0 - Ls) Is tanh(I / Is)`, so the inductance falls from
+/// `L0` with no current to `Ls` well above the saturation current `Is`, the opposite of the
+/// kinetic inductance of a `KiLine`. Each update conserves the flux, and the ferrite has no
+/// hysteresis.
+pub struct FerriteLine {
+    cap: Vec<Float>,
+    ind: Vec<Float>,
+    saturated_ind: Vec<Float>,
+    saturation_curr: Vec<Float>,
+    res: Vec<Float>,
+    cond: Vec<Float>,
+    npoints: usize,
+    length: Float,
+    max_iterations: usize,
+    tolerance: Float,
+}
+impl FerriteLine {
+    pub fn new<
+        Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+        Fs: Fn(Float) -> Float, Fi: Fn(Float) -> Float,
+        Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float,
+    >(
+        desc: FerriteLineDescriptor<Fc, Fl, Fs, Fi, Fr, Fg>,
+    ) -> Result<Self, Error> {
+        if desc.max_iterations == 0 {
+            let message = "FerriteLine needs at least one Newton iteration";
+            return Err(Error::BadParameter(message.to_string()))
+        }
+        let delta_z = desc.length / (desc.npoints as Float);
+        let sample = |param_fn: &dyn Fn(Float) -> Float| (0..desc.npoints)
+            .map(|n| param_fn((n as Float + 0.5) * delta_z))
+            .collect::<Vec<_>>();
+
+        let saturated_ind = sample(&desc.saturated_inductance_fn);
+        let saturation_curr = sample(&desc.saturation_current_fn);
+        if saturated_ind.iter().any(|&ind| ind <= 0.0) {
+            let message = "FerriteLine needs a positive saturated inductance";
+            return Err(Error::BadParameter(message.to_string()))
+        }
+        if saturation_curr.iter().any(|&curr| curr <= 0.0) {
+            let message = "FerriteLine needs a positive saturation current";
+            return Err(Error::BadParameter(message.to_string()))
+        }
+
+        Ok(Self {
+            cap: sample(&desc.capacitance_fn),
+            ind: sample(&desc.inductance_fn),
+            saturated_ind,
+            saturation_curr,
+            res: sample(&desc.resistance_fn),
+            cond: sample(&desc.conductance_fn),
+            npoints: desc.npoints,
+            length: desc.length,
+            max_iterations: desc.max_iterations,
+            tolerance: desc.tolerance,
+        })
+    }
+
+    /// Rebuilds a `FerriteLine` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("FerriteLine")?;
+        let cap = snapshot.get("capacitance")?.clone();
+        let npoints = cap.len();
+        let per_cell = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != npoints {
+                let message = format!("FerriteLine {} has the wrong length", name);
+                return Err(Error::BadSnapshot(message))
+            }
+            Ok(values.clone())
+        };
+
+        Ok(Self {
+            ind: per_cell("inductance")?,
+            saturated_ind: per_cell("saturated_inductance")?,
+            saturation_curr: per_cell("saturation_current")?,
+            res: per_cell("resistance")?,
+            cond: per_cell("conductance")?,
+            cap,
+            npoints,
+            length: snapshot.get_scalar("length")?,
+            max_iterations: snapshot.get_scalar("max_iterations")? as usize,
+            tolerance: snapshot.get_scalar("tolerance")?,
+        })
+    }
+
+    /// Gets the flux per unit length of cell `index` carrying `current`, and the inductance per
+    /// unit length, which is its slope.
+    #[inline]
+    fn flux(&self, index: usize, current: Float) -> (Float, Float) {
+        let (saturated, saturation) = (self.saturated_ind[index], self.saturation_curr[index]);
+        let ferrite = self.ind[index] - saturated;
+        let ratio = Float::tanh(current / saturation);
+        (
+            saturated*current + ferrite*saturation*ratio,
+            saturated + ferrite*(1.0 - ratio.powi(2)),
+        )
+    }
+
+    /// Gets the inductance per unit length of cell `index` carrying `current`.
+    #[inline]
+    pub fn inductance(&self, index: usize, current: Float) -> Float {
+        self.flux(index, current).1
+    }
+}
+impl Component for FerriteLine {
+    #[inline]
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+
+        *next_volt = (d_ratio*self.cap[index] + sim_params.delta_z*self.cond[index]/2.0).recip()
+            * ( (d_ratio*self.cap[index] - sim_params.delta_z*self.cond[index]/2.0) * last_volt
+                + (last_currs[0] - last_currs[1]) );
+    }
+    #[inline]
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+        let loss = sim_params.delta_z*self.res[index]/2.0;
+        let drive = last_volts[0] - last_volts[1];
+        let (last_flux, _) = self.flux(index, last_curr);
+
+        // solve `(Δz/Δt) (Φ(I') - Φ(I)) + Δz R (I + I')/2 = ΔV`, where the flux only grows with
+        // the current
+        let mut next_guess = last_curr;
+        let mut converged = false;
+        for _ in 0..self.max_iterations {
+            let this_guess = next_guess;
+            let (flux, inductance) = self.flux(index, this_guess);
+
+            let residual = d_ratio*(flux - last_flux) + loss*(last_curr + this_guess) - drive;
+            next_guess = this_guess - residual / (d_ratio*inductance + loss);
+            if (next_guess - this_guess).abs() <= self.tolerance {
+                converged = true;
+                break;
+            }
+        }
+
+        if !converged {
+            warn("FerriteLine", "Newton iteration did not converge");
+        }
+
+        *next_curr = next_guess;
+    }
+    #[inline]
+    fn dc_series_voltage(
+        &self,
+        current: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        sim_params.delta_z*self.res[index] * current
+    }
+    #[inline]
+    fn dc_shunt_current(
+        &self,
+        voltage: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        sim_params.delta_z*self.cond[index] * voltage
+    }
+}
+impl TransmissionLine for FerriteLine {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.npoints
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.length
+    }
+    /// The fastest phase velocity with the saturated inductance, which the ferrite only slows
+    /// down.
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.max_phase_velocity_in(0..self.npoints)
+    }
+    #[inline]
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        self.saturated_ind[cells.clone()].iter().zip(&self.cap[cells])
+            .map(|(ind, cap)| Float::sqrt(ind * cap).recip())
+            .fold(0.0, |accum, item| if accum >= item { accum } else { item })
+    }
+    /// The parameters with no current flowing, before the ferrite saturates.
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        Some(LineParameters {
+            inductance: self.ind[0],
+            capacitance: self.cap[0],
+            resistance: self.res[0],
+            conductance: self.cond[0],
+        })
+    }
+    #[inline]
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("FerriteLine")
+            .with("length", vec![self.length])
+            .with("capacitance", self.cap.clone())
+            .with("inductance", self.ind.clone())
+            .with("saturated_inductance", self.saturated_ind.clone())
+            .with("saturation_current", self.saturation_curr.clone())
+            .with("resistance", self.res.clone())
+            .with("conductance", self.cond.clone())
+            .with("max_iterations", vec![self.max_iterations as Float])
+            .with("tolerance", vec![self.tolerance])
+    }
+}
@@ -1,66 +1,355 @@
-use crate::SimulationParameters;
-use crate::fdtd::{TransmissionLine, Component};
+use crate::{warn, ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+use crate::fdtd::grid::NonuniformGrid;
 
 pub struct KiLineDescriptor<
-    Fc: Fn(f32) -> f32, Fl: Fn(f32) -> f32,
-    Fk: Fn(f32) -> f32, Fi: Fn(f32) -> f32,
+    Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+    Fk: Fn(Float) -> Float, Fi: Fn(Float) -> Float,
+    Fr: Fn(Float) -> Float, Fq: Fn(Float) -> Float,
+    Ff: Fn(Float) -> Float, Fb: Fn(Float) -> Float,
 >{
-    pub length: f32,
+    pub length: Float,
     pub npoints: usize,
     pub capacitance_fn: Fc,
     pub inductance_fn: Fl,
     pub kinetic_inductance_fn: Fk,
     pub critical_current_fn: Fi,
+    /// The resistance per unit length with no current.
+    pub resistance_fn: Fr,
+    /// The resistance per unit length added at the current scaling the nonlinear inductance,
+    /// growing with the square of the current.
+    pub nonlinear_resistance_fn: Fq,
+    /// The flux flow resistance per unit length, which drops `Rf (|I| - Ic)` once the current
+    /// exceeds the critical current.
+    pub flux_flow_resistance_fn: Ff,
+    /// The DC bias current flowing through the line, which the currents of the state are on top
+    /// of, and which is zero for an unbiased line.
+    pub bias_current_fn: Fb,
+    /// The most Newton iterations taken to solve for each current.
+    ///
+    /// Lines need at least one.
+    pub max_iterations: usize,
+    /// The step size, relative to the critical current, at which an iteration has converged.
+    pub tolerance: Float,
 }
 
+/// How the differential inductance of a `KiLine` depends on the current.
+pub enum KiNonlinearity {
+    /// The expansion `L0 (1 + c1 (I / I*)² + c2 (I / I*)⁴ + ...)` with coefficients `[c1, c2, ..]`,
+    /// which is `[4.0]` by default.
+    Polynomial(Vec<Float>),
+    /// A closure giving the ratio `L(I) / L0`, called with the index of the cell and the current,
+    /// which should be 1 with no current, such as for a saturating model.
+    Custom(Box<dyn Fn(usize, Float) -> Float>),
+}
+impl Default for KiNonlinearity {
+    fn default() -> Self {
+        Self::Polynomial(vec![4.0])
+    }
+}
+impl KiNonlinearity {
+    /// Gets the ratio `L(I) / L0` of cell `index` and its slope with the current, where `I*` is
+    /// `crit_cur`.
+    #[inline]
+    fn ratio(&self, index: usize, current: Float, crit_cur: Float) -> (Float, Float) {
+        match self {
+            Self::Polynomial(coefficients) => {
+                let normalized = (current / crit_cur).powi(2);
+                let derivative = current / crit_cur.powi(2);
+                let (mut ratio, mut slope, mut power) = (1.0, 0.0, 1.0);
+                for (k, coefficient) in coefficients.iter().enumerate() {
+                    // `power` is `(I / I*)^(2k)`
+                    slope += coefficient * 2.0*(k as Float + 1.0) * power * derivative;
+                    power *= normalized;
+                    ratio += coefficient * power;
+                }
+                (ratio, slope)
+            },
+            Self::Custom(ratio_fn) => {
+                let step = Float::EPSILON.sqrt() * current.abs().max(crit_cur);
+                let slope = (ratio_fn(index, current + step) - ratio_fn(index, current - step))
+                    / (2.0*step);
+                (ratio_fn(index, current), slope)
+            },
+        }
+    }
+}
+
+/// A cell of a kinetic inductance line, as seen by the Newton solve of its next current.
+pub(super) struct KiCell<'a, D: Fn(Float) -> (Float, Float)> {
+    pub(super) nonlinearity: &'a KiNonlinearity,
+    pub(super) index: usize,
+    pub(super) inductance: Float,
+    pub(super) crit_cur: Float,
+    pub(super) bias_cur: Float,
+    /// The resistive drop per unit length at a total current, and its slope with the current.
+    pub(super) series_drop: D,
+}
+impl<D: Fn(Float) -> (Float, Float)> KiCell<'_, D> {
+    /// Solves for the next AC current from the voltage step `dv` across the cell, giving whether
+    /// the iteration converged.
+    pub(super) fn next_current(
+        &self,
+        last_curr: Float,
+        dv: Float,
+        sim_params: &SimulationParameters,
+        max_iterations: usize,
+        tolerance: Float,
+    ) -> (Float, bool) {
+        let SimulationParameters { delta_z, delta_t } = *sim_params;
+        let (ind, i_crit, i_bias) = (self.inductance, self.crit_cur, self.bias_cur);
+        let drive = i_crit.powi(2) * delta_t * dv / (delta_z * ind);
+        let loss = i_crit.powi(2) * delta_t / ind;
+        // the bias supplies the drop of the bias current itself
+        let (bias_drop, _) = (self.series_drop)(i_bias);
+
+        // solve `(i' - i) Ic² L(Ib + ī) / L0 + Ic² Δt (V(Ib + ī) - V(Ib)) / (Δz L0)
+        // = -Ic² Δt dv / (Δz L0)`, where `ī` is the average current and `V` the resistive drop,
+        // which is a cubic by default
+        let mut next_guess = last_curr;
+        for _ in 0..max_iterations {
+            let this_guess = next_guess;
+            let total = i_bias + (last_curr + this_guess) / 2.0;
+            let (ratio, ratio_slope) = self.nonlinearity.ratio(self.index, total, i_crit);
+            let (drop, drop_slope) = (self.series_drop)(total);
+            let residual = (this_guess - last_curr)*i_crit.powi(2)*ratio
+                + loss*(drop - bias_drop) + drive;
+            let slope = i_crit.powi(2)*(ratio + (this_guess - last_curr)*ratio_slope/2.0)
+                + loss*drop_slope/2.0;
+
+            next_guess = this_guess - residual / slope;
+            if (next_guess - this_guess).abs() <= tolerance * i_crit {
+                return (next_guess, true)
+            }
+        }
+        (next_guess, false)
+    }
+}
+
+/// A kinetic inductance line, whose inductance grows with the square of the current.
+///
+/// The differential inductance is `L0 (1 + 4 (I / I*)²)` by default, where `I*` is the critical
+/// current scaled by `sqrt(L0 / Lk)`, and can be expanded to higher orders or replaced by a
+/// custom model. The resistance also grows with the square of the current, as
+/// `R0 + R2 (I / I*)²`, so the nonlinearity can be dissipative as well as reactive.
+///
+/// Above the critical current, the flux flow resistance drops a voltage growing with the excess
+/// current, which limits overdriven pulses. Without it, the line can't model currents past the
+/// critical current, and warns as they approach it.
+///
+/// A line can carry a DC bias current for three wave mixing. The voltages and currents of the
+/// state are then only the AC part on top of the bias, which never has to be ramped up by the
+/// source, while the inductance depends on the total current. A bias `Ib` adds a term to the
+/// inductance linear in the AC current `i`, proportional to `Ib i`, which mixes three waves,
+/// besides the usual quadratic term that mixes four. The bias also raises the inductance
+/// everywhere, slowing the line down.
 pub struct KiLine {
-    cap: Vec<f32>,
-    ind0: Vec<f32>,
-    crit_cur: Vec<f32>,
+    cap: Vec<Float>,
+    ind0: Vec<Float>,
+    crit_cur: Vec<Float>,
+    res: Vec<Float>,
+    nonlinear_res: Vec<Float>,
+    flux_flow_res: Vec<Float>,
+    /// The critical current, past which the flux flow resistance switches on.
+    switch_cur: Vec<Float>,
+    bias_cur: Vec<Float>,
+    nonlinearity: KiNonlinearity,
     npoints: usize,
-    length: f32,
+    length: Float,
+    max_iterations: usize,
+    tolerance: Float,
 }
 impl KiLine {
     #[inline]
     pub fn new<
-        Fc: Fn(f32) -> f32, Fl: Fn(f32) -> f32,
-        Fk: Fn(f32) -> f32, Fi: Fn(f32) -> f32,
+        Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+        Fk: Fn(Float) -> Float, Fi: Fn(Float) -> Float,
+        Fr: Fn(Float) -> Float, Fq: Fn(Float) -> Float,
+        Ff: Fn(Float) -> Float, Fb: Fn(Float) -> Float,
     >(
-        desc: KiLineDescriptor<Fc, Fl, Fk, Fi>,
-    ) -> Self {
-        let delta_z = desc.length / (desc.npoints as f32);
+        desc: KiLineDescriptor<Fc, Fl, Fk, Fi, Fr, Fq, Ff, Fb>,
+    ) -> Result<Self, Error> {
+        if desc.max_iterations == 0 {
+            let message = "KiLine needs at least one Newton iteration";
+            return Err(Error::BadParameter(message.to_string()))
+        }
+        let delta_z = desc.length / (desc.npoints as Float);
 
-        Self {
+        Ok(Self {
             cap: (0..desc.npoints)
-                .map(|n| { (desc.capacitance_fn)((n as f32 + 0.5) * delta_z) })
+                .map(|n| { (desc.capacitance_fn)((n as Float + 0.5) * delta_z) })
                 .collect::<Vec<_>>(),
             ind0: (0..desc.npoints)
                 .map(|n| {
-                    let ki_ind = (desc.kinetic_inductance_fn)((n as f32 + 0.5) * delta_z);
-                    let ind = (desc.inductance_fn)((n as f32 + 0.5) * delta_z);
+                    let ki_ind = (desc.kinetic_inductance_fn)((n as Float + 0.5) * delta_z);
+                    let ind = (desc.inductance_fn)((n as Float + 0.5) * delta_z);
                     ind + ki_ind
                 })
                 .collect::<Vec<_>>(),
             crit_cur: (0..desc.npoints)
                 .map(|n| {
-                    let ki_ind = (desc.kinetic_inductance_fn)((n as f32 + 0.5) * delta_z);
-                    let ind = (desc.inductance_fn)((n as f32 + 0.5) * delta_z);
-                    let crit_cur = (desc.critical_current_fn)((n as f32 + 0.5) * delta_z);
-                    crit_cur * f32::sqrt((ind + ki_ind) / ki_ind)
+                    let ki_ind = (desc.kinetic_inductance_fn)((n as Float + 0.5) * delta_z);
+                    let ind = (desc.inductance_fn)((n as Float + 0.5) * delta_z);
+                    let crit_cur = (desc.critical_current_fn)((n as Float + 0.5) * delta_z);
+                    crit_cur * Float::sqrt((ind + ki_ind) / ki_ind)
                 })
                 .collect::<Vec<_>>(),
+            res: (0..desc.npoints)
+                .map(|n| { (desc.resistance_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            nonlinear_res: (0..desc.npoints)
+                .map(|n| { (desc.nonlinear_resistance_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            flux_flow_res: (0..desc.npoints)
+                .map(|n| { (desc.flux_flow_resistance_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            switch_cur: (0..desc.npoints)
+                .map(|n| { (desc.critical_current_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            bias_cur: (0..desc.npoints)
+                .map(|n| { (desc.bias_current_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            nonlinearity: KiNonlinearity::default(),
             npoints: desc.npoints,
             length: desc.length,
+            max_iterations: desc.max_iterations,
+            tolerance: desc.tolerance,
+        })
+    }
+
+    /// Creates a new `KiLine` on a nonuniform grid, which sets its length and number of points in
+    /// place of the descriptor.
+    ///
+    /// The parameters of each cell are sampled at its center, with the capacitance scaled by the
+    /// length of the cell and the inductances and resistances by the distance to the previous cell.
+    pub fn new_on_grid<
+        Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+        Fk: Fn(Float) -> Float, Fi: Fn(Float) -> Float,
+        Fr: Fn(Float) -> Float, Fq: Fn(Float) -> Float,
+        Ff: Fn(Float) -> Float, Fb: Fn(Float) -> Float,
+    >(
+        desc: KiLineDescriptor<Fc, Fl, Fk, Fi, Fr, Fq, Ff, Fb>,
+        grid: &NonuniformGrid,
+    ) -> Result<Self, Error> {
+        if desc.max_iterations == 0 {
+            let message = "KiLine needs at least one Newton iteration";
+            return Err(Error::BadParameter(message.to_string()))
+        }
+        let npoints = grid.npoints();
+        let (mut cap, mut ind0, mut crit_cur) = (Vec::new(), Vec::new(), Vec::new());
+        let (mut res, mut nonlinear_res) = (Vec::new(), Vec::new());
+        let (mut flux_flow_res, mut switch_cur, mut bias_cur) =
+            (Vec::new(), Vec::new(), Vec::new());
+        for n in 0..npoints {
+            let z = grid.cell_center(n);
+            let ki_ind = (desc.kinetic_inductance_fn)(z);
+            let ind = (desc.inductance_fn)(z);
+            cap.push(grid.shunt_scale(n) * (desc.capacitance_fn)(z));
+            ind0.push(grid.series_scale(n) * (ind + ki_ind));
+            crit_cur.push((desc.critical_current_fn)(z) * Float::sqrt((ind + ki_ind) / ki_ind));
+            res.push(grid.series_scale(n) * (desc.resistance_fn)(z));
+            nonlinear_res.push(grid.series_scale(n) * (desc.nonlinear_resistance_fn)(z));
+            flux_flow_res.push(grid.series_scale(n) * (desc.flux_flow_resistance_fn)(z));
+            switch_cur.push((desc.critical_current_fn)(z));
+            bias_cur.push((desc.bias_current_fn)(z));
         }
+
+        Ok(Self {
+            cap,
+            ind0,
+            crit_cur,
+            res,
+            nonlinear_res,
+            flux_flow_res,
+            switch_cur,
+            bias_cur,
+            nonlinearity: KiNonlinearity::default(),
+            npoints,
+            length: grid.length(),
+            max_iterations: desc.max_iterations,
+            tolerance: desc.tolerance,
+        })
+    }
+
+    /// Uses `nonlinearity` for the dependence of the inductance on the current.
+    pub fn with_nonlinearity(mut self, nonlinearity: KiNonlinearity) -> Self {
+        self.nonlinearity = nonlinearity;
+        self
+    }
+
+    /// Rebuilds a `KiLine` from its snapshot.
+    ///
+    /// Snapshots saved before the Newton iteration was configurable take the 3 iterations and
+    /// tolerance of 1e-4 it used to be fixed at, and those saved before the line could dissipate
+    /// are lossless and have no flux flow resistance or bias. Lines with a custom nonlinearity
+    /// can't be rebuilt, as their closures aren't part of the snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("KiLine")?;
+        if snapshot.get_scalar("custom_nonlinearity").is_ok_and(|custom| custom != 0.0) {
+            let message = "KiLine has a custom nonlinearity, which isn't part of its snapshot";
+            return Err(Error::BadSnapshot(message.to_string()))
+        }
+        let cap = snapshot.get("capacitance")?.clone();
+        let npoints = cap.len();
+        let per_cell = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != npoints {
+                return Err(Error::BadSnapshot(format!("KiLine {} has the wrong length", name)))
+            }
+            Ok(values.clone())
+        };
+
+        let lossless = vec![0.0; npoints];
+        let flux_flow_res = snapshot.get("flux_flow_resistance")
+            .map_or(Ok(lossless.clone()), |_| per_cell("flux_flow_resistance"))?;
+        let switch_cur = snapshot.get("switching_current")
+            .map_or(Ok(vec![Float::INFINITY; npoints]), |_| per_cell("switching_current"))?;
+        let bias_cur = snapshot.get("bias_current")
+            .map_or(Ok(lossless.clone()), |_| per_cell("bias_current"))?;
+        Ok(Self {
+            ind0: per_cell("inductance")?,
+            crit_cur: per_cell("critical_current")?,
+            res: snapshot.get("resistance")
+                .map_or(Ok(lossless.clone()), |_| per_cell("resistance"))?,
+            nonlinear_res: snapshot.get("nonlinear_resistance")
+                .map_or(Ok(lossless), |_| per_cell("nonlinear_resistance"))?,
+            flux_flow_res,
+            switch_cur,
+            bias_cur,
+            nonlinearity: snapshot.get("nonlinearity")
+                .map_or(KiNonlinearity::default(), |coefficients| {
+                    KiNonlinearity::Polynomial(coefficients.clone())
+                }),
+            cap,
+            npoints,
+            length: snapshot.get_scalar("length")?,
+            max_iterations: snapshot.get_scalar("max_iterations").map_or(3, |x| x as usize),
+            tolerance: snapshot.get_scalar("tolerance").unwrap_or(1e-4),
+        })
+    }
+
+    /// The small signal inductance per unit length of point `index` with its bias.
+    pub fn biased_inductance(&self, index: usize) -> Float {
+        let (ratio, _) = self.nonlinearity.ratio(index, self.bias_cur[index], self.crit_cur[index]);
+        self.ind0[index] * ratio
+    }
+
+    /// The resistive voltage per unit length of point `index` at a total current of `current`.
+    #[inline]
+    fn series_drop(&self, current: Float, index: usize) -> Float {
+        let i_crit = self.crit_cur[index];
+        let excess = (current.abs() - self.switch_cur[index]).max(0.0);
+        self.res[index]*current + self.nonlinear_res[index]*current.powi(3) / i_crit.powi(2)
+            + self.flux_flow_res[index]*excess.copysign(current)
     }
 }
 impl Component for KiLine {
     #[inline]
     fn next_voltage(
         &self,
-        next_volt: &mut f32,
-        last_volt: f32,
-        last_currs: ndarray::ArrayView1<f32>,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
         index: usize,
         sim_params: &SimulationParameters,
     ) {
@@ -72,36 +361,54 @@ impl Component for KiLine {
     #[inline]
     fn next_current(
         &self,
-        next_curr: &mut f32,
-        last_volts: ndarray::ArrayView1<f32>,
-        last_curr: f32,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
         index: usize,
         sim_params: &SimulationParameters,
     ) {
-        let ind = self.ind0[index];
         let i_crit = self.crit_cur[index];
-        let delta_z = sim_params.delta_z;
-        let delta_t = sim_params.delta_t;
+        let (res, nonlinear_res) = (self.res[index], self.nonlinear_res[index]);
+        let (flux_flow_res, switch_cur) = (self.flux_flow_res[index], self.switch_cur[index]);
+        let i_bias = self.bias_cur[index];
+        let cell = KiCell {
+            nonlinearity: &self.nonlinearity,
+            index,
+            inductance: self.ind0[index],
+            crit_cur: i_crit,
+            bias_cur: i_bias,
+            series_drop: |total: Float| {
+                let flux_flow_slope = if total.abs() > switch_cur { flux_flow_res } else { 0.0 };
+                let slope = res + flux_flow_slope + 3.0*nonlinear_res*(total / i_crit).powi(2);
+                (self.series_drop(total, index), slope)
+            },
+        };
         let dv = last_volts[1] - last_volts[0];
+        let (next_guess, converged) =
+            cell.next_current(last_curr, dv, sim_params, self.max_iterations, self.tolerance);
 
-        let a = 1.0;
-        let b = last_curr;
-        let c = i_crit.powi(2) - last_curr.powi(2);
-        let d = i_crit.powi(2) * delta_t * dv / (delta_z * ind)
-            - i_crit.powi(2)*last_curr - last_curr.powi(3);
-
-        let mut next_guess = last_curr;
-        let mut this_guess;
-        for _ in 0..3 {
-            this_guess = next_guess;
-
-            next_guess = this_guess
-                - (a*this_guess.powi(3)+b*this_guess.powi(2)+c*this_guess+d)
-                / (3.0*a*this_guess.powi(2)+2.0*b*this_guess+c);
+        if !converged {
+            warn("KiLine", "Newton iteration did not converge");
+        }
+        // snapshots from before the flux flow resistance only know the effective current, which
+        // is never below the critical current
+        if flux_flow_res == 0.0 && (i_bias + next_guess).abs() > 0.95 * switch_cur.min(i_crit) {
+            warn("KiLine", "current is within 5% of the critical current");
         }
 
         *next_curr = next_guess;
     }
+    #[inline]
+    fn dc_series_voltage(
+        &self,
+        current: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        let i_bias = self.bias_cur[index];
+        sim_params.delta_z
+            * (self.series_drop(i_bias + current, index) - self.series_drop(i_bias, index))
+    }
 }
 impl TransmissionLine for KiLine {
     #[inline]
@@ -109,14 +416,54 @@ impl TransmissionLine for KiLine {
         self.npoints
     }
     #[inline]
-    fn length(&self) -> f32 {
+    fn length(&self) -> Float {
         self.length
     }
     #[inline]
-    fn max_phase_velocity(&self) -> f32 {
-        self.ind0.iter().zip(self.cap.iter())
-            .map(|(ind0, cap)| f32::sqrt(ind0 * cap).recip())
-            .reduce(|accum, item| if accum >= item { accum } else { item })
-            .unwrap()
+    fn max_phase_velocity(&self) -> Float {
+        self.max_phase_velocity_in(0..self.npoints)
+    }
+    #[inline]
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        self.ind0[cells.clone()].iter().zip(&self.cap[cells])
+            .map(|(ind0, cap)| Float::sqrt(ind0 * cap).recip())
+            .fold(0.0, |accum, item| if accum >= item { accum } else { item })
+    }
+    /// The small signal parameters about the bias.
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        let i_bias = self.bias_cur[0];
+        let resistance = self.res[0]
+            + 3.0*self.nonlinear_res[0]*(i_bias / self.crit_cur[0]).powi(2)
+            + if i_bias.abs() > self.switch_cur[0] { self.flux_flow_res[0] } else { 0.0 };
+        Some(LineParameters {
+            inductance: self.biased_inductance(0),
+            capacitance: self.cap[0],
+            resistance,
+            conductance: 0.0,
+        })
+    }
+    #[inline]
+    fn snapshot(&self) -> ComponentSnapshot {
+        // `critical_current` is the effective value scaling the total inductance
+        ComponentSnapshot::new("KiLine")
+            .with("length", vec![self.length])
+            .with("capacitance", self.cap.clone())
+            .with("inductance", self.ind0.clone())
+            .with("critical_current", self.crit_cur.clone())
+            .with("resistance", self.res.clone())
+            .with("nonlinear_resistance", self.nonlinear_res.clone())
+            .with("flux_flow_resistance", self.flux_flow_res.clone())
+            .with("switching_current", self.switch_cur.clone())
+            .with("bias_current", self.bias_cur.clone())
+            .with("nonlinearity", match self.nonlinearity {
+                KiNonlinearity::Polynomial(ref coefficients) => coefficients.clone(),
+                KiNonlinearity::Custom(_) => Vec::new(),
+            })
+            .with("custom_nonlinearity", vec![
+                if let KiNonlinearity::Custom(_) = self.nonlinearity { 1.0 } else { 0.0 }
+            ])
+            .with("max_iterations", vec![self.max_iterations as Float])
+            .with("tolerance", vec![self.tolerance])
     }
 }
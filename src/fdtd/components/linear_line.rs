@@ -1,11 +1,12 @@
-use crate::SimulationParameters;
-use crate::fdtd::{TransmissionLine, Component};
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+use crate::fdtd::grid::NonuniformGrid;
 
 pub struct LinearLineDescriptor<
-    Fc: Fn(f32) -> f32, Fl: Fn(f32) -> f32,
-    Fr: Fn(f32) -> f32, Fg: Fn(f32) -> f32,
+    Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+    Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float,
 >{
-    pub length: f32,
+    pub length: Float,
     pub npoints: usize,
     pub capacitance_fn: Fc,
     pub inductance_fn: Fl,
@@ -14,47 +15,132 @@ pub struct LinearLineDescriptor<
 }
 
 pub struct LinearLine {
-    cap: Vec<f32>,
-    ind: Vec<f32>,
-    res: Vec<f32>,
-    cond: Vec<f32>,
+    cap: Vec<Float>,
+    ind: Vec<Float>,
+    res: Vec<Float>,
+    cond: Vec<Float>,
     npoints: usize,
-    length: f32,
+    length: Float,
+    fourth_order: bool,
 }
 impl LinearLine {
     pub fn new<
-        Fc: Fn(f32) -> f32, Fl: Fn(f32) -> f32,
-        Fr: Fn(f32) -> f32, Fg: Fn(f32) -> f32,
+        Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+        Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float,
     >(
         desc: LinearLineDescriptor<Fc, Fl, Fr, Fg>,
     ) -> Self {
-        let delta_z = desc.length / (desc.npoints as f32);
+        let delta_z = desc.length / (desc.npoints as Float);
 
         Self {
             cap: (0..desc.npoints)
-                .map(|n| { (desc.capacitance_fn)((n as f32 + 0.5) * delta_z) })
+                .map(|n| { (desc.capacitance_fn)((n as Float + 0.5) * delta_z) })
                 .collect::<Vec<_>>(),
             ind: (0..desc.npoints)
-                .map(|n| { (desc.inductance_fn)((n as f32 + 0.5) * delta_z) })
+                .map(|n| { (desc.inductance_fn)((n as Float + 0.5) * delta_z) })
                 .collect::<Vec<_>>(),
             res: (0..desc.npoints)
-                .map(|n| { (desc.resistance_fn)((n as f32 + 0.5) * delta_z) })
+                .map(|n| { (desc.resistance_fn)((n as Float + 0.5) * delta_z) })
                 .collect::<Vec<_>>(),
             cond: (0..desc.npoints)
-                .map(|n| { (desc.conductance_fn)((n as f32 + 0.5) * delta_z) })
+                .map(|n| { (desc.conductance_fn)((n as Float + 0.5) * delta_z) })
                 .collect::<Vec<_>>(),
             npoints: desc.npoints,
             length: desc.length,
+            fourth_order: false,
         }
     }
+
+    /// Creates a new `LinearLine` on a nonuniform grid, which sets its length and number of points
+    /// in place of the descriptor.
+    ///
+    /// The parameters of each cell are sampled at its center, with the shunt parameters scaled by
+    /// the length of the cell and the series parameters by the distance to the previous cell.
+    pub fn new_on_grid<
+        Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+        Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float,
+    >(
+        desc: LinearLineDescriptor<Fc, Fl, Fr, Fg>,
+        grid: &NonuniformGrid,
+    ) -> Self {
+        let npoints = grid.npoints();
+        let shunt = |param_fn: &dyn Fn(Float) -> Float| (0..npoints)
+            .map(|n| grid.shunt_scale(n) * param_fn(grid.cell_center(n)))
+            .collect::<Vec<_>>();
+        let series = |param_fn: &dyn Fn(Float) -> Float| (0..npoints)
+            .map(|n| grid.series_scale(n) * param_fn(grid.cell_center(n)))
+            .collect::<Vec<_>>();
+
+        Self {
+            cap: shunt(&desc.capacitance_fn),
+            ind: series(&desc.inductance_fn),
+            res: series(&desc.resistance_fn),
+            cond: shunt(&desc.conductance_fn),
+            npoints,
+            length: grid.length(),
+            fourth_order: false,
+        }
+    }
+
+    /// Uses fourth order accurate spatial differences, which have far less numerical dispersion
+    /// on coarse grids.
+    ///
+    /// The first and last cells keep second order differences, since their stencils would reach
+    /// past the ends of the line. Time stepping stays second order, so the gain is largest with
+    /// time steps well below the stability limit, which is 6/7 of the usual time step and is
+    /// accounted for by `max_phase_velocity`.
+    pub fn with_fourth_order_stencil(mut self) -> Self {
+        self.fourth_order = true;
+        self
+    }
+
+    /// Gets the spatial difference of the adjacent pair of `values`, which has `stencil_width`
+    /// values on each side of the cell at `index`.
+    #[inline]
+    fn difference(&self, values: ndarray::ArrayView1<Float>, index: usize) -> Float {
+        if self.fourth_order && index > 0 && index + 1 < self.npoints {
+            9.0/8.0*(values[1] - values[2]) - (values[0] - values[3])/24.0
+        } else {
+            let width = self.stencil_width();
+            values[width - 1] - values[width]
+        }
+    }
+
+    /// Rebuilds a `LinearLine` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("LinearLine")?;
+        let cap = snapshot.get("capacitance")?.clone();
+        let npoints = cap.len();
+        let per_cell = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != npoints {
+                return Err(Error::BadSnapshot(format!("LinearLine {} has the wrong length", name)))
+            }
+            Ok(values.clone())
+        };
+
+        Ok(Self {
+            ind: per_cell("inductance")?,
+            res: per_cell("resistance")?,
+            cond: per_cell("conductance")?,
+            cap,
+            npoints,
+            length: snapshot.get_scalar("length")?,
+            fourth_order: snapshot.get_scalar("spatial_order").is_ok_and(|order| order == 4.0),
+        })
+    }
 }
 impl Component for LinearLine {
+    #[inline]
+    fn stencil_width(&self) -> usize {
+        if self.fourth_order { 2 } else { 1 }
+    }
     #[inline]
     fn next_voltage(
         &self,
-        next_volt: &mut f32,
-        last_volt: f32,
-        last_currs: ndarray::ArrayView1<f32>,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
         index: usize,
         sim_params: &SimulationParameters,
     ) {
@@ -62,14 +148,14 @@ impl Component for LinearLine {
 
         *next_volt = (d_ratio*self.cap[index] + sim_params.delta_z*self.cond[index]/2.0).recip()
             * ( (d_ratio*self.cap[index] - sim_params.delta_z*self.cond[index]/2.0) * last_volt
-                + (last_currs[0] - last_currs[1]) );
+                + self.difference(last_currs, index) );
     }
     #[inline]
     fn next_current(
         &self,
-        next_curr: &mut f32,
-        last_volts: ndarray::ArrayView1<f32>,
-        last_curr: f32,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
         index: usize,
         sim_params: &SimulationParameters,
     ) {
@@ -77,7 +163,25 @@ impl Component for LinearLine {
 
         *next_curr = (d_ratio*self.ind[index] + sim_params.delta_z*self.res[index]/2.0).recip()
             *  ( (d_ratio*self.ind[index] - sim_params.delta_z*self.res[index]/2.0) * last_curr
-                + (last_volts[0] - last_volts[1]) );
+                + self.difference(last_volts, index) );
+    }
+    #[inline]
+    fn dc_series_voltage(
+        &self,
+        current: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        sim_params.delta_z*self.res[index] * current
+    }
+    #[inline]
+    fn dc_shunt_current(
+        &self,
+        voltage: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        sim_params.delta_z*self.cond[index] * voltage
     }
 }
 impl TransmissionLine for LinearLine {
@@ -86,14 +190,39 @@ impl TransmissionLine for LinearLine {
         self.npoints
     }
     #[inline]
-    fn length(&self) -> f32 {
+    fn length(&self) -> Float {
         self.length
     }
     #[inline]
-    fn max_phase_velocity(&self) -> f32 {
-        self.ind.iter().zip(self.cap.iter())
-            .map(|(ind, cap)| f32::sqrt(ind * cap).recip())
-            .reduce(|accum, item| if accum >= item { accum } else { item })
-            .unwrap()
+    fn max_phase_velocity(&self) -> Float {
+        self.max_phase_velocity_in(0..self.npoints)
+    }
+    /// The fastest phase velocity within `cells`, scaled up by 7/6 with a fourth order stencil so
+    /// time steps derived from it stay stable.
+    #[inline]
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        let velocity = self.ind[cells.clone()].iter().zip(&self.cap[cells])
+            .map(|(ind, cap)| Float::sqrt(ind * cap).recip())
+            .fold(0.0, |accum: Float, item| if accum >= item { accum } else { item });
+        if self.fourth_order { velocity * 7.0/6.0 } else { velocity }
+    }
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        Some(LineParameters {
+            inductance: self.ind[0],
+            capacitance: self.cap[0],
+            resistance: self.res[0],
+            conductance: self.cond[0],
+        })
+    }
+    #[inline]
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("LinearLine")
+            .with("length", vec![self.length])
+            .with("capacitance", self.cap.clone())
+            .with("inductance", self.ind.clone())
+            .with("resistance", self.res.clone())
+            .with("conductance", self.cond.clone())
+            .with("spatial_order", vec![if self.fourth_order { 4.0 } else { 2.0 }])
     }
 }
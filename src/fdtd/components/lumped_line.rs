@@ -0,0 +1,413 @@
+use std::cell::Cell;
+
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+
+/// A lumped circuit element inserted into a `LumpedLine`.
+///
+/// Values are totals for the element, not per unit length.
+pub enum LumpedElement {
+    Resistor(Float),
+    Inductor(Float),
+    Capacitor(Float),
+    /// A component that replaces the update of the line at the cell, called with the index of the
+    /// cell within the whole line.
+    Custom(Box<dyn Component>),
+}
+
+/// An inserted element, with the current through a shunt inductor or the charge on a series
+/// capacitor at the end of the last update.
+struct Inserted {
+    element: LumpedElement,
+    state: Cell<Float>,
+}
+
+/// Wraps a `TransmissionLine` to insert lumped elements at chosen cells, such as tuning
+/// capacitors or a series isolation resistor.
+///
+/// Shunt elements connect the voltage of a cell to ground, and series elements sit in the current
+/// of a cell, between its voltage and the next. Each element is solved together with the update of
+/// the line, whose response to the current or voltage of the element is found by probing the
+/// update, which is exact for linear lines. Any state the line keeps is put back after probing and
+/// stepped with the current or voltage of the element. The state of shunt inductors and series
+/// capacitors starts from zero and is part of the snapshot, and DC solves only see resistors.
+///
+/// Elements assume a line with the 2nd order stencil. With a wider stencil, such as a 4th order
+/// `LinearLine`, only the update of the cell holding an element sees it, while the updates of the
+/// cells around it still reach across it, so the response of the line is slightly off.
+pub struct LumpedLine<L: TransmissionLine> {
+    line: L,
+    shunts: Vec<Option<Inserted>>,
+    series: Vec<Option<Inserted>>,
+}
+impl<L: TransmissionLine> LumpedLine<L> {
+    /// Creates a new `LumpedLine` instance, with no elements.
+    pub fn new(line: L) -> Self {
+        let npoints = line.npoints();
+        Self {
+            line,
+            shunts: (0..npoints).map(|_| None).collect(),
+            series: (0..npoints).map(|_| None).collect(),
+        }
+    }
+
+    /// Connects `element` from the voltage of cell `index` to ground, replacing any shunt element
+    /// already there.
+    pub fn with_shunt(mut self, index: usize, element: LumpedElement) -> Self {
+        self.shunts[index] = Some(Inserted { element, state: Cell::new(0.0) });
+        self
+    }
+
+    /// Puts `element` in series with the current of cell `index`, replacing any series element
+    /// already there.
+    pub fn with_series(mut self, index: usize, element: LumpedElement) -> Self {
+        self.series[index] = Some(Inserted { element, state: Cell::new(0.0) });
+        self
+    }
+
+    /// Rebuilds a `LumpedLine` from its snapshot and the wrapped line, with the state of its
+    /// elements.
+    ///
+    /// Custom elements are not part of the snapshot and must be inserted again.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot, line: L) -> Result<Self, Error> {
+        let mut lumped = Self::new(line);
+        let npoints = lumped.line.npoints();
+        let pairs = |name: &str| -> Result<Vec<(usize, Float)>, Error> {
+            let Ok(values) = snapshot.get(name) else {
+                return Ok(Vec::new())
+            };
+            values.chunks(2)
+                .map(|pair| match *pair {
+                    [index, value] if (index as usize) < npoints => Ok((index as usize, value)),
+                    _ => Err(Error::BadSnapshot(format!("LumpedLine {} are malformed", name))),
+                })
+                .collect()
+        };
+
+        for (kind, name) in ["resistors", "inductors", "capacitors"].into_iter().enumerate() {
+            let inserted = |value| {
+                let element = match kind {
+                    0 => LumpedElement::Resistor(value),
+                    1 => LumpedElement::Inductor(value),
+                    _ => LumpedElement::Capacitor(value),
+                };
+                Some(Inserted { element, state: Cell::new(0.0) })
+            };
+            for (index, value) in pairs(&format!("shunt_{}", name))? {
+                lumped.shunts[index] = inserted(value);
+            }
+            for (index, value) in pairs(&format!("series_{}", name))? {
+                lumped.series[index] = inserted(value);
+            }
+        }
+        let states = [
+            ("shunt_inductor_currents", &lumped.shunts),
+            ("series_capacitor_charges", &lumped.series),
+        ];
+        for (name, elements) in states {
+            for (index, value) in pairs(name)? {
+                let inserted = elements[index].as_ref().ok_or_else(|| {
+                    Error::BadSnapshot(format!("LumpedLine {} has no element at {}", name, index))
+                })?;
+                inserted.state.set(value);
+            }
+        }
+
+        Ok(lumped)
+    }
+
+    /// Gets the wrapped line.
+    pub fn line(&self) -> &L {
+        &self.line
+    }
+}
+
+/// Adds `offset` to the values of `stencil` past the updated point of a line with
+/// `stencil_width`.
+fn offset_stencil(
+    stencil_width: usize,
+    stencil: ndarray::ArrayView1<Float>,
+    offset: Float,
+) -> ndarray::Array1<Float> {
+    let mut shifted = stencil.to_owned();
+    shifted.slice_mut(ndarray::s![stencil_width.max(1)..]).mapv_inplace(|value| value + offset);
+    shifted
+}
+
+/// Finds the update `update` of point `index` of `line` from `next`, and how much it decreases per
+/// unit offset added to the values of its stencil past the updated point.
+///
+/// A unit offset gives a first estimate, which sets a smaller offset for lines whose updates
+/// aren't linear. Any state the line keeps for the point is put back as it was, to be stepped by
+/// `step_line_state` once the offset is known.
+pub(super) fn line_response<C: Component + ?Sized>(
+    line: &C,
+    index: usize,
+    stencil: ndarray::ArrayView1<Float>,
+    next: Float,
+    last: Float,
+    update: impl Fn(&mut Float, ndarray::ArrayView1<Float>),
+) -> (Float, Float) {
+    let before = line.point_state(index);
+    let mut unperturbed = next;
+    update(&mut unperturbed, stencil);
+    let probe = |offset: Float| {
+        line.restore_point_state(index, &before);
+        let mut perturbed = next;
+        update(&mut perturbed, offset_stencil(line.stencil_width(), stencil, offset).view());
+        (unperturbed - perturbed) / offset
+    };
+    let rough = probe(1.0);
+    let scale = last.abs().max(unperturbed.abs()).max(rough.abs());
+    let response = if rough == 0.0 || scale == 0.0 { rough } else { probe(1e-3 * scale / rough) };
+    line.restore_point_state(index, &before);
+
+    (unperturbed, response)
+}
+
+/// Steps any state `line` keeps for point `index` with the update `update`, given `unperturbed`
+/// and `response` from `line_response` and the value `next` an element brought the update to.
+pub(super) fn step_line_state<C: Component + ?Sized>(
+    line: &C,
+    index: usize,
+    stencil: ndarray::ArrayView1<Float>,
+    [unperturbed, next, response]: [Float; 3],
+    update: impl Fn(&mut Float, ndarray::ArrayView1<Float>),
+) {
+    if line.point_state(index).is_empty() {
+        return
+    }
+    // the element acts as an offset past the updated point, which brings the update to `next`
+    let offset = if response != 0.0 { (unperturbed - next) / response } else { 0.0 };
+    let mut stepped = next;
+    update(&mut stepped, offset_stencil(line.stencil_width(), stencil, offset).view());
+}
+
+impl<L: TransmissionLine> Component for LumpedLine<L> {
+    #[inline]
+    fn stencil_width(&self) -> usize {
+        self.line.stencil_width()
+    }
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let update = |volt: &mut Float, currs: ndarray::ArrayView1<Float>| {
+            self.line.next_voltage(volt, last_volt, currs, index, sim_params)
+        };
+        let Some(Inserted { ref element, ref state }) = self.shunts[index] else {
+            return update(next_volt, last_currs)
+        };
+        if let LumpedElement::Custom(ref component) = *element {
+            return component.next_voltage(next_volt, last_volt, last_currs, index, sim_params)
+        }
+
+        // the element draws a current `I` averaged over the step, which lowers the unperturbed
+        // voltage by `K I`
+        let (unperturbed, response) =
+            line_response(&self.line, index, last_currs, *next_volt, last_volt, update);
+        let delta_t = sim_params.delta_t;
+        *next_volt = match *element {
+            LumpedElement::Resistor(res) => {
+                (unperturbed - response*last_volt/(2.0*res)) / (1.0 + response/(2.0*res))
+            },
+            LumpedElement::Capacitor(cap) => {
+                (unperturbed + response*cap*last_volt/delta_t) / (1.0 + response*cap/delta_t)
+            },
+            LumpedElement::Inductor(ind) => {
+                let next = (unperturbed - response*(state.get() + delta_t*last_volt/(4.0*ind)))
+                    / (1.0 + response*delta_t/(4.0*ind));
+                state.set(state.get() + delta_t*(last_volt + next)/(2.0*ind));
+                next
+            },
+            LumpedElement::Custom(_) => unreachable!("custom elements replace the update"),
+        };
+        let values = [unperturbed, *next_volt, response];
+        step_line_state(&self.line, index, last_currs, values, update);
+    }
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let update = |curr: &mut Float, volts: ndarray::ArrayView1<Float>| {
+            self.line.next_current(curr, volts, last_curr, index, sim_params)
+        };
+        let Some(Inserted { ref element, ref state }) = self.series[index] else {
+            return update(next_curr, last_volts)
+        };
+        if let LumpedElement::Custom(ref component) = *element {
+            return component.next_current(next_curr, last_volts, last_curr, index, sim_params)
+        }
+
+        // the element drops a voltage `V` averaged over the step, which lowers the unperturbed
+        // current by `K V`
+        let (unperturbed, response) =
+            line_response(&self.line, index, last_volts, *next_curr, last_curr, update);
+        let delta_t = sim_params.delta_t;
+        *next_curr = match *element {
+            LumpedElement::Resistor(res) => {
+                (unperturbed - response*res*last_curr/2.0) / (1.0 + response*res/2.0)
+            },
+            LumpedElement::Inductor(ind) => {
+                (unperturbed + response*ind*last_curr/delta_t) / (1.0 + response*ind/delta_t)
+            },
+            LumpedElement::Capacitor(cap) => {
+                let next = (unperturbed - response*(state.get() + delta_t*last_curr/4.0)/cap)
+                    / (1.0 + response*delta_t/(4.0*cap));
+                state.set(state.get() + delta_t*(last_curr + next)/2.0);
+                next
+            },
+            LumpedElement::Custom(_) => unreachable!("custom elements replace the update"),
+        };
+        let values = [unperturbed, *next_curr, response];
+        step_line_state(&self.line, index, last_volts, values, update);
+    }
+    fn dc_series_voltage(
+        &self,
+        current: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        let line_voltage = self.line.dc_series_voltage(current, index, sim_params);
+        match self.series[index] {
+            Some(Inserted { element: LumpedElement::Resistor(res), .. }) => {
+                line_voltage + res*current
+            },
+            Some(Inserted { element: LumpedElement::Custom(ref component), .. }) => {
+                component.dc_series_voltage(current, index, sim_params)
+            },
+            _ => line_voltage,
+        }
+    }
+    fn dc_shunt_current(
+        &self,
+        voltage: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        let line_current = self.line.dc_shunt_current(voltage, index, sim_params);
+        match self.shunts[index] {
+            Some(Inserted { element: LumpedElement::Resistor(res), .. }) => {
+                line_current + voltage/res
+            },
+            Some(Inserted { element: LumpedElement::Custom(ref component), .. }) => {
+                component.dc_shunt_current(voltage, index, sim_params)
+            },
+            _ => line_current,
+        }
+    }
+    /// The states of the shunt and series elements, followed by those of the wrapped line and any
+    /// custom shunt and series elements, so all zeros is a cell at rest.
+    fn point_state(&self, index: usize) -> Vec<Float> {
+        let own = |inserted: &Option<Inserted>| inserted.as_ref().map_or(0.0, |x| x.state.get());
+        let mut state = vec![own(&self.shunts[index]), own(&self.series[index])];
+        state.extend(self.line.point_state(index));
+        for inserted in [&self.shunts[index], &self.series[index]] {
+            if let Some(Inserted { element: LumpedElement::Custom(ref custom), .. }) = *inserted {
+                state.extend(custom.point_state(index));
+            }
+        }
+        state
+    }
+    fn restore_point_state(&self, index: usize, state: &[Float]) {
+        let elements = [&self.shunts[index], &self.series[index]];
+        for (inserted, &value) in elements.into_iter().zip(state) {
+            if let Some(inserted) = inserted {
+                inserted.state.set(value);
+            }
+        }
+        // each part is as long as the state it currently has
+        let (line_state, mut rest) = state[2..].split_at(self.line.point_state(index).len());
+        self.line.restore_point_state(index, line_state);
+        for inserted in elements {
+            if let Some(Inserted { element: LumpedElement::Custom(ref custom), .. }) = *inserted {
+                let (part, tail) = rest.split_at(custom.point_state(index).len());
+                custom.restore_point_state(index, part);
+                rest = tail;
+            }
+        }
+    }
+}
+impl<L: TransmissionLine> TransmissionLine for LumpedLine<L> {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.line.npoints()
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.line.length()
+    }
+    /// The fastest phase velocity of the wrapped line, which lumped elements only slow down.
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.line.max_phase_velocity()
+    }
+    #[inline]
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        self.line.max_phase_velocity_in(cells)
+    }
+    /// The parameters of the wrapped line, which any lumped element at the start adds to.
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        self.line.port_parameters()
+    }
+    fn begin_step(&mut self, time: Float, sim_params: &SimulationParameters) {
+        self.line.begin_step(time, sim_params)
+    }
+    fn channels(&self) -> Vec<(String, usize, &'static str)> {
+        self.line.channels()
+    }
+    fn record_channels(&self, rows: &mut [ndarray::ArrayViewMut1<Float>]) {
+        self.line.record_channels(rows)
+    }
+    /// The snapshot of the wrapped line, with the cell and value of each resistor, inductor, and
+    /// capacitor in pairs, and the cell and state of each shunt inductor and series capacitor.
+    fn snapshot(&self) -> ComponentSnapshot {
+        let mut snapshot = self.line.snapshot();
+        let placements = [("shunt", &self.shunts), ("series", &self.series)];
+        for (placement, elements) in placements {
+            let mut pairs = [Vec::new(), Vec::new(), Vec::new()];
+            for (index, inserted) in elements.iter().enumerate() {
+                let (kind, value) = match inserted {
+                    Some(Inserted { element: LumpedElement::Resistor(res), .. }) => (0, *res),
+                    Some(Inserted { element: LumpedElement::Inductor(ind), .. }) => (1, *ind),
+                    Some(Inserted { element: LumpedElement::Capacitor(cap), .. }) => (2, *cap),
+                    _ => continue,
+                };
+                pairs[kind].extend([index as Float, value]);
+            }
+            for (kind, values) in ["resistors", "inductors", "capacitors"].iter().zip(pairs) {
+                if !values.is_empty() {
+                    snapshot = snapshot.with(&format!("{}_{}", placement, kind), values);
+                }
+            }
+        }
+        let stateful = |inserted: &Option<Inserted>| match *inserted {
+            Some(Inserted { element: LumpedElement::Inductor(_), ref state }) => Some(state.get()),
+            Some(Inserted { element: LumpedElement::Capacitor(_), ref state }) => Some(state.get()),
+            _ => None,
+        };
+        let states = [
+            ("shunt_inductor_currents", &self.shunts),
+            ("series_capacitor_charges", &self.series),
+        ];
+        for (name, elements) in states {
+            let pairs = elements.iter().enumerate()
+                .filter_map(|(index, inserted)| Some([index as Float, stateful(inserted)?]))
+                .flatten()
+                .collect::<Vec<_>>();
+            if !pairs.is_empty() {
+                snapshot = snapshot.with(name, pairs);
+            }
+        }
+        snapshot
+    }
+}
@@ -0,0 +1,114 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::{ComponentSnapshot, Float, SimulationParameters};
+use crate::fdtd::Terminator;
+
+/// The load side quantities recorded by a `RecordingTerminator`, one entry per time step.
+///
+/// The power is that flowing through the port into the end node, which is the load together with
+/// the capacitance of the end node. It matches the power dissipated in the load on average over
+/// whole periods of a steady state, but not from step to step.
+#[derive(Clone, Debug, Default)]
+pub struct LoadRecord {
+    /// The voltage across the load at the end of each time step.
+    pub voltages: Vec<Float>,
+    /// The current flowing from the line into the load during each time step.
+    pub currents: Vec<Float>,
+    /// The power flowing through the port during each time step.
+    pub port_powers: Vec<Float>,
+}
+
+impl LoadRecord {
+    /// Gets the average power flowing through the port.
+    pub fn average_port_power(&self) -> Float {
+        self.port_powers.iter().sum::<Float>() / (self.port_powers.len().max(1) as Float)
+    }
+
+    /// Gets the total energy that flowed through the port.
+    pub fn port_energy(&self, sim_params: &SimulationParameters) -> Float {
+        self.port_powers.iter().sum::<Float>() * sim_params.delta_t
+    }
+}
+
+/// A shared handle to the record of a `RecordingTerminator`.
+///
+/// Clones refer to the same record, so a handle can be kept after the terminator is given to a
+/// solver.
+#[derive(Clone, Debug, Default)]
+pub struct LoadRecorder(Rc<RefCell<LoadRecord>>);
+
+impl LoadRecorder {
+    /// Gets a copy of everything recorded so far.
+    pub fn record(&self) -> LoadRecord {
+        self.0.borrow().clone()
+    }
+
+    /// Takes everything recorded so far, leaving the record empty.
+    pub fn take(&self) -> LoadRecord {
+        self.0.take()
+    }
+}
+
+/// Wraps a `Terminator` to record the voltage, current, and power at its port.
+pub struct RecordingTerminator<T: Terminator> {
+    terminator: T,
+    recorder: LoadRecorder,
+}
+
+impl<T: Terminator> RecordingTerminator<T> {
+    /// Creates a new `RecordingTerminator` instance.
+    #[inline]
+    pub fn new(terminator: T) -> Self {
+        Self {
+            terminator,
+            recorder: LoadRecorder::default(),
+        }
+    }
+
+    /// Gets a handle to the record.
+    #[inline]
+    pub fn recorder(&self) -> LoadRecorder {
+        self.recorder.clone()
+    }
+}
+
+impl<T: Terminator> Terminator for RecordingTerminator<T> {
+    fn next_voltage(
+        &self,
+        last_volt: Float,
+        last_curr: Float,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        let next_volt = self.terminator.next_voltage(last_volt, last_curr, sim_params);
+
+        // the current is centered between the last and next voltages
+        let mut record = self.recorder.0.borrow_mut();
+        record.voltages.push(next_volt);
+        record.currents.push(last_curr);
+        record.port_powers.push((last_volt + next_volt) / 2.0 * last_curr);
+
+        next_volt
+    }
+
+    fn next_current(
+        &self,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        self.terminator.next_current(last_volts, last_curr, sim_params)
+    }
+
+    fn dc_end(&self, scale: Float, sim_params: &SimulationParameters) -> (Float, Float) {
+        self.terminator.dc_end(scale, sim_params)
+    }
+
+    fn dc_series_voltage(&self, current: Float, sim_params: &SimulationParameters) -> Float {
+        self.terminator.dc_series_voltage(current, sim_params)
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        self.terminator.snapshot()
+    }
+}
@@ -0,0 +1,257 @@
+use std::cell::Cell;
+
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+use super::lumped_line::{line_response, step_line_state};
+
+/// A shunt resonator loading a `ResonatorLine`, as a parallel inductor and capacitor to ground
+/// coupled to the line through a capacitor.
+///
+/// Values are totals for the resonator, not per unit length.
+#[derive(Copy, Clone, Debug)]
+pub struct Resonator {
+    pub coupling_capacitance: Float,
+    pub inductance: Float,
+    pub capacitance: Float,
+}
+
+/// Describes a `ResonatorLine`.
+pub struct ResonatorLineDescriptor<L: TransmissionLine> {
+    pub line: L,
+    pub resonator: Resonator,
+    /// The number of cells from one resonator to the next.
+    pub period: usize,
+    /// The cell of the first resonator.
+    pub offset: usize,
+}
+
+/// The voltage across a resonator and the current through its inductor at the end of the last
+/// update.
+struct ResonatorState {
+    voltage: Cell<Float>,
+    current: Cell<Float>,
+}
+
+/// Wraps a `TransmissionLine` to load it with shunt resonators at regular intervals, as in
+/// resonantly phase matched traveling wave parametric amplifiers.
+///
+/// Each loaded cell solves for the current drawn by its resonator together with the voltage
+/// update of the line, with the resonator stepped by the trapezoidal rule. As in a `LumpedLine`,
+/// the response of the line to that current is found by probing its update, which puts back any
+/// state the line keeps before stepping it with the current drawn. The resonator
+/// voltages are recorded in the `resonator_voltages` channel, and the coupling capacitors block
+/// DC.
+pub struct ResonatorLine<L: TransmissionLine> {
+    line: L,
+    resonator: Resonator,
+    period: usize,
+    offset: usize,
+    states: Vec<ResonatorState>,
+}
+impl<L: TransmissionLine> ResonatorLine<L> {
+    pub fn new(desc: ResonatorLineDescriptor<L>) -> Self {
+        let period = desc.period.max(1);
+        let count = desc.line.npoints().saturating_sub(desc.offset).div_ceil(period);
+        Self {
+            line: desc.line,
+            resonator: desc.resonator,
+            period,
+            offset: desc.offset,
+            states: (0..count)
+                .map(|_| ResonatorState { voltage: Cell::new(0.0), current: Cell::new(0.0) })
+                .collect(),
+        }
+    }
+
+    /// Rebuilds a `ResonatorLine` from its snapshot and the wrapped line, with the voltages and
+    /// currents of its resonators.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot, line: L) -> Result<Self, Error> {
+        let resonator_line = Self::new(ResonatorLineDescriptor {
+            line,
+            resonator: Resonator {
+                coupling_capacitance: snapshot.get_scalar("resonator_coupling_capacitance")?,
+                inductance: snapshot.get_scalar("resonator_inductance")?,
+                capacitance: snapshot.get_scalar("resonator_capacitance")?,
+            },
+            period: snapshot.get_scalar("resonator_period")? as usize,
+            offset: snapshot.get_scalar("resonator_offset")? as usize,
+        });
+
+        let count = resonator_line.states.len();
+        let per_resonator = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != count {
+                let message = format!("ResonatorLine {} has the wrong length", name);
+                return Err(Error::BadSnapshot(message))
+            }
+            Ok(values)
+        };
+        let voltages = per_resonator("resonator_voltage")?;
+        let currents = per_resonator("resonator_current")?;
+        let values = voltages.iter().zip(currents);
+        for (state, (&voltage, &current)) in resonator_line.states.iter().zip(values) {
+            state.voltage.set(voltage);
+            state.current.set(current);
+        }
+
+        Ok(resonator_line)
+    }
+
+    /// Gets the wrapped line.
+    pub fn line(&self) -> &L {
+        &self.line
+    }
+
+    /// The state of the resonator at cell `index`, if it's loaded.
+    #[inline]
+    fn state(&self, index: usize) -> Option<&ResonatorState> {
+        let shifted = index.checked_sub(self.offset)?;
+        if shifted % self.period != 0 {
+            return None
+        }
+        self.states.get(shifted / self.period)
+    }
+}
+impl<L: TransmissionLine> Component for ResonatorLine<L> {
+    #[inline]
+    fn stencil_width(&self) -> usize {
+        self.line.stencil_width()
+    }
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let update = |volt: &mut Float, currs: ndarray::ArrayView1<Float>| {
+            self.line.next_voltage(volt, last_volt, currs, index, sim_params)
+        };
+        let Some(state) = self.state(index) else {
+            return update(next_volt, last_currs)
+        };
+
+        let (unperturbed, response) =
+            line_response(&self.line, index, last_currs, *next_volt, last_volt, update);
+
+        // the resonator draws `g ΔV + h` on average over the step, where `ΔV` is the change in
+        // the voltage of the line, which lowers the unperturbed voltage by `K (g ΔV + h)`
+        let Resonator { coupling_capacitance: coupling, inductance, capacitance } = self.resonator;
+        let delta_t = sim_params.delta_t;
+        let (voltage, current) = (state.voltage.get(), state.current.get());
+        let grounded = capacitance + delta_t.powi(2)/(4.0*inductance);
+        let total = coupling + grounded;
+        let drive = delta_t*current + delta_t.powi(2)*voltage/(2.0*inductance);
+        let slope = coupling*grounded / (total*delta_t);
+        let offset = coupling*drive / (total*delta_t);
+
+        *next_volt = (unperturbed + response*(slope*last_volt - offset)) / (1.0 + response*slope);
+        let values = [unperturbed, *next_volt, response];
+        step_line_state(&self.line, index, last_currs, values, update);
+
+        let voltage_change = (coupling*(*next_volt - last_volt) - drive) / total;
+        state.current.set(current + delta_t*(voltage + voltage_change/2.0)/inductance);
+        state.voltage.set(voltage + voltage_change);
+    }
+    #[inline]
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        self.line.next_current(next_curr, last_volts, last_curr, index, sim_params);
+    }
+    #[inline]
+    fn dc_series_voltage(
+        &self,
+        current: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        self.line.dc_series_voltage(current, index, sim_params)
+    }
+    #[inline]
+    fn dc_shunt_current(
+        &self,
+        voltage: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        self.line.dc_shunt_current(voltage, index, sim_params)
+    }
+    /// The state of the wrapped line, followed by the voltage and current of any resonator.
+    fn point_state(&self, index: usize) -> Vec<Float> {
+        let mut state = self.line.point_state(index);
+        if let Some(resonator) = self.state(index) {
+            state.extend([resonator.voltage.get(), resonator.current.get()]);
+        }
+        state
+    }
+    fn restore_point_state(&self, index: usize, state: &[Float]) {
+        match self.state(index) {
+            Some(resonator) => {
+                let (line_state, own) = state.split_at(state.len() - 2);
+                self.line.restore_point_state(index, line_state);
+                resonator.voltage.set(own[0]);
+                resonator.current.set(own[1]);
+            },
+            None => self.line.restore_point_state(index, state),
+        }
+    }
+}
+impl<L: TransmissionLine> TransmissionLine for ResonatorLine<L> {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.line.npoints()
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.line.length()
+    }
+    /// The fastest phase velocity of the wrapped line, which the resonators only slow down.
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.line.max_phase_velocity()
+    }
+    #[inline]
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        self.line.max_phase_velocity_in(cells)
+    }
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        self.line.port_parameters()
+    }
+    fn begin_step(&mut self, time: Float, sim_params: &SimulationParameters) {
+        self.line.begin_step(time, sim_params)
+    }
+    /// The channels of the wrapped line, followed by the voltage across each resonator.
+    fn channels(&self) -> Vec<(String, usize, &'static str)> {
+        let mut channels = self.line.channels();
+        channels.push(("resonator_voltages".to_string(), self.states.len(), "V"));
+        channels
+    }
+    fn record_channels(&self, rows: &mut [ndarray::ArrayViewMut1<Float>]) {
+        let (last, own) = rows.split_last_mut().expect("resonator lines have a channel");
+        self.line.record_channels(own);
+        for (value, state) in last.iter_mut().zip(&self.states) {
+            *value = state.voltage.get();
+        }
+    }
+    /// The snapshot of the wrapped line, with the resonators and their states.
+    fn snapshot(&self) -> ComponentSnapshot {
+        let Resonator { coupling_capacitance, inductance, capacitance } = self.resonator;
+        let states = |value: fn(&ResonatorState) -> Float| self.states.iter().map(value).collect();
+        self.line.snapshot()
+            .with("resonator_period", vec![self.period as Float])
+            .with("resonator_offset", vec![self.offset as Float])
+            .with("resonator_coupling_capacitance", vec![coupling_capacitance])
+            .with("resonator_inductance", vec![inductance])
+            .with("resonator_capacitance", vec![capacitance])
+            .with("resonator_voltage", states(|state| state.voltage.get()))
+            .with("resonator_current", states(|state| state.current.get()))
+    }
+}
@@ -0,0 +1,301 @@
+use std::cell::Cell;
+
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+
+/// The number of poles per decade fitting the skin effect impedance.
+const POLES_PER_DECADE: Float = 3.0;
+/// How far past each end of the fitted band the poles extend, as a factor in frequency.
+const BAND_MARGIN: Float = 10.0;
+
+/// Describes a `SkinEffectLine`.
+pub struct SkinEffectLineDescriptor<
+    Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+    Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float,
+    Fs: Fn(Float) -> Float,
+>{
+    pub length: Float,
+    pub npoints: usize,
+    pub capacitance_fn: Fc,
+    /// The external inductance per unit length, which the skin effect adds to.
+    pub inductance_fn: Fl,
+    /// The resistance per unit length that doesn't depend on frequency.
+    pub resistance_fn: Fr,
+    pub conductance_fn: Fg,
+    /// The skin effect resistance per unit length at the reference frequency.
+    pub skin_resistance_fn: Fs,
+    pub reference_frequency: Float,
+    /// The lowest frequency at which the skin effect is accurate.
+    pub min_frequency: Float,
+    /// The highest frequency at which the skin effect is accurate.
+    pub max_frequency: Float,
+}
+
+/// A linear line with conductor loss from the skin effect, which grows with the square root of
+/// frequency.
+///
+/// The skin effect impedance `Rs sqrt(jω / ωref)` has equal resistance and internal inductance at
+/// every frequency. It is fitted within 1% over the band by a chain of parallel resistor and
+/// inductor pairs, along with a series resistor and inductor for the poles beyond it, and the
+/// current through the inductor of each pair is stepped alongside the line current, which is a
+/// recursive convolution with the impulse response. Below the band the resistance levels off.
+///
+/// The inductor currents start from zero and are advanced by every current update.
+pub struct SkinEffectLine {
+    cap: Vec<Float>,
+    ind: Vec<Float>,
+    res: Vec<Float>,
+    cond: Vec<Float>,
+    skin_res: Vec<Float>,
+    reference_frequency: Float,
+    band: (Float, Float),
+    /// The angular frequency and weight of each pole in the fit of `sqrt(s)`.
+    poles: Vec<(Float, Float)>,
+    /// The resistance and inductance fitting `sqrt(s)` beyond the poles.
+    tails: (Float, Float),
+    /// The inductor current of each pole of each cell.
+    branch_currs: Vec<Cell<Float>>,
+    npoints: usize,
+    length: Float,
+}
+impl SkinEffectLine {
+    pub fn new<
+        Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+        Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float,
+        Fs: Fn(Float) -> Float,
+    >(
+        desc: SkinEffectLineDescriptor<Fc, Fl, Fr, Fg, Fs>,
+    ) -> Self {
+        let delta_z = desc.length / (desc.npoints as Float);
+        let sample = |param_fn: &dyn Fn(Float) -> Float| (0..desc.npoints)
+            .map(|n| param_fn((n as Float + 0.5) * delta_z))
+            .collect::<Vec<_>>();
+
+        Self::from_parameters(
+            [
+                sample(&desc.capacitance_fn),
+                sample(&desc.inductance_fn),
+                sample(&desc.resistance_fn),
+                sample(&desc.conductance_fn),
+                sample(&desc.skin_resistance_fn),
+            ],
+            desc.reference_frequency,
+            (desc.min_frequency, desc.max_frequency),
+            desc.length,
+        )
+    }
+
+    /// Rebuilds a `SkinEffectLine` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("SkinEffectLine")?;
+        let cap = snapshot.get("capacitance")?.clone();
+        let npoints = cap.len();
+        let per_cell = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != npoints {
+                let message = format!("SkinEffectLine {} has the wrong length", name);
+                return Err(Error::BadSnapshot(message))
+            }
+            Ok(values.clone())
+        };
+
+        let mut line = Self::from_parameters(
+            [
+                cap,
+                per_cell("inductance")?,
+                per_cell("resistance")?,
+                per_cell("conductance")?,
+                per_cell("skin_resistance")?,
+            ],
+            snapshot.get_scalar("reference_frequency")?,
+            (snapshot.get_scalar("min_frequency")?, snapshot.get_scalar("max_frequency")?),
+            snapshot.get_scalar("length")?,
+        );
+        let branch_currs = snapshot.get("branch_current")?;
+        if branch_currs.len() != line.branch_currs.len() {
+            let message = "SkinEffectLine branch_current has the wrong length".to_string();
+            return Err(Error::BadSnapshot(message))
+        }
+        line.branch_currs = branch_currs.iter().copied().map(Cell::new).collect();
+        Ok(line)
+    }
+
+    /// Fits the skin effect over `band` and assembles the line.
+    fn from_parameters(
+        [cap, ind, res, cond, skin_res]: [Vec<Float>; 5],
+        reference_frequency: Float,
+        band: (Float, Float),
+        length: Float,
+    ) -> Self {
+        // `sqrt(s)` is the integral of `s / (s + x) / (π sqrt(x))` over `x`, which is summed on a
+        // logarithmic grid of poles, with the parts below and above the grid integrated as if `s`
+        // were far above or below them
+        let two_pi = 2.0*crate::consts::PI;
+        let lowest = Float::ln(two_pi*band.0 / BAND_MARGIN);
+        let highest = Float::ln(two_pi*band.1 * BAND_MARGIN);
+        let count = ((highest - lowest) / Float::ln(10.0) * POLES_PER_DECADE).ceil().max(1.0);
+        let step = (highest - lowest) / count;
+        let poles = (0..(count as usize))
+            .map(|k| {
+                let pole = Float::exp(lowest + step*(k as Float + 0.5));
+                (pole, pole.sqrt()*step / crate::consts::PI)
+            })
+            .collect::<Vec<_>>();
+        let tails = (
+            2.0*Float::exp(lowest / 2.0) / crate::consts::PI,
+            2.0*Float::exp(-highest / 2.0) / crate::consts::PI,
+        );
+        let npoints = cap.len();
+
+        Self {
+            branch_currs: (0..(npoints*poles.len())).map(|_| Cell::new(0.0)).collect(),
+            cap,
+            ind,
+            res,
+            cond,
+            skin_res,
+            reference_frequency,
+            band,
+            poles,
+            tails,
+            npoints,
+            length,
+        }
+    }
+
+    /// The coefficient of `sqrt(s)` in the skin effect impedance per unit length of cell `index`.
+    #[inline]
+    fn skin_coefficient(&self, index: usize) -> Float {
+        self.skin_res[index] * Float::sqrt(2.0 / (2.0*crate::consts::PI*self.reference_frequency))
+    }
+}
+impl Component for SkinEffectLine {
+    #[inline]
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+
+        *next_volt = (d_ratio*self.cap[index] + sim_params.delta_z*self.cond[index]/2.0).recip()
+            * ( (d_ratio*self.cap[index] - sim_params.delta_z*self.cond[index]/2.0) * last_volt
+                + (last_currs[0] - last_currs[1]) );
+    }
+    #[inline]
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let SimulationParameters { delta_z, delta_t } = *sim_params;
+        let d_ratio = delta_z / delta_t;
+        let coefficient = self.skin_coefficient(index);
+        let branch_currs = &self.branch_currs[(index*self.poles.len())..][..self.poles.len()];
+
+        // with the trapezoidal rule, pair `k` drops `Rk / (1 + αk) (Ī - ik)` on average over the
+        // step, where `Ī` is the average line current, `ik` the last inductor current, and
+        // `αk = xk Δt / 2`
+        let (mut res, mut drop) = (self.res[index] + coefficient*self.tails.0, 0.0);
+        for (&(pole, weight), branch_curr) in self.poles.iter().zip(branch_currs) {
+            let damping = 1.0 + pole*delta_t/2.0;
+            res += coefficient*weight / damping;
+            drop += coefficient*weight / damping * branch_curr.get();
+        }
+        let ind = self.ind[index] + coefficient*self.tails.1;
+
+        *next_curr = (d_ratio*ind + delta_z*res/2.0).recip()
+            * ( (d_ratio*ind - delta_z*res/2.0) * last_curr
+                + (last_volts[0] - last_volts[1]) + delta_z*drop );
+
+        let average = (last_curr + *next_curr) / 2.0;
+        for (&(pole, _), branch_curr) in self.poles.iter().zip(branch_currs) {
+            let alpha = pole*delta_t/2.0;
+            branch_curr.set(((1.0 - alpha)*branch_curr.get() + 2.0*alpha*average) / (1.0 + alpha));
+        }
+    }
+    fn point_state(&self, index: usize) -> Vec<Float> {
+        let branch_currs = &self.branch_currs[(index*self.poles.len())..][..self.poles.len()];
+        branch_currs.iter().map(Cell::get).collect()
+    }
+    fn restore_point_state(&self, index: usize, state: &[Float]) {
+        let branch_currs = &self.branch_currs[(index*self.poles.len())..][..self.poles.len()];
+        for (branch_curr, &value) in branch_currs.iter().zip(state) {
+            branch_curr.set(value);
+        }
+    }
+    /// The DC voltage across the frequency independent resistance, and the part of the fit that
+    /// levels off below its band.
+    #[inline]
+    fn dc_series_voltage(
+        &self,
+        current: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        let res = self.res[index] + self.skin_coefficient(index)*self.tails.0;
+        sim_params.delta_z*res * current
+    }
+    #[inline]
+    fn dc_shunt_current(
+        &self,
+        voltage: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        sim_params.delta_z*self.cond[index] * voltage
+    }
+}
+impl TransmissionLine for SkinEffectLine {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.npoints
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.length
+    }
+    /// The fastest phase velocity with the external inductance alone, which the internal
+    /// inductance of the skin effect only slows down.
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.max_phase_velocity_in(0..self.npoints)
+    }
+    #[inline]
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        self.ind[cells.clone()].iter().zip(&self.cap[cells])
+            .map(|(ind, cap)| Float::sqrt(ind * cap).recip())
+            .fold(0.0, |accum, item| if accum >= item { accum } else { item })
+    }
+    /// The parameters without the skin effect, which only adds loss and inductance at high
+    /// frequencies.
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        Some(LineParameters {
+            inductance: self.ind[0],
+            capacitance: self.cap[0],
+            resistance: self.res[0],
+            conductance: self.cond[0],
+        })
+    }
+    #[inline]
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("SkinEffectLine")
+            .with("length", vec![self.length])
+            .with("capacitance", self.cap.clone())
+            .with("inductance", self.ind.clone())
+            .with("resistance", self.res.clone())
+            .with("conductance", self.cond.clone())
+            .with("skin_resistance", self.skin_res.clone())
+            .with("reference_frequency", vec![self.reference_frequency])
+            .with("min_frequency", vec![self.band.0])
+            .with("max_frequency", vec![self.band.1])
+            .with("branch_current", self.branch_currs.iter().map(Cell::get).collect())
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+use super::{LinearLine, LinearLineDescriptor};
+
+/// How a `TabulatedLine` interpolates between its samples.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TableInterpolation {
+    /// Straight lines between samples.
+    Linear,
+    /// A natural cubic spline through the samples, which is smooth but may overshoot.
+    Spline,
+}
+
+/// Describes a `TabulatedLine`.
+///
+/// The tables hold the parameters per unit length at each of `positions`, which must be strictly
+/// increasing.
+pub struct TabulatedLineDescriptor {
+    pub length: Float,
+    pub npoints: usize,
+    pub positions: Vec<Float>,
+    pub capacitance: Vec<Float>,
+    pub inductance: Vec<Float>,
+    pub resistance: Vec<Float>,
+    pub conductance: Vec<Float>,
+    pub interpolation: TableInterpolation,
+}
+
+/// A parameter sampled at increasing positions.
+struct Table<'a> {
+    positions: &'a [Float],
+    values: &'a [Float],
+    /// The second derivative at each sample, for a spline.
+    curvatures: Option<Vec<Float>>,
+}
+impl<'a> Table<'a> {
+    fn new(positions: &'a [Float], values: &'a [Float], interpolation: TableInterpolation) -> Self {
+        let curvatures = match interpolation {
+            TableInterpolation::Linear => None,
+            TableInterpolation::Spline => Some(Self::curvatures(positions, values)),
+        };
+        Self { positions, values, curvatures }
+    }
+
+    /// Solves for the second derivatives of the natural spline, which vanish at the ends, by
+    /// elimination of the tridiagonal system.
+    fn curvatures(positions: &[Float], values: &[Float]) -> Vec<Float> {
+        let count = positions.len();
+        let mut curvatures = vec![0.0; count];
+        if count < 3 {
+            return curvatures
+        }
+        let mut diagonal = vec![1.0; count];
+        let mut rhs = vec![0.0; count];
+        for k in 1..(count - 1) {
+            let before = positions[k] - positions[k - 1];
+            let after = positions[k + 1] - positions[k];
+            diagonal[k] = (before + after) / 3.0;
+            rhs[k] = (values[k + 1] - values[k])/after - (values[k] - values[k - 1])/before;
+            if k > 1 {
+                let factor = before / 6.0 / diagonal[k - 1];
+                diagonal[k] -= factor * before / 6.0;
+                rhs[k] -= factor * rhs[k - 1];
+            }
+        }
+        for k in (1..(count - 1)).rev() {
+            let after = positions[k + 1] - positions[k];
+            curvatures[k] = (rhs[k] - after/6.0*curvatures[k + 1]) / diagonal[k];
+        }
+        curvatures
+    }
+
+    /// Interpolates the table at `z`, holding the end values past either end.
+    fn at(&self, z: Float) -> Float {
+        let last = self.positions.len() - 1;
+        if z <= self.positions[0] {
+            return self.values[0]
+        } else if z >= self.positions[last] {
+            return self.values[last]
+        }
+
+        let k = self.positions.partition_point(|&position| position <= z) - 1;
+        let width = self.positions[k + 1] - self.positions[k];
+        let fraction = (z - self.positions[k]) / width;
+        let linear = (1.0 - fraction)*self.values[k] + fraction*self.values[k + 1];
+        match self.curvatures {
+            None => linear,
+            Some(ref curvatures) => {
+                linear - width.powi(2)/6.0 * fraction*(1.0 - fraction)
+                    * ((2.0 - fraction)*curvatures[k] + (1.0 + fraction)*curvatures[k + 1])
+            },
+        }
+    }
+}
+
+/// A linear line whose parameters are interpolated from tables, such as measured or optimized
+/// profiles.
+///
+/// Each cell takes the parameters interpolated at its center, and is then updated as a
+/// `LinearLine`.
+pub struct TabulatedLine {
+    line: LinearLine,
+    positions: Vec<Float>,
+    /// The capacitance, inductance, resistance, and conductance tables.
+    tables: [Vec<Float>; 4],
+    interpolation: TableInterpolation,
+}
+impl TabulatedLine {
+    pub fn new(desc: TabulatedLineDescriptor) -> Result<Self, Error> {
+        let count = desc.positions.len();
+        if count == 0 {
+            return Err(Error::BadGrid("a tabulated line needs a sample".to_string()))
+        }
+        if let Some(k) = desc.positions.windows(2).position(|pair| pair[0] >= pair[1]) {
+            return Err(Error::BadGrid(format!(
+                "sample {} at {} doesn't follow sample {} at {}",
+                k + 1, desc.positions[k + 1], k, desc.positions[k],
+            )))
+        }
+        let names = ["capacitance", "inductance", "resistance", "conductance"];
+        let tables = [desc.capacitance, desc.inductance, desc.resistance, desc.conductance];
+        for (name, table) in names.iter().zip(&tables) {
+            if table.len() != count {
+                return Err(Error::BadInit {
+                    array_name: name.to_string(),
+                    input_length: table.len(),
+                    expected_length: count,
+                })
+            }
+        }
+
+        let [cap, ind, res, cond] = tables.each_ref()
+            .map(|values| Table::new(&desc.positions, values, desc.interpolation));
+        let line = LinearLine::new(LinearLineDescriptor {
+            length: desc.length,
+            npoints: desc.npoints,
+            capacitance_fn: |z| cap.at(z),
+            inductance_fn: |z| ind.at(z),
+            resistance_fn: |z| res.at(z),
+            conductance_fn: |z| cond.at(z),
+        });
+
+        Ok(Self {
+            line,
+            positions: desc.positions,
+            tables,
+            interpolation: desc.interpolation,
+        })
+    }
+
+    /// Rebuilds a `TabulatedLine` from its snapshot, by interpolating its tables again.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("TabulatedLine")?;
+        let line = Self::new(TabulatedLineDescriptor {
+            length: snapshot.get_scalar("length")?,
+            npoints: snapshot.get("capacitance")?.len(),
+            positions: snapshot.get("table_positions")?.clone(),
+            capacitance: snapshot.get("table_capacitance")?.clone(),
+            inductance: snapshot.get("table_inductance")?.clone(),
+            resistance: snapshot.get("table_resistance")?.clone(),
+            conductance: snapshot.get("table_conductance")?.clone(),
+            interpolation: if snapshot.get_scalar("spline")? == 1.0 {
+                TableInterpolation::Spline
+            } else {
+                TableInterpolation::Linear
+            },
+        })?;
+        if snapshot.get_scalar("spatial_order").is_ok_and(|order| order == 4.0) {
+            Ok(line.with_fourth_order_stencil())
+        } else {
+            Ok(line)
+        }
+    }
+
+    /// Uses fourth order accurate spatial differences, as `LinearLine::with_fourth_order_stencil`.
+    pub fn with_fourth_order_stencil(mut self) -> Self {
+        self.line = self.line.with_fourth_order_stencil();
+        self
+    }
+
+    /// Gets the line the tables were interpolated onto.
+    pub fn line(&self) -> &LinearLine {
+        &self.line
+    }
+}
+impl Component for TabulatedLine {
+    #[inline]
+    fn stencil_width(&self) -> usize {
+        self.line.stencil_width()
+    }
+    #[inline]
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        self.line.next_voltage(next_volt, last_volt, last_currs, index, sim_params);
+    }
+    #[inline]
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        self.line.next_current(next_curr, last_volts, last_curr, index, sim_params);
+    }
+    #[inline]
+    fn dc_series_voltage(
+        &self,
+        current: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        self.line.dc_series_voltage(current, index, sim_params)
+    }
+    #[inline]
+    fn dc_shunt_current(
+        &self,
+        voltage: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        self.line.dc_shunt_current(voltage, index, sim_params)
+    }
+}
+impl TransmissionLine for TabulatedLine {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.line.npoints()
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.line.length()
+    }
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.line.max_phase_velocity()
+    }
+    #[inline]
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        self.line.max_phase_velocity_in(cells)
+    }
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        self.line.port_parameters()
+    }
+    /// The interpolated parameters of each cell, as for a `LinearLine`, along with the tables.
+    fn snapshot(&self) -> ComponentSnapshot {
+        let mut snapshot = self.line.snapshot();
+        snapshot.kind = "TabulatedLine".to_string();
+        let names = ["capacitance", "inductance", "resistance", "conductance"];
+        for (name, table) in names.iter().zip(&self.tables) {
+            snapshot = snapshot.with(&format!("table_{}", name), table.clone());
+        }
+        snapshot
+            .with("table_positions", self.positions.clone())
+            .with("spline", vec![(self.interpolation == TableInterpolation::Spline) as u8 as Float])
+    }
+}
@@ -1,20 +1,32 @@
-use crate::SimulationParameters;
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
 use crate::fdtd::Terminator;
 
 pub struct MatchedTerminator {
-    pub inductance: f32,
-    pub capacitance: f32,
-    pub resistance: f32,
-    pub conductance: f32,
+    pub inductance: Float,
+    pub capacitance: Float,
+    pub resistance: Float,
+    pub conductance: Float,
+}
+impl MatchedTerminator {
+    /// Rebuilds a `MatchedTerminator` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("MatchedTerminator")?;
+        Ok(Self {
+            inductance: snapshot.get_scalar("inductance")?,
+            capacitance: snapshot.get_scalar("capacitance")?,
+            resistance: snapshot.get_scalar("resistance")?,
+            conductance: snapshot.get_scalar("conductance")?,
+        })
+    }
 }
 impl Terminator for MatchedTerminator {
     fn next_voltage(
         &self,
-        last_volt: f32,
-        last_curr: f32,
+        last_volt: Float,
+        last_curr: Float,
         sim_params: &SimulationParameters,
-    ) -> f32 {
-        let load_conductance = f32::sqrt(self.capacitance / self.inductance);
+    ) -> Float {
+        let load_conductance = Float::sqrt(self.capacitance / self.inductance);
         let total_conductance = sim_params.delta_z*self.conductance + load_conductance;
         let d_ratio = sim_params.delta_z / sim_params.delta_t;
 
@@ -25,14 +37,31 @@ impl Terminator for MatchedTerminator {
 
     fn next_current(
         &self,
-        last_volts: ndarray::ArrayView1<f32>,
-        last_curr: f32,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
         sim_params: &SimulationParameters,
-    ) -> f32 {
+    ) -> Float {
         let d_ratio = sim_params.delta_z / sim_params.delta_t;
 
         (d_ratio*self.inductance + sim_params.delta_z*self.resistance/2.0).recip()
             *  ( (d_ratio*self.inductance - sim_params.delta_z*self.resistance/2.0) * last_curr
                 + (last_volts[0] - last_volts[1]) )
     }
+
+    fn dc_end(&self, scale: Float, sim_params: &SimulationParameters) -> (Float, Float) {
+        let load_conductance = Float::sqrt(self.capacitance / self.inductance);
+        (scale, (sim_params.delta_z*self.conductance + load_conductance) * scale)
+    }
+
+    fn dc_series_voltage(&self, current: Float, sim_params: &SimulationParameters) -> Float {
+        sim_params.delta_z*self.resistance * current
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("MatchedTerminator")
+            .with("inductance", vec![self.inductance])
+            .with("capacitance", vec![self.capacitance])
+            .with("resistance", vec![self.resistance])
+            .with("conductance", vec![self.conductance])
+    }
 }
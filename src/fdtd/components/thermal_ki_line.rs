@@ -0,0 +1,402 @@
+use std::cell::Cell;
+
+use crate::{warn, ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+use super::ki_line::{KiCell, KiNonlinearity};
+
+/// The smallest superfluid fraction `1 - (T/Tc)^4` of a superconducting cell, which keeps the
+/// kinetic inductance finite just below the critical temperature.
+const MIN_SUPERFLUID: Float = 0.01;
+
+/// Describes a `ThermalKiLine`.
+///
+/// The kinetic inductance and critical current are given at zero temperature.
+pub struct ThermalKiLineDescriptor<
+    Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+    Fk: Fn(Float) -> Float, Fi: Fn(Float) -> Float,
+    Fr: Fn(Float) -> Float, Fh: Fn(Float) -> Float,
+    Fb: Fn(Float) -> Float, Ft: Fn(Float) -> Float,
+>{
+    pub length: Float,
+    pub npoints: usize,
+    pub capacitance_fn: Fc,
+    pub inductance_fn: Fl,
+    pub kinetic_inductance_fn: Fk,
+    pub critical_current_fn: Fi,
+    /// The resistance per unit length in the normal state.
+    pub normal_resistance_fn: Fr,
+    /// The heat capacity per unit length.
+    pub heat_capacity_fn: Fh,
+    /// The thermal conductance per unit length to the bath.
+    pub bath_conductance_fn: Fb,
+    /// The thermal conductance times unit length along the line.
+    pub thermal_conductivity_fn: Ft,
+    pub critical_temperature: Float,
+    /// The temperature of the bath, which the line starts at.
+    pub bath_temperature: Float,
+    /// The most Newton iterations taken to solve for each current.
+    ///
+    /// Lines need at least one.
+    pub max_iterations: usize,
+    /// The step size, relative to the critical current, at which an iteration has converged.
+    pub tolerance: Float,
+}
+
+/// A kinetic inductance line heated by its own dissipation.
+///
+/// Each cell has a temperature `T`, heated by the power dissipated in it, cooled by the bath, and
+/// conducting heat to its neighbors, which is stepped implicitly at the start of each time step.
+/// With two fluid temperature dependences, the kinetic inductance grows as `1 / (1 - (T/Tc)^4)`,
+/// the critical current shrinks as `(1 - (T/Tc)^2)^(3/2)`, and the quasiparticles give a fraction
+/// `(T/Tc)^4` of the normal resistance. A cell above the critical temperature or critical current
+/// is normal, with only its geometric inductance and its normal resistance. Normal cells that heat
+/// themselves above the critical temperature stay normal until the current drops, which forms hot
+/// spots with hysteresis.
+///
+/// The temperatures are recorded in the `temperature` channel. Normal cells lose their kinetic
+/// inductance, so lines that switch need a time step short enough for the geometric inductance.
+pub struct ThermalKiLine {
+    cap: Vec<Float>,
+    geometric_ind: Vec<Float>,
+    kinetic_ind: Vec<Float>,
+    crit_cur: Vec<Float>,
+    normal_res: Vec<Float>,
+    heat_cap: Vec<Float>,
+    bath_cond: Vec<Float>,
+    thermal_cond: Vec<Float>,
+    critical_temperature: Float,
+    bath_temperature: Float,
+    temperature: Vec<Float>,
+    // parameters at the temperatures of the current step
+    ind0: Vec<Float>,
+    effective_crit_cur: Vec<Float>,
+    res: Vec<Float>,
+    normal: Vec<bool>,
+    // dissipated power and whether the critical current was exceeded during the current step
+    power: Vec<Cell<Float>>,
+    switched: Vec<Cell<bool>>,
+    nonlinearity: KiNonlinearity,
+    npoints: usize,
+    length: Float,
+    max_iterations: usize,
+    tolerance: Float,
+}
+impl ThermalKiLine {
+    pub fn new<
+        Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+        Fk: Fn(Float) -> Float, Fi: Fn(Float) -> Float,
+        Fr: Fn(Float) -> Float, Fh: Fn(Float) -> Float,
+        Fb: Fn(Float) -> Float, Ft: Fn(Float) -> Float,
+    >(
+        desc: ThermalKiLineDescriptor<Fc, Fl, Fk, Fi, Fr, Fh, Fb, Ft>,
+    ) -> Result<Self, Error> {
+        if desc.max_iterations == 0 {
+            let message = "ThermalKiLine needs at least one Newton iteration";
+            return Err(Error::BadParameter(message.to_string()))
+        }
+        let delta_z = desc.length / (desc.npoints as Float);
+        let sample = |param_fn: &dyn Fn(Float) -> Float| (0..desc.npoints)
+            .map(|n| param_fn((n as Float + 0.5) * delta_z))
+            .collect::<Vec<_>>();
+
+        Ok(Self::from_parameters(
+            [
+                sample(&desc.capacitance_fn),
+                sample(&desc.inductance_fn),
+                sample(&desc.kinetic_inductance_fn),
+                sample(&desc.critical_current_fn),
+                sample(&desc.normal_resistance_fn),
+                sample(&desc.heat_capacity_fn),
+                sample(&desc.bath_conductance_fn),
+                sample(&desc.thermal_conductivity_fn),
+                vec![desc.bath_temperature; desc.npoints],
+            ],
+            desc.critical_temperature,
+            desc.bath_temperature,
+            desc.length,
+            desc.max_iterations,
+            desc.tolerance,
+        ))
+    }
+
+    /// Rebuilds a `ThermalKiLine` from its snapshot, including its temperatures.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("ThermalKiLine")?;
+        let cap = snapshot.get("capacitance")?.clone();
+        let npoints = cap.len();
+        let per_cell = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != npoints {
+                let message = format!("ThermalKiLine {} has the wrong length", name);
+                return Err(Error::BadSnapshot(message))
+            }
+            Ok(values.clone())
+        };
+
+        Ok(Self::from_parameters(
+            [
+                cap,
+                per_cell("inductance")?,
+                per_cell("kinetic_inductance")?,
+                per_cell("critical_current")?,
+                per_cell("normal_resistance")?,
+                per_cell("heat_capacity")?,
+                per_cell("bath_conductance")?,
+                per_cell("thermal_conductivity")?,
+                per_cell("temperature")?,
+            ],
+            snapshot.get_scalar("critical_temperature")?,
+            snapshot.get_scalar("bath_temperature")?,
+            snapshot.get_scalar("length")?,
+            snapshot.get_scalar("max_iterations")? as usize,
+            snapshot.get_scalar("tolerance")?,
+        ))
+    }
+
+    fn from_parameters(
+        [cap, geometric_ind, kinetic_ind, crit_cur, normal_res, heat_cap, bath_cond, thermal_cond,
+            temperature]: [Vec<Float>; 9],
+        critical_temperature: Float,
+        bath_temperature: Float,
+        length: Float,
+        max_iterations: usize,
+        tolerance: Float,
+    ) -> Self {
+        let npoints = cap.len();
+        let mut line = Self {
+            cap,
+            geometric_ind,
+            kinetic_ind,
+            crit_cur,
+            normal_res,
+            heat_cap,
+            bath_cond,
+            thermal_cond,
+            critical_temperature,
+            bath_temperature,
+            temperature,
+            ind0: vec![0.0; npoints],
+            effective_crit_cur: vec![0.0; npoints],
+            res: vec![0.0; npoints],
+            normal: vec![false; npoints],
+            power: (0..npoints).map(|_| Cell::new(0.0)).collect(),
+            switched: (0..npoints).map(|_| Cell::new(false)).collect(),
+            nonlinearity: KiNonlinearity::default(),
+            npoints,
+            length,
+            max_iterations,
+            tolerance,
+        };
+        line.update_parameters();
+        line
+    }
+
+    /// Gets the temperature of each cell.
+    pub fn temperature(&self) -> &[Float] {
+        &self.temperature
+    }
+
+    /// The critical current of cell `index` at its temperature.
+    #[inline]
+    fn critical_current(&self, index: usize) -> Float {
+        let reduced = (self.temperature[index] / self.critical_temperature).min(1.0);
+        self.crit_cur[index] * (1.0 - reduced.powi(2)).powf(1.5)
+    }
+
+    /// Sets the parameters of each cell from its temperature and whether it switched.
+    fn update_parameters(&mut self) {
+        for n in 0..self.npoints {
+            let reduced = self.temperature[n] / self.critical_temperature;
+            let switched = self.switched[n].replace(false);
+            self.normal[n] = reduced >= 1.0 || switched;
+            if self.normal[n] {
+                self.ind0[n] = self.geometric_ind[n];
+                self.res[n] = self.normal_res[n];
+            } else {
+                let kinetic_ind = self.kinetic_ind[n] / (1.0 - reduced.powi(4)).max(MIN_SUPERFLUID);
+                self.ind0[n] = self.geometric_ind[n] + kinetic_ind;
+                self.effective_crit_cur[n] = self.critical_current(n)
+                    * Float::sqrt(self.ind0[n] / kinetic_ind);
+                self.res[n] = self.normal_res[n] * reduced.powi(4);
+            }
+        }
+    }
+}
+impl Component for ThermalKiLine {
+    #[inline]
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+
+        *next_volt = (d_ratio*self.cap[index]).recip()
+            * ( d_ratio*self.cap[index]*last_volt + (last_currs[0] - last_currs[1]) );
+    }
+    #[inline]
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+        let (ind, res) = (self.ind0[index], self.res[index]);
+
+        if self.normal[index] {
+            *next_curr = (d_ratio*ind + sim_params.delta_z*res/2.0).recip()
+                *  ( (d_ratio*ind - sim_params.delta_z*res/2.0) * last_curr
+                    + (last_volts[0] - last_volts[1]) );
+        } else {
+            // the cubic of `KiLine`, at the inductance and critical current of the temperature
+            let cell = KiCell {
+                nonlinearity: &self.nonlinearity,
+                index,
+                inductance: ind,
+                crit_cur: self.effective_crit_cur[index],
+                bias_cur: 0.0,
+                series_drop: |current: Float| (res*current, res),
+            };
+            let dv = last_volts[1] - last_volts[0];
+            let (next_guess, converged) =
+                cell.next_current(last_curr, dv, sim_params, self.max_iterations, self.tolerance);
+
+            if !converged {
+                warn("ThermalKiLine", "Newton iteration did not converge");
+            }
+            *next_curr = next_guess;
+        }
+
+        let average = (last_curr + *next_curr) / 2.0;
+        self.power[index].set(res * average.powi(2));
+        if next_curr.abs() > self.critical_current(index) {
+            self.switched[index].set(true);
+        }
+    }
+    /// The DC voltage across the resistance of the cell at its temperature.
+    #[inline]
+    fn dc_series_voltage(
+        &self,
+        current: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        sim_params.delta_z*self.res[index] * current
+    }
+    fn point_state(&self, index: usize) -> Vec<Float> {
+        vec![self.power[index].get(), if self.switched[index].get() { 1.0 } else { 0.0 }]
+    }
+    fn restore_point_state(&self, index: usize, state: &[Float]) {
+        self.power[index].set(state[0]);
+        self.switched[index].set(state[1] != 0.0);
+    }
+}
+impl TransmissionLine for ThermalKiLine {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.npoints
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.length
+    }
+    /// The fastest phase velocity along the line while superconducting at zero temperature.
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.max_phase_velocity_in(0..self.npoints)
+    }
+    #[inline]
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        cells
+            .map(|n| {
+                let ind = self.geometric_ind[n] + self.kinetic_ind[n];
+                Float::sqrt(ind * self.cap[n]).recip()
+            })
+            .fold(0.0, |accum, item| if accum >= item { accum } else { item })
+    }
+    /// The parameters at the temperature of the current step, with no current flowing.
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        Some(LineParameters {
+            inductance: self.ind0[0],
+            capacitance: self.cap[0],
+            resistance: self.res[0],
+            conductance: 0.0,
+        })
+    }
+    /// Steps the temperatures with the power dissipated in the last time step, and updates the
+    /// parameters of each cell.
+    fn begin_step(&mut self, _time: Float, sim_params: &SimulationParameters) {
+        let SimulationParameters { delta_z, delta_t } = *sim_params;
+        let npoints = self.npoints;
+
+        // solve `C (T' - T) / Δt = P - G (T' - Tb) + d/dz (K dT'/dz)` with insulated ends, by
+        // elimination of the tridiagonal system
+        let coupling = (0..npoints)
+            .map(|n| match n {
+                0 => 0.0,
+                _ => (self.thermal_cond[n - 1] + self.thermal_cond[n]) / (2.0*delta_z.powi(2)),
+            })
+            .collect::<Vec<_>>();
+        let mut diagonal = vec![0.0; npoints];
+        let mut rhs = vec![0.0; npoints];
+        for n in 0..npoints {
+            let next_coupling = coupling.get(n + 1).copied().unwrap_or(0.0);
+            diagonal[n] = self.heat_cap[n]/delta_t + self.bath_cond[n] + coupling[n]
+                + next_coupling;
+            rhs[n] = self.heat_cap[n]/delta_t*self.temperature[n] + self.power[n].get()
+                + self.bath_cond[n]*self.bath_temperature;
+        }
+        for n in 1..npoints {
+            let factor = coupling[n] / diagonal[n - 1];
+            diagonal[n] -= factor*coupling[n];
+            rhs[n] += factor*rhs[n - 1];
+        }
+        for n in (0..npoints).rev() {
+            let next = if n + 1 < npoints { coupling[n + 1]*self.temperature[n + 1] } else { 0.0 };
+            self.temperature[n] = (rhs[n] + next) / diagonal[n];
+        }
+
+        let was_normal = self.normal.clone();
+        self.update_parameters();
+        let newly_normal = self.normal.iter().zip(&was_normal)
+            .enumerate()
+            .any(|(n, (&normal, &was))| {
+                let velocity = Float::sqrt(self.geometric_ind[n] * self.cap[n]).recip();
+                normal && !was && velocity * delta_t > delta_z
+            });
+        if newly_normal {
+            warn("ThermalKiLine", "a normal cell is faster than the time step allows");
+        }
+    }
+    fn channels(&self) -> Vec<(String, usize, &'static str)> {
+        vec![("temperature".to_string(), self.npoints, "K")]
+    }
+    fn record_channels(&self, rows: &mut [ndarray::ArrayViewMut1<Float>]) {
+        rows[0].assign(&ndarray::ArrayView1::from(&self.temperature));
+    }
+    #[inline]
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("ThermalKiLine")
+            .with("length", vec![self.length])
+            .with("capacitance", self.cap.clone())
+            .with("inductance", self.geometric_ind.clone())
+            .with("kinetic_inductance", self.kinetic_ind.clone())
+            .with("critical_current", self.crit_cur.clone())
+            .with("normal_resistance", self.normal_res.clone())
+            .with("heat_capacity", self.heat_cap.clone())
+            .with("bath_conductance", self.bath_cond.clone())
+            .with("thermal_conductivity", self.thermal_cond.clone())
+            .with("temperature", self.temperature.clone())
+            .with("critical_temperature", vec![self.critical_temperature])
+            .with("bath_temperature", vec![self.bath_temperature])
+            .with("max_iterations", vec![self.max_iterations as Float])
+            .with("tolerance", vec![self.tolerance])
+    }
+}
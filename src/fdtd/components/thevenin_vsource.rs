@@ -0,0 +1,169 @@
+use num_complex::Complex;
+
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::VSource;
+use crate::fdtd::waveform::Waveform;
+
+/// Describes a `TheveninVSource`.
+pub struct TheveninVSourceDescriptor<Fs: Waveform> {
+    /// The open circuit voltage of the source.
+    pub source_fn: Fs,
+    /// The inductance per unit length of the line fed by the source.
+    pub inductance: Float,
+    /// The capacitance per unit length of the line fed by the source.
+    pub capacitance: Float,
+    /// The resistance per unit length of the line fed by the source.
+    pub resistance: Float,
+    /// The conductance per unit length of the line fed by the source.
+    pub conductance: Float,
+    /// The series resistance of the source.
+    pub source_resistance: Float,
+    /// The series inductance of the source.
+    pub source_inductance: Float,
+    /// The series capacitance of the source, or infinity for none.
+    pub source_capacitance: Float,
+}
+
+/// A voltage source behind an internal impedance of a series resistor, inductor, and capacitor,
+/// for mismatched drives and reflections off the source.
+///
+/// The source feeds a node with the capacitance and conductance of a cell of the line, through the
+/// internal impedance in series with the inductance and resistance of the cell, so it matches a
+/// lossless line when the source resistance is its impedance alone. The current and capacitor
+/// charge of the source are stepped by the trapezoidal rule.
+pub struct TheveninVSource<Fs: Waveform> {
+    source_fn: Fs,
+    inductance: Float,
+    capacitance: Float,
+    resistance: Float,
+    conductance: Float,
+    source_resistance: Float,
+    source_inductance: Float,
+    source_capacitance: Float,
+    // series current and series capacitor charge
+    state: [Float; 2],
+}
+impl<Fs: Waveform> TheveninVSource<Fs> {
+    /// Creates a new `TheveninVSource` instance.
+    #[inline]
+    pub fn new(desc: TheveninVSourceDescriptor<Fs>) -> Self {
+        Self {
+            source_fn: desc.source_fn,
+            inductance: desc.inductance,
+            capacitance: desc.capacitance,
+            resistance: desc.resistance,
+            conductance: desc.conductance,
+            source_resistance: desc.source_resistance,
+            source_inductance: desc.source_inductance,
+            source_capacitance: desc.source_capacitance,
+            state: [0.0; 2],
+        }
+    }
+
+    /// Rebuilds a `TheveninVSource` from its snapshot and source function.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot, source_fn: Fs) -> Result<Self, Error> {
+        snapshot.expect_kind("TheveninVSource")?;
+        let state = snapshot.get("state")?;
+        if state.len() != 2 {
+            return Err(Error::BadSnapshot("TheveninVSource state has the wrong length".to_string()))
+        }
+
+        let mut source = Self::new(TheveninVSourceDescriptor {
+            source_fn,
+            inductance: snapshot.get_scalar("inductance")?,
+            capacitance: snapshot.get_scalar("capacitance")?,
+            resistance: snapshot.get_scalar("resistance")?,
+            conductance: snapshot.get_scalar("conductance")?,
+            source_resistance: snapshot.get_scalar("source_resistance")?,
+            source_inductance: snapshot.get_scalar("source_inductance")?,
+            source_capacitance: snapshot.get_scalar("source_capacitance")?,
+        });
+        source.state = [state[0], state[1]];
+
+        Ok(source)
+    }
+
+    /// Calculates the internal impedance of the source at `frequency`.
+    pub fn impedance(&self, frequency: Float) -> Complex<Float> {
+        let jw = Complex::new(0.0, 2.0 * crate::consts::PI * frequency);
+        let series_cap = if self.source_capacitance.is_finite() {
+            (jw*self.source_capacitance).inv()
+        } else {
+            Complex::new(0.0, 0.0)
+        };
+
+        self.source_resistance + jw*self.source_inductance + series_cap
+    }
+}
+impl<Fs: Waveform> VSource for TheveninVSource<Fs> {
+    fn next_voltage_scaled(
+        &mut self,
+        gain: Float,
+        t: Float,
+        last_volt: Float,
+        last_curr: Float,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        let SimulationParameters { delta_z, delta_t } = *sim_params;
+        let ind = self.source_inductance + delta_z*self.inductance;
+        let res = self.source_resistance + delta_z*self.resistance;
+        let elastance = self.source_capacitance.recip();
+        let [last_source_curr, charge] = self.state;
+
+        // with the trapezoidal rule, the next source current is `fixed - V' / 2 implicit`, where
+        // `V'` is the next voltage of the node
+        let implicit = ind/delta_t + res/2.0 + delta_t*elastance/4.0;
+        let explicit = ind/delta_t - res/2.0 - delta_t*elastance/4.0;
+        let time = self.generation_time(t, sim_params);
+        let drive = gain*self.generate(time) - last_volt/2.0 - charge*elastance;
+        let fixed = (explicit*last_source_curr + drive) / implicit;
+
+        // solve the node equation with the source current averaged over the step
+        let node_cap = delta_z*self.capacitance / delta_t;
+        let node_cond = delta_z*self.conductance / 2.0;
+        let next_volt = (node_cap + node_cond + (4.0*implicit).recip()).recip()
+            * ( (node_cap - node_cond) * last_volt + (last_source_curr + fixed)/2.0 - last_curr );
+
+        let source_curr = fixed - next_volt/(2.0*implicit);
+        self.state = [source_curr, charge + delta_t*(last_source_curr + source_curr)/2.0];
+
+        next_volt
+    }
+
+    fn generate(&self, time: Float) -> Float {
+        self.source_fn.at(time)
+    }
+
+    /// Halfway through the step, where the trapezoidal rule averages the source.
+    fn generation_time(&self, t: Float, sim_params: &SimulationParameters) -> Float {
+        t + sim_params.delta_t/2.0
+    }
+
+    /// The DC voltage behind the series resistance, or none when a series capacitor blocks DC,
+    /// which leaves the line at rest.
+    fn dc_voltage(&self, bias: Float, current: Float, sim_params: &SimulationParameters) -> Float {
+        if self.source_capacitance.is_finite() {
+            return 0.0
+        }
+        let total_resistance = self.source_resistance + sim_params.delta_z*self.resistance;
+
+        // the source current also feeds the conductance of the source node
+        (bias - total_resistance*current)
+            / (1.0 + total_resistance*sim_params.delta_z*self.conductance)
+    }
+
+    /// The parameters and state of the source, with those of the waveform prefixed by
+    /// `waveform_`.
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("TheveninVSource")
+            .with("inductance", vec![self.inductance])
+            .with("capacitance", vec![self.capacitance])
+            .with("resistance", vec![self.resistance])
+            .with("conductance", vec![self.conductance])
+            .with("source_resistance", vec![self.source_resistance])
+            .with("source_inductance", vec![self.source_inductance])
+            .with("source_capacitance", vec![self.source_capacitance])
+            .with("state", self.state.to_vec())
+            .with_nested("waveform", self.source_fn.snapshot())
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{warn, ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+
+/// Describes a `TimeVaryingLine`.
+///
+/// The capacitance and inductance functions take the position and the time.
+pub struct TimeVaryingLineDescriptor<
+    Fc: Fn(Float, Float) -> Float, Fl: Fn(Float, Float) -> Float,
+    Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float,
+>{
+    pub length: Float,
+    pub npoints: usize,
+    pub capacitance_fn: Fc,
+    pub inductance_fn: Fl,
+    pub resistance_fn: Fr,
+    pub conductance_fn: Fg,
+}
+
+/// A linear line whose capacitance and inductance are externally modulated in time.
+///
+/// The updates conserve charge `C V` and flux `L I` across changes in the parameters, so energy
+/// is exchanged with the modulation as in a parametrically pumped structure.
+///
+/// The time step is only checked against the parameters at time zero when a simulation is made,
+/// so each step warns if the modulation has raised the phase velocity past the stability limit.
+pub struct TimeVaryingLine<Fc: Fn(Float, Float) -> Float, Fl: Fn(Float, Float) -> Float> {
+    capacitance_fn: Fc,
+    inductance_fn: Fl,
+    // parameters at the start and end of the voltage and current updates of the current step
+    cap: [Vec<Float>; 2],
+    ind: [Vec<Float>; 2],
+    res: Vec<Float>,
+    cond: Vec<Float>,
+    npoints: usize,
+    length: Float,
+}
+impl<Fc: Fn(Float, Float) -> Float, Fl: Fn(Float, Float) -> Float> TimeVaryingLine<Fc, Fl> {
+    pub fn new<Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float>(
+        desc: TimeVaryingLineDescriptor<Fc, Fl, Fr, Fg>,
+    ) -> Self {
+        let delta_z = desc.length / (desc.npoints as Float);
+
+        let mut line = Self {
+            capacitance_fn: desc.capacitance_fn,
+            inductance_fn: desc.inductance_fn,
+            cap: [vec![0.0; desc.npoints], vec![0.0; desc.npoints]],
+            ind: [vec![0.0; desc.npoints], vec![0.0; desc.npoints]],
+            res: (0..desc.npoints)
+                .map(|n| { (desc.resistance_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            cond: (0..desc.npoints)
+                .map(|n| { (desc.conductance_fn)((n as Float + 0.5) * delta_z) })
+                .collect::<Vec<_>>(),
+            npoints: desc.npoints,
+            length: desc.length,
+        };
+        line.sample(0.0, 0.0);
+
+        line
+    }
+
+    /// Rebuilds a `TimeVaryingLine` from its snapshot.
+    ///
+    /// The parameter functions are not part of the snapshot and must be provided again.
+    pub fn from_snapshot(
+        snapshot: &ComponentSnapshot,
+        capacitance_fn: Fc,
+        inductance_fn: Fl,
+    ) -> Result<Self, Error> {
+        snapshot.expect_kind("TimeVaryingLine")?;
+        let res = snapshot.get("resistance")?.clone();
+        let cond = snapshot.get("conductance")?.clone();
+        if res.len() != cond.len() {
+            return Err(Error::BadSnapshot("TimeVaryingLine conductance has the wrong length".to_string()))
+        }
+
+        let npoints = res.len();
+        let mut line = Self {
+            capacitance_fn,
+            inductance_fn,
+            cap: [vec![0.0; npoints], vec![0.0; npoints]],
+            ind: [vec![0.0; npoints], vec![0.0; npoints]],
+            res,
+            cond,
+            npoints,
+            length: snapshot.get_scalar("length")?,
+        };
+        line.sample(0.0, 0.0);
+
+        Ok(line)
+    }
+
+    /// Samples the parameters for a step whose voltage update spans `[t, t + Δt]`.
+    fn sample(&mut self, time: Float, delta_t: Float) {
+        let delta_z = self.length / (self.npoints as Float);
+        for n in 0..self.npoints {
+            let z = (n as Float + 0.5) * delta_z;
+            self.cap[0][n] = (self.capacitance_fn)(z, time);
+            self.cap[1][n] = (self.capacitance_fn)(z, time + delta_t);
+            // currents lag the voltages by half a step
+            self.ind[0][n] = (self.inductance_fn)(z, time + delta_t/2.0);
+            self.ind[1][n] = (self.inductance_fn)(z, time + 3.0*delta_t/2.0);
+        }
+    }
+}
+impl<Fc: Fn(Float, Float) -> Float, Fl: Fn(Float, Float) -> Float> Component for TimeVaryingLine<Fc, Fl> {
+    #[inline]
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+
+        *next_volt = (d_ratio*self.cap[1][index] + sim_params.delta_z*self.cond[index]/2.0).recip()
+            * ( (d_ratio*self.cap[0][index] - sim_params.delta_z*self.cond[index]/2.0) * last_volt
+                + (last_currs[0] - last_currs[1]) );
+    }
+    #[inline]
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+
+        *next_curr = (d_ratio*self.ind[1][index] + sim_params.delta_z*self.res[index]/2.0).recip()
+            *  ( (d_ratio*self.ind[0][index] - sim_params.delta_z*self.res[index]/2.0) * last_curr
+                + (last_volts[0] - last_volts[1]) );
+    }
+}
+impl<Fc: Fn(Float, Float) -> Float, Fl: Fn(Float, Float) -> Float> TransmissionLine for TimeVaryingLine<Fc, Fl> {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.npoints
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.length
+    }
+    /// The largest phase velocity over the times sampled for the current step, which are both
+    /// time zero before the first step.
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.max_phase_velocity_in(0..self.npoints)
+    }
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        cells
+            .map(|n| {
+                let ind = self.ind[0][n].min(self.ind[1][n]);
+                let cap = self.cap[0][n].min(self.cap[1][n]);
+                Float::sqrt(ind * cap).recip()
+            })
+            .fold(0.0, |accum, item| if accum >= item { accum } else { item })
+    }
+    /// The parameters at the start of the current step.
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        Some(LineParameters {
+            inductance: self.ind[0][0],
+            capacitance: self.cap[0][0],
+            resistance: self.res[0],
+            conductance: self.cond[0],
+        })
+    }
+    fn begin_step(&mut self, time: Float, sim_params: &SimulationParameters) {
+        self.sample(time, sim_params.delta_t);
+        if self.max_phase_velocity()*sim_params.delta_t > sim_params.delta_z {
+            let message = "the modulation raised the phase velocity past the stability limit";
+            warn("TimeVaryingLine", message);
+        }
+    }
+    #[inline]
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("TimeVaryingLine")
+            .with("length", vec![self.length])
+            .with("resistance", self.res.clone())
+            .with("conductance", self.cond.clone())
+    }
+}
@@ -0,0 +1,257 @@
+use std::cell::Cell;
+
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+
+/// Describes a `TwoFluidLine`.
+///
+/// The kinetic inductance is given at zero temperature.
+pub struct TwoFluidLineDescriptor<
+    Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+    Fk: Fn(Float) -> Float, Fr: Fn(Float) -> Float,
+    Fg: Fn(Float) -> Float,
+>{
+    pub length: Float,
+    pub npoints: usize,
+    pub capacitance_fn: Fc,
+    /// The geometric inductance per unit length.
+    pub inductance_fn: Fl,
+    pub kinetic_inductance_fn: Fk,
+    /// The resistance per unit length in the normal state.
+    pub normal_resistance_fn: Fr,
+    pub conductance_fn: Fg,
+    pub critical_temperature: Float,
+    /// The operating temperature.
+    pub temperature: Float,
+}
+
+/// A linear superconducting line with the loss and kinetic inductance of the two fluid model, at
+/// a fixed temperature.
+///
+/// The current is carried in parallel by the superfluid, a kinetic inductance growing as
+/// `1 / (1 - (T/Tc)^4)`, and by the quasiparticles, a conductance that is the fraction `(T/Tc)^4`
+/// of the normal state conductance, all in series with the geometric inductance. The quasiparticle
+/// loss grows with the square of the frequency well below `R / Lk`, as in superconducting coax and
+/// coplanar waveguides. The current through the kinetic inductance is stepped alongside the line
+/// current by the trapezoidal rule.
+pub struct TwoFluidLine {
+    cap: Vec<Float>,
+    geometric_ind: Vec<Float>,
+    kinetic_ind: Vec<Float>,
+    normal_res: Vec<Float>,
+    cond: Vec<Float>,
+    critical_temperature: Float,
+    temperature: Float,
+    /// The current through the kinetic inductance of each cell.
+    superfluid_currs: Vec<Cell<Float>>,
+    npoints: usize,
+    length: Float,
+}
+impl TwoFluidLine {
+    pub fn new<
+        Fc: Fn(Float) -> Float, Fl: Fn(Float) -> Float,
+        Fk: Fn(Float) -> Float, Fr: Fn(Float) -> Float,
+        Fg: Fn(Float) -> Float,
+    >(
+        desc: TwoFluidLineDescriptor<Fc, Fl, Fk, Fr, Fg>,
+    ) -> Self {
+        let delta_z = desc.length / (desc.npoints as Float);
+        let sample = |param_fn: &dyn Fn(Float) -> Float| (0..desc.npoints)
+            .map(|n| param_fn((n as Float + 0.5) * delta_z))
+            .collect::<Vec<_>>();
+
+        Self {
+            cap: sample(&desc.capacitance_fn),
+            geometric_ind: sample(&desc.inductance_fn),
+            kinetic_ind: sample(&desc.kinetic_inductance_fn),
+            normal_res: sample(&desc.normal_resistance_fn),
+            cond: sample(&desc.conductance_fn),
+            critical_temperature: desc.critical_temperature,
+            temperature: desc.temperature,
+            superfluid_currs: (0..desc.npoints).map(|_| Cell::new(0.0)).collect(),
+            npoints: desc.npoints,
+            length: desc.length,
+        }
+    }
+
+    /// Rebuilds a `TwoFluidLine` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("TwoFluidLine")?;
+        let cap = snapshot.get("capacitance")?.clone();
+        let npoints = cap.len();
+        let per_cell = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != npoints {
+                let message = format!("TwoFluidLine {} has the wrong length", name);
+                return Err(Error::BadSnapshot(message))
+            }
+            Ok(values.clone())
+        };
+
+        Ok(Self {
+            geometric_ind: per_cell("inductance")?,
+            kinetic_ind: per_cell("kinetic_inductance")?,
+            normal_res: per_cell("normal_resistance")?,
+            cond: per_cell("conductance")?,
+            superfluid_currs: per_cell("superfluid_current")?.into_iter().map(Cell::new).collect(),
+            cap,
+            critical_temperature: snapshot.get_scalar("critical_temperature")?,
+            temperature: snapshot.get_scalar("temperature")?,
+            npoints,
+            length: snapshot.get_scalar("length")?,
+        })
+    }
+
+    /// Gets the fraction `(T/Tc)^4` of the electrons that are quasiparticles.
+    #[inline]
+    fn normal_fraction(&self) -> Float {
+        (self.temperature / self.critical_temperature).powi(4).min(1.0)
+    }
+
+    /// Gets the conductance per unit length of the quasiparticles of cell `index`, which is zero
+    /// at zero temperature whatever the normal resistance.
+    #[inline]
+    fn quasiparticle_conductance(&self, index: usize) -> Float {
+        let fraction = self.normal_fraction();
+        if fraction == 0.0 { 0.0 } else { fraction / self.normal_res[index] }
+    }
+
+    /// Gets the kinetic inductance per unit length of cell `index` at the temperature, which is
+    /// infinite at and above the critical temperature.
+    #[inline]
+    pub fn kinetic_inductance(&self, index: usize) -> Float {
+        self.kinetic_ind[index] / (1.0 - self.normal_fraction())
+    }
+}
+impl Component for TwoFluidLine {
+    #[inline]
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+
+        *next_volt = (d_ratio*self.cap[index] + sim_params.delta_z*self.cond[index]/2.0).recip()
+            * ( (d_ratio*self.cap[index] - sim_params.delta_z*self.cond[index]/2.0) * last_volt
+                + (last_currs[0] - last_currs[1]) );
+    }
+    #[inline]
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let SimulationParameters { delta_z, delta_t } = *sim_params;
+        let d_ratio = delta_z / delta_t;
+        let ind = self.geometric_ind[index];
+        let superfluid_curr = &self.superfluid_currs[index];
+
+        // with the trapezoidal rule, the superfluid and quasiparticles drop `Rs (Ī - is)` on
+        // average over the step, where `Ī` is the average line current, `is` the last superfluid
+        // current, and `1 / Rs = Gq + Δt / 2Lk` with the quasiparticle conductance `Gq`
+        let quasiparticle_cond = self.quasiparticle_conductance(index);
+        let superfluid_cond = delta_t / (2.0*self.kinetic_inductance(index));
+        let res = (quasiparticle_cond + superfluid_cond).recip();
+
+        *next_curr = (d_ratio*ind + delta_z*res/2.0).recip()
+            * ( (d_ratio*ind - delta_z*res/2.0) * last_curr
+                + (last_volts[0] - last_volts[1]) + delta_z*res*superfluid_curr.get() );
+
+        let average = (last_curr + *next_curr) / 2.0;
+        let last = superfluid_curr.get();
+        superfluid_curr.set(last + 2.0*superfluid_cond*res*(average - last));
+    }
+    fn point_state(&self, index: usize) -> Vec<Float> {
+        vec![self.superfluid_currs[index].get()]
+    }
+    fn restore_point_state(&self, index: usize, state: &[Float]) {
+        self.superfluid_currs[index].set(state[0]);
+    }
+    /// The DC voltage across the normal resistance at and above the critical temperature, below
+    /// which the superfluid shorts the quasiparticles.
+    #[inline]
+    fn dc_series_voltage(
+        &self,
+        current: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        if self.normal_fraction() < 1.0 {
+            return 0.0
+        }
+        sim_params.delta_z*self.normal_res[index] * current
+    }
+    #[inline]
+    fn dc_shunt_current(
+        &self,
+        voltage: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        sim_params.delta_z*self.cond[index] * voltage
+    }
+}
+impl TransmissionLine for TwoFluidLine {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.npoints
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.length
+    }
+    /// The fastest phase velocity with the geometric inductance, along with the kinetic inductance
+    /// at zero temperature, since otherwise the quasiparticles short it at high frequencies.
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.max_phase_velocity_in(0..self.npoints)
+    }
+    #[inline]
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        cells
+            .map(|n| {
+                let ind = if self.normal_fraction() > 0.0 {
+                    self.geometric_ind[n]
+                } else {
+                    self.geometric_ind[n] + self.kinetic_ind[n]
+                };
+                Float::sqrt(ind * self.cap[n]).recip()
+            })
+            .fold(0.0, |accum, item| if accum >= item { accum } else { item })
+    }
+    /// The parameters well below the frequency at which the quasiparticles short the kinetic
+    /// inductance, or of the normal line at and above the critical temperature.
+    fn port_parameters(&self) -> Option<LineParameters> {
+        let superconducting = self.normal_fraction() < 1.0;
+        Some(LineParameters {
+            inductance: if superconducting {
+                self.geometric_ind[0] + self.kinetic_inductance(0)
+            } else {
+                self.geometric_ind[0]
+            },
+            capacitance: self.cap[0],
+            resistance: if superconducting { 0.0 } else { self.normal_res[0] },
+            conductance: self.cond[0],
+        })
+    }
+    #[inline]
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("TwoFluidLine")
+            .with("length", vec![self.length])
+            .with("capacitance", self.cap.clone())
+            .with("inductance", self.geometric_ind.clone())
+            .with("kinetic_inductance", self.kinetic_ind.clone())
+            .with("normal_resistance", self.normal_res.clone())
+            .with("conductance", self.cond.clone())
+            .with("critical_temperature", vec![self.critical_temperature])
+            .with("temperature", vec![self.temperature])
+            .with("superfluid_current", self.superfluid_currs.iter().map(Cell::get).collect())
+    }
+}
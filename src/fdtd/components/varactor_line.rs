@@ -0,0 +1,226 @@
+use crate::{warn, ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, Component, LineParameters};
+
+/// Describes a `VaractorLine`.
+///
+/// The capacitance function takes the position and the local voltage.
+pub struct VaractorLineDescriptor<
+    Fc: Fn(Float, Float) -> Float, Fl: Fn(Float) -> Float,
+    Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float,
+>{
+    pub length: Float,
+    pub npoints: usize,
+    pub capacitance_fn: Fc,
+    pub inductance_fn: Fl,
+    pub resistance_fn: Fr,
+    pub conductance_fn: Fg,
+    /// The most Newton iterations taken to solve for each voltage.
+    ///
+    /// Lines need at least one.
+    pub max_iterations: usize,
+    /// The step size, in volts, at which an iteration has converged.
+    pub tolerance: Float,
+}
+
+/// A line loaded by varactors, with a capacitance that depends on the local voltage.
+///
+/// This is the voltage dependent counterpart of the current dependent inductance of a `KiLine`.
+/// Each update takes the capacitance at the midpoint of the old and new voltages, such as
+/// `C0 / sqrt(1 - V/φ)` for abrupt junctions, which should stay positive and finite over the
+/// voltages reached.
+pub struct VaractorLine<Fc: Fn(Float, Float) -> Float> {
+    capacitance_fn: Fc,
+    positions: Vec<Float>,
+    ind: Vec<Float>,
+    res: Vec<Float>,
+    cond: Vec<Float>,
+    npoints: usize,
+    length: Float,
+    max_iterations: usize,
+    tolerance: Float,
+}
+impl<Fc: Fn(Float, Float) -> Float> VaractorLine<Fc> {
+    pub fn new<Fl: Fn(Float) -> Float, Fr: Fn(Float) -> Float, Fg: Fn(Float) -> Float>(
+        desc: VaractorLineDescriptor<Fc, Fl, Fr, Fg>,
+    ) -> Result<Self, Error> {
+        if desc.max_iterations == 0 {
+            let message = "VaractorLine needs at least one Newton iteration";
+            return Err(Error::BadParameter(message.to_string()))
+        }
+        let delta_z = desc.length / (desc.npoints as Float);
+        let positions = (0..desc.npoints)
+            .map(|n| (n as Float + 0.5) * delta_z)
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            capacitance_fn: desc.capacitance_fn,
+            ind: positions.iter().map(|&z| (desc.inductance_fn)(z)).collect(),
+            res: positions.iter().map(|&z| (desc.resistance_fn)(z)).collect(),
+            cond: positions.iter().map(|&z| (desc.conductance_fn)(z)).collect(),
+            positions,
+            npoints: desc.npoints,
+            length: desc.length,
+            max_iterations: desc.max_iterations,
+            tolerance: desc.tolerance,
+        })
+    }
+
+    /// Rebuilds a `VaractorLine` from its snapshot.
+    ///
+    /// The capacitance function is not part of the snapshot and must be provided again.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot, capacitance_fn: Fc) -> Result<Self, Error> {
+        snapshot.expect_kind("VaractorLine")?;
+        let ind = snapshot.get("inductance")?.clone();
+        let npoints = ind.len();
+        let per_cell = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != npoints {
+                let message = format!("VaractorLine {} has the wrong length", name);
+                return Err(Error::BadSnapshot(message))
+            }
+            Ok(values.clone())
+        };
+        let length = snapshot.get_scalar("length")?;
+        let delta_z = length / (npoints as Float);
+
+        Ok(Self {
+            capacitance_fn,
+            positions: (0..npoints).map(|n| (n as Float + 0.5) * delta_z).collect(),
+            res: per_cell("resistance")?,
+            cond: per_cell("conductance")?,
+            ind,
+            npoints,
+            length,
+            max_iterations: snapshot.get_scalar("max_iterations")? as usize,
+            tolerance: snapshot.get_scalar("tolerance")?,
+        })
+    }
+}
+impl<Fc: Fn(Float, Float) -> Float> Component for VaractorLine<Fc> {
+    #[inline]
+    fn next_voltage(
+        &self,
+        next_volt: &mut Float,
+        last_volt: Float,
+        last_currs: ndarray::ArrayView1<Float>,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+        let loss = sim_params.delta_z*self.cond[index]/2.0;
+        let drive = last_currs[0] - last_currs[1];
+        let capacitance = |voltage: Float| (self.capacitance_fn)(self.positions[index], voltage);
+
+        // solve `(Δz/Δt) C((V + V')/2) (V' - V) + Δz G (V + V')/2 = ΔI`, with the slope of the
+        // capacitance taken by central differences
+        let mut next_guess = last_volt;
+        let mut converged = false;
+        for _ in 0..self.max_iterations {
+            let this_guess = next_guess;
+            let midpoint = (last_volt + this_guess) / 2.0;
+            let step = Float::EPSILON.sqrt() * midpoint.abs().max(1.0);
+            let slope = (capacitance(midpoint + step) - capacitance(midpoint - step)) / (2.0*step);
+            let change = this_guess - last_volt;
+
+            let residual = d_ratio*capacitance(midpoint)*change + loss*(last_volt + this_guess)
+                - drive;
+            let derivative = d_ratio*(capacitance(midpoint) + slope*change/2.0) + loss;
+            next_guess = this_guess - residual / derivative;
+            if (next_guess - this_guess).abs() <= self.tolerance {
+                converged = true;
+                break;
+            }
+        }
+
+        if !converged {
+            warn("VaractorLine", "Newton iteration did not converge");
+        }
+
+        *next_volt = next_guess;
+    }
+    #[inline]
+    fn next_current(
+        &self,
+        next_curr: &mut Float,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+
+        *next_curr = (d_ratio*self.ind[index] + sim_params.delta_z*self.res[index]/2.0).recip()
+            *  ( (d_ratio*self.ind[index] - sim_params.delta_z*self.res[index]/2.0) * last_curr
+                + (last_volts[0] - last_volts[1]) );
+    }
+    #[inline]
+    fn dc_series_voltage(
+        &self,
+        current: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        sim_params.delta_z*self.res[index] * current
+    }
+    #[inline]
+    fn dc_shunt_current(
+        &self,
+        voltage: Float,
+        index: usize,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        sim_params.delta_z*self.cond[index] * voltage
+    }
+}
+impl<Fc: Fn(Float, Float) -> Float> TransmissionLine for VaractorLine<Fc> {
+    #[inline]
+    fn npoints(&self) -> usize {
+        self.npoints
+    }
+    #[inline]
+    fn length(&self) -> Float {
+        self.length
+    }
+    /// The largest phase velocity with no voltage on the line.
+    ///
+    /// Varactors that lose capacitance with voltage speed the line up, so the time step needs a
+    /// margin for the lowest capacitance reached.
+    #[inline]
+    fn max_phase_velocity(&self) -> Float {
+        self.max_phase_velocity_in(0..self.npoints)
+    }
+    #[inline]
+    fn max_phase_velocity_in(&self, cells: std::ops::Range<usize>) -> Float {
+        cells
+            .map(|n| {
+                let cap = (self.capacitance_fn)(self.positions[n], 0.0);
+                Float::sqrt(self.ind[n] * cap).recip()
+            })
+            .fold(0.0, |accum, item| if accum >= item { accum } else { item })
+    }
+    /// The parameters at zero volts.
+    #[inline]
+    fn port_parameters(&self) -> Option<LineParameters> {
+        Some(LineParameters {
+            inductance: self.ind[0],
+            capacitance: (self.capacitance_fn)(self.positions[0], 0.0),
+            resistance: self.res[0],
+            conductance: self.cond[0],
+        })
+    }
+    #[inline]
+    fn snapshot(&self) -> ComponentSnapshot {
+        // the capacitance is recorded at zero volts
+        ComponentSnapshot::new("VaractorLine")
+            .with("length", vec![self.length])
+            .with(
+                "capacitance",
+                self.positions.iter().map(|&z| (self.capacitance_fn)(z, 0.0)).collect(),
+            )
+            .with("inductance", self.ind.clone())
+            .with("resistance", self.res.clone())
+            .with("conductance", self.cond.clone())
+            .with("max_iterations", vec![self.max_iterations as Float])
+            .with("tolerance", vec![self.tolerance])
+    }
+}
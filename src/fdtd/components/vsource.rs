@@ -1,37 +1,92 @@
-use crate::SimulationParameters;
-use crate::fdtd::VSource;
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{TransmissionLine, VSource};
+use crate::fdtd::waveform::Waveform;
 
-/// A simple voltage source.
-pub struct MatchedVSource<Fs> where Fs: Fn(f32)->f32 {
+/// A simple voltage source, emitting a waveform into a line it matches.
+pub struct MatchedVSource<Fs> where Fs: Waveform {
     pub source_fn: Fs,
-    pub capacitance: f32,
-    pub inductance: f32,
-    pub resistance: f32,
-    pub conductance: f32,
+    pub capacitance: Float,
+    pub inductance: Float,
+    pub resistance: Float,
+    pub conductance: Float,
 }
-impl<Fs> VSource for MatchedVSource<Fs> where Fs: Fn(f32)->f32 {
-    fn next_voltage(
-        &self,
-        t: f32,
-        last_volt: f32,
-        last_curr: f32,
+impl<Fs> MatchedVSource<Fs> where Fs: Waveform {
+    /// Rebuilds a `MatchedVSource` from its snapshot and source function.
+    ///
+    /// Waveforms can be rebuilt from `snapshot.nested("waveform", kind)`.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot, source_fn: Fs) -> Result<Self, Error> {
+        snapshot.expect_kind("MatchedVSource")?;
+        Ok(Self {
+            source_fn,
+            capacitance: snapshot.get_scalar("capacitance")?,
+            inductance: snapshot.get_scalar("inductance")?,
+            resistance: snapshot.get_scalar("resistance")?,
+            conductance: snapshot.get_scalar("conductance")?,
+        })
+    }
+
+    /// Creates a `MatchedVSource` matched to the start of `tline`, at z = 0.
+    ///
+    /// The parameters are taken from the line itself, so they follow any change to its profile.
+    pub fn matched_to<L>(tline: &L, source_fn: Fs) -> Result<Self, Error>
+    where
+        L: TransmissionLine + ?Sized,
+    {
+        let port = tline.port_parameters().ok_or_else(|| {
+            Error::BadParameter("the line can't be matched by a source".to_string())
+        })?;
+        Ok(Self {
+            source_fn,
+            capacitance: port.capacitance,
+            inductance: port.inductance,
+            resistance: port.resistance,
+            conductance: port.conductance,
+        })
+    }
+}
+impl<Fs> VSource for MatchedVSource<Fs> where Fs: Waveform {
+    fn next_voltage_scaled(
+        &mut self,
+        gain: Float,
+        t: Float,
+        last_volt: Float,
+        last_curr: Float,
         sim_params: &SimulationParameters,
-    ) -> f32 {
+    ) -> Float {
         // calculate first voltage from vsource
-        let impedance = f32::sqrt(self.inductance / self.capacitance);
+        let impedance = Float::sqrt(self.inductance / self.capacitance);
         let total_resistance = sim_params.delta_z*self.resistance + impedance;
         let d_ratio = sim_params.delta_z / sim_params.delta_t;
 
         let last_source_curr = (d_ratio*self.inductance + total_resistance/2.0).recip()
             *  ( (d_ratio*self.inductance - total_resistance/2.0) * last_curr
-                + (self.generate(t) - last_volt) );
+                + (gain*self.generate(t) - last_volt) );
 
         (d_ratio*self.capacitance + sim_params.delta_z*self.conductance/2.0).recip()
             * ( (d_ratio*self.capacitance - sim_params.delta_z*self.conductance/2.0) * last_volt
                 + (last_source_curr - last_curr) )
     }
 
-    fn generate(&self, time: f32) -> f32 {
-        (self.source_fn)(time)
+    fn generate(&self, time: Float) -> Float {
+        self.source_fn.at(time)
+    }
+
+    fn dc_voltage(&self, bias: Float, current: Float, sim_params: &SimulationParameters) -> Float {
+        let impedance = Float::sqrt(self.inductance / self.capacitance);
+        let total_resistance = sim_params.delta_z*self.resistance + impedance;
+
+        // the source current also feeds the conductance of the source node
+        (bias - total_resistance*current)
+            / (1.0 + total_resistance*sim_params.delta_z*self.conductance)
+    }
+
+    /// The parameters of the source, with those of the waveform prefixed by `waveform_`.
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("MatchedVSource")
+            .with("capacitance", vec![self.capacitance])
+            .with("inductance", vec![self.inductance])
+            .with("resistance", vec![self.resistance])
+            .with("conductance", vec![self.conductance])
+            .with_nested("waveform", self.source_fn.snapshot())
     }
 }
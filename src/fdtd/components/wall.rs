@@ -0,0 +1,202 @@
+use crate::{ComponentSnapshot, Error, Float, SimulationParameters};
+use crate::fdtd::{VSource, Terminator};
+
+/// An ideal electric wall (V = 0), for simulating half of a symmetric structure.
+///
+/// Can be used as either a source or a terminator. The wall sits on the boundary node, half a
+/// cell beyond the end of the line, and the line parameters should match the adjacent cell.
+pub struct ElectricWall {
+    pub inductance: Float,
+    pub capacitance: Float,
+    pub resistance: Float,
+    pub conductance: Float,
+}
+
+/// An ideal magnetic wall (I = 0), for simulating half of a symmetric structure.
+///
+/// Can be used as either a source or a terminator. The wall sits on the boundary node, half a
+/// cell beyond the end of the line, and the line parameters should match the adjacent cell.
+pub struct MagneticWall {
+    pub inductance: Float,
+    pub capacitance: Float,
+    pub resistance: Float,
+    pub conductance: Float,
+}
+
+impl ElectricWall {
+    /// Rebuilds an `ElectricWall` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("ElectricWall")?;
+        Ok(Self {
+            inductance: snapshot.get_scalar("inductance")?,
+            capacitance: snapshot.get_scalar("capacitance")?,
+            resistance: snapshot.get_scalar("resistance")?,
+            conductance: snapshot.get_scalar("conductance")?,
+        })
+    }
+}
+impl VSource for ElectricWall {
+    /// Steps the wall, which generates nothing to scale.
+    fn next_voltage_scaled(
+        &mut self,
+        _gain: Float,
+        _t: Float,
+        _last_volt: Float,
+        _last_curr: Float,
+        _sim_params: &SimulationParameters,
+    ) -> Float {
+        0.0
+    }
+
+    fn generate(&self, _time: Float) -> Float {
+        0.0
+    }
+
+    fn dc_voltage(&self, _bias: Float, _current: Float, _sim_params: &SimulationParameters) -> Float {
+        0.0
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        wall_snapshot("ElectricWall", self.inductance, self.capacitance, self.resistance, self.conductance)
+    }
+}
+impl Terminator for ElectricWall {
+    fn next_voltage(
+        &self,
+        _last_volt: Float,
+        _last_curr: Float,
+        _sim_params: &SimulationParameters,
+    ) -> Float {
+        0.0
+    }
+
+    fn next_current(
+        &self,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        wall_current(self.inductance, self.resistance, last_volts, last_curr, sim_params)
+    }
+
+    fn dc_end(&self, scale: Float, _sim_params: &SimulationParameters) -> (Float, Float) {
+        (0.0, scale)
+    }
+
+    fn dc_series_voltage(&self, current: Float, sim_params: &SimulationParameters) -> Float {
+        sim_params.delta_z*self.resistance * current
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        wall_snapshot("ElectricWall", self.inductance, self.capacitance, self.resistance, self.conductance)
+    }
+}
+
+impl MagneticWall {
+    /// Rebuilds a `MagneticWall` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("MagneticWall")?;
+        Ok(Self {
+            inductance: snapshot.get_scalar("inductance")?,
+            capacitance: snapshot.get_scalar("capacitance")?,
+            resistance: snapshot.get_scalar("resistance")?,
+            conductance: snapshot.get_scalar("conductance")?,
+        })
+    }
+
+    /// Calculates the boundary voltage, which only sees half a cell of charge.
+    fn next_half_cell_voltage(
+        &self,
+        last_volt: Float,
+        net_curr: Float,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        let d_ratio = sim_params.delta_z / sim_params.delta_t;
+
+        (d_ratio*self.capacitance/2.0 + sim_params.delta_z*self.conductance/4.0).recip()
+            * ( (d_ratio*self.capacitance/2.0 - sim_params.delta_z*self.conductance/4.0) * last_volt
+                + net_curr )
+    }
+}
+impl VSource for MagneticWall {
+    /// Steps the wall, which generates nothing to scale.
+    fn next_voltage_scaled(
+        &mut self,
+        _gain: Float,
+        _t: Float,
+        last_volt: Float,
+        last_curr: Float,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        self.next_half_cell_voltage(last_volt, -last_curr, sim_params)
+    }
+
+    fn generate(&self, _time: Float) -> Float {
+        0.0
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        wall_snapshot("MagneticWall", self.inductance, self.capacitance, self.resistance, self.conductance)
+    }
+}
+impl Terminator for MagneticWall {
+    fn next_voltage(
+        &self,
+        last_volt: Float,
+        last_curr: Float,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        self.next_half_cell_voltage(last_volt, last_curr, sim_params)
+    }
+
+    fn next_current(
+        &self,
+        last_volts: ndarray::ArrayView1<Float>,
+        last_curr: Float,
+        sim_params: &SimulationParameters,
+    ) -> Float {
+        wall_current(self.inductance, self.resistance, last_volts, last_curr, sim_params)
+    }
+
+    fn dc_end(&self, scale: Float, sim_params: &SimulationParameters) -> (Float, Float) {
+        // the wall node only sees half a cell of conductance
+        (scale, sim_params.delta_z*self.conductance/2.0 * scale)
+    }
+
+    fn dc_series_voltage(&self, current: Float, sim_params: &SimulationParameters) -> Float {
+        sim_params.delta_z*self.resistance * current
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        wall_snapshot("MagneticWall", self.inductance, self.capacitance, self.resistance, self.conductance)
+    }
+}
+
+/// Calculates the current flowing from the last line cell into the wall node.
+fn wall_current(
+    inductance: Float,
+    resistance: Float,
+    last_volts: ndarray::ArrayView1<Float>,
+    last_curr: Float,
+    sim_params: &SimulationParameters,
+) -> Float {
+    let d_ratio = sim_params.delta_z / sim_params.delta_t;
+
+    (d_ratio*inductance + sim_params.delta_z*resistance/2.0).recip()
+        *  ( (d_ratio*inductance - sim_params.delta_z*resistance/2.0) * last_curr
+            + (last_volts[0] - last_volts[1]) )
+}
+
+fn wall_snapshot(
+    kind: &str,
+    inductance: Float,
+    capacitance: Float,
+    resistance: Float,
+    conductance: Float,
+) -> ComponentSnapshot {
+    ComponentSnapshot::new(kind)
+        .with("inductance", vec![inductance])
+        .with("capacitance", vec![capacitance])
+        .with("resistance", vec![resistance])
+        .with("conductance", vec![conductance])
+}
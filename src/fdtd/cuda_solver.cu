@@ -0,0 +1,122 @@
+// Leapfrog updates of linear and kinetic inductance lines, one launch of each kernel per step.
+//
+// Voltages and currents hold one row per time step of a chunk, with the initial state in the
+// first row. Linear updates are `next = decay*last + gain*drive`, with the coefficients of each
+// point packed as `(decay, gain)`. The boundaries hold the updates of the source voltage, source
+// current, end voltage, and end current, in that order.
+
+// flags raised by the kinetic inductance kernel, matching the warnings of `KiLine`
+#define FLAG_NOT_CONVERGED 1
+#define FLAG_NEAR_CRITICAL 2
+
+extern "C" __global__ void update_voltages(
+    const float *volt_coeffs,
+    const float *boundary,
+    const float *source_volts,
+    float *voltages,
+    const float *currents,
+    int npoints,
+    int t
+) {
+    int k = blockIdx.x*blockDim.x + threadIdx.x;
+    if (k > npoints + 1) {
+        return;
+    }
+    const float *last_volts = voltages + t*(npoints + 2);
+    float *next_volts = voltages + (t + 1)*(npoints + 2);
+    const float *last_currs = currents + t*(npoints + 1);
+
+    if (k == 0) {
+        float source_curr = boundary[2]*last_currs[0]
+            + boundary[3]*(source_volts[t] - last_volts[0]);
+        next_volts[0] = boundary[0]*last_volts[0] + boundary[1]*(source_curr - last_currs[0]);
+    } else if (k == npoints + 1) {
+        next_volts[k] = boundary[4]*last_volts[k] + boundary[5]*last_currs[npoints];
+    } else {
+        next_volts[k] = volt_coeffs[2*(k - 1)]*last_volts[k]
+            + volt_coeffs[2*(k - 1) + 1]*(last_currs[k - 1] - last_currs[k]);
+    }
+}
+
+extern "C" __global__ void update_currents(
+    const float *curr_coeffs,
+    const float *boundary,
+    const float *voltages,
+    float *currents,
+    int *flags,
+    int npoints,
+    int t
+) {
+    int m = blockIdx.x*blockDim.x + threadIdx.x;
+    if (m > npoints) {
+        return;
+    }
+    const float *next_volts = voltages + (t + 1)*(npoints + 2);
+    const float *last_currs = currents + t*(npoints + 1);
+    float *next_currs = currents + (t + 1)*(npoints + 1);
+
+    const float *coeffs = m < npoints ? curr_coeffs + 2*m : boundary + 6;
+    next_currs[m] = coeffs[0]*last_currs[m] + coeffs[1]*(next_volts[m] - next_volts[m + 1]);
+}
+
+// Solves the cubic of the kinetic inductance with Newton's method, exactly as `KiLine` does. The
+// coefficients of each point are packed as `(delta_t / (delta_z*inductance), critical_current,
+// warning_current)`, followed by the most iterations and the tolerance relative to the critical
+// current, where the critical current is the effective one and the warning current the raw one.
+extern "C" __global__ void ki_update_currents(
+    const float *curr_coeffs,
+    const float *boundary,
+    const float *voltages,
+    float *currents,
+    int *flags,
+    int npoints,
+    int t
+) {
+    int m = blockIdx.x*blockDim.x + threadIdx.x;
+    if (m > npoints) {
+        return;
+    }
+    const float *next_volts = voltages + (t + 1)*(npoints + 2);
+    const float *last_currs = currents + t*(npoints + 1);
+    float *next_currs = currents + (t + 1)*(npoints + 1);
+
+    if (m == npoints) {
+        next_currs[m] = boundary[6]*last_currs[m]
+            + boundary[7]*(next_volts[m] - next_volts[m + 1]);
+        return;
+    }
+
+    float ratio = curr_coeffs[3*m];
+    float i_crit = curr_coeffs[3*m + 1];
+    float i_warn = curr_coeffs[3*m + 2];
+    float last_curr = last_currs[m];
+    float dv = next_volts[m + 1] - next_volts[m];
+
+    float b = last_curr;
+    float c = i_crit*i_crit - last_curr*last_curr;
+    float d = i_crit*i_crit*ratio*dv - i_crit*i_crit*last_curr - last_curr*last_curr*last_curr;
+
+    int max_iterations = (int)curr_coeffs[3*npoints];
+    float tolerance = curr_coeffs[3*npoints + 1];
+
+    float next_guess = last_curr;
+    bool converged = false;
+    for (int i = 0; i < max_iterations; i++) {
+        float this_guess = next_guess;
+        next_guess = this_guess
+            - (this_guess*this_guess*this_guess + b*this_guess*this_guess + c*this_guess + d)
+            / (3.0f*this_guess*this_guess + 2.0f*b*this_guess + c);
+        if (fabsf(next_guess - this_guess) <= tolerance*i_crit) {
+            converged = true;
+            break;
+        }
+    }
+
+    if (!converged) {
+        atomicOr(flags, FLAG_NOT_CONVERGED);
+    }
+    if (fabsf(next_guess) > 0.95f*i_warn) {
+        atomicOr(flags, FLAG_NEAR_CRITICAL);
+    }
+    next_currs[m] = next_guess;
+}
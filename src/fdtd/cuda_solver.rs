@@ -0,0 +1,309 @@
+// the device computes in single precision, so conversions are only needed in double precision
+// builds
+#![cfg_attr(not(feature = "f64"), allow(clippy::unnecessary_cast))]
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use cudarc::driver::{CudaDevice, CudaFunction, LaunchAsync, LaunchConfig};
+
+use crate::{
+    warn, ComponentSnapshot, ComputeDescriptor, ComputeOutput, Error, Float,
+    SimulationParameters, Solver,
+};
+use crate::fdtd::{check_finite, Component, TransmissionLine, Terminator, Update, VSource};
+use crate::fdtd::components::{KiLine, LinearLine, MatchedTerminator, MatchedVSource};
+
+/// The name the kernels are loaded under.
+const MODULE: &str = "tline";
+/// The number of time steps launched between progress updates.
+const STEPS_PER_SYNC: usize = 1024;
+/// The most bytes of source samples, voltages, and currents uploaded for each batch of a chunk.
+const MAX_BATCH_BYTES: usize = 1 << 30;
+/// Raised by the kinetic inductance kernel when a Newton iteration does not converge.
+const FLAG_NOT_CONVERGED: i32 = 1;
+/// Raised by the kinetic inductance kernel when a current nears the critical current.
+const FLAG_NEAR_CRITICAL: i32 = 2;
+
+/// A transmission line with update equations that run on a CUDA device.
+pub trait CudaLine: TransmissionLine {
+    /// The name of the kernel updating the currents of the line.
+    fn current_kernel(&self) -> &'static str;
+
+    /// Gets the voltage and current coefficients of each cell, packed for the kernels.
+    fn cuda_coefficients(
+        &self,
+        sim_params: &SimulationParameters,
+    ) -> Result<(Vec<Float>, Vec<Float>), Error>;
+}
+
+impl CudaLine for LinearLine {
+    fn current_kernel(&self) -> &'static str {
+        "update_currents"
+    }
+
+    fn cuda_coefficients(
+        &self,
+        sim_params: &SimulationParameters,
+    ) -> Result<(Vec<Float>, Vec<Float>), Error> {
+        if self.stencil_width() > 1 {
+            let message = "the kernels only support second order stencils";
+            return Err(Error::CudaError(message.to_string()))
+        }
+        let SimulationParameters { delta_z, delta_t } = *sim_params;
+        let d_ratio = delta_z / delta_t;
+        let snapshot = self.snapshot();
+        let pack = |reactances: &Vec<Float>, losses: &Vec<Float>| {
+            reactances.iter().zip(losses)
+                .map(|(&reactance, &loss)| Update::new(d_ratio*reactance, delta_z*loss/2.0))
+                .flat_map(|update| [update.decay, update.gain])
+                .collect::<Vec<_>>()
+        };
+
+        Ok((
+            pack(snapshot.get("capacitance")?, snapshot.get("conductance")?),
+            pack(snapshot.get("inductance")?, snapshot.get("resistance")?),
+        ))
+    }
+}
+
+impl CudaLine for KiLine {
+    fn current_kernel(&self) -> &'static str {
+        "ki_update_currents"
+    }
+
+    fn cuda_coefficients(
+        &self,
+        sim_params: &SimulationParameters,
+    ) -> Result<(Vec<Float>, Vec<Float>), Error> {
+        let SimulationParameters { delta_z, delta_t } = *sim_params;
+        let d_ratio = delta_z / delta_t;
+        let snapshot = self.snapshot();
+        let lossless = ["resistance", "nonlinear_resistance", "flux_flow_resistance"].iter()
+            .all(|name| snapshot.get(name).is_ok_and(|values| values.iter().all(|&x| x == 0.0)));
+        if !lossless {
+            let message = "the kinetic inductance kernel only supports lossless lines";
+            return Err(Error::CudaError(message.to_string()))
+        }
+        if snapshot.get("bias_current")?.iter().any(|&x| x != 0.0) {
+            let message = "the kinetic inductance kernel only supports unbiased lines";
+            return Err(Error::CudaError(message.to_string()))
+        }
+        if snapshot.get("nonlinearity")?.as_slice() != [4.0] {
+            let message = "the kinetic inductance kernel only supports the default nonlinearity";
+            return Err(Error::CudaError(message.to_string()))
+        }
+
+        let volt_coeffs = snapshot.get("capacitance")?.iter()
+            .flat_map(|&cap| [1.0, (d_ratio*cap).recip()])
+            .collect();
+        // the Newton iteration settings follow the triples of the last point, which warn near the
+        // critical current itself rather than the effective one, as `KiLine` does
+        let curr_coeffs = snapshot.get("inductance")?.iter()
+            .zip(snapshot.get("critical_current")?)
+            .zip(snapshot.get("switching_current")?)
+            .flat_map(|((&ind, &crit_cur), &switch_cur)| {
+                [delta_t / (delta_z*ind), crit_cur, switch_cur.min(crit_cur)]
+            })
+            .chain([snapshot.get_scalar("max_iterations")?, snapshot.get_scalar("tolerance")?])
+            .collect();
+        Ok((volt_coeffs, curr_coeffs))
+    }
+}
+
+/// Describes the composition of a `CudaFdtdSolver`.
+pub struct CudaFdtdSolverDescriptor<L: CudaLine, Fs: Fn(Float) -> Float> {
+    pub tline: L,
+    pub source: MatchedVSource<Fs>,
+    pub terminator: MatchedTerminator,
+    /// The ordinal of the CUDA device to run on.
+    pub device: usize,
+}
+
+/// Does computations of linear and kinetic inductance lines on a CUDA device.
+///
+/// The kernels are compiled when the solver is created and always compute in single precision.
+/// Source voltages are generated on the CPU, and each chunk is copied back once it is done.
+pub struct CudaFdtdSolver<L: CudaLine, Fs: Fn(Float) -> Float> {
+    tline: L,
+    source: MatchedVSource<Fs>,
+    terminator: MatchedTerminator,
+    device: Arc<CudaDevice>,
+    update_voltages: CudaFunction,
+    update_currents: CudaFunction,
+}
+
+impl<L: CudaLine, Fs: Fn(Float) -> Float> CudaFdtdSolver<L, Fs> {
+    /// Creates a new `CudaFdtdSolver`, compiling its kernels for the device.
+    pub fn new(desc: CudaFdtdSolverDescriptor<L, Fs>) -> Result<Self, Error> {
+        let device = CudaDevice::new(desc.device).map_err(cuda_error)?;
+        let ptx = cudarc::nvrtc::compile_ptx(include_str!("cuda_solver.cu"))
+            .map_err(cuda_error)?;
+        let kernels = ["update_voltages", "update_currents", "ki_update_currents"];
+        device.load_ptx(ptx, MODULE, &kernels).map_err(cuda_error)?;
+        let function = |name| {
+            device.get_func(MODULE, name)
+                .ok_or_else(|| Error::CudaError(format!("missing kernel {}", name)))
+        };
+
+        Ok(Self {
+            update_voltages: function("update_voltages")?,
+            update_currents: function(desc.tline.current_kernel())?,
+            tline: desc.tline,
+            source: desc.source,
+            terminator: desc.terminator,
+            device,
+        })
+    }
+
+    /// Gets the updates of the boundaries, in the order the kernels expect.
+    fn boundary(&self, sim_params: &SimulationParameters) -> Vec<f32> {
+        let SimulationParameters { delta_z, delta_t } = *sim_params;
+        let d_ratio = delta_z / delta_t;
+        let (source, terminator) = (&self.source, &self.terminator);
+
+        let source_resistance = delta_z*source.resistance
+            + Float::sqrt(source.inductance / source.capacitance);
+        let load_conductance = delta_z*terminator.conductance
+            + Float::sqrt(terminator.capacitance / terminator.inductance);
+        [
+            Update::new(d_ratio*source.capacitance, delta_z*source.conductance/2.0),
+            Update::new(d_ratio*source.inductance, source_resistance/2.0),
+            Update::new(d_ratio*terminator.capacitance, load_conductance/2.0),
+            Update::new(d_ratio*terminator.inductance, delta_z*terminator.resistance/2.0),
+        ]
+            .iter()
+            .flat_map(|update| [update.decay as f32, update.gain as f32])
+            .collect()
+    }
+}
+
+impl<L: CudaLine, Fs: Fn(Float) -> Float> Solver for CudaFdtdSolver<L, Fs> {
+    fn compute(
+        &mut self,
+        desc: ComputeDescriptor,
+    ) -> Result<ComputeOutput, Error> {
+        let npoints = self.tline.npoints();
+        let (nvolts, ncurrs) = (npoints + 2, npoints + 1);
+        let delta_t = desc.sim_params.delta_t;
+        let device = &self.device;
+
+        let (volt_coeffs, curr_coeffs) = self.tline.cuda_coefficients(&desc.sim_params)?;
+        let single = |values: Vec<Float>| values.into_iter().map(|x| x as f32).collect::<Vec<_>>();
+        let volt_coeffs = device.htod_copy(single(volt_coeffs)).map_err(cuda_error)?;
+        let curr_coeffs = device.htod_copy(single(curr_coeffs)).map_err(cuda_error)?;
+        let boundary = device.htod_copy(self.boundary(&desc.sim_params)).map_err(cuda_error)?;
+        let mut flags = device.htod_copy(vec![0i32]).map_err(cuda_error)?;
+
+        let mut voltages = ndarray::Array2::<Float>::zeros((desc.nsteps + 1, nvolts));
+        voltages.row_mut(0).assign(&desc.state.voltages);
+        let mut currents = ndarray::Array2::<Float>::zeros((desc.nsteps + 1, ncurrs));
+        currents.row_mut(0).assign(&desc.state.currents);
+
+        // split the chunk into batches whose source samples and full waveforms fit in the budget
+        let batch_size = (MAX_BATCH_BYTES / (4*(nvolts + ncurrs + 1))).saturating_sub(1).max(1);
+        let volt_config = LaunchConfig::for_num_elems(nvolts as u32);
+        let curr_config = LaunchConfig::for_num_elems(ncurrs as u32);
+        let mut first_step = 0;
+        while first_step < desc.nsteps {
+            let nsteps = batch_size.min(desc.nsteps - first_step);
+            let start_time = (first_step as Float)*delta_t + desc.state.time;
+
+            let source_volts = (0..nsteps)
+                .map(|t_index| self.source.generate((t_index as Float)*delta_t + start_time) as f32)
+                .collect::<Vec<_>>();
+            let source_volts = device.htod_copy(source_volts).map_err(cuda_error)?;
+            let upload = |values: ndarray::ArrayView1<Float>, width: usize| {
+                let mut batch = values.iter().map(|&x| x as f32).collect::<Vec<_>>();
+                batch.resize((nsteps + 1)*width, 0.0);
+                device.htod_copy(batch).map_err(cuda_error)
+            };
+            let mut batch_volts = upload(voltages.row(first_step), nvolts)?;
+            let mut batch_currs = upload(currents.row(first_step), ncurrs)?;
+
+            // kernels run in order on the default stream, so each sees the writes of the last
+            for t_index in 0..nsteps {
+                let t = t_index as i32;
+                unsafe {
+                    self.update_voltages.clone().launch(
+                        volt_config,
+                        (&volt_coeffs, &boundary, &source_volts, &mut batch_volts, &batch_currs,
+                            npoints as i32, t),
+                    ).map_err(cuda_error)?;
+                }
+                unsafe {
+                    self.update_currents.clone().launch(
+                        curr_config,
+                        (&curr_coeffs, &boundary, &batch_volts, &mut batch_currs, &mut flags,
+                            npoints as i32, t),
+                    ).map_err(cuda_error)?;
+                }
+
+                if (t_index + 1) % STEPS_PER_SYNC == 0 || t_index + 1 == nsteps {
+                    device.synchronize().map_err(cuda_error)?;
+                    if let Some(ref bar) = desc.bar {
+                        bar.inc(((t_index % STEPS_PER_SYNC) + 1) as u64)
+                    }
+                }
+            }
+
+            let rows = ndarray::s![first_step..(first_step + nsteps + 1), ..];
+            let download = |values: Vec<f32>, width| {
+                ndarray::Array2::from_shape_vec(
+                    (nsteps + 1, width),
+                    values.into_iter().map(|x| x as Float).collect(),
+                ).unwrap()
+            };
+            let batch_volts = device.dtoh_sync_copy(&batch_volts).map_err(cuda_error)?;
+            let batch_volts = download(batch_volts, nvolts);
+            voltages.slice_mut(rows).assign(&batch_volts);
+            let batch_currs = device.dtoh_sync_copy(&batch_currs).map_err(cuda_error)?;
+            let batch_currs = download(batch_currs, ncurrs);
+            currents.slice_mut(rows).assign(&batch_currs);
+            // batches run on the device, so divergence is only found once each is copied back
+            check_finite(
+                voltages.slice(rows),
+                currents.slice(rows),
+                (desc.state.time / delta_t).round() as usize + first_step,
+            )?;
+
+            first_step += nsteps;
+        }
+
+        let flags = device.dtoh_sync_copy(&flags).map_err(cuda_error)?[0];
+        if flags & FLAG_NOT_CONVERGED != 0 {
+            warn("KiLine", "Newton iteration did not converge");
+        }
+        if flags & FLAG_NEAR_CRITICAL != 0 {
+            warn("KiLine", "current is within 5% of the critical current");
+        }
+
+        Ok(ComputeOutput {
+            voltages,
+            currents,
+            channels: BTreeMap::new(),
+            channel_units: BTreeMap::new(),
+        })
+    }
+
+    fn npoints(&self) -> usize {
+        self.tline.npoints()
+    }
+
+    fn max_phase_velocity(&self) -> Option<Float> {
+        Some(self.tline.max_phase_velocity())
+    }
+
+    fn snapshot(&self) -> BTreeMap<String, ComponentSnapshot> {
+        BTreeMap::from([
+            ("tline".to_string(), self.tline.snapshot()),
+            ("source".to_string(), self.source.snapshot()),
+            ("terminator".to_string(), self.terminator.snapshot()),
+        ])
+    }
+}
+
+/// Converts an error from the CUDA driver or compiler.
+fn cuda_error(err: impl std::fmt::Display) -> Error {
+    Error::CudaError(err.to_string())
+}
@@ -0,0 +1,84 @@
+//! Correlated random perturbations of line parameters.
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, StandardNormal};
+
+use crate::Float;
+use crate::fdtd::Perturbation;
+
+/// Describes a `Disorder`.
+pub struct DisorderDescriptor {
+    /// The length of the perturbed line.
+    pub length: Float,
+    /// The number of cells in the perturbed line.
+    pub npoints: usize,
+    /// The rms relative deviation of the perturbed parameter.
+    pub rms: Float,
+    /// The length over which the deviations are correlated.
+    ///
+    /// The deviations have a Gaussian autocorrelation `exp(-Δz² / 2ℓ²)`. Lengths shorter than a
+    /// cell give uncorrelated deviations.
+    pub correlation_length: Float,
+    /// The seed of the random number generator.
+    pub seed: u64,
+}
+
+/// A reproducible, spatially correlated random deviation along a line.
+///
+/// The same `Disorder` can be applied to several parameters for fully correlated deviations, such
+/// as a width variation changing both the capacitance and inductance, or separate instances with
+/// different seeds can be used for independent deviations.
+#[derive(Clone, Debug)]
+pub struct Disorder {
+    delta_z: Float,
+    deviations: Vec<Float>,
+}
+
+impl Disorder {
+    /// Creates a new `Disorder` instance.
+    pub fn new(desc: DisorderDescriptor) -> Self {
+        let delta_z = desc.length / (desc.npoints as Float);
+        let mut rng = ChaCha8Rng::seed_from_u64(desc.seed);
+
+        // smooth white noise with a Gaussian kernel exp(-z² / ℓ²), normalized to unit variance
+        let width = desc.correlation_length / delta_z;
+        let half_width = (3.0 * width).ceil() as usize;
+        let kernel = (0..=2*half_width)
+            .map(|k| {
+                let offset = k as Float - half_width as Float;
+                if width > 0.0 { (-(offset / width).powi(2)).exp() } else { 1.0 }
+            })
+            .collect::<Vec<_>>();
+        let norm = kernel.iter().map(|k| k*k).sum::<Float>().sqrt();
+
+        let noise = (0..desc.npoints + 2*half_width)
+            .map(|_| StandardNormal.sample(&mut rng))
+            .collect::<Vec<Float>>();
+        let deviations = noise.windows(kernel.len())
+            .map(|window| {
+                let smoothed = window.iter().zip(kernel.iter())
+                    .map(|(x, k)| x * k)
+                    .sum::<Float>();
+                desc.rms * smoothed / norm
+            })
+            .collect();
+
+        Self { delta_z, deviations }
+    }
+
+    /// Gets the relative deviation of each cell.
+    pub fn deviations(&self) -> &[Float] {
+        &self.deviations
+    }
+}
+
+impl Perturbation for Disorder {
+    /// Gets the relative deviation of the cell containing position `z`, or zero if there are no
+    /// cells.
+    fn deviation(&self, z: Float) -> Float {
+        let index = ((z / self.delta_z).max(0.0) as usize)
+            .min(self.deviations.len().saturating_sub(1));
+        self.deviations.get(index).copied().unwrap_or(0.0)
+    }
+}
@@ -1,5 +1,11 @@
-use crate::{Error, Solver, ComputeDescriptor};
-use crate::fdtd::{TransmissionLine, VSource, Terminator};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::{
+    diagnostics, ComponentSnapshot, Error, Float, Solver, ComputeDescriptor, ComputeOutput,
+    SimulationParameters, SimulationState,
+};
+use crate::fdtd::{check_finite, TransmissionLine, VSource, Terminator};
 
 /// Describes the composition of a `StandardSolver`.
 pub struct FdtdSolverDescriptor<L: TransmissionLine> {
@@ -11,8 +17,26 @@ pub struct FdtdSolverDescriptor<L: TransmissionLine> {
 /// Does single threaded computations on the CPU.
 pub struct FdtdSolver<L: TransmissionLine> {
     tline: L,
-    source: Box<dyn VSource>,
-    terminator: Box<dyn Terminator>,
+    boundary: Boundary,
+    field_fn: Option<Box<dyn Fn(Float, Float) -> Float>>,
+    subcycles: Vec<Subcycle>,
+}
+
+/// A range of cells updated with a time step `factor` times shorter than the rest of the line.
+struct Subcycle {
+    cells: Range<usize>,
+    factor: usize,
+}
+
+/// How the ends of the line are treated.
+enum Boundary {
+    /// The start is driven by a source and the end is loaded by a terminator.
+    Ports {
+        source: Box<dyn VSource>,
+        terminator: Box<dyn Terminator>,
+    },
+    /// The end of the line is connected back to its start.
+    Periodic,
 }
 
 impl<L: TransmissionLine> FdtdSolver<L> {
@@ -20,9 +44,267 @@ impl<L: TransmissionLine> FdtdSolver<L> {
     pub fn new(desc: FdtdSolverDescriptor<L>) -> Self {
         Self {
             tline: desc.tline,
-            source: desc.source,
-            terminator: desc.terminator,
+            boundary: Boundary::Ports {
+                source: desc.source,
+                terminator: desc.terminator,
+            },
+            field_fn: None,
+            subcycles: Vec::new(),
+        }
+    }
+
+    /// Creates a new `FdtdSolver` for a closed ring, with the last cell connected to the first.
+    ///
+    /// The first and last voltages and the last current of the simulation state mirror the
+    /// opposite end of the ring.
+    #[inline]
+    pub fn new_periodic(tline: L) -> Self {
+        Self {
+            tline,
+            boundary: Boundary::Periodic,
+            field_fn: None,
+            subcycles: Vec::new(),
+        }
+    }
+
+    /// The line the solver updates.
+    pub(crate) fn tline(&self) -> &L {
+        &self.tline
+    }
+
+    /// Couples an incident field `E(z, t)` into the whole line.
+    ///
+    /// The field drives a distributed series voltage source of `E(z, t) Δz` at each current, with
+    /// a positive field pushing current towards the end of the line.
+    pub fn with_incident_field<F: Fn(Float, Float) -> Float + 'static>(mut self, field_fn: F) -> Self {
+        self.field_fn = Some(Box::new(field_fn));
+        self
+    }
+
+    /// Updates `cells` with a time step `factor` times shorter than the rest of the line, so a
+    /// short nonlinear or fast section doesn't limit the time step of the whole line.
+    ///
+    /// At each substep, the voltages next to the cells are interpolated between steps and the
+    /// currents next to them are extrapolated from their last two values. Currents inside the
+    /// cells are half a substep ahead of the voltages instead of half a step, and incident fields
+    /// aren't coupled into them. Fails if the cells overlap those of an earlier call.
+    ///
+    /// The edges of the cells are less stable than the rest of the line, so time steps should be
+    /// kept at least 10% below the stability limit, as is warned about.
+    pub fn with_subcycling(mut self, cells: Range<usize>, factor: usize) -> Result<Self, Error> {
+        let end = cells.end.min(self.tline.npoints());
+        if cells.start < end && factor > 1 {
+            let overlap = self.subcycles.iter()
+                .find(|subcycle| subcycle.cells.start < end && cells.start < subcycle.cells.end);
+            if let Some(subcycle) = overlap {
+                return Err(Error::BadParameter(format!(
+                    "subcycled cells {:?} overlap cells {:?}", cells.start..end, subcycle.cells,
+                )))
+            }
+            self.subcycles.push(Subcycle { cells: cells.start..end, factor });
+        }
+        Ok(self)
+    }
+
+    /// Calculates the DC operating point of the line with its source biased at `bias`.
+    ///
+    /// Using the operating point as the initial state of a simulation avoids the charging transient
+    /// of a biased line. Periodic lines have no source, so they are at rest.
+    pub fn dc_operating_point(
+        &self,
+        bias: Float,
+        sim_params: &SimulationParameters,
+    ) -> SimulationState {
+        let npoints = self.tline.npoints();
+        let mut state = SimulationState {
+            time: 0.0,
+            voltages: ndarray::Array1::zeros(npoints + 2),
+            currents: ndarray::Array1::zeros(npoints + 1),
+        };
+        let (source, terminator) = match self.boundary {
+            Boundary::Ports { ref source, ref terminator } => (source, terminator),
+            Boundary::Periodic => return state,
+        };
+
+        // shoot from the end of the line, using the secant method to find the end state that
+        // satisfies the source, which takes a single step when every element is linear
+        let tolerance = 1e-6 * bias.abs().max(1e-6);
+        let shoot = |scale, state: &mut SimulationState| {
+            self.dc_shoot(&**source, &**terminator, bias, scale, sim_params, state)
+        };
+        let mut last_scale = 0.0;
+        let mut last_residual = shoot(last_scale, &mut state);
+        let mut scale = if bias != 0.0 { bias } else { 1.0 };
+        for _ in 0..50 {
+            let residual = shoot(scale, &mut state);
+            if residual.abs() <= tolerance {
+                return state
+            }
+            if residual == last_residual {
+                break
+            }
+
+            let next_scale = scale - residual * (scale - last_scale) / (residual - last_residual);
+            last_scale = scale;
+            last_residual = residual;
+            scale = next_scale;
+        }
+
+        diagnostics::warn("FdtdSolver", "DC operating point did not converge");
+        state
+    }
+
+    /// Fills `state` with the DC state of the line for an end state of `scale`, working back to
+    /// the source.
+    ///
+    /// Returns how far the source node voltage is from that required by the source.
+    fn dc_shoot(
+        &self,
+        source: &dyn VSource,
+        terminator: &dyn Terminator,
+        bias: Float,
+        scale: Float,
+        sim_params: &SimulationParameters,
+        state: &mut SimulationState,
+    ) -> Float {
+        let npoints = self.tline.npoints();
+        let (voltages, currents) = (&mut state.voltages, &mut state.currents);
+
+        let (end_volt, end_curr) = terminator.dc_end(scale, sim_params);
+        voltages[npoints+1] = end_volt;
+        currents[npoints] = end_curr;
+        voltages[npoints] = end_volt + terminator.dc_series_voltage(end_curr, sim_params);
+        for index in (0..npoints).rev() {
+            currents[index] = currents[index+1]
+                + self.tline.dc_shunt_current(voltages[index+1], index, sim_params);
+            voltages[index] = voltages[index+1]
+                + self.tline.dc_series_voltage(currents[index], index, sim_params);
+        }
+
+        source.dc_voltage(bias, currents[0], sim_params) - voltages[0]
+    }
+
+    /// Extends a row of voltages or currents by `pad` points past each end, for wide stencils.
+    ///
+    /// The line's points start at `first` in the row. Open lines repeat their end values, while
+    /// periodic lines wrap around.
+    fn pad(&self, row: ndarray::ArrayView1<Float>, pad: usize, first: usize) -> ndarray::Array1<Float> {
+        (0..row.len() + 2*pad)
+            .map(|k| row[self.wrap(k as isize - pad as isize, row.len(), first)])
+            .collect()
+    }
+
+    /// Maps an index past the ends of a row of `len` values to the value `pad` extends it with.
+    fn wrap(&self, index: isize, len: usize, first: usize) -> usize {
+        match self.boundary {
+            Boundary::Ports { .. } => index.clamp(0, len as isize - 1) as usize,
+            Boundary::Periodic => {
+                let npoints = self.tline.npoints() as isize;
+                first + (index - first as isize).rem_euclid(npoints) as usize
+            },
+        }
+    }
+
+    /// The fastest phase velocity relative to the time step of each cell, which limits the time
+    /// step of the whole line.
+    fn limiting_velocity(&self) -> Float {
+        let npoints = self.tline.npoints();
+        let mut factors = vec![1; npoints];
+        for subcycle in &self.subcycles {
+            factors[subcycle.cells.clone()].iter_mut().for_each(|factor| *factor = subcycle.factor);
         }
+
+        let mut velocity: Float = 0.0;
+        let mut start = 0;
+        for end in 1..=npoints {
+            if end == npoints || factors[end] != factors[start] {
+                let range_velocity = self.tline.max_phase_velocity_in(start..end);
+                velocity = velocity.max(range_velocity / (factors[start] as Float));
+                start = end;
+            }
+        }
+        velocity
+    }
+
+    /// Advances the voltages and currents inside a subcycled range of cells from row `t_index` to
+    /// the next, after the other voltages of the next row have been calculated.
+    fn subcycle(
+        &self,
+        subcycle: &Subcycle,
+        voltages: &mut ndarray::Array2<Float>,
+        currents: &mut ndarray::Array2<Float>,
+        t_index: usize,
+        sim_params: &SimulationParameters,
+    ) {
+        let Subcycle { ref cells, factor } = *subcycle;
+        let width = self.tline.stencil_width().max(1);
+        let fine_params = SimulationParameters {
+            delta_z: sim_params.delta_z,
+            delta_t: sim_params.delta_t / (factor as Float),
+        };
+
+        // line voltage `i` is at row index `i + 1`, and the currents of the range, which feed each
+        // of its cells, are at the same row indices as the cells
+        let volt_rows = (0..(cells.len() + 2*width - 1))
+            .map(|j| {
+                let index = (cells.start + 1 + j) as isize - width as isize;
+                self.wrap(index, voltages.ncols(), 1)
+            })
+            .collect::<Vec<_>>();
+        let curr_rows = (0..(cells.len() + 2*width - 1))
+            .map(|j| {
+                let index = (cells.start + 1 + j) as isize - width as isize;
+                self.wrap(index, currents.ncols(), 0)
+            })
+            .collect::<Vec<_>>();
+        let is_fine_volt = |row: usize| row > cells.start && row <= cells.end;
+        let is_fine_curr = |row: usize| cells.contains(&row);
+
+        let mut fine_volts = voltages.slice(ndarray::s![t_index, (cells.start+1)..(cells.end+1)])
+            .to_owned();
+        let mut fine_currs = currents.slice(ndarray::s![t_index, cells.clone()]).to_owned();
+        let last_step = t_index.saturating_sub(1);
+        let mut volt_window = ndarray::Array1::<Float>::zeros(volt_rows.len());
+        let mut curr_window = ndarray::Array1::<Float>::zeros(curr_rows.len());
+        for substep in 0..factor {
+            // currents outside the range are extrapolated to the middle of the substep
+            let curr_time = (substep as Float + 0.5) / (factor as Float) - 0.5;
+            for (value, &row) in curr_window.iter_mut().zip(&curr_rows) {
+                *value = if is_fine_curr(row) {
+                    fine_currs[row - cells.start]
+                } else {
+                    let (last, now) = (currents[[last_step, row]], currents[[t_index, row]]);
+                    now + curr_time*(now - last)
+                };
+            }
+            for (index, volt) in fine_volts.iter_mut().enumerate() {
+                let cell = cells.start + index;
+                let stencil = curr_window.slice(ndarray::s![index..(index + 2*width)]);
+                let last_volt = *volt;
+                self.tline.next_voltage(volt, last_volt, stencil, cell, &fine_params);
+            }
+
+            // voltages outside the range are interpolated to the end of the substep
+            let volt_time = (substep as Float + 1.0) / (factor as Float);
+            for (value, &row) in volt_window.iter_mut().zip(&volt_rows) {
+                *value = if is_fine_volt(row) {
+                    fine_volts[row - cells.start - 1]
+                } else {
+                    let (last, next) = (voltages[[t_index, row]], voltages[[t_index + 1, row]]);
+                    last + volt_time*(next - last)
+                };
+            }
+            for (index, curr) in fine_currs.iter_mut().enumerate() {
+                let cell = cells.start + index;
+                let stencil = volt_window.slice(ndarray::s![index..(index + 2*width)]);
+                let last_curr = *curr;
+                self.tline.next_current(curr, stencil, last_curr, cell, &fine_params);
+            }
+        }
+
+        voltages.slice_mut(ndarray::s![t_index + 1, (cells.start+1)..(cells.end+1)])
+            .assign(&fine_volts);
+        currents.slice_mut(ndarray::s![t_index + 1, cells.clone()]).assign(&fine_currs);
     }
 }
 
@@ -31,26 +313,63 @@ impl<L: TransmissionLine> Solver for FdtdSolver<L> {
     fn compute(
         &mut self,
         desc: ComputeDescriptor,
-    ) -> Result<(ndarray::Array2<f32>, ndarray::Array2<f32>), Error> {
+    ) -> Result<ComputeOutput, Error> {
         let total_points: usize = 1 + self.tline.npoints();
+        let stability_margin = desc.sim_params.delta_z
+            / (self.limiting_velocity() * desc.sim_params.delta_t);
+        if stability_margin < 1.1 {
+            diagnostics::set_time(desc.state.time);
+            diagnostics::warn("FdtdSolver", "time step is within 10% of the stability limit");
+        }
 
         // create storage arrays for voltage and current
-        let mut voltages = ndarray::Array2::<f32>::zeros((desc.nsteps+1, total_points + 1));
+        let mut voltages = ndarray::Array2::<Float>::zeros((desc.nsteps+1, total_points + 1));
         voltages.slice_mut(ndarray::s![0, ..]).assign(&desc.state.voltages);
-        let mut currents = ndarray::Array2::<f32>::zeros((desc.nsteps+1, total_points));
+        let mut currents = ndarray::Array2::<Float>::zeros((desc.nsteps+1, total_points));
         currents.slice_mut(ndarray::s![0, ..]).assign(&desc.state.currents);
+        let last_ind = total_points;
+        if let Boundary::Periodic = self.boundary {
+            voltages[[0, 0]] = voltages[[0, last_ind-1]];
+            voltages[[0, last_ind]] = voltages[[0, 1]];
+            currents[[0, last_ind-1]] = currents[[0, 0]];
+        }
+        let channel_specs = self.tline.channels();
+        let mut channels = channel_specs.iter()
+            .map(|(_, width, _)| ndarray::Array2::<Float>::zeros((desc.nsteps+1, *width)))
+            .collect::<Vec<_>>();
+        let mut record_channels = |tline: &L, row: usize| {
+            if !channels.is_empty() {
+                let mut rows = channels.iter_mut()
+                    .map(|channel| channel.row_mut(row))
+                    .collect::<Vec<_>>();
+                tline.record_channels(&mut rows);
+            }
+        };
+        record_channels(&self.tline, 0);
+        let first_step = (desc.state.time / desc.sim_params.delta_t).round() as usize;
+        // subcycled voltages and currents are skipped by the updates of the whole line
+        let mut fine_volts = vec![false; self.tline.npoints()];
+        let mut fine_currs = vec![false; self.tline.npoints()];
+        for Subcycle { cells, .. } in &self.subcycles {
+            fine_volts[cells.clone()].iter_mut().for_each(|fine| *fine = true);
+            fine_currs[cells.clone()].iter_mut().for_each(|fine| *fine = true);
+        }
 
         // loop through time
         for t_index in 0..desc.nsteps {
-            let t = (t_index as f32)*desc.sim_params.delta_t + desc.state.time;
+            let t = (t_index as Float)*desc.sim_params.delta_t + desc.state.time;
+            diagnostics::set_time(t);
+            self.tline.begin_step(t, &desc.sim_params);
 
             // calculate first voltage from vsource
-            voltages[[t_index+1, 0]] = self.source.next_voltage(
-                t,
-                voltages[[t_index, 0]],
-                currents[[t_index, 0]],
-                &desc.sim_params,
-            );
+            if let Boundary::Ports { ref mut source, .. } = self.boundary {
+                voltages[[t_index+1, 0]] = source.next_voltage(
+                    t,
+                    voltages[[t_index, 0]],
+                    currents[[t_index, 0]],
+                    &desc.sim_params,
+                );
+            }
 
             // get 1D views of voltages at relevent times
             let (volts1, mut volts2) = voltages
@@ -58,55 +377,129 @@ impl<L: TransmissionLine> Solver for FdtdSolver<L> {
                 .split_at(ndarray::Axis(0), t_index+1);
             let last_volts = volts1.row(t_index);
             let mut next_volts = volts2.row_mut(0);
-            // get 1D views of currents at relevent times
-            let (currs1, mut currs2) = currents
-                .view_mut()
-                .split_at(ndarray::Axis(0), t_index+1);
-            let last_currs = currs1.row(t_index);
-            let mut next_currs = currs2.row_mut(0);
+            // get 1D view of currents at relevent time
+            let last_currs = currents.row(t_index);
 
             let npoints = self.tline.npoints();
+            let width = self.tline.stencil_width().max(1);
+            let padded_currs = (width > 1).then(|| self.pad(last_currs, width-1, 0));
+            let curr_stencil = padded_currs.as_ref().map_or(last_currs, |padded| padded.view());
             ndarray::Zip::from(&mut next_volts.slice_mut(ndarray::s![1..(1+npoints)]))
                 .and(&last_volts.slice(ndarray::s![1..(1+npoints)]))
-                .and(last_currs.slice(ndarray::s![0..(1+npoints)]).windows(2))
+                .and(curr_stencil.slice(ndarray::s![0..(npoints+2*width-1)]).windows(2*width))
                 .and(&(0..(npoints)).collect::<Vec<usize>>())
                 .for_each(|nv, &lv, lc, &z| {
-                    self.tline.next_voltage(nv, lv, lc, z, &desc.sim_params);
+                    if !fine_volts[z] {
+                        self.tline.next_voltage(nv, lv, lc, z, &desc.sim_params);
+                    }
                 });
-            // calculate last voltage
-            let last_ind = total_points;
-            voltages[[t_index+1, last_ind]] = self.terminator.next_voltage(
-                last_volts[last_ind],
-                last_currs[last_ind-1],
-                &desc.sim_params,
-            );
+            // calculate boundary voltages
+            match self.boundary {
+                Boundary::Ports { ref terminator, .. } => {
+                    voltages[[t_index+1, last_ind]] = terminator.next_voltage(
+                        last_volts[last_ind],
+                        last_currs[last_ind-1],
+                        &desc.sim_params,
+                    );
+                },
+                Boundary::Periodic => {
+                    voltages[[t_index+1, 0]] = voltages[[t_index+1, last_ind-1]];
+                    voltages[[t_index+1, last_ind]] = voltages[[t_index+1, 1]];
+                },
+            }
+            for subcycle in &self.subcycles {
+                self.subcycle(subcycle, &mut voltages, &mut currents, t_index, &desc.sim_params);
+            }
+            if !self.subcycles.is_empty() {
+                if let Boundary::Periodic = self.boundary {
+                    voltages[[t_index+1, 0]] = voltages[[t_index+1, last_ind-1]];
+                    voltages[[t_index+1, last_ind]] = voltages[[t_index+1, 1]];
+                }
+            }
+            // get 1D views of currents at relevent times
+            let (currs1, mut currs2) = currents
+                .view_mut()
+                .split_at(ndarray::Axis(0), t_index+1);
+            let last_currs = currs1.row(t_index);
+            let mut next_currs = currs2.row_mut(0);
 
             // calculate currents for next time step
             let last_volts = voltages.row(t_index+1);
             let npoints = self.tline.npoints();
+            let padded_volts = (width > 1).then(|| self.pad(last_volts, width-1, 1));
+            let volt_stencil = padded_volts.as_ref().map_or(last_volts, |padded| padded.view());
+            // an incident field acts as a potential offset, so each voltage difference gains its emf
+            let driven_volts = self.field_fn.as_ref().map(|field_fn| {
+                let (delta_z, next_t) = (desc.sim_params.delta_z, t + desc.sim_params.delta_t);
+                let mut offset = 0.0;
+                volt_stencil.iter()
+                    .enumerate()
+                    .map(|(k, &volt)| {
+                        let mut index = k as isize - (width - 1) as isize;
+                        if let Boundary::Periodic = self.boundary {
+                            index = index.rem_euclid(npoints as isize);
+                        }
+                        let driven = volt - offset;
+                        offset += field_fn((index as Float)*delta_z, next_t) * delta_z;
+                        driven
+                    })
+                    .collect::<ndarray::Array1<Float>>()
+            });
+            let volt_stencil = driven_volts.as_ref().map_or(volt_stencil, |driven| driven.view());
             ndarray::Zip::from(&mut next_currs.slice_mut(ndarray::s![0..npoints]))
-                .and(last_volts.slice(ndarray::s![0..(1+npoints)]).windows(2))
+                .and(volt_stencil.slice(ndarray::s![0..(npoints+2*width-1)]).windows(2*width))
                 .and(&last_currs.slice(ndarray::s![0..npoints]))
                 .and(&(0..(npoints)).collect::<Vec<usize>>())
                 .for_each(|nv, lv, &lc, &z| {
-                    self.tline.next_current(nv, lv, lc, z, &desc.sim_params);
+                    if !fine_currs[z] {
+                        self.tline.next_current(nv, lv, lc, z, &desc.sim_params);
+                    }
                 });
             // calculate last current
-            currents[[t_index+1, last_ind-1]] = self.terminator.next_current(
-                last_volts.slice(ndarray::s![-2..=-1]),
-                last_currs[last_ind-1],
-                &desc.sim_params,
-            );
+            currents[[t_index+1, last_ind-1]] = match self.boundary {
+                Boundary::Ports { ref terminator, .. } => terminator.next_current(
+                    volt_stencil.slice(ndarray::s![(npoints+width-1)..(npoints+width+1)]),
+                    last_currs[last_ind-1],
+                    &desc.sim_params,
+                ),
+                Boundary::Periodic => currents[[t_index+1, 0]],
+            };
+            record_channels(&self.tline, t_index+1);
+            let rows = ndarray::s![(t_index+1)..(t_index+2), ..];
+            check_finite(voltages.slice(rows), currents.slice(rows), first_step + t_index + 1)?;
 
             if let Some(ref bar) = desc.bar {
                 bar.inc(1)
             }
         }
 
-        Ok((voltages, currents))
+        Ok(ComputeOutput {
+            voltages,
+            currents,
+            channel_units: channel_specs.iter()
+                .map(|(name, _, units)| (name.clone(), *units))
+                .collect(),
+            channels: channel_specs.into_iter()
+                .map(|(name, _, _)| name)
+                .zip(channels)
+                .collect(),
+        })
     }
 
     fn npoints(&self) -> usize {
         self.tline.npoints()
     }
+
+    fn max_phase_velocity(&self) -> Option<Float> {
+        Some(self.limiting_velocity())
+    }
+
+    fn snapshot(&self) -> BTreeMap<String, ComponentSnapshot> {
+        let mut snapshot = BTreeMap::from([("tline".to_string(), self.tline.snapshot())]);
+        if let Boundary::Ports { ref source, ref terminator } = self.boundary {
+            snapshot.insert("source".to_string(), source.snapshot());
+            snapshot.insert("terminator".to_string(), terminator.snapshot());
+        }
+        snapshot
+    }
 }
@@ -0,0 +1,367 @@
+// the GPU computes in single precision, so conversions are only needed in double precision builds
+#![cfg_attr(not(feature = "f64"), allow(clippy::unnecessary_cast))]
+
+use std::collections::BTreeMap;
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    ComponentSnapshot, ComputeDescriptor, ComputeOutput, Error, Float, SimulationParameters,
+    Solver,
+};
+use crate::fdtd::{check_finite, Component, TransmissionLine, Terminator, Update, VSource};
+use crate::fdtd::components::{LinearLine, MatchedTerminator, MatchedVSource};
+
+/// The number of threads in each workgroup of the update kernels.
+const WORKGROUP_SIZE: usize = 64;
+/// The number of time steps submitted to the GPU at once.
+const STEPS_PER_SUBMIT: usize = 256;
+
+/// Describes the composition of a `GpuFdtdSolver`.
+pub struct GpuFdtdSolverDescriptor<Fs: Fn(Float) -> Float> {
+    pub tline: LinearLine,
+    pub source: MatchedVSource<Fs>,
+    pub terminator: MatchedTerminator,
+}
+
+/// Does computations of a linear line on the GPU, with compute shaders.
+///
+/// The GPU always computes in single precision. Source voltages are generated on the CPU, and
+/// each chunk is copied back once it is done.
+pub struct GpuFdtdSolver<Fs: Fn(Float) -> Float> {
+    tline: LinearLine,
+    source: MatchedVSource<Fs>,
+    terminator: MatchedTerminator,
+    /// The capacitance, inductance, resistance, and conductance per unit length of each cell.
+    parameters: [Vec<Float>; 4],
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    layout: wgpu::BindGroupLayout,
+    update_voltages: wgpu::ComputePipeline,
+    update_currents: wgpu::ComputePipeline,
+    advance: wgpu::ComputePipeline,
+}
+
+impl<Fs: Fn(Float) -> Float> GpuFdtdSolver<Fs> {
+    /// Creates a new `GpuFdtdSolver` on the default GPU.
+    pub fn new(desc: GpuFdtdSolverDescriptor<Fs>) -> Result<Self, Error> {
+        if desc.tline.stencil_width() > 1 {
+            return Err(Error::GpuError("the GPU only supports second order stencils".to_string()))
+        }
+        let snapshot = desc.tline.snapshot();
+        let parameters = [
+            snapshot.get("capacitance")?.clone(),
+            snapshot.get("inductance")?.clone(),
+            snapshot.get("resistance")?.clone(),
+            snapshot.get("conductance")?.clone(),
+        ];
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(
+            &wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                ..Default::default()
+            },
+        )).ok_or_else(|| Error::GpuError("no GPU adapter is available".to_string()))?;
+        // long chunks need the largest buffers the adapter allows
+        let limits = adapter.limits();
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("tline"),
+                required_features: wgpu::Features::empty(),
+                required_limits: wgpu::Limits {
+                    max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size,
+                    max_buffer_size: limits.max_buffer_size,
+                    ..Default::default()
+                },
+            },
+            None,
+        )).map_err(|err| Error::GpuError(err.to_string()))?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("gpu_solver"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu_solver.wgsl").into()),
+        });
+        let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = |read_only| wgpu::BufferBindingType::Storage { read_only };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("gpu_solver"),
+            entries: &[
+                entry(0, wgpu::BufferBindingType::Uniform),
+                entry(1, storage(true)),
+                entry(2, storage(true)),
+                entry(3, storage(true)),
+                entry(4, storage(false)),
+                entry(5, storage(false)),
+                entry(6, storage(false)),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("gpu_solver"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point,
+            })
+        };
+
+        Ok(Self {
+            update_voltages: pipeline("update_voltages"),
+            update_currents: pipeline("update_currents"),
+            advance: pipeline("advance"),
+            tline: desc.tline,
+            source: desc.source,
+            terminator: desc.terminator,
+            parameters,
+            device,
+            queue,
+            layout,
+        })
+    }
+
+    /// Gets the uniform parameters of the shader, holding the updates of the boundaries.
+    fn uniforms(&self, sim_params: &SimulationParameters) -> Vec<u8> {
+        let SimulationParameters { delta_z, delta_t } = *sim_params;
+        let d_ratio = delta_z / delta_t;
+        let (source, terminator) = (&self.source, &self.terminator);
+
+        let source_resistance = delta_z*source.resistance
+            + Float::sqrt(source.inductance / source.capacitance);
+        let load_conductance = delta_z*terminator.conductance
+            + Float::sqrt(terminator.capacitance / terminator.inductance);
+        let updates = [
+            Update::new(d_ratio*source.capacitance, delta_z*source.conductance/2.0),
+            Update::new(d_ratio*source.inductance, source_resistance/2.0),
+            Update::new(d_ratio*terminator.capacitance, load_conductance/2.0),
+            Update::new(d_ratio*terminator.inductance, delta_z*terminator.resistance/2.0),
+        ];
+
+        let mut uniforms = (self.tline.npoints() as u32).to_ne_bytes().to_vec();
+        uniforms.extend([0u8; 12]);
+        uniforms.extend(to_bytes(updates.iter().flat_map(|update| [update.decay, update.gain])));
+        uniforms
+    }
+
+    /// Gets the updates of the line's voltages and currents, packed for the shader.
+    fn coefficients(&self, sim_params: &SimulationParameters) -> (Vec<u8>, Vec<u8>) {
+        let SimulationParameters { delta_z, delta_t } = *sim_params;
+        let d_ratio = delta_z / delta_t;
+        let [ref cap, ref ind, ref res, ref cond] = self.parameters;
+
+        let volt_coeffs = cap.iter().zip(cond)
+            .map(|(&cap, &cond)| Update::new(d_ratio*cap, delta_z*cond/2.0))
+            .flat_map(|update| [update.decay, update.gain]);
+        let curr_coeffs = ind.iter().zip(res)
+            .map(|(&ind, &res)| Update::new(d_ratio*ind, delta_z*res/2.0))
+            .flat_map(|update| [update.decay, update.gain]);
+        (to_bytes(volt_coeffs), to_bytes(curr_coeffs))
+    }
+
+    /// Creates a buffer holding `contents`.
+    fn buffer(&self, label: &str, contents: &[u8], usage: wgpu::BufferUsages) -> wgpu::Buffer {
+        self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents,
+            usage,
+        })
+    }
+
+    /// Copies a buffer back from the GPU into `values`.
+    fn read(&self, buffer: &wgpu::Buffer, values: &mut [Float]) -> Result<(), Error> {
+        let size = buffer.size();
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("staging"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("read"),
+        });
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv()
+            .map_err(|err| Error::GpuError(err.to_string()))?
+            .map_err(|err| Error::GpuError(err.to_string()))?;
+
+        let data = slice.get_mapped_range();
+        for (value, bytes) in values.iter_mut().zip(data.chunks_exact(4)) {
+            *value = f32::from_ne_bytes(bytes.try_into().unwrap()) as Float;
+        }
+        drop(data);
+        staging.unmap();
+        Ok(())
+    }
+}
+
+impl<Fs: Fn(Float) -> Float> Solver for GpuFdtdSolver<Fs> {
+    fn compute(
+        &mut self,
+        desc: ComputeDescriptor,
+    ) -> Result<ComputeOutput, Error> {
+        let npoints = self.tline.npoints();
+        let (nvolts, ncurrs) = (npoints + 2, npoints + 1);
+        let delta_t = desc.sim_params.delta_t;
+        let mut voltages = ndarray::Array2::<Float>::zeros((desc.nsteps+1, nvolts));
+        voltages.row_mut(0).assign(&desc.state.voltages);
+        let mut currents = ndarray::Array2::<Float>::zeros((desc.nsteps+1, ncurrs));
+        currents.row_mut(0).assign(&desc.state.currents);
+
+        let uniforms = self.uniforms(&desc.sim_params);
+        let (volt_coeffs, curr_coeffs) = self.coefficients(&desc.sim_params);
+        let uniforms = self.buffer("params", &uniforms, wgpu::BufferUsages::UNIFORM);
+        let volt_coeffs = self.buffer("volt_coeffs", &volt_coeffs, wgpu::BufferUsages::STORAGE);
+        let curr_coeffs = self.buffer("curr_coeffs", &curr_coeffs, wgpu::BufferUsages::STORAGE);
+
+        // split the chunk into batches whose full waveforms fit in a single buffer
+        let limits = self.device.limits();
+        let max_bytes = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+        let max_rows = (max_bytes / (4*nvolts as u64)) as usize;
+        if max_rows < 2 {
+            return Err(Error::GpuError("the line does not fit in a GPU buffer".to_string()))
+        }
+        let batch_size = max_rows - 1;
+        let state_usage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC;
+
+        let mut first_step = 0;
+        while first_step < desc.nsteps {
+            let nsteps = batch_size.min(desc.nsteps - first_step);
+            let start_time = (first_step as Float)*delta_t + desc.state.time;
+
+            let source_volts = to_bytes((0..nsteps).map(|t_index| {
+                self.source.generate((t_index as Float)*delta_t + start_time)
+            }));
+            let mut batch_volts = voltages.row(first_step).to_vec();
+            batch_volts.resize((nsteps + 1)*nvolts, 0.0);
+            let mut batch_currs = currents.row(first_step).to_vec();
+            batch_currs.resize((nsteps + 1)*ncurrs, 0.0);
+
+            let source_volts = self.buffer(
+                "source_volts", &source_volts, wgpu::BufferUsages::STORAGE,
+            );
+            let batch_volts = self.buffer("voltages", &to_bytes(batch_volts), state_usage);
+            let batch_currs = self.buffer("currents", &to_bytes(batch_currs), state_usage);
+            let step = self.buffer("step", &0u32.to_ne_bytes(), wgpu::BufferUsages::STORAGE);
+            // the buffers in the order of their bindings
+            let buffers = [
+                &uniforms, &volt_coeffs, &curr_coeffs, &source_volts, &batch_volts, &batch_currs,
+                &step,
+            ];
+            let entries = buffers.iter()
+                .enumerate()
+                .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                    binding: binding as u32,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect::<Vec<_>>();
+            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("gpu_solver"),
+                layout: &self.layout,
+                entries: &entries,
+            });
+
+            // every dispatch sees the writes of the last, so each step is three dispatches
+            let mut submitted = 0;
+            while submitted < nsteps {
+                let submit_steps = STEPS_PER_SUBMIT.min(nsteps - submitted);
+                let mut encoder = self.device.create_command_encoder(
+                    &wgpu::CommandEncoderDescriptor { label: Some("steps") },
+                );
+                {
+                    let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                        label: Some("steps"),
+                        timestamp_writes: None,
+                    });
+                    pass.set_bind_group(0, &bind_group, &[]);
+                    for _ in 0..submit_steps {
+                        pass.set_pipeline(&self.update_voltages);
+                        pass.dispatch_workgroups(workgroups(nvolts), 1, 1);
+                        pass.set_pipeline(&self.update_currents);
+                        pass.dispatch_workgroups(workgroups(ncurrs), 1, 1);
+                        pass.set_pipeline(&self.advance);
+                        pass.dispatch_workgroups(1, 1, 1);
+                    }
+                }
+                self.queue.submit(Some(encoder.finish()));
+                self.device.poll(wgpu::Maintain::Wait);
+                submitted += submit_steps;
+
+                if let Some(ref bar) = desc.bar {
+                    bar.inc(submit_steps as u64)
+                }
+            }
+
+            let rows = ndarray::s![first_step..(first_step + nsteps + 1), ..];
+            let mut batch = voltages.slice(rows).to_owned();
+            self.read(&batch_volts, batch.as_slice_mut().unwrap())?;
+            voltages.slice_mut(rows).assign(&batch);
+            let mut batch = currents.slice(rows).to_owned();
+            self.read(&batch_currs, batch.as_slice_mut().unwrap())?;
+            currents.slice_mut(rows).assign(&batch);
+            // batches run on the device, so divergence is only found once each is read back
+            check_finite(
+                voltages.slice(rows),
+                currents.slice(rows),
+                (desc.state.time / delta_t).round() as usize + first_step,
+            )?;
+
+            first_step += nsteps;
+        }
+
+        Ok(ComputeOutput {
+            voltages,
+            currents,
+            channels: BTreeMap::new(),
+            channel_units: BTreeMap::new(),
+        })
+    }
+
+    fn npoints(&self) -> usize {
+        self.tline.npoints()
+    }
+
+    fn max_phase_velocity(&self) -> Option<Float> {
+        Some(self.tline.max_phase_velocity())
+    }
+
+    fn snapshot(&self) -> BTreeMap<String, ComponentSnapshot> {
+        BTreeMap::from([
+            ("tline".to_string(), self.tline.snapshot()),
+            ("source".to_string(), self.source.snapshot()),
+            ("terminator".to_string(), self.terminator.snapshot()),
+        ])
+    }
+}
+
+/// Converts values to the single precision bytes the shader reads.
+fn to_bytes(values: impl IntoIterator<Item = Float>) -> Vec<u8> {
+    values.into_iter()
+        .flat_map(|value| (value as f32).to_ne_bytes())
+        .collect()
+}
+
+/// Gets the number of workgroups needed to cover `len` points.
+fn workgroups(len: usize) -> u32 {
+    len.div_ceil(WORKGROUP_SIZE) as u32
+}
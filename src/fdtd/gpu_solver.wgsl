@@ -0,0 +1,76 @@
+// Leapfrog updates of a linear line, one time step per set of dispatches.
+//
+// Voltages and currents hold one row per time step of a batch, with the initial state in the
+// first row. Each update is `next = decay*last + gain*drive`, with the coefficients of each point
+// packed as `vec2(decay, gain)`.
+
+struct Params {
+    npoints: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+    source_volt: vec2<f32>,
+    source_curr: vec2<f32>,
+    end_volt: vec2<f32>,
+    end_curr: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> volt_coeffs: array<vec2<f32>>;
+@group(0) @binding(2) var<storage, read> curr_coeffs: array<vec2<f32>>;
+@group(0) @binding(3) var<storage, read> source_volts: array<f32>;
+@group(0) @binding(4) var<storage, read_write> voltages: array<f32>;
+@group(0) @binding(5) var<storage, read_write> currents: array<f32>;
+@group(0) @binding(6) var<storage, read_write> step_index: array<u32>;
+
+@compute @workgroup_size(64)
+fn update_voltages(@builtin(global_invocation_id) id: vec3<u32>) {
+    let n = params.npoints;
+    let k = id.x;
+    if (k > n + 1u) {
+        return;
+    }
+    let t = step_index[0];
+    let last_v = t*(n + 2u);
+    let next_v = last_v + n + 2u;
+    let last_i = t*(n + 1u);
+
+    if (k == 0u) {
+        let source_curr = params.source_curr.x*currents[last_i]
+            + params.source_curr.y*(source_volts[t] - voltages[last_v]);
+        voltages[next_v] = params.source_volt.x*voltages[last_v]
+            + params.source_volt.y*(source_curr - currents[last_i]);
+    } else if (k == n + 1u) {
+        voltages[next_v + k] = params.end_volt.x*voltages[last_v + k]
+            + params.end_volt.y*currents[last_i + n];
+    } else {
+        let c = volt_coeffs[k - 1u];
+        voltages[next_v + k] = c.x*voltages[last_v + k]
+            + c.y*(currents[last_i + k - 1u] - currents[last_i + k]);
+    }
+}
+
+@compute @workgroup_size(64)
+fn update_currents(@builtin(global_invocation_id) id: vec3<u32>) {
+    let n = params.npoints;
+    let m = id.x;
+    if (m > n) {
+        return;
+    }
+    let t = step_index[0];
+    let next_v = (t + 1u)*(n + 2u);
+    let last_i = t*(n + 1u);
+    let next_i = last_i + n + 1u;
+
+    var c = params.end_curr;
+    if (m < n) {
+        c = curr_coeffs[m];
+    }
+    currents[next_i + m] = c.x*currents[last_i + m]
+        + c.y*(voltages[next_v + m] - voltages[next_v + m + 1u]);
+}
+
+@compute @workgroup_size(1)
+fn advance() {
+    step_index[0] = step_index[0] + 1u;
+}
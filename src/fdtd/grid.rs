@@ -0,0 +1,129 @@
+//! Nonuniform grids, with cells of varying length along the line.
+
+use crate::{Error, Float};
+
+/// The number of samples per cell used to integrate point densities.
+const SAMPLES_PER_CELL: usize = 16;
+
+/// The cells of a line with lengths that vary along it.
+///
+/// Solvers step every line on a uniform grid with the nominal cell length `length / npoints`, so
+/// a line on a nonuniform grid is made by scaling the parameters of each cell by its length
+/// relative to the nominal one. Lines created with `new_on_grid` do this, and the sources and
+/// terminators at the ends still use the nominal cell length. Positions along the line are given
+/// by the grid rather than `SimulationParameters`.
+#[derive(Clone, Debug)]
+pub struct NonuniformGrid {
+    edges: Vec<Float>,
+}
+
+impl NonuniformGrid {
+    /// Creates a grid of `npoints` cells over `length`, with the number of cells per unit length
+    /// proportional to `density_fn(z)`.
+    ///
+    /// The density should be positive everywhere, and is higher where finer cells are needed.
+    pub fn new<F>(length: Float, npoints: usize, density_fn: F) -> Result<Self, Error>
+    where
+        F: Fn(Float) -> Float,
+    {
+        if npoints == 0 {
+            return Err(Error::BadGrid("a grid needs a cell".to_string()))
+        }
+        let nsamples = SAMPLES_PER_CELL*npoints;
+        let step = length / (nsamples as Float);
+        let mut cumulative = vec![0.0];
+        for k in 0..nsamples {
+            let density = density_fn((k as Float + 0.5)*step).max(Float::EPSILON);
+            cumulative.push(cumulative[k] + density*step);
+        }
+        let total = cumulative[nsamples];
+
+        // place the edges where the integrated density crosses each fraction of its total
+        let mut edges = vec![0.0];
+        let mut sample = 0;
+        for k in 1..npoints {
+            let target = total*(k as Float) / (npoints as Float);
+            while sample + 1 < nsamples && cumulative[sample + 1] < target {
+                sample += 1;
+            }
+            let fraction = (target - cumulative[sample])
+                / (cumulative[sample + 1] - cumulative[sample]);
+            edges.push((sample as Float + fraction)*step);
+        }
+        edges.push(length);
+
+        Ok(Self { edges })
+    }
+
+    /// Creates a grid from the positions of the edges of its cells, starting at zero.
+    pub fn from_edges(edges: Vec<Float>) -> Result<Self, Error> {
+        if edges.len() < 2 || edges[0] != 0.0 {
+            return Err(Error::BadGrid("edges must start at zero and bound a cell".to_string()))
+        }
+        if edges.windows(2).any(|pair| pair[1] <= pair[0]) {
+            return Err(Error::BadGrid("edges must be strictly increasing".to_string()))
+        }
+
+        Ok(Self { edges })
+    }
+
+    /// The number of cells.
+    pub fn npoints(&self) -> usize {
+        self.edges.len() - 1
+    }
+
+    /// The length of the line.
+    pub fn length(&self) -> Float {
+        self.edges[self.npoints()]
+    }
+
+    /// The cell length of the uniform grid the line is stepped on, to be used as `delta_z`.
+    pub fn nominal_delta_z(&self) -> Float {
+        self.length() / (self.npoints() as Float)
+    }
+
+    /// The length of cell `index`.
+    pub fn cell_length(&self, index: usize) -> Float {
+        self.edges[index + 1] - self.edges[index]
+    }
+
+    /// The position of the center of cell `index`.
+    pub fn cell_center(&self, index: usize) -> Float {
+        (self.edges[index] + self.edges[index + 1]) / 2.0
+    }
+
+    /// The length of cell `index` relative to the nominal cell length, which scales its shunt
+    /// parameters.
+    pub fn shunt_scale(&self, index: usize) -> Float {
+        self.cell_length(index) / self.nominal_delta_z()
+    }
+
+    /// The distance between the centers of cell `index` and the one before it, relative to the
+    /// nominal cell length, which scales the series parameters of the cell.
+    ///
+    /// The first cell is treated as if mirrored past the start of the line.
+    pub fn series_scale(&self, index: usize) -> Float {
+        let before = if index == 0 { self.cell_length(0) } else { self.cell_length(index - 1) };
+        (before + self.cell_length(index)) / (2.0*self.nominal_delta_z())
+    }
+
+    /// Gets the position along the line of a voltage, by its index into the state's voltages.
+    ///
+    /// Voltages sit at the centers of the cells, and the source and end nodes are half the length
+    /// of the cells next to them past the ends of the line.
+    pub fn voltage_position(&self, index: usize) -> Float {
+        let npoints = self.npoints();
+        match index {
+            0 => -self.cell_length(0) / 2.0,
+            _ if index > npoints => self.length() + self.cell_length(npoints - 1) / 2.0,
+            _ => self.cell_center(index - 1),
+        }
+    }
+
+    /// Gets the position along the line of a current, by its index into the state's currents.
+    ///
+    /// Currents sit on the boundaries between cells, so the first is at the start of the line.
+    pub fn current_position(&self, index: usize) -> Float {
+        self.edges[index.min(self.npoints())]
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    diagnostics, ComponentSnapshot, ComputeDescriptor, ComputeOutput, Error, Float,
+    SimulationParameters, Solver,
+};
+use crate::fdtd::{check_finite, TransmissionLine};
+use crate::fdtd::components::LinearLine;
+
+/// Describes the composition of a `LaxWendroffSolver`.
+pub struct LaxWendroffSolverDescriptor {
+    pub tline: LinearLine,
+    /// The open circuit voltage of the source at the start of the line.
+    pub source_fn: Box<dyn Fn(Float) -> Float>,
+    /// The internal resistance of the source.
+    pub source_resistance: Float,
+    /// The resistance loading the end of the line, which may be infinite for an open circuit.
+    pub load_resistance: Float,
+}
+
+/// Does single threaded computations of a linear line on the CPU, with the two step Lax-Wendroff
+/// scheme.
+///
+/// Voltages and currents are both kept at the voltage nodes and at the same times, unlike the
+/// staggered leapfrog of an `FdtdSolver`, so the two schemes have different numerical dispersion
+/// and dissipation. Features that show up with both are physical rather than numerical. The
+/// currents of each state are averaged from the nodes on either side, and the ends are set by
+/// the waves entering and leaving them, as through resistive terminations.
+///
+/// The node currents are kept by the solver between chunks, and are part of the `fields` snapshot
+/// of the solver, so checkpoints hold them, and a solver rebuilt for a snapshot should restore
+/// them with `with_fields`. Otherwise they are averaged again from the state, and the run drifts
+/// from one that wasn't interrupted.
+pub struct LaxWendroffSolver {
+    tline: LinearLine,
+    source_fn: Box<dyn Fn(Float) -> Float>,
+    source_resistance: Float,
+    load_resistance: Float,
+    /// The capacitance, inductance, resistance, and conductance per unit length at each node,
+    /// with the source and end nodes taking those of the cells next to them.
+    parameters: [Vec<Float>; 4],
+    /// The currents at the nodes after the last chunk, with the time and state currents they
+    /// were reported as, so chunks continue without averaging them again.
+    node_currents: Option<(Float, ndarray::Array1<Float>, Vec<Float>)>,
+}
+
+impl LaxWendroffSolver {
+    /// Creates a new `LaxWendroffSolver`.
+    pub fn new(desc: LaxWendroffSolverDescriptor) -> Self {
+        let npoints = desc.tline.npoints();
+        let snapshot = desc.tline.snapshot();
+        let parameters = ["capacitance", "inductance", "resistance", "conductance"].map(|name| {
+            let values = snapshot.get(name).expect("linear lines snapshot every parameter");
+            (0..(npoints + 2))
+                .map(|node| values[node.clamp(1, npoints) - 1])
+                .collect()
+        });
+
+        Self {
+            tline: desc.tline,
+            source_fn: desc.source_fn,
+            source_resistance: desc.source_resistance,
+            load_resistance: desc.load_resistance,
+            parameters,
+            node_currents: None,
+        }
+    }
+
+    /// Restores the node currents from the `fields` snapshot of a solver.
+    pub fn with_fields(mut self, snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("LaxWendroffFields")?;
+        // solvers that haven't run yet have no node currents
+        let Some(&time) = snapshot.get("time")?.first() else {
+            self.node_currents = None;
+            return Ok(self)
+        };
+
+        let npoints = self.tline.npoints();
+        let values = |name, size| {
+            let values = snapshot.get(name)?;
+            if values.len() != size {
+                let message = format!("LaxWendroffFields {} has the wrong length", name);
+                return Err(Error::BadSnapshot(message))
+            }
+            Ok(values.clone())
+        };
+        let reported = ndarray::Array1::from(values("currents", npoints + 1)?);
+        self.node_currents = Some((time, reported, values("node_currents", npoints + 2)?));
+        Ok(self)
+    }
+
+    /// Steps the voltages and currents of every node from time `t` by a time step.
+    fn step(
+        &self,
+        volts: &mut [Float],
+        currs: &mut [Float],
+        t: Float,
+        sim_params: &SimulationParameters,
+    ) {
+        let SimulationParameters { delta_z, delta_t } = *sim_params;
+        let [ref cap, ref ind, ref res, ref cond] = self.parameters;
+        let nodes = volts.len();
+        let ratio = delta_t / delta_z;
+
+        // predict the voltages and currents halfway between nodes, half a step ahead
+        let (mut half_volts, mut half_currs) = (vec![0.0; nodes - 1], vec![0.0; nodes - 1]);
+        for j in 0..(nodes - 1) {
+            let (c, l) = ((cap[j] + cap[j+1]) / 2.0, (ind[j] + ind[j+1]) / 2.0);
+            let (g, r) = ((cond[j] + cond[j+1]) / 2.0, (res[j] + res[j+1]) / 2.0);
+            let (v, i) = ((volts[j] + volts[j+1]) / 2.0, (currs[j] + currs[j+1]) / 2.0);
+            half_volts[j] = v - ratio/2.0*(currs[j+1] - currs[j]) / c - delta_t/2.0*g/c*v;
+            half_currs[j] = i - ratio/2.0*(volts[j+1] - volts[j]) / l - delta_t/2.0*r/l*i;
+        }
+
+        // the waves leaving each end arrive from a fraction of a cell inside it, where they are
+        // interpolated before the interior is updated
+        let last = nodes - 1;
+        let impedance = |node: usize| Float::sqrt(ind[node] / cap[node]);
+        let courant = |node: usize| (ratio / Float::sqrt(ind[node]*cap[node])).min(1.0);
+        let backward = |node: usize| volts[node] - impedance(0)*currs[node];
+        let forward = |node: usize| volts[node] + impedance(last)*currs[node];
+        let start_wave = (1.0 - courant(0))*backward(0) + courant(0)*backward(1);
+        let end_wave = (1.0 - courant(last))*forward(last) + courant(last)*forward(last - 1);
+
+        for j in 1..last {
+            let (v, i) = (volts[j], currs[j]);
+            let half_v = (half_volts[j-1] + half_volts[j]) / 2.0;
+            let half_i = (half_currs[j-1] + half_currs[j]) / 2.0;
+            volts[j] = v - ratio*(half_currs[j] - half_currs[j-1]) / cap[j]
+                - delta_t*cond[j]/cap[j]*half_v;
+            currs[j] = i - ratio*(half_volts[j] - half_volts[j-1]) / ind[j]
+                - delta_t*res[j]/ind[j]*half_i;
+        }
+
+        // the source satisfies `V = Vs - Rs I` and the load `V = Rl I`, along with the waves
+        let source_volt = (self.source_fn)(t + delta_t);
+        currs[0] = (source_volt - start_wave) / (self.source_resistance + impedance(0));
+        volts[0] = start_wave + impedance(0)*currs[0];
+        if self.load_resistance.is_finite() {
+            currs[last] = end_wave / (self.load_resistance + impedance(last));
+            volts[last] = self.load_resistance*currs[last];
+        } else {
+            currs[last] = 0.0;
+            volts[last] = end_wave;
+        }
+    }
+}
+
+impl Solver for LaxWendroffSolver {
+    fn compute(
+        &mut self,
+        desc: ComputeDescriptor,
+    ) -> Result<ComputeOutput, Error> {
+        let npoints = self.tline.npoints();
+        let delta_t = desc.sim_params.delta_t;
+        let stability_margin = desc.sim_params.delta_z
+            / (self.tline.max_phase_velocity() * delta_t);
+        if stability_margin < 1.1 {
+            diagnostics::set_time(desc.state.time);
+            let message = "time step is within 10% of the stability limit";
+            diagnostics::warn("LaxWendroffSolver", message);
+        }
+
+        let mut volts = desc.state.voltages.to_vec();
+        // node currents are averaged from the state, unless it's where the last chunk left off
+        let mut currs = match self.node_currents.take() {
+            Some((time, ref reported, node_currents))
+                if time == desc.state.time && *reported == desc.state.currents => node_currents,
+            _ => {
+                let currents = &desc.state.currents;
+                (0..(npoints + 2))
+                    .map(|node| match node {
+                        0 => currents[0],
+                        _ if node > npoints => currents[npoints],
+                        _ => (currents[node-1] + currents[node]) / 2.0,
+                    })
+                    .collect()
+            },
+        };
+
+        let mut voltages = ndarray::Array2::<Float>::zeros((desc.nsteps + 1, npoints + 2));
+        let mut currents = ndarray::Array2::<Float>::zeros((desc.nsteps + 1, npoints + 1));
+        voltages.row_mut(0).assign(&desc.state.voltages);
+        currents.row_mut(0).assign(&desc.state.currents);
+        let first_step = (desc.state.time / delta_t).round() as usize;
+
+        for t_index in 0..desc.nsteps {
+            let t = (t_index as Float)*delta_t + desc.state.time;
+            diagnostics::set_time(t);
+            self.step(&mut volts, &mut currs, t, &desc.sim_params);
+
+            voltages.row_mut(t_index + 1).assign(&ndarray::ArrayView1::from(&volts));
+            for (current, pair) in currents.row_mut(t_index + 1).iter_mut().zip(currs.windows(2)) {
+                *current = (pair[0] + pair[1]) / 2.0;
+            }
+            let rows = ndarray::s![(t_index+1)..(t_index+2), ..];
+            check_finite(voltages.slice(rows), currents.slice(rows), first_step + t_index + 1)?;
+
+            if let Some(ref bar) = desc.bar {
+                bar.inc(1)
+            }
+        }
+
+        let time = desc.state.time + (desc.nsteps as Float)*delta_t;
+        self.node_currents = Some((time, currents.row(desc.nsteps).to_owned(), currs));
+
+        Ok(ComputeOutput {
+            voltages,
+            currents,
+            channels: BTreeMap::new(),
+            channel_units: BTreeMap::new(),
+        })
+    }
+
+    fn npoints(&self) -> usize {
+        self.tline.npoints()
+    }
+
+    fn max_phase_velocity(&self) -> Option<Float> {
+        Some(self.tline.max_phase_velocity())
+    }
+
+    fn snapshot(&self) -> BTreeMap<String, ComponentSnapshot> {
+        BTreeMap::from([
+            ("tline".to_string(), self.tline.snapshot()),
+            (
+                "terminations".to_string(),
+                ComponentSnapshot::new("LaxWendroffTerminations")
+                    .with("source_resistance", vec![self.source_resistance])
+                    .with("load_resistance", vec![self.load_resistance]),
+            ),
+            (
+                "fields".to_string(),
+                match self.node_currents {
+                    Some((time, ref reported, ref node_currents)) => {
+                        ComponentSnapshot::new("LaxWendroffFields")
+                            .with("time", vec![time])
+                            .with("currents", reported.to_vec())
+                            .with("node_currents", node_currents.clone())
+                    },
+                    None => ComponentSnapshot::new("LaxWendroffFields")
+                        .with("time", vec![])
+                        .with("currents", vec![])
+                        .with("node_currents", vec![]),
+                },
+            ),
+        ])
+    }
+}
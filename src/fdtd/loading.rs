@@ -0,0 +1,73 @@
+//! Periodic loading of line parameters, for dispersion engineering.
+
+use crate::Float;
+use crate::fdtd::Perturbation;
+
+/// The shape of each period of a `PeriodicLoading`.
+#[derive(Copy, Clone, Debug)]
+pub enum LoadingProfile {
+    /// A sinusoidal modulation, as from a periodically varying line width.
+    Sinusoidal,
+    /// A loaded section at the start of each period, as from a loading capacitor or a wider
+    /// segment, with the rest of the period unperturbed.
+    Loaded {
+        /// The length of the loaded section.
+        width: Float,
+    },
+}
+
+/// Describes a `PeriodicLoading`.
+pub struct PeriodicLoadingDescriptor {
+    /// The length of each period.
+    pub period: Float,
+    /// The relative deviation of the perturbed parameter, at the peak of a sinusoid or within a
+    /// loaded section.
+    pub depth: Float,
+    /// The shape of each period.
+    pub profile: LoadingProfile,
+}
+
+/// A periodic relative deviation along a line, which opens stopbands at the Bragg frequencies of
+/// the period.
+///
+/// Stopbands just above a pump or its harmonics shift the phase velocities nearby, which is how
+/// kinetic inductance parametric amplifiers are phase matched and keep the pump from generating
+/// its third harmonic. Loadings with different periods can be stacked, and the period should span
+/// many cells, since line parameters are only sampled at the centers of cells.
+#[derive(Clone, Debug)]
+pub struct PeriodicLoading {
+    period: Float,
+    depth: Float,
+    profile: LoadingProfile,
+}
+
+impl PeriodicLoading {
+    /// Creates a new `PeriodicLoading` instance.
+    pub fn new(desc: PeriodicLoadingDescriptor) -> Self {
+        Self {
+            period: desc.period,
+            depth: desc.depth,
+            profile: desc.profile,
+        }
+    }
+
+    /// Gets the center frequency of stopband `order`, counting from one, for a line with an average
+    /// phase velocity of `phase_velocity`.
+    ///
+    /// A sinusoidal loading only opens the first stopband, to first order in its depth.
+    pub fn stopband_frequency(&self, phase_velocity: Float, order: usize) -> Float {
+        (order as Float) * phase_velocity / (2.0*self.period)
+    }
+}
+
+impl Perturbation for PeriodicLoading {
+    fn deviation(&self, z: Float) -> Float {
+        let phase = (z / self.period).rem_euclid(1.0);
+        match self.profile {
+            LoadingProfile::Sinusoidal => self.depth * Float::cos(2.0*crate::consts::PI * phase),
+            LoadingProfile::Loaded { width } => {
+                if phase*self.period < width { self.depth } else { 0.0 }
+            },
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    ComponentSnapshot, ComputeDescriptor, ComputeOutput, Error, Float, SimulationState, Solver,
+};
+use crate::fdtd::{FdtdSolver, TransmissionLine};
+
+/// The name of the channel holding the offset of the window, in cells, at each time step.
+const OFFSET_CHANNEL: &str = "window_offset";
+
+/// Describes the composition of a `MovingWindowSolver`.
+pub struct MovingWindowDescriptor<L: TransmissionLine> {
+    /// The solver of the window, which should end in a matched terminator.
+    pub solver: FdtdSolver<L>,
+    /// The speed the window moves along the line, usually the group velocity of the pulse.
+    pub velocity: Float,
+    /// The time the window starts moving, once the source has launched the pulse.
+    pub start_time: Float,
+    /// The number of cells the window moves by at once, which must be a period of the line.
+    pub shift_cells: usize,
+}
+
+/// Follows a pulse along a line much longer than the pulse itself, with a window of cells that
+/// moves along with it.
+///
+/// Memory and compute scale with the window rather than the line it moves along. The window is
+/// moved by whole periods of its line, so the line is the same after every move and is never
+/// rebuilt. This suits uniform lines and periodically loaded lines, with `shift_cells` set to a
+/// period. Cells entering the front of the window are at rest, so the pulse should stay well
+/// inside it, and whatever is left behind is dropped.
+///
+/// The internal state of each cell, such as the polarizations of a `DispersiveLine` or the
+/// elements of a `LumpedLine`, moves along with it through `point_state`, so any line keeping its
+/// state there is supported. State that lines update between time steps instead, such as the
+/// temperatures of a `ThermalKiLine`, stays where it is, so those lines are not.
+///
+/// Each chunk also has a `window_offset` channel with the number of cells the window has moved
+/// at each time step, so position `z` in the window is at `z + offset Δz` along the line.
+///
+/// The state only holds the window, so a solver continuing from a snapshot should be rebuilt with
+/// `from_snapshot`, which restores the offset. A state from before the last run starts the window
+/// over at the start of the line.
+pub struct MovingWindowSolver<L: TransmissionLine> {
+    solver: FdtdSolver<L>,
+    velocity: Float,
+    start_time: Float,
+    shift_cells: usize,
+    offset: usize,
+    /// The time at the end of the last run, if there was one.
+    time: Option<Float>,
+}
+
+impl<L: TransmissionLine> MovingWindowSolver<L> {
+    /// Creates a new `MovingWindowSolver`, with the window at the start of the line.
+    pub fn new(desc: MovingWindowDescriptor<L>) -> Self {
+        Self {
+            solver: desc.solver,
+            velocity: desc.velocity,
+            start_time: desc.start_time,
+            shift_cells: desc.shift_cells.max(1),
+            offset: 0,
+            time: None,
+        }
+    }
+
+    /// Rebuilds a `MovingWindowSolver` from the `window` snapshot of a solver and the solver of
+    /// its window, with the offset it had moved to.
+    pub fn from_snapshot(
+        snapshot: &ComponentSnapshot,
+        solver: FdtdSolver<L>,
+    ) -> Result<Self, Error> {
+        snapshot.expect_kind("MovingWindow")?;
+        Ok(Self {
+            solver,
+            velocity: snapshot.get_scalar("velocity")?,
+            start_time: snapshot.get_scalar("start_time")?,
+            shift_cells: (snapshot.get_scalar("shift_cells")? as usize).max(1),
+            offset: snapshot.get_scalar("offset")? as usize,
+            time: snapshot.get("time").ok().and_then(|time| time.first().copied()),
+        })
+    }
+
+    /// The number of cells the window has moved.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The offset the window should have at `time`, by whole shifts.
+    fn target_offset(&self, time: Float, delta_z: Float) -> usize {
+        if time <= self.start_time || self.velocity <= 0.0 {
+            return 0
+        }
+        let shift_length = (self.shift_cells as Float)*delta_z;
+        let shifts = ((time - self.start_time)*self.velocity / shift_length).floor() as usize;
+        shifts*self.shift_cells
+    }
+
+    /// Moves `state` and the state of each cell of the line back by `cells` cells, filling the
+    /// front of the window with a line at rest.
+    fn shift(&self, state: &mut SimulationState, cells: usize) {
+        let SimulationState { ref mut voltages, ref mut currents, .. } = *state;
+        // the source node moves along with the cells, while the end node is cleared
+        let nvolts = voltages.len() - 1;
+        for k in 0..nvolts {
+            voltages[k] = if k + cells < nvolts { voltages[k + cells] } else { 0.0 };
+        }
+        voltages[nvolts] = 0.0;
+        let ncurrs = currents.len();
+        for k in 0..ncurrs {
+            currents[k] = if k + cells + 1 < ncurrs { currents[k + cells] } else { 0.0 };
+        }
+
+        let tline = self.solver.tline();
+        let npoints = tline.npoints();
+        for index in 0..npoints {
+            let moved = if index + cells < npoints {
+                tline.point_state(index + cells)
+            } else {
+                vec![0.0; tline.point_state(index).len()]
+            };
+            tline.restore_point_state(index, &moved);
+        }
+    }
+}
+
+impl<L: TransmissionLine> Solver for MovingWindowSolver<L> {
+    fn compute(
+        &mut self,
+        desc: ComputeDescriptor,
+    ) -> Result<ComputeOutput, Error> {
+        let delta_t = desc.sim_params.delta_t;
+        let delta_z = desc.sim_params.delta_z;
+        let nrows = desc.nsteps + 1;
+        let mut voltages = ndarray::Array2::<Float>::zeros((nrows, desc.state.voltages.len()));
+        let mut currents = ndarray::Array2::<Float>::zeros((nrows, desc.state.currents.len()));
+        voltages.row_mut(0).assign(&desc.state.voltages);
+        currents.row_mut(0).assign(&desc.state.currents);
+        let mut channels = BTreeMap::<String, ndarray::Array2<Float>>::new();
+        let mut channel_units = BTreeMap::new();
+        if self.time.is_some_and(|time| desc.state.time < time - delta_t/2.0) {
+            self.offset = 0;
+        }
+        let mut offsets = ndarray::Array2::<Float>::zeros((nrows, 1));
+        offsets[[0, 0]] = self.offset as Float;
+
+        // run the inner solver up to each move of the window, then move its state
+        let mut state = desc.state.clone();
+        let mut done = 0;
+        while done < desc.nsteps {
+            state.time = desc.state.time + (done as Float)*delta_t;
+            let target = self.target_offset(state.time, delta_z);
+            if target > self.offset {
+                self.shift(&mut state, target - self.offset);
+                self.offset = target;
+            }
+
+            let next_time = if self.velocity > 0.0 {
+                self.start_time
+                    + ((self.offset + self.shift_cells) as Float)*delta_z / self.velocity
+            } else {
+                Float::INFINITY
+            };
+            let nsteps = (((next_time - state.time) / delta_t).ceil() as usize)
+                .clamp(1, desc.nsteps - done);
+            let output = self.solver.compute(ComputeDescriptor {
+                state: &state,
+                sim_params: desc.sim_params,
+                nsteps,
+                bar: desc.bar,
+            })?;
+
+            let rows = ndarray::s![(done + 1)..(done + nsteps + 1), ..];
+            let computed = ndarray::s![1.., ..];
+            voltages.slice_mut(rows).assign(&output.voltages.slice(computed));
+            currents.slice_mut(rows).assign(&output.currents.slice(computed));
+            offsets.slice_mut(rows).fill(self.offset as Float);
+            for (name, channel) in &output.channels {
+                channels.entry(name.clone())
+                    .or_insert_with(|| ndarray::Array2::zeros((nrows, channel.ncols())))
+                    .slice_mut(rows)
+                    .assign(&channel.slice(computed));
+            }
+            channel_units.extend(output.channel_units);
+
+            state.voltages.assign(&output.voltages.row(nsteps));
+            state.currents.assign(&output.currents.row(nsteps));
+            done += nsteps;
+        }
+        channels.insert(OFFSET_CHANNEL.to_string(), offsets);
+        channel_units.insert(OFFSET_CHANNEL.to_string(), "");
+        self.time = Some(desc.state.time + (desc.nsteps as Float)*delta_t);
+
+        Ok(ComputeOutput {
+            voltages,
+            currents,
+            channels,
+            channel_units,
+        })
+    }
+
+    fn npoints(&self) -> usize {
+        self.solver.npoints()
+    }
+
+    fn max_phase_velocity(&self) -> Option<Float> {
+        self.solver.max_phase_velocity()
+    }
+
+    fn snapshot(&self) -> BTreeMap<String, ComponentSnapshot> {
+        let mut snapshot = self.solver.snapshot();
+        snapshot.insert(
+            "window".to_string(),
+            ComponentSnapshot::new("MovingWindow")
+                .with("velocity", vec![self.velocity])
+                .with("start_time", vec![self.start_time])
+                .with("shift_cells", vec![self.shift_cells as Float])
+                .with("offset", vec![self.offset as Float])
+                .with("time", self.time.into_iter().collect()),
+        );
+        snapshot
+    }
+}
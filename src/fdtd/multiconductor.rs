@@ -0,0 +1,405 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    diagnostics, ComponentSnapshot, ComputeDescriptor, ComputeOutput, Error, Float, Solver,
+};
+use crate::fdtd::check_finite;
+
+/// Describes a `MulticonductorLine`.
+///
+/// The parameter functions take the position and give matrices with a row and column for each
+/// conductor.
+pub struct MulticonductorLineDescriptor<
+    Fc: Fn(Float) -> ndarray::Array2<Float>, Fl: Fn(Float) -> ndarray::Array2<Float>,
+>{
+    pub length: Float,
+    pub npoints: usize,
+    /// The number of conductors, besides the ground.
+    pub conductors: usize,
+    /// The Maxwell capacitance matrix per unit length, with the total capacitance of each
+    /// conductor on the diagonal and the negated mutual capacitances off it.
+    pub capacitance_fn: Fc,
+    /// The inductance matrix per unit length, with the mutual inductances off the diagonal.
+    pub inductance_fn: Fl,
+}
+
+/// A lossless line of several coupled conductors over a ground.
+pub struct MulticonductorLine {
+    cap: Vec<ndarray::Array2<Float>>,
+    ind: Vec<ndarray::Array2<Float>>,
+    conductors: usize,
+    npoints: usize,
+    length: Float,
+}
+
+impl MulticonductorLine {
+    pub fn new<
+        Fc: Fn(Float) -> ndarray::Array2<Float>, Fl: Fn(Float) -> ndarray::Array2<Float>,
+    >(
+        desc: MulticonductorLineDescriptor<Fc, Fl>,
+    ) -> Result<Self, Error> {
+        let delta_z = desc.length / (desc.npoints as Float);
+        let shape = (desc.conductors, desc.conductors);
+        let sample = |parameter_fn: &dyn Fn(Float) -> ndarray::Array2<Float>, name: &str| {
+            (0..desc.npoints)
+                .map(|n| {
+                    let matrix = parameter_fn((n as Float + 0.5) * delta_z);
+                    if matrix.dim() != shape {
+                        return Err(Error::BadInit {
+                            array_name: name.to_string(),
+                            input_length: matrix.len(),
+                            expected_length: desc.conductors*desc.conductors,
+                        })
+                    }
+                    Ok(matrix)
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(Self {
+            cap: sample(&desc.capacitance_fn, "Capacitance matrix")?,
+            ind: sample(&desc.inductance_fn, "Inductance matrix")?,
+            conductors: desc.conductors,
+            npoints: desc.npoints,
+            length: desc.length,
+        })
+    }
+
+    /// The number of conductors, besides the ground.
+    pub fn conductors(&self) -> usize {
+        self.conductors
+    }
+
+    pub fn npoints(&self) -> usize {
+        self.npoints
+    }
+
+    pub fn length(&self) -> Float {
+        self.length
+    }
+
+    /// The fastest velocity of any mode anywhere on the line.
+    pub fn max_phase_velocity(&self) -> Float {
+        self.cap.iter().zip(&self.ind)
+            .flat_map(|(cap, ind)| {
+                // the squared mode velocities are the eigenvalues of `(L C)⁻¹`, which are all
+                // positive, so power iteration finds the largest from any start that isn't missing
+                // its mode, as one of the unit vectors can't be
+                let inverse = invert(&ind.dot(cap));
+                (0..self.conductors).map(move |start| {
+                    let mut vector = ndarray::Array1::<Float>::zeros(inverse.nrows());
+                    vector[start] = 1.0;
+                    let mut eigenvalue = 0.0;
+                    for _ in 0..POWER_ITERATIONS {
+                        let next = inverse.dot(&vector);
+                        eigenvalue = next.dot(&vector) / vector.dot(&vector);
+                        let norm = next.dot(&next).sqrt();
+                        vector = next / norm;
+                    }
+                    eigenvalue.sqrt()
+                })
+            })
+            .fold(0.0, |accum, item| if accum >= item { accum } else { item })
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        let flatten = |matrices: &[ndarray::Array2<Float>]| {
+            matrices.iter().flat_map(|matrix| matrix.iter().copied()).collect()
+        };
+        ComponentSnapshot::new("MulticonductorLine")
+            .with("length", vec![self.length])
+            .with("conductors", vec![self.conductors as Float])
+            .with("capacitance", flatten(&self.cap))
+            .with("inductance", flatten(&self.ind))
+    }
+}
+
+/// The number of power iterations used to find the fastest mode of each cell.
+const POWER_ITERATIONS: usize = 100;
+
+/// Describes the composition of a `MulticonductorSolver`.
+pub struct MulticonductorSolverDescriptor {
+    pub tline: MulticonductorLine,
+    /// The open circuit voltage of the source driving each conductor at the start of the line.
+    pub source_fns: Vec<Box<dyn Fn(Float) -> Float>>,
+    /// The resistance between each conductor and its source, which must be positive.
+    pub source_resistances: Vec<Float>,
+    /// The resistance loading each conductor at the end of the line, which must be positive and
+    /// may be infinite for an open circuit.
+    pub load_resistances: Vec<Float>,
+}
+
+/// Does single threaded computations of coupled conductors on the CPU, for crosstalk and coupled
+/// mode problems.
+///
+/// Each conductor has the voltages and currents of a single conductor line, updated by the same
+/// leapfrog scheme with the matrices of each cell in place of its parameters. The simulation state
+/// holds the first conductor, and the rest are kept by the solver between chunks and output as the
+/// `conductor_<k>_voltages` and `conductor_<k>_currents` channels, which are saved in the
+/// `channels` group. Every conductor is part of the `fields` snapshot of the solver, so
+/// checkpoints hold them, and a solver rebuilt for a snapshot should restore them with
+/// `with_fields`.
+///
+/// Each end node of each conductor is a resistor to its source or to ground, in parallel with the
+/// capacitance of a cell.
+pub struct MulticonductorSolver {
+    tline: MulticonductorLine,
+    source_fns: Vec<Box<dyn Fn(Float) -> Float>>,
+    source_resistances: Vec<Float>,
+    load_resistances: Vec<Float>,
+    /// The inverse capacitance and inductance matrices of each cell.
+    inverse_cap: Vec<ndarray::Array2<Float>>,
+    inverse_ind: Vec<ndarray::Array2<Float>>,
+    /// The fastest velocity of any mode anywhere on the line.
+    max_phase_velocity: Float,
+    /// The voltages and currents of every conductor, by conductor and then point.
+    fields: (ndarray::Array2<Float>, ndarray::Array2<Float>),
+    /// The time of the fields, unless every conductor is still at rest from the start.
+    time: Option<Float>,
+}
+
+impl MulticonductorSolver {
+    /// Creates a new `MulticonductorSolver`, with every conductor at rest.
+    pub fn new(desc: MulticonductorSolverDescriptor) -> Result<Self, Error> {
+        let conductors = desc.tline.conductors;
+        let npoints = desc.tline.npoints;
+        let lengths = [
+            ("Source functions", desc.source_fns.len()),
+            ("Source resistances", desc.source_resistances.len()),
+            ("Load resistances", desc.load_resistances.len()),
+        ];
+        for (name, length) in lengths {
+            if length != conductors {
+                return Err(Error::BadInit {
+                    array_name: name.to_string(),
+                    input_length: length,
+                    expected_length: conductors,
+                })
+            }
+        }
+        // the end nodes conduct to their sources and to ground through the resistances
+        let resistances = [
+            ("source", &desc.source_resistances),
+            ("load", &desc.load_resistances),
+        ];
+        for (name, resistances) in resistances {
+            if resistances.iter().any(|&resistance| resistance <= 0.0) {
+                let message = format!("MulticonductorSolver needs positive {} resistances", name);
+                return Err(Error::BadParameter(message))
+            }
+        }
+
+        Ok(Self {
+            inverse_cap: desc.tline.cap.iter().map(invert).collect(),
+            inverse_ind: desc.tline.ind.iter().map(invert).collect(),
+            max_phase_velocity: desc.tline.max_phase_velocity(),
+            fields: (
+                ndarray::Array2::zeros((conductors, npoints + 2)),
+                ndarray::Array2::zeros((conductors, npoints + 1)),
+            ),
+            time: None,
+            tline: desc.tline,
+            source_fns: desc.source_fns,
+            source_resistances: desc.source_resistances,
+            load_resistances: desc.load_resistances,
+        })
+    }
+
+    /// Restores the voltages and currents of every conductor from the `fields` snapshot of a
+    /// solver.
+    pub fn with_fields(mut self, snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("MulticonductorFields")?;
+        let (volts_shape, currs_shape) = (self.fields.0.dim(), self.fields.1.dim());
+        let reshape = |name, shape: (usize, usize)| {
+            let values = snapshot.get(name)?;
+            ndarray::Array2::from_shape_vec(shape, values.clone()).map_err(|_| {
+                Error::BadSnapshot(format!("MulticonductorFields {} has the wrong length", name))
+            })
+        };
+
+        self.fields = (reshape("voltages", volts_shape)?, reshape("currents", currs_shape)?);
+        // conductors still at rest from the start have no time
+        self.time = snapshot.get("time")?.first().copied();
+        Ok(self)
+    }
+
+    /// Puts every conductor back at rest, for a state that starts over.
+    pub fn reset_fields(&mut self) {
+        self.fields.0.fill(0.0);
+        self.fields.1.fill(0.0);
+        self.time = None;
+    }
+
+    /// The update of an end node, which solves `(C Δz/Δt + G/2) V' = (C Δz/Δt - G/2) V + drive`
+    /// for a diagonal conductance `G` to its source or to ground.
+    fn end_update(&self, cell: usize, resistances: &[Float], d_ratio: Float)
+        -> (ndarray::Array2<Float>, ndarray::Array2<Float>)
+    {
+        let cap = &self.tline.cap[cell]*d_ratio;
+        let conductance = ndarray::Array2::from_diag(
+            &resistances.iter().map(|resistance| resistance.recip()).collect::<ndarray::Array1<_>>()
+        );
+        let inverse = invert(&(&cap + &(&conductance / 2.0)));
+        let decay = inverse.dot(&(&cap - &(&conductance / 2.0)));
+
+        (decay, inverse)
+    }
+}
+
+impl Solver for MulticonductorSolver {
+    fn compute(
+        &mut self,
+        desc: ComputeDescriptor,
+    ) -> Result<ComputeOutput, Error> {
+        let npoints = self.tline.npoints;
+        let conductors = self.tline.conductors;
+        let delta_t = desc.sim_params.delta_t;
+        let d_ratio = desc.sim_params.delta_z / delta_t;
+        let courant = d_ratio / self.max_phase_velocity;
+        if courant < 1.1 {
+            diagnostics::set_time(desc.state.time);
+            let message = "time step is within 10% of the stability limit";
+            diagnostics::warn("MulticonductorSolver", message);
+        }
+
+        // the other conductors have to be where the state of the first one is
+        if let Some(time) = self.time.filter(|time| (time - desc.state.time).abs() > delta_t/2.0) {
+            return Err(Error::BadSnapshot(format!(
+                "the other conductors are at {} s, not at the {} s of the state",
+                time,
+                desc.state.time,
+            )))
+        }
+        self.fields.0.row_mut(0).assign(&desc.state.voltages);
+        self.fields.1.row_mut(0).assign(&desc.state.currents);
+        let (start_decay, start_gain) = self.end_update(0, &self.source_resistances, d_ratio);
+        let (end_decay, end_gain) = self.end_update(npoints - 1, &self.load_resistances, d_ratio);
+
+        // the voltages and currents of every conductor, which are split into channels at the end
+        let mut all_voltages =
+            ndarray::Array3::<Float>::zeros((conductors, desc.nsteps + 1, npoints + 2));
+        let mut all_currents =
+            ndarray::Array3::<Float>::zeros((conductors, desc.nsteps + 1, npoints + 1));
+        let record = |row: usize, fields: &(ndarray::Array2<Float>, ndarray::Array2<Float>),
+            all_voltages: &mut ndarray::Array3<Float>, all_currents: &mut ndarray::Array3<Float>|
+        {
+            all_voltages.index_axis_mut(ndarray::Axis(1), row).assign(&fields.0);
+            all_currents.index_axis_mut(ndarray::Axis(1), row).assign(&fields.1);
+        };
+        record(0, &self.fields, &mut all_voltages, &mut all_currents);
+        let first_step = (desc.state.time / delta_t).round() as usize;
+
+        for t_index in 0..desc.nsteps {
+            let t = (t_index as Float)*delta_t + desc.state.time;
+            diagnostics::set_time(t);
+            let (ref mut volts, ref mut currs) = self.fields;
+
+            // interior nodes, then the source and end nodes through their resistors
+            for n in 1..(npoints + 1) {
+                let drive = &currs.column(n - 1) - &currs.column(n);
+                let change = self.inverse_cap[n - 1].dot(&drive) / d_ratio;
+                let mut volt = volts.column_mut(n);
+                volt += &change;
+            }
+            let sources = self.source_fns.iter().zip(&self.source_resistances)
+                .map(|(source_fn, resistance)| source_fn(t) / resistance)
+                .collect::<ndarray::Array1<Float>>();
+            let start = start_decay.dot(&volts.column(0))
+                + start_gain.dot(&(&sources - &currs.column(0)));
+            volts.column_mut(0).assign(&start);
+            let end = end_decay.dot(&volts.column(npoints + 1))
+                + end_gain.dot(&currs.column(npoints));
+            volts.column_mut(npoints + 1).assign(&end);
+
+            for n in 0..(npoints + 1) {
+                let drive = &volts.column(n) - &volts.column(n + 1);
+                let change = self.inverse_ind[n.min(npoints - 1)].dot(&drive) / d_ratio;
+                let mut curr = currs.column_mut(n);
+                curr += &change;
+            }
+
+            record(t_index + 1, &self.fields, &mut all_voltages, &mut all_currents);
+            let rows = ndarray::s![0, (t_index+1)..(t_index+2), ..];
+            let (volts, currs) = (all_voltages.slice(rows), all_currents.slice(rows));
+            check_finite(volts, currs, first_step + t_index + 1)?;
+
+            if let Some(ref bar) = desc.bar {
+                bar.inc(1)
+            }
+        }
+
+        self.time = Some(desc.state.time + (desc.nsteps as Float)*delta_t);
+        let mut channels = BTreeMap::new();
+        let mut channel_units = BTreeMap::new();
+        for k in 1..conductors {
+            let volts = all_voltages.index_axis(ndarray::Axis(0), k).to_owned();
+            channels.insert(format!("conductor_{}_voltages", k), volts);
+            channel_units.insert(format!("conductor_{}_voltages", k), "V");
+            let currs = all_currents.index_axis(ndarray::Axis(0), k).to_owned();
+            channels.insert(format!("conductor_{}_currents", k), currs);
+            channel_units.insert(format!("conductor_{}_currents", k), "A");
+        }
+
+        Ok(ComputeOutput {
+            voltages: all_voltages.index_axis(ndarray::Axis(0), 0).to_owned(),
+            currents: all_currents.index_axis(ndarray::Axis(0), 0).to_owned(),
+            channels,
+            channel_units,
+        })
+    }
+
+    fn npoints(&self) -> usize {
+        self.tline.npoints
+    }
+
+    fn max_phase_velocity(&self) -> Option<Float> {
+        Some(self.max_phase_velocity)
+    }
+
+    fn snapshot(&self) -> BTreeMap<String, ComponentSnapshot> {
+        BTreeMap::from([
+            ("tline".to_string(), self.tline.snapshot()),
+            (
+                "terminations".to_string(),
+                ComponentSnapshot::new("MulticonductorTerminations")
+                    .with("source_resistances", self.source_resistances.clone())
+                    .with("load_resistances", self.load_resistances.clone()),
+            ),
+            (
+                "fields".to_string(),
+                ComponentSnapshot::new("MulticonductorFields")
+                    .with("time", self.time.into_iter().collect())
+                    .with("voltages", self.fields.0.iter().copied().collect())
+                    .with("currents", self.fields.1.iter().copied().collect()),
+            ),
+        ])
+    }
+}
+
+/// Inverts a small square matrix by Gauss-Jordan elimination with partial pivoting.
+fn invert(matrix: &ndarray::Array2<Float>) -> ndarray::Array2<Float> {
+    let size = matrix.nrows();
+    let mut left = matrix.clone();
+    let mut right = ndarray::Array2::<Float>::eye(size);
+    for column in 0..size {
+        let pivot = (column..size)
+            .max_by(|&a, &b| left[[a, column]].abs().total_cmp(&left[[b, column]].abs()))
+            .expect("the column has rows below the diagonal");
+        for k in 0..size {
+            left.swap([column, k], [pivot, k]);
+            right.swap([column, k], [pivot, k]);
+        }
+        let scale = left[[column, column]].recip();
+        left.row_mut(column).mapv_inplace(|value| value*scale);
+        right.row_mut(column).mapv_inplace(|value| value*scale);
+        let left_pivot = left.row(column).to_owned();
+        let right_pivot = right.row(column).to_owned();
+        for row in (0..size).filter(|&row| row != column) {
+            let factor = left[[row, column]];
+            left.row_mut(row).scaled_add(-factor, &left_pivot);
+            right.row_mut(row).scaled_add(-factor, &right_pivot);
+        }
+    }
+
+    right
+}
@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use num_complex::Complex;
+
+use crate::{
+    diagnostics, ComponentSnapshot, ComputeDescriptor, ComputeOutput, Error, Float,
+    SimulationParameters, Solver,
+};
+use crate::fdtd::{check_finite, TransmissionLine, Update};
+use crate::fdtd::components::LinearLine;
+
+/// Describes the composition of a `SpectralSolver` with matched ends.
+pub struct SpectralSolverDescriptor {
+    pub tline: LinearLine,
+    /// The open circuit voltage of a matched source at the start of the line.
+    pub source_fn: Box<dyn Fn(Float) -> Float>,
+    /// The number of cells in the absorbing layer past each end of the line.
+    pub absorber_cells: usize,
+}
+
+/// Does single threaded computations of a linear line on the CPU, with spatial derivatives taken
+/// by FFT.
+///
+/// Derivatives are exact for every wavelength the grid can hold, so smooth waveforms need only a
+/// few points per wavelength instead of the tens an `FdtdSolver` needs for the same dispersion.
+/// The grid is periodic, so matched ends are made from graded lossy layers that absorb the waves
+/// leaving the line. Time stepping is the same leapfrog scheme as an `FdtdSolver`, which is
+/// stable up to `2/π` of its usual time step.
+///
+/// The absorbers are kept by the solver between chunks, and are part of the `fields` snapshot of
+/// the solver, so checkpoints hold them, and a solver rebuilt for a snapshot should restore them
+/// with `with_fields`.
+pub struct SpectralSolver {
+    tline: LinearLine,
+    source_fn: Option<Box<dyn Fn(Float) -> Float>>,
+    /// The number of cells before the line, which is also the number after it.
+    absorber_cells: usize,
+    /// The capacitance, inductance, resistance, and conductance per unit length of each cell of
+    /// the whole grid.
+    parameters: [Vec<Float>; 4],
+    /// The voltages and currents of the whole grid, which keep the absorbers between chunks.
+    fields: (Vec<Float>, Vec<Float>),
+    /// The time of the fields, unless the absorbers are still at rest from the start.
+    time: Option<Float>,
+    forward: Arc<dyn rustfft::Fft<Float>>,
+    inverse: Arc<dyn rustfft::Fft<Float>>,
+}
+
+impl SpectralSolver {
+    /// Creates a new `SpectralSolver` for a line driven by a matched source and loaded by a
+    /// matched terminator.
+    ///
+    /// The first and last voltages and the last current of the simulation state are those of the
+    /// absorber cells next to each end of the line.
+    pub fn new(desc: SpectralSolverDescriptor) -> Self {
+        Self::build(desc.tline, Some(desc.source_fn), desc.absorber_cells.max(1))
+    }
+
+    /// Creates a new `SpectralSolver` for a closed ring, with the last cell connected to the first.
+    ///
+    /// The first and last voltages and the last current of the simulation state mirror the
+    /// opposite end of the ring, as with `FdtdSolver::new_periodic`.
+    pub fn new_periodic(tline: LinearLine) -> Self {
+        Self::build(tline, None, 0)
+    }
+
+    fn build(
+        tline: LinearLine,
+        source_fn: Option<Box<dyn Fn(Float) -> Float>>,
+        absorber_cells: usize,
+    ) -> Self {
+        let npoints = tline.npoints();
+        let delta_z = tline.length() / (npoints as Float);
+        let snapshot = tline.snapshot();
+        let line = ["capacitance", "inductance", "resistance", "conductance"]
+            .map(|name| snapshot.get(name).expect("linear lines snapshot every parameter").clone());
+
+        // graded layers with matched series and shunt losses, each copying its end of the line,
+        // with a cubic profile strong enough to absorb waves a million fold
+        let layer = |edge: usize, depth: usize| {
+            let (cap, ind) = (line[0][edge], line[1][edge]);
+            let velocity = Float::sqrt(ind*cap).recip();
+            let max_rate = 28.0*velocity / ((absorber_cells as Float)*delta_z);
+            let rate = max_rate*((depth as Float) / (absorber_cells as Float)).powi(3);
+            [cap, ind, line[2][edge] + rate*ind, line[3][edge] + rate*cap]
+        };
+        let cells = (0..absorber_cells).rev().map(|depth| layer(0, depth + 1))
+            .chain((0..npoints).map(|n| [line[0][n], line[1][n], line[2][n], line[3][n]]))
+            .chain((0..absorber_cells).map(|depth| layer(npoints - 1, depth + 1)))
+            .collect::<Vec<_>>();
+        let parameters = [0, 1, 2, 3].map(|k| cells.iter().map(|cell| cell[k]).collect());
+
+        let size = cells.len();
+        let mut planner = rustfft::FftPlanner::new();
+        Self {
+            forward: planner.plan_fft_forward(size),
+            inverse: planner.plan_fft_inverse(size),
+            tline,
+            source_fn,
+            absorber_cells,
+            parameters,
+            fields: (vec![0.0; size], vec![0.0; size]),
+            time: None,
+        }
+    }
+
+    /// Restores the voltages and currents of the absorbers from the `fields` snapshot of a solver.
+    pub fn with_fields(mut self, snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("SpectralFields")?;
+        let size = self.fields.0.len();
+        let values = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != size {
+                let message = format!("SpectralFields {} has the wrong length", name);
+                return Err(Error::BadSnapshot(message))
+            }
+            Ok(values.clone())
+        };
+
+        self.fields = (values("voltages")?, values("currents")?);
+        // absorbers still at rest from the start have no time
+        self.time = snapshot.get("time")?.first().copied();
+        Ok(self)
+    }
+
+    /// Puts the absorbers back at rest, for a state that starts over.
+    pub fn reset_fields(&mut self) {
+        self.fields.0.fill(0.0);
+        self.fields.1.fill(0.0);
+        self.time = None;
+    }
+
+    /// Calculates simulation parameters with a time step `courant` times shorter than the
+    /// stability limit of the solver.
+    pub fn calculate_simulation_parameters(&self, courant: Float) -> SimulationParameters {
+        let delta_z = self.tline.length() / (self.tline.npoints() as Float);
+        let max_phase_velocity = self.max_phase_velocity().expect("the solver is CFL limited");
+
+        SimulationParameters {
+            delta_z,
+            delta_t: delta_z / (courant*max_phase_velocity),
+        }
+    }
+
+    /// Calculates `-Δz` times the spatial derivative of `values`, shifted half a cell towards the
+    /// end of the line if `forward` is set, or towards its start otherwise.
+    fn difference(&self, values: &[Float], forward: bool) -> Vec<Float> {
+        let size = values.len();
+        let mut buffer = values.iter()
+            .map(|&value| Complex::new(value, 0.0))
+            .collect::<Vec<_>>();
+        self.forward.process(&mut buffer);
+
+        let shift = if forward { 0.5 } else { -0.5 };
+        for (k, value) in buffer.iter_mut().enumerate() {
+            // the Nyquist mode has no well defined derivative
+            let wavenumber = if 2*k == size {
+                0.0
+            } else if 2*k < size {
+                k as Float
+            } else {
+                k as Float - size as Float
+            } * 2.0*crate::consts::PI / (size as Float);
+            let factor = Complex::new(0.0, wavenumber)*Complex::from_polar(1.0, shift*wavenumber);
+            *value *= -factor / (size as Float);
+        }
+        self.inverse.process(&mut buffer);
+
+        buffer.into_iter().map(|value| value.re).collect()
+    }
+}
+
+impl Solver for SpectralSolver {
+    fn compute(
+        &mut self,
+        desc: ComputeDescriptor,
+    ) -> Result<ComputeOutput, Error> {
+        let npoints = self.tline.npoints();
+        let first = self.absorber_cells;
+        let size = self.fields.0.len();
+        let SimulationParameters { delta_z, delta_t } = desc.sim_params;
+        let d_ratio = delta_z / delta_t;
+        let courant = delta_z / (self.max_phase_velocity().unwrap_or(0.0)*delta_t);
+        if courant < 1.1 {
+            diagnostics::set_time(desc.state.time);
+            diagnostics::warn("SpectralSolver", "time step is within 10% of the stability limit");
+        }
+
+        // the absorbers have to be where the state of the line is
+        if let Some(time) = self.time.filter(|time| (time - desc.state.time).abs() > delta_t/2.0) {
+            return Err(Error::BadSnapshot(format!(
+                "the absorbers are at {} s, not at the {} s of the state",
+                time,
+                desc.state.time,
+            )))
+        }
+
+        // each state voltage `k` is grid cell `first + k - 1`, and each state current `k` is the
+        // grid current after that cell, wrapping around for periodic lines
+        let cell = |k: usize| (first + k + size - 1) % size;
+        let volt_cells = (0..(npoints + 2)).map(cell).collect::<Vec<_>>();
+        let curr_cells = (0..(npoints + 1)).map(cell).collect::<Vec<_>>();
+        // the mirrored ends of periodic lines are left out, as they duplicate other points
+        let (volt_range, curr_range) = match self.source_fn {
+            Some(_) => (0..(npoints + 2), 0..(npoints + 1)),
+            None => (1..(npoints + 1), 0..npoints),
+        };
+        for k in volt_range {
+            self.fields.0[volt_cells[k]] = desc.state.voltages[k];
+        }
+        for k in curr_range {
+            self.fields.1[curr_cells[k]] = desc.state.currents[k];
+        }
+
+        let [ref cap, ref ind, ref res, ref cond] = self.parameters;
+        let volt_updates = cap.iter().zip(cond)
+            .map(|(&cap, &cond)| Update::new(d_ratio*cap, delta_z*cond/2.0))
+            .collect::<Vec<_>>();
+        let curr_updates = ind.iter().zip(res)
+            .map(|(&ind, &res)| Update::new(d_ratio*ind, delta_z*res/2.0))
+            .collect::<Vec<_>>();
+        // a current of `V / Z` into the first cell launches a wave of `V / 2` each way, as from a
+        // matched source
+        let source_admittance = Float::sqrt(cap[first] / ind[first]);
+
+        let mut voltages = ndarray::Array2::<Float>::zeros((desc.nsteps + 1, npoints + 2));
+        let mut currents = ndarray::Array2::<Float>::zeros((desc.nsteps + 1, npoints + 1));
+        let record = |row: ndarray::ArrayViewMut1<Float>, fields: &[Float], cells: &[usize]| {
+            for (value, &cell) in row.into_iter().zip(cells) {
+                *value = fields[cell];
+            }
+        };
+        record(voltages.row_mut(0), &self.fields.0, &volt_cells);
+        record(currents.row_mut(0), &self.fields.1, &curr_cells);
+        let first_step = (desc.state.time / delta_t).round() as usize;
+
+        for t_index in 0..desc.nsteps {
+            let t = (t_index as Float)*delta_t + desc.state.time;
+            diagnostics::set_time(t);
+
+            let mut drive = self.difference(&self.fields.1, false);
+            if let Some(ref source_fn) = self.source_fn {
+                drive[first] += source_fn(t)*source_admittance;
+            }
+            for ((volt, update), drive) in self.fields.0.iter_mut().zip(&volt_updates).zip(drive) {
+                *volt = update.decay*(*volt) + update.gain*drive;
+            }
+
+            let drive = self.difference(&self.fields.0, true);
+            for ((curr, update), drive) in self.fields.1.iter_mut().zip(&curr_updates).zip(drive) {
+                *curr = update.decay*(*curr) + update.gain*drive;
+            }
+
+            record(voltages.row_mut(t_index + 1), &self.fields.0, &volt_cells);
+            record(currents.row_mut(t_index + 1), &self.fields.1, &curr_cells);
+            let rows = ndarray::s![(t_index+1)..(t_index+2), ..];
+            check_finite(voltages.slice(rows), currents.slice(rows), first_step + t_index + 1)?;
+
+            if let Some(ref bar) = desc.bar {
+                bar.inc(1)
+            }
+        }
+
+        self.time = Some(desc.state.time + (desc.nsteps as Float)*delta_t);
+
+        Ok(ComputeOutput {
+            voltages,
+            currents,
+            channels: BTreeMap::new(),
+            channel_units: BTreeMap::new(),
+        })
+    }
+
+    fn npoints(&self) -> usize {
+        self.tline.npoints()
+    }
+
+    /// The fastest phase velocity along the line, scaled up by `π/2` so time steps derived from it
+    /// stay stable.
+    fn max_phase_velocity(&self) -> Option<Float> {
+        Some(self.tline.max_phase_velocity()*crate::consts::FRAC_PI_2)
+    }
+
+    fn snapshot(&self) -> BTreeMap<String, ComponentSnapshot> {
+        BTreeMap::from([
+            ("tline".to_string(), self.tline.snapshot()),
+            (
+                "fields".to_string(),
+                ComponentSnapshot::new("SpectralFields")
+                    .with("time", self.time.into_iter().collect())
+                    .with("voltages", self.fields.0.clone())
+                    .with("currents", self.fields.1.clone()),
+            ),
+        ])
+    }
+}
@@ -0,0 +1,188 @@
+//! Impedance taper profiles.
+
+use crate::Float;
+use crate::fdtd::components::{LinearLine, LinearLineDescriptor};
+
+/// The shape of an impedance taper.
+#[derive(Copy, Clone, Debug)]
+pub enum TaperProfile {
+    /// The impedance changes linearly with position.
+    Linear,
+    /// The logarithm of the impedance changes linearly with position.
+    Exponential,
+    /// The Klopfenstein profile, which is optimal for a given passband reflection.
+    Klopfenstein {
+        /// The largest reflection coefficient magnitude allowed in the passband.
+        max_reflection: Float,
+    },
+}
+
+/// Describes a `Taper`.
+pub struct TaperDescriptor {
+    /// The length of the taper.
+    pub length: Float,
+    /// The characteristic impedance at the start of the taper.
+    pub start_impedance: Float,
+    /// The characteristic impedance at the end of the taper.
+    pub end_impedance: Float,
+    /// The phase velocity along the taper.
+    pub phase_velocity: Float,
+    /// The shape of the taper.
+    pub profile: TaperProfile,
+}
+
+/// A characteristic impedance profile between two impedances.
+#[derive(Clone, Debug)]
+pub struct Taper {
+    length: Float,
+    start_impedance: Float,
+    end_impedance: Float,
+    phase_velocity: Float,
+    profile: TaperProfile,
+    // ln(Z) sampled uniformly along the taper, for profiles without a closed form
+    log_table: Vec<Float>,
+}
+
+/// The number of samples used to tabulate profiles without a closed form.
+const TABLE_SIZE: usize = 1001;
+
+impl Taper {
+    /// Creates a new `Taper` instance.
+    pub fn new(desc: TaperDescriptor) -> Self {
+        let log_table = match desc.profile {
+            TaperProfile::Klopfenstein { max_reflection } => klopfenstein_table(
+                desc.start_impedance as f64,
+                desc.end_impedance as f64,
+                max_reflection as f64,
+            ),
+            _ => Vec::new(),
+        };
+
+        Self {
+            length: desc.length,
+            start_impedance: desc.start_impedance,
+            end_impedance: desc.end_impedance,
+            phase_velocity: desc.phase_velocity,
+            profile: desc.profile,
+            log_table,
+        }
+    }
+
+    /// Gets the characteristic impedance at position `z`, clamped to the taper.
+    pub fn impedance(&self, z: Float) -> Float {
+        let x = (z / self.length).clamp(0.0, 1.0);
+        match self.profile {
+            TaperProfile::Linear => {
+                self.start_impedance + (self.end_impedance - self.start_impedance) * x
+            },
+            TaperProfile::Exponential => {
+                self.start_impedance * (self.end_impedance / self.start_impedance).powf(x)
+            },
+            TaperProfile::Klopfenstein { .. } => {
+                let position = x * ((TABLE_SIZE - 1) as Float);
+                let index = (position.floor() as usize).min(TABLE_SIZE - 2);
+                let fraction = position - (index as Float);
+                let log_z = self.log_table[index]
+                    + fraction * (self.log_table[index+1] - self.log_table[index]);
+                log_z.exp()
+            },
+        }
+    }
+
+    /// Gets the capacitance per unit length at position `z`.
+    pub fn capacitance(&self, z: Float) -> Float {
+        (self.impedance(z) * self.phase_velocity).recip()
+    }
+
+    /// Gets the inductance per unit length at position `z`.
+    pub fn inductance(&self, z: Float) -> Float {
+        self.impedance(z) / self.phase_velocity
+    }
+
+    /// Creates a capacitance function for use in line descriptors.
+    pub fn capacitance_fn(&self) -> impl Fn(Float) -> Float {
+        let taper = self.clone();
+        move |z| taper.capacitance(z)
+    }
+
+    /// Creates an inductance function for use in line descriptors.
+    pub fn inductance_fn(&self) -> impl Fn(Float) -> Float {
+        let taper = self.clone();
+        move |z| taper.inductance(z)
+    }
+
+    /// Samples the capacitance per unit length at the center of each of `npoints` cells.
+    pub fn capacitances(&self, npoints: usize) -> Vec<Float> {
+        self.sample(npoints, |z| self.capacitance(z))
+    }
+
+    /// Samples the inductance per unit length at the center of each of `npoints` cells.
+    pub fn inductances(&self, npoints: usize) -> Vec<Float> {
+        self.sample(npoints, |z| self.inductance(z))
+    }
+
+    /// Creates a lossless `LinearLine` of `npoints` cells along the taper, ready to join the lines
+    /// it matches.
+    pub fn line(&self, npoints: usize) -> LinearLine {
+        LinearLine::new(LinearLineDescriptor {
+            length: self.length,
+            npoints,
+            capacitance_fn: |z| self.capacitance(z),
+            inductance_fn: |z| self.inductance(z),
+            resistance_fn: |_| 0.0,
+            conductance_fn: |_| 0.0,
+        })
+    }
+
+    fn sample<F: Fn(Float) -> Float>(&self, npoints: usize, f: F) -> Vec<Float> {
+        let delta_z = self.length / (npoints as Float);
+        (0..npoints)
+            .map(|n| f((n as Float + 0.5) * delta_z))
+            .collect()
+    }
+}
+
+/// Tabulates the logarithm of the Klopfenstein impedance profile.
+fn klopfenstein_table(start_impedance: f64, end_impedance: f64, max_reflection: f64) -> Vec<Float> {
+    let gamma_0 = 0.5 * (end_impedance / start_impedance).ln();
+    let a = (gamma_0.abs() / max_reflection).max(1.0).acosh();
+    let mean = 0.5 * (start_impedance * end_impedance).ln();
+
+    // φ(x, A) = ∫_0^x I1(A √(1 - y²)) / (A √(1 - y²)) dy, integrated over x in [0, 1]
+    let integrand = |y: f64| {
+        let arg = a * (1.0 - y*y).max(0.0).sqrt();
+        bessel_i1_over_x(arg)
+    };
+    let half = TABLE_SIZE / 2;
+    let step = 1.0 / (half as f64);
+    let mut phi = vec![0.0; half + 1];
+    for k in 1..=half {
+        let (y0, y1) = (((k-1) as f64) * step, (k as f64) * step);
+        let midpoint = integrand(0.5 * (y0 + y1));
+        phi[k] = phi[k-1] + step * (integrand(y0) + 4.0*midpoint + integrand(y1)) / 6.0;
+    }
+
+    (0..TABLE_SIZE)
+        .map(|n| {
+            // φ is odd in x = 2z/L - 1
+            let offset = (n as isize) - (half as isize);
+            let phi_x = offset.signum() as f64 * phi[offset.unsigned_abs()];
+            (mean + gamma_0 * a * a * phi_x / a.cosh()) as Float
+        })
+        .collect()
+}
+
+/// Evaluates `I1(x) / x`, where `I1` is the first order modified Bessel function.
+fn bessel_i1_over_x(x: f64) -> f64 {
+    let quarter_x2 = x*x / 4.0;
+    let mut term = 0.5;
+    let mut sum = term;
+    for k in 1..100 {
+        term *= quarter_x2 / ((k * (k + 1)) as f64);
+        sum += term;
+        if term < 1e-16 * sum {
+            break;
+        }
+    }
+    sum
+}
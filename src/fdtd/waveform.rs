@@ -0,0 +1,74 @@
+//! Source waveforms and waveform builders, for use as source functions.
+
+mod gaussian_pulse;
+mod modulated;
+mod multi_tone;
+mod noise;
+mod sampled;
+mod step;
+
+pub use gaussian_pulse::{GaussianPulse};
+pub use modulated::{Modulation, ModulatedCarrier};
+pub use multi_tone::{MultiTone, Tone};
+pub use noise::{Noise, NoiseSpectrum};
+pub use sampled::{SampleInterpolation, SampledWaveform};
+pub use step::{Edge, Step};
+
+use crate::{ComponentSnapshot, Float};
+
+/// A waveform emitted by a source, such as a closure of the time.
+pub trait Waveform {
+    /// The value of the waveform at `time`.
+    fn at(&self, time: Float) -> Float;
+
+    /// Captures the configuration of the waveform.
+    ///
+    /// Defaults to no parameters, for waveforms such as closures that can't be captured.
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new(std::any::type_name::<Self>())
+    }
+}
+impl<F: Fn(Float) -> Float> Waveform for F {
+    #[inline]
+    fn at(&self, time: Float) -> Float {
+        self(time)
+    }
+}
+
+/// Describes a `pulse_train`.
+pub struct PulseTrainDescriptor<F: Fn(Float) -> Float> {
+    /// The waveform emitted during each burst, as a function of the time since the burst started.
+    pub base_fn: F,
+    /// The number of bursts per unit time.
+    pub repetition_rate: Float,
+    /// The fraction of each period spent emitting, between 0 and 1.
+    pub duty_cycle: Float,
+    /// The number of bursts to emit, or `None` for an endless train.
+    pub npulses: Option<usize>,
+    /// The time the first burst starts.
+    pub delay: Float,
+}
+
+/// Creates a waveform emitting repeated bursts of a base waveform, and zero in between.
+///
+/// Every burst restarts the base waveform, so all bursts are identical.
+pub fn pulse_train<F: Fn(Float) -> Float>(desc: PulseTrainDescriptor<F>) -> impl Fn(Float) -> Float {
+    let period = desc.repetition_rate.recip();
+    let width = desc.duty_cycle.clamp(0.0, 1.0) * period;
+
+    move |time| {
+        let elapsed = time - desc.delay;
+        if elapsed < 0.0 {
+            return 0.0
+        }
+
+        let index = (elapsed / period).floor();
+        let in_burst = elapsed - index*period;
+        let finished = desc.npulses.is_some_and(|npulses| index >= npulses as Float);
+        if finished || in_burst >= width {
+            0.0
+        } else {
+            (desc.base_fn)(in_burst)
+        }
+    }
+}
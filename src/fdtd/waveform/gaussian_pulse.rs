@@ -0,0 +1,38 @@
+use crate::{ComponentSnapshot, Error, Float};
+use super::Waveform;
+
+/// A single Gaussian pulse, for broadband excitation and time domain reflectometry.
+///
+/// The pulse is `A exp(-(t - t0)² / 2σ²)`, which only turns on smoothly if the run starts well
+/// before `t0 - 4σ`, so `t0` should be at least `4σ` for a run starting at time zero.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GaussianPulse {
+    pub amplitude: Float,
+    /// The time of the peak.
+    pub t0: Float,
+    /// The standard deviation of the pulse in time.
+    pub sigma: Float,
+}
+impl GaussianPulse {
+    /// Rebuilds a `GaussianPulse` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("GaussianPulse")?;
+        Ok(Self {
+            amplitude: snapshot.get_scalar("amplitude")?,
+            t0: snapshot.get_scalar("t0")?,
+            sigma: snapshot.get_scalar("sigma")?,
+        })
+    }
+}
+impl Waveform for GaussianPulse {
+    fn at(&self, time: Float) -> Float {
+        self.amplitude * Float::exp(-((time - self.t0) / self.sigma).powi(2) / 2.0)
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("GaussianPulse")
+            .with("amplitude", vec![self.amplitude])
+            .with("t0", vec![self.t0])
+            .with("sigma", vec![self.sigma])
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{ComponentSnapshot, Error, Float};
+use super::Waveform;
+
+/// The modulation of the carrier of a `ModulatedCarrier`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Modulation {
+    /// Amplitude modulation by a tone, scaling the carrier by `1 + m sin(2πfₘt)`.
+    Amplitude {
+        /// The modulation depth `m`, which is 1 for full modulation.
+        depth: Float,
+        /// The frequency `fₘ` of the modulating tone.
+        frequency: Float,
+    },
+    /// Frequency modulation by a tone, shifting the carrier frequency by `Δf cos(2πfₘt)`.
+    Frequency {
+        /// The peak frequency shift `Δf`.
+        deviation: Float,
+        /// The frequency `fₘ` of the modulating tone.
+        frequency: Float,
+    },
+    /// Phase shift keying, shifting the carrier phase by `2πs / order` for each symbol `s`, such
+    /// as an order of 2 for BPSK and 4 for QPSK.
+    Phase {
+        symbols: Vec<usize>,
+        order: usize,
+        /// The number of symbols per unit time.
+        symbol_rate: Float,
+    },
+}
+
+/// A modulated carrier `A sin(2πf t + φ(t))`.
+///
+/// Modulation by a tone goes on for all time, while a stream of symbols starts at time zero and
+/// leaves the carrier silent outside it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModulatedCarrier {
+    amplitude: Float,
+    carrier_frequency: Float,
+    modulation: Modulation,
+}
+impl ModulatedCarrier {
+    /// Creates a new `ModulatedCarrier` instance, checking that frequency modulation has a
+    /// modulating tone, and that symbols have an order and a rate.
+    pub fn new(
+        amplitude: Float,
+        carrier_frequency: Float,
+        modulation: Modulation,
+    ) -> Result<Self, Error> {
+        let positive = |rate: Float| rate > 0.0 && rate.is_finite();
+        let problem = match modulation {
+            Modulation::Frequency { frequency, .. } if !positive(frequency) => {
+                Some("frequency modulation needs a positive modulation frequency")
+            },
+            Modulation::Phase { order: 0, .. } => Some("phase shift keying needs a nonzero order"),
+            Modulation::Phase { symbol_rate, .. } if !positive(symbol_rate) => {
+                Some("phase shift keying needs a positive symbol rate")
+            },
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            return Err(Error::BadParameter(problem.to_string()))
+        }
+
+        Ok(Self { amplitude, carrier_frequency, modulation })
+    }
+
+    /// Rebuilds a `ModulatedCarrier` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("ModulatedCarrier")?;
+        let modulation = match snapshot.get_scalar("modulation")? as usize {
+            0 => Modulation::Amplitude {
+                depth: snapshot.get_scalar("depth")?,
+                frequency: snapshot.get_scalar("modulation_frequency")?,
+            },
+            1 => Modulation::Frequency {
+                deviation: snapshot.get_scalar("deviation")?,
+                frequency: snapshot.get_scalar("modulation_frequency")?,
+            },
+            2 => Modulation::Phase {
+                symbols: snapshot.get("symbols")?.iter().map(|&symbol| symbol as usize).collect(),
+                order: snapshot.get_scalar("order")? as usize,
+                symbol_rate: snapshot.get_scalar("symbol_rate")?,
+            },
+            other => {
+                let message = format!("ModulatedCarrier has no modulation {}", other);
+                return Err(Error::BadSnapshot(message))
+            },
+        };
+
+        Self::new(
+            snapshot.get_scalar("amplitude")?,
+            snapshot.get_scalar("carrier_frequency")?,
+            modulation,
+        )
+    }
+
+    #[inline]
+    pub fn amplitude(&self) -> Float {
+        self.amplitude
+    }
+
+    #[inline]
+    pub fn carrier_frequency(&self) -> Float {
+        self.carrier_frequency
+    }
+
+    #[inline]
+    pub fn modulation(&self) -> &Modulation {
+        &self.modulation
+    }
+}
+impl Waveform for ModulatedCarrier {
+    fn at(&self, time: Float) -> Float {
+        let two_pi = 2.0*crate::consts::PI;
+        let carrier = two_pi*self.carrier_frequency*time;
+        match self.modulation {
+            Modulation::Amplitude { depth, frequency } => {
+                let envelope = 1.0 + depth*Float::sin(two_pi*frequency*time);
+                self.amplitude * envelope * carrier.sin()
+            },
+            Modulation::Frequency { deviation, frequency } => {
+                // the phase is the integral of the frequency shift
+                let phase = deviation/frequency * Float::sin(two_pi*frequency*time);
+                self.amplitude * Float::sin(carrier + phase)
+            },
+            Modulation::Phase { ref symbols, order, symbol_rate } => {
+                let index = (time * symbol_rate).floor();
+                if index < 0.0 || index >= symbols.len() as Float {
+                    return 0.0
+                }
+                let phase = two_pi * symbols[index as usize] as Float / order as Float;
+                self.amplitude * Float::sin(carrier + phase)
+            },
+        }
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        let snapshot = ComponentSnapshot::new("ModulatedCarrier")
+            .with("amplitude", vec![self.amplitude])
+            .with("carrier_frequency", vec![self.carrier_frequency]);
+        match self.modulation {
+            Modulation::Amplitude { depth, frequency } => snapshot
+                .with("modulation", vec![0.0])
+                .with("depth", vec![depth])
+                .with("modulation_frequency", vec![frequency]),
+            Modulation::Frequency { deviation, frequency } => snapshot
+                .with("modulation", vec![1.0])
+                .with("deviation", vec![deviation])
+                .with("modulation_frequency", vec![frequency]),
+            Modulation::Phase { ref symbols, order, symbol_rate } => snapshot
+                .with("modulation", vec![2.0])
+                .with("symbols", symbols.iter().map(|&symbol| symbol as Float).collect())
+                .with("order", vec![order as Float])
+                .with("symbol_rate", vec![symbol_rate]),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{ComponentSnapshot, Error, Float};
+use super::Waveform;
+
+/// A tone of a `MultiTone`.
+#[derive(Copy, Clone, Debug)]
+pub struct Tone {
+    pub frequency: Float,
+    pub amplitude: Float,
+    /// The phase in radians at time zero.
+    pub phase: Float,
+    /// The time the tone takes to rise to its amplitude, or zero to start at full amplitude.
+    pub ramp_time: Float,
+}
+impl Tone {
+    /// The voltage of the tone at `time`.
+    #[inline]
+    fn at(&self, time: Float) -> Float {
+        let envelope = if time >= self.ramp_time {
+            1.0
+        } else if time <= 0.0 {
+            0.0
+        } else {
+            (1.0 - Float::cos(crate::consts::PI * time / self.ramp_time)) / 2.0
+        };
+        let angle = 2.0*crate::consts::PI*self.frequency*time + self.phase;
+        envelope * self.amplitude * angle.sin()
+    }
+}
+
+/// A sum of tones, such as the pump, signal, and idler of a parametric amplifier.
+///
+/// Each tone is `A sin(2πft + φ)`, turned on over its ramp time by a raised cosine so that a strong
+/// pump doesn't splatter across the spectrum. The tones are silent before time zero.
+#[derive(Clone, Debug)]
+pub struct MultiTone {
+    pub tones: Vec<Tone>,
+}
+impl MultiTone {
+    /// Rebuilds a `MultiTone` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("MultiTone")?;
+        let frequencies = snapshot.get("tone_frequency")?;
+        let per_tone = |name| {
+            let values = snapshot.get(name)?;
+            if values.len() != frequencies.len() {
+                let message = format!("MultiTone {} has the wrong length", name);
+                return Err(Error::BadSnapshot(message))
+            }
+            Ok(values)
+        };
+        let (amplitudes, phases, ramp_times) = (
+            per_tone("tone_amplitude")?,
+            per_tone("tone_phase")?,
+            per_tone("tone_ramp_time")?,
+        );
+
+        Ok(Self {
+            tones: (0..frequencies.len())
+                .map(|k| Tone {
+                    frequency: frequencies[k],
+                    amplitude: amplitudes[k],
+                    phase: phases[k],
+                    ramp_time: ramp_times[k],
+                })
+                .collect(),
+        })
+    }
+}
+impl Waveform for MultiTone {
+    fn at(&self, time: Float) -> Float {
+        self.tones.iter().map(|tone| tone.at(time)).sum()
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        let tones = |value: fn(&Tone) -> Float| self.tones.iter().map(value).collect();
+        ComponentSnapshot::new("MultiTone")
+            .with("tone_frequency", tones(|tone| tone.frequency))
+            .with("tone_amplitude", tones(|tone| tone.amplitude))
+            .with("tone_phase", tones(|tone| tone.phase))
+            .with("tone_ramp_time", tones(|tone| tone.ramp_time))
+    }
+}
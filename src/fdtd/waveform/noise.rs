@@ -0,0 +1,117 @@
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{ComponentSnapshot, Error, Float};
+use super::Waveform;
+use super::sampled::sinc;
+
+/// The samples on each side weighted by the interpolation of a `Noise`.
+const NOISE_TAPS: i64 = 8;
+
+/// The spectrum of a `Noise`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NoiseSpectrum {
+    /// A flat spectrum up to the bandwidth.
+    White,
+    /// A `1/f` spectrum over `octaves` octaves below the bandwidth, and flat below them.
+    Pink { octaves: usize },
+}
+
+/// Band limited Gaussian noise, reproducible from its seed.
+///
+/// Independent Gaussian samples are drawn at twice the bandwidth and interpolated by a windowed
+/// sinc. Pink noise sums one sequence per octave, each holding its samples twice as long as the
+/// last. Every sample is drawn from its own position in the random stream, so the noise depends
+/// only on the time and seed, and is the same for every run and for restarts from a checkpoint.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Noise {
+    /// The rms voltage of the noise.
+    pub rms: Float,
+    /// The highest frequency in the noise.
+    pub bandwidth: Float,
+    pub spectrum: NoiseSpectrum,
+    /// The seed of the random number generator.
+    pub seed: u64,
+}
+impl Noise {
+    /// Rebuilds a `Noise` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("Noise")?;
+        let seed = snapshot.get("seed")?;
+        if seed.len() != 4 {
+            return Err(Error::BadSnapshot("Noise seed has the wrong length".to_string()))
+        }
+        let octaves = snapshot.get_scalar("octaves")? as usize;
+
+        Ok(Self {
+            rms: snapshot.get_scalar("rms")?,
+            bandwidth: snapshot.get_scalar("bandwidth")?,
+            spectrum: if octaves == 0 {
+                NoiseSpectrum::White
+            } else {
+                NoiseSpectrum::Pink { octaves }
+            },
+            seed: seed.iter().rev().fold(0, |seed, &part| seed << 16 | part as u64),
+        })
+    }
+
+    /// The number of independent sequences summed for the spectrum.
+    #[inline]
+    fn octaves(&self) -> usize {
+        match self.spectrum {
+            NoiseSpectrum::White => 1,
+            NoiseSpectrum::Pink { octaves } => octaves.max(1),
+        }
+    }
+
+    /// Draws the standard normal sample `index` of sequence `octave`.
+    fn draw(rng: &mut ChaCha8Rng, octave: usize, index: i64) -> Float {
+        // each sample takes two words of 64 bits, for the Box-Muller transform
+        rng.set_stream(octave as u64);
+        rng.set_word_pos(4 * (index as u64 as u128));
+        let uniform = |word: u64| ((word >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+        let (radius, angle) = (uniform(rng.next_u64()), uniform(rng.next_u64()));
+        (f64::sqrt(-2.0*radius.ln()) * f64::cos(2.0*std::f64::consts::PI*angle)) as Float
+    }
+}
+impl Waveform for Noise {
+    fn at(&self, time: Float) -> Float {
+        let position = time * 2.0*self.bandwidth;
+        if !position.is_finite() {
+            return 0.0
+        }
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let octaves = self.octaves();
+
+        // sum the samples within reach of the kernel, weighted by it, drawing each held sample of
+        // the slower octaves once
+        let nearest = position.floor() as i64;
+        let mut total = 0.0;
+        for octave in 0..octaves {
+            let mut held = None;
+            for index in (nearest - NOISE_TAPS + 1)..=(nearest + NOISE_TAPS) {
+                let sample = match held {
+                    Some((held_index, sample)) if held_index == index >> octave => sample,
+                    _ => Self::draw(&mut rng, octave, index >> octave),
+                };
+                held = Some((index >> octave, sample));
+                let offset = position - index as Float;
+                total += sample * sinc(offset) * sinc(offset / NOISE_TAPS as Float);
+            }
+        }
+        self.rms * total / (octaves as Float).sqrt()
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        let octaves = match self.spectrum {
+            NoiseSpectrum::White => 0,
+            NoiseSpectrum::Pink { octaves } => octaves.max(1),
+        };
+        ComponentSnapshot::new("Noise")
+            .with("rms", vec![self.rms])
+            .with("bandwidth", vec![self.bandwidth])
+            .with("octaves", vec![octaves as Float])
+            // the seed is split into 16 bit parts, which floats hold exactly
+            .with("seed", (0..4).map(|k| (self.seed >> (16*k) & 0xffff) as Float).collect())
+    }
+}
@@ -0,0 +1,234 @@
+use std::path::Path;
+
+use crate::{ComponentSnapshot, Error, Float};
+use super::Waveform;
+
+/// How a `SampledWaveform` interpolates between its samples.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SampleInterpolation {
+    /// Straight lines between samples.
+    Linear,
+    /// Band limited interpolation by a sinc kernel, tapered by a Lanczos window to `taps`
+    /// samples on each side, at least one.
+    Sinc { taps: usize },
+}
+
+/// The error for a waveform file at `path` that can't be read.
+fn bad_file(path: &Path, message: &str) -> Error {
+    Error::IoError(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("{} {}", path.display(), message),
+    ))
+}
+
+/// The normalized sinc function `sin(πx) / πx`.
+#[inline]
+pub(super) fn sinc(x: Float) -> Float {
+    if x == 0.0 {
+        1.0
+    } else {
+        Float::sin(crate::consts::PI * x) / (crate::consts::PI * x)
+    }
+}
+
+/// A sampled waveform, such as the output of an arbitrary waveform generator.
+///
+/// Sample `k` is emitted at `start_time + k / sample_rate`, and the waveform is zero outside the
+/// samples, so it should start and end near zero to turn on and off smoothly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SampledWaveform {
+    pub samples: Vec<Float>,
+    pub sample_rate: Float,
+    /// The time of the first sample.
+    pub start_time: Float,
+    pub interpolation: SampleInterpolation,
+}
+impl SampledWaveform {
+    /// Rebuilds a `SampledWaveform` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("SampledWaveform")?;
+        let taps = snapshot.get_scalar("sinc_taps")? as usize;
+        Ok(Self {
+            samples: snapshot.get("samples")?.clone(),
+            sample_rate: snapshot.get_scalar("sample_rate")?,
+            start_time: snapshot.get_scalar("start_time")?,
+            interpolation: if taps == 0 {
+                SampleInterpolation::Linear
+            } else {
+                SampleInterpolation::Sinc { taps }
+            },
+        })
+    }
+
+    /// Reads the waveform in `value_column` of a CSV file, sampled at the times in `time_column`.
+    ///
+    /// Columns are counted from zero. Blank lines, lines starting with `#`, and a header line are
+    /// skipped. The times must be evenly spaced, to within a hundredth of the sample period, as
+    /// they are only used for the start time and sample rate. The samples are interpolated
+    /// linearly, which can be changed afterwards.
+    pub fn from_csv(
+        path: impl AsRef<Path>,
+        time_column: usize,
+        value_column: usize,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let (mut times, mut samples) = (Vec::new(), Vec::new());
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            let field = |column: usize| fields.get(column)?.parse::<Float>().ok();
+            match (field(time_column), field(value_column)) {
+                (Some(time), Some(value)) => {
+                    times.push(time);
+                    samples.push(value);
+                },
+                _ if times.is_empty() => continue,
+                _ => {
+                    let message = format!("has no number in column {} or {} of line {}",
+                        time_column, value_column, number + 1);
+                    return Err(bad_file(path, &message))
+                },
+            }
+        }
+
+        let (Some(&first), Some(&last)) = (times.first(), times.last()) else {
+            return Err(bad_file(path, "has no samples"))
+        };
+        if times.len() < 2 || times.windows(2).any(|pair| pair[1] <= pair[0]) {
+            return Err(bad_file(path, "needs at least two samples at increasing times"))
+        }
+        let period = (last - first) / (times.len() - 1) as Float;
+        let uneven = times.iter().enumerate()
+            .any(|(k, &time)| (time - first - k as Float * period).abs() > 0.01 * period);
+        if uneven {
+            return Err(bad_file(path, "has unevenly spaced sample times"))
+        }
+        Ok(Self::linear(samples, period.recip(), first))
+    }
+
+    /// Reads the waveform in `channel` of a WAV file, scaled so full scale is 1.
+    ///
+    /// Channels are counted from zero. Integer PCM of 8 to 32 bits and floating point samples are
+    /// read, starting at time zero. The samples are interpolated linearly, which can be changed
+    /// afterwards.
+    pub fn from_wav(path: impl AsRef<Path>, channel: usize) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(bad_file(path, "isn't a WAV file"))
+        }
+
+        // walk the chunks, which are padded to an even length
+        let (mut format, mut data) = (None, None);
+        let mut offset = 12;
+        while offset + 8 <= bytes.len() {
+            let id = &bytes[offset..(offset + 4)];
+            let len = u32::from_le_bytes(bytes[(offset + 4)..(offset + 8)].try_into().unwrap());
+            let start = offset + 8;
+            let end = start.saturating_add(len as usize).min(bytes.len());
+            match id {
+                b"fmt " => format = Some(&bytes[start..end]),
+                b"data" => data = Some(&bytes[start..end]),
+                _ => (),
+            }
+            offset = end + (len as usize & 1);
+        }
+        let (Some(format), Some(data)) = (format, data) else {
+            return Err(bad_file(path, "is missing its format or data"))
+        };
+        if format.len() < 16 {
+            return Err(bad_file(path, "has a truncated format"))
+        }
+
+        let field = |at: usize| u16::from_le_bytes([format[at], format[at + 1]]);
+        let mut encoding = field(0);
+        if encoding == 0xfffe && format.len() >= 26 {
+            // the extensible format holds the encoding at the start of its subformat
+            encoding = field(24);
+        }
+        let nchannels = field(2) as usize;
+        let sample_rate = u32::from_le_bytes(format[4..8].try_into().unwrap()) as Float;
+        let width = field(14) as usize / 8;
+        if channel >= nchannels {
+            let message = format!("has {} channels, so there is no channel {}", nchannels, channel);
+            return Err(bad_file(path, &message))
+        }
+
+        let decode: fn(&[u8]) -> Float = match (encoding, width) {
+            (1, 1) => |bytes| (bytes[0] as Float - 128.0) / 128.0,
+            (1, 2) => |bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as Float / 32768.0,
+            (1, 3) => |bytes| {
+                i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as Float / 2147483648.0
+            },
+            (1, 4) => |bytes| {
+                i32::from_le_bytes(bytes.try_into().unwrap()) as Float / 2147483648.0
+            },
+            (3, 4) => |bytes| f32::from_le_bytes(bytes.try_into().unwrap()) as Float,
+            (3, 8) => |bytes| f64::from_le_bytes(bytes.try_into().unwrap()) as Float,
+            _ => {
+                let message = format!("has unsupported {} bit samples of encoding {}",
+                    8*width, encoding);
+                return Err(bad_file(path, &message))
+            },
+        };
+        let samples = data.chunks_exact(nchannels*width)
+            .map(|frame| decode(&frame[(channel*width)..((channel + 1)*width)]))
+            .collect();
+
+        Ok(Self::linear(samples, sample_rate, 0.0))
+    }
+
+    /// The waveform interpolating `samples` linearly.
+    fn linear(samples: Vec<Float>, sample_rate: Float, start_time: Float) -> Self {
+        Self { samples, sample_rate, start_time, interpolation: SampleInterpolation::Linear }
+    }
+}
+impl Waveform for SampledWaveform {
+    fn at(&self, time: Float) -> Float {
+        let position = (time - self.start_time) * self.sample_rate;
+        let reach = match self.interpolation {
+            SampleInterpolation::Linear => 1,
+            SampleInterpolation::Sinc { taps } => taps.max(1),
+        };
+        if !position.is_finite() {
+            return 0.0
+        }
+
+        // sum the samples within reach of the kernel, weighted by it
+        let nearest = position.floor();
+        let first = (nearest - reach as Float + 1.0).max(0.0);
+        let last = (nearest + reach as Float).min(self.samples.len() as Float - 1.0);
+        if first > last {
+            return 0.0
+        }
+        (first as usize..=last as usize)
+            .map(|k| {
+                let offset = position - k as Float;
+                let weight = match self.interpolation {
+                    SampleInterpolation::Linear => 1.0 - offset.abs(),
+                    SampleInterpolation::Sinc { .. } => {
+                        sinc(offset) * sinc(offset / reach as Float)
+                    },
+                };
+                self.samples[k] * weight
+            })
+            .sum()
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        let taps = match self.interpolation {
+            SampleInterpolation::Linear => 0,
+            SampleInterpolation::Sinc { taps } => taps.max(1),
+        };
+        ComponentSnapshot::new("SampledWaveform")
+            .with("samples", self.samples.clone())
+            .with("sample_rate", vec![self.sample_rate])
+            .with("start_time", vec![self.start_time])
+            .with("sinc_taps", vec![taps as Float])
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{ComponentSnapshot, Error, Float};
+use crate::fdtd::components::Envelope;
+use super::Waveform;
+
+/// The shape of the edge of a `Step`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Edge {
+    /// A raised cosine, which has no jump in voltage or slope.
+    RaisedCosine,
+    /// A straight line, as for a ramp.
+    Linear,
+}
+
+/// A step, for time domain reflectometry.
+///
+/// The step starts at `start` and rises to its amplitude over `rise_time` along its edge, or
+/// instantly for a rise time of zero.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Step {
+    pub amplitude: Float,
+    /// The time the step starts to rise.
+    pub start: Float,
+    /// The time from the start of the step to its full amplitude.
+    pub rise_time: Float,
+    pub edge: Edge,
+}
+impl Step {
+    /// Rebuilds a `Step` from its snapshot.
+    pub fn from_snapshot(snapshot: &ComponentSnapshot) -> Result<Self, Error> {
+        snapshot.expect_kind("Step")?;
+        Ok(Self {
+            amplitude: snapshot.get_scalar("amplitude")?,
+            start: snapshot.get_scalar("start")?,
+            rise_time: snapshot.get_scalar("rise_time")?,
+            edge: if snapshot.get_scalar("linear_edge")? != 0.0 {
+                Edge::Linear
+            } else {
+                Edge::RaisedCosine
+            },
+        })
+    }
+
+    /// The envelope tracing the edge of the step.
+    #[inline]
+    fn envelope(&self) -> Envelope {
+        let (start, rise_time) = (self.start, self.rise_time);
+        match self.edge {
+            Edge::RaisedCosine => Envelope::RaisedCosine { start, rise_time },
+            Edge::Linear => Envelope::Linear { start, rise_time },
+        }
+    }
+}
+impl Waveform for Step {
+    fn at(&self, time: Float) -> Float {
+        self.amplitude * self.envelope().gain(time)
+    }
+
+    fn snapshot(&self) -> ComponentSnapshot {
+        ComponentSnapshot::new("Step")
+            .with("amplitude", vec![self.amplitude])
+            .with("start", vec![self.start])
+            .with("rise_time", vec![self.rise_time])
+            .with("linear_edge", vec![(self.edge == Edge::Linear) as u8 as Float])
+    }
+}
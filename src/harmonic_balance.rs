@@ -0,0 +1,350 @@
+use std::f64::consts::PI;
+
+use num_complex::Complex;
+
+use crate::{warn, Error, Float};
+use crate::fdtd::TransmissionLine;
+use crate::fdtd::components::KiLine;
+
+/// The number of past iterations mixed into each update.
+const MIXING_DEPTH: usize = 5;
+
+/// The solution of an iteration, with how far it moved the fields it was solved from.
+type Iterate = (Vec<Complex<f64>>, Vec<Complex<f64>>);
+
+/// Describes a harmonic balance solution of a kinetic inductance line.
+pub struct HarmonicBalanceDescriptor {
+    /// The line, whose resistances are left out, so it should be lossless. It must be unbiased, with
+    /// a polynomial nonlinearity.
+    pub tline: KiLine,
+    /// The open circuit voltage of the source at the start of the line, which must repeat with the
+    /// period of `frequency`.
+    pub source_fn: Box<dyn Fn(Float) -> Float>,
+    /// The fundamental frequency, which every tone of the source is a harmonic of.
+    pub frequency: Float,
+    /// The number of harmonics of the fundamental to solve for.
+    pub harmonics: usize,
+    /// The internal resistance of the source, which must be positive.
+    pub source_resistance: Float,
+    /// The resistance loading the end of the line, which may be infinite for an open circuit.
+    pub load_resistance: Float,
+    /// The most iterations to take.
+    pub max_iterations: usize,
+    /// The largest change of any current harmonic between iterations, relative to the largest
+    /// current harmonic, at which the solution has converged.
+    pub tolerance: Float,
+}
+
+/// The periodic steady state of a line, by harmonic.
+#[derive(Clone, Debug)]
+pub struct HarmonicBalanceSolution {
+    /// The frequency of each harmonic, starting from DC.
+    pub frequencies: Vec<Float>,
+    /// The complex amplitude of the voltages of a state at each harmonic, with a row per harmonic.
+    ///
+    /// Amplitudes follow `analysis::spectrum::phasor`, so `A cos(2πft + φ)` is `A e^(iφ)`, and
+    /// the DC row holds the mean.
+    pub voltages: ndarray::Array2<Complex<Float>>,
+    /// The complex amplitude of the currents of a state at each harmonic, with a row per harmonic.
+    pub currents: ndarray::Array2<Complex<Float>>,
+    /// The number of iterations taken.
+    pub iterations: usize,
+    /// Whether the iterations met the tolerance.
+    pub converged: bool,
+}
+
+/// Solves for the periodic steady state of a kinetic inductance line directly, without running
+/// through the transient to reach it.
+///
+/// The line is discretized in space just like a `KiLine` in an `FdtdSolver`, with the source and
+/// load as resistances at its ends, and the voltages and currents are made up of harmonics of the
+/// fundamental. Each iteration takes the inductance of every cell averaged over a period as its
+/// linear part, solves each harmonic of the line exactly, and moves what's left of the nonlinear
+/// flux to the next iteration, mixing in the solutions of the last few iterations. This converges
+/// in tens of iterations while the currents are well below the critical current, but may not once
+/// the line steepens the drive into shock waves, which need more harmonics than it can resolve.
+///
+/// Harmonics past `harmonics` are dropped, so there should be enough to hold every mixing product
+/// with significant power. Biased lines and lines with a custom nonlinearity can't be solved.
+pub fn harmonic_balance(desc: HarmonicBalanceDescriptor) -> Result<HarmonicBalanceSolution, Error> {
+    let npoints = desc.tline.npoints();
+    let delta_z = (desc.tline.length() / (npoints as Float)) as f64;
+    let snapshot = desc.tline.snapshot();
+    if snapshot.get_scalar("custom_nonlinearity")? != 0.0 {
+        let message = "harmonic balance needs a polynomial nonlinearity, not a custom one";
+        return Err(Error::BadParameter(message.to_string()))
+    }
+    if snapshot.get("bias_current")?.iter().any(|&x| x != 0.0) {
+        let message = "harmonic balance needs an unbiased line";
+        return Err(Error::BadParameter(message.to_string()))
+    }
+    let [cap, ind, crit_cur] = ["capacitance", "inductance", "critical_current"].map(|name| {
+        snapshot.get(name).expect("kinetic inductance lines snapshot every parameter")
+            .iter()
+            .map(|&value| value as f64)
+            .collect::<Vec<_>>()
+    });
+    let coefficients = snapshot.get("nonlinearity")
+        .expect("kinetic inductance lines snapshot every parameter")
+        .iter()
+        .map(|&value| value as f64)
+        .collect::<Vec<_>>();
+
+    // enough samples of a period that the nonlinearity, which is cubic by default, doesn't alias
+    // onto the harmonics
+    let nharmonics = desc.harmonics + 1;
+    let nsamples = 2*(coefficients.len() + 1).max(2)*nharmonics;
+    let mut planner = rustfft::FftPlanner::<f64>::new();
+    let forward = planner.plan_fft_forward(nsamples);
+    let inverse = planner.plan_fft_inverse(nsamples);
+    let period = 1.0 / desc.frequency;
+    let to_harmonics = |samples: Vec<f64>| {
+        let mut buffer = samples.into_iter().map(|x| Complex::new(x, 0.0)).collect::<Vec<_>>();
+        forward.process(&mut buffer);
+        buffer.into_iter().take(nharmonics).map(|x| x / (nsamples as f64)).collect::<Vec<_>>()
+    };
+    let to_samples = |harmonics: &[Complex<f64>]| {
+        let mut buffer = vec![Complex::new(0.0, 0.0); nsamples];
+        buffer[0] = harmonics[0];
+        for k in 1..nharmonics {
+            buffer[k] = harmonics[k];
+            buffer[nsamples - k] = harmonics[k].conj();
+        }
+        inverse.process(&mut buffer);
+        buffer.into_iter().map(|x| x.re).collect::<Vec<_>>()
+    };
+    let source = to_harmonics(
+        (0..nsamples)
+            .map(|m| (desc.source_fn)((m as Float)*period / (nsamples as Float)) as f64)
+            .collect(),
+    );
+
+    // `fields[k]` holds the two sided amplitudes of harmonic `k`, alternating between the currents
+    // and voltages of each cell as `[I0, V1, I1, V2, ..]`
+    let size = 2*npoints;
+    let mut fields = vec![vec![Complex::new(0.0, 0.0); size]; nharmonics];
+    // the solutions of the last few iterations, with how far each moved the fields
+    let mut history = std::collections::VecDeque::new();
+    let (source_resistance, load_resistance) =
+        (desc.source_resistance as f64, desc.load_resistance as f64);
+    let load = if load_resistance.is_finite() { load_resistance.recip() } else { 0.0 };
+    let mut iterations = 0;
+    let mut converged = false;
+    while iterations < desc.max_iterations && !converged {
+        iterations += 1;
+
+        // the differential inductance is `L0 (1 + c1 I² / Ic² + c2 I⁴ / Ic⁴ + ..)`, as in `KiLine`,
+        // which is linearized about its mean
+        let mut inductances = Vec::with_capacity(npoints);
+        let mut residuals = Vec::with_capacity(npoints);
+        for n in 0..npoints {
+            let samples = to_samples(&fields.iter().map(|field| field[2*n]).collect::<Vec<_>>());
+            let flux = |i: f64| {
+                let normalized = (i / crit_cur[n]).powi(2);
+                let terms = coefficients.iter().enumerate()
+                    .map(|(k, c)| c * normalized.powi(k as i32 + 1) / (2.0*(k as f64) + 3.0))
+                    .sum::<f64>();
+                ind[n]*i*(1.0 + terms)
+            };
+            let mean_ratio = samples.iter()
+                .map(|i| {
+                    let normalized = (i / crit_cur[n]).powi(2);
+                    coefficients.iter().enumerate()
+                        .map(|(k, c)| c * normalized.powi(k as i32 + 1))
+                        .sum::<f64>()
+                })
+                .sum::<f64>() / (nsamples as f64);
+            let inductance = ind[n]*(1.0 + mean_ratio);
+            let residual = samples.iter()
+                .map(|&i| flux(i) - inductance*i)
+                .collect();
+            inductances.push(inductance);
+            residuals.push(to_harmonics(residual));
+        }
+
+        let solutions = (0..nharmonics)
+            .map(|k| {
+                let omega = Complex::new(0.0, 2.0*PI*(k as f64)*(desc.frequency as f64));
+                let mut lower = vec![Complex::new(-1.0, 0.0); size];
+                let mut upper = vec![Complex::new(1.0, 0.0); size];
+                let mut diagonal = Vec::with_capacity(size);
+                let mut rhs = Vec::with_capacity(size);
+                for n in 0..npoints {
+                    diagonal.push(omega*delta_z*inductances[n]);
+                    rhs.push(-omega*delta_z*residuals[n][k]);
+                    diagonal.push(omega*delta_z*cap[n]);
+                    rhs.push(Complex::new(0.0, 0.0));
+                }
+                diagonal[0] += source_resistance;
+                rhs[0] += source[k];
+                diagonal[size - 1] += load;
+                lower[0] = Complex::new(0.0, 0.0);
+                upper[size - 1] = Complex::new(0.0, 0.0);
+
+                solve_tridiagonal(&lower, &diagonal, &upper, rhs)
+            })
+            .collect::<Vec<_>>();
+
+        // mix the solution with those of the last few iterations, which cancels most of the
+        // overshoot that otherwise makes strongly driven lines converge slowly
+        let solution = solutions.into_iter().flatten().collect::<Vec<_>>();
+        let update = solution.iter().zip(fields.iter().flatten()).map(|(x, y)| x - y).collect();
+        history.push_back((solution, update));
+        if history.len() > MIXING_DEPTH + 1 {
+            history.pop_front();
+        }
+        let next = mix(&history);
+
+        let (mut change, mut largest): (f64, f64) = (0.0, 0.0);
+        for (k, field) in fields.iter_mut().enumerate() {
+            for (j, value) in field.iter_mut().enumerate() {
+                let next = next[k*size + j];
+                if j % 2 == 0 {
+                    change = change.max((next - *value).norm());
+                    largest = largest.max(next.norm());
+                }
+                *value = next;
+            }
+        }
+        converged = change <= (desc.tolerance as f64)*largest;
+    }
+    if !converged {
+        warn("harmonic_balance", "iterations did not converge");
+    }
+
+    // the source node drops the source resistance, and the end node is the load
+    let mut volt_amplitudes = ndarray::Array2::zeros((nharmonics, npoints + 2));
+    let mut curr_amplitudes = ndarray::Array2::zeros((nharmonics, npoints + 1));
+    for (k, field) in fields.iter().enumerate() {
+        let scale = if k == 0 { 1.0 } else { 2.0 };
+        let to_float = |value: Complex<f64>| {
+            Complex::new((scale*value.re) as Float, (scale*value.im) as Float)
+        };
+        let end_volt = field[size - 1];
+        let end_curr = if load_resistance.is_finite() {
+            end_volt / load_resistance
+        } else {
+            Complex::new(0.0, 0.0)
+        };
+        volt_amplitudes[[k, 0]] = to_float(source[k] - source_resistance*field[0]);
+        for n in 0..npoints {
+            curr_amplitudes[[k, n]] = to_float(field[2*n]);
+            volt_amplitudes[[k, n + 1]] = to_float(field[2*n + 1]);
+        }
+        volt_amplitudes[[k, npoints + 1]] = to_float(end_volt);
+        curr_amplitudes[[k, npoints]] = to_float(end_curr);
+    }
+
+    Ok(HarmonicBalanceSolution {
+        frequencies: (0..nharmonics).map(|k| (k as Float)*desc.frequency).collect(),
+        voltages: volt_amplitudes,
+        currents: curr_amplitudes,
+        iterations,
+        converged,
+    })
+}
+
+/// Mixes the latest solution in `history` with the earlier ones, Anderson style.
+///
+/// Each entry holds a solution and how far it moved the fields it was solved from. The mix is the
+/// combination of solutions whose updates would cancel as much as they can, assuming the updates
+/// change linearly with the fields.
+fn mix(history: &std::collections::VecDeque<Iterate>) -> Vec<Complex<f64>> {
+    let (solution, update) = history.back().expect("the latest solution is in the history");
+    let differences = history.iter()
+        .zip(history.iter().skip(1))
+        .map(|(earlier, later)| {
+            let difference = |a: &[Complex<f64>], b: &[Complex<f64>]| {
+                a.iter().zip(b).map(|(a, b)| a - b).collect::<Vec<_>>()
+            };
+            (difference(&later.0, &earlier.0), difference(&later.1, &earlier.1))
+        })
+        .collect::<Vec<_>>();
+    let dot = |a: &[Complex<f64>], b: &[Complex<f64>]| {
+        a.iter().zip(b).map(|(a, b)| (a.conj()*b).re).sum::<f64>()
+    };
+
+    // least squares weights from the normal equations, slightly regularized as the differences
+    // become nearly parallel close to convergence
+    let count = differences.len();
+    let mut matrix = vec![vec![0.0; count + 1]; count];
+    for i in 0..count {
+        for j in 0..count {
+            matrix[i][j] = dot(&differences[i].1, &differences[j].1);
+        }
+        matrix[i][i] *= 1.0 + 1e-10;
+        matrix[i][count] = dot(&differences[i].1, update);
+    }
+    let weights = solve_dense(matrix);
+
+    let mut mixed = solution.clone();
+    for ((solution_difference, _), weight) in differences.iter().zip(weights) {
+        for (value, difference) in mixed.iter_mut().zip(solution_difference) {
+            *value -= weight*difference;
+        }
+    }
+
+    mixed
+}
+
+/// Solves a small dense system by Gaussian elimination, with each row of `augmented` holding the
+/// coefficients followed by the right hand side.
+///
+/// Rows without a usable pivot get a weight of zero.
+fn solve_dense(mut augmented: Vec<Vec<f64>>) -> Vec<f64> {
+    let size = augmented.len();
+    for column in 0..size {
+        let pivot = (column..size)
+            .max_by(|&a, &b| augmented[a][column].abs().total_cmp(&augmented[b][column].abs()))
+            .expect("the column has rows below the diagonal");
+        augmented.swap(column, pivot);
+        if augmented[column][column].abs() <= Float::EPSILON as f64 {
+            continue
+        }
+        let pivot_row = augmented[column].clone();
+        for row in &mut augmented[(column + 1)..] {
+            let factor = row[column] / pivot_row[column];
+            for (value, pivot) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+                *value -= factor*pivot;
+            }
+        }
+    }
+
+    let mut solution = vec![0.0; size];
+    for row in (0..size).rev() {
+        if augmented[row][row].abs() <= Float::EPSILON as f64 {
+            continue
+        }
+        let known = ((row + 1)..size).map(|k| augmented[row][k]*solution[k]).sum::<f64>();
+        solution[row] = (augmented[row][size] - known) / augmented[row][row];
+    }
+
+    solution
+}
+
+/// Solves a tridiagonal system with the Thomas algorithm, where row `n` is
+/// `lower[n] x[n-1] + diagonal[n] x[n] + upper[n] x[n+1] = rhs[n]`.
+fn solve_tridiagonal(
+    lower: &[Complex<f64>],
+    diagonal: &[Complex<f64>],
+    upper: &[Complex<f64>],
+    mut rhs: Vec<Complex<f64>>,
+) -> Vec<Complex<f64>> {
+    let size = rhs.len();
+    let mut upper_scaled = vec![Complex::new(0.0, 0.0); size];
+    for n in 0..size {
+        let (previous_upper, previous_rhs) = if n == 0 {
+            (Complex::new(0.0, 0.0), Complex::new(0.0, 0.0))
+        } else {
+            (upper_scaled[n - 1], rhs[n - 1])
+        };
+        let pivot = diagonal[n] - lower[n]*previous_upper;
+        upper_scaled[n] = upper[n] / pivot;
+        rhs[n] = (rhs[n] - lower[n]*previous_rhs) / pivot;
+    }
+    for n in (0..(size - 1)).rev() {
+        rhs[n] = rhs[n] - upper_scaled[n]*rhs[n + 1];
+    }
+
+    rhs
+}
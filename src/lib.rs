@@ -2,15 +2,68 @@
 //!
 //! To get started, refer to the `\examples` directory in the main repository.
 
+// conversions to `f64` are only needed in single precision builds
+#![cfg_attr(feature = "f64", allow(clippy::unnecessary_cast))]
+
+mod checkpoint;
+mod control;
+mod csv_output;
+mod diagnostics;
+mod harmonic_balance;
+mod npy_output;
+#[cfg(feature = "parquet")]
+mod parquet_output;
+mod probe;
+mod provenance;
 mod simulation;
+mod snapshot;
+mod steady_state;
+mod sweep;
+mod units;
 
+pub mod analysis;
 pub mod fdtd;
 pub mod prelude;
+pub mod scenarios;
+
+/// The floating point type of every simulated quantity.
+///
+/// This is `f32`, or `f64` with the `f64` feature enabled.
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+/// The floating point type of every simulated quantity.
+///
+/// This is `f32`, or `f64` with the `f64` feature enabled.
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+/// Mathematical constants of the `Float` type.
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+/// Mathematical constants of the `Float` type.
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
 
 pub use simulation::{
-    RunDescriptor, SaveSettings, SaveType, Simulation, SimulationDescriptor, SimulationParameters,
-    SimulationState,
+    QuantityDiff, RunDescriptor, SaveFormat, SaveSettings, SaveType, Simulation,
+    SimulationDescriptor, SimulationParameters, SimulationState, StateDiff,
+};
+pub use checkpoint::{Checkpoint, CheckpointSettings, RunProgress};
+pub use control::{
+    Controller, Observation, Parameter, PidController, PidControllerDescriptor,
+};
+pub use diagnostics::{warn, Warning};
+pub use harmonic_balance::{
+    harmonic_balance, HarmonicBalanceDescriptor, HarmonicBalanceSolution,
 };
+pub use probe::{
+    EnergyProbe, EnergyProbeDescriptor, LockInProbe, LockInProbeDescriptor, Probe, ProbeTarget,
+    SpectralWindow, SpectrumProbe, SpectrumProbeDescriptor, StatisticsProbe,
+    StatisticsProbeDescriptor,
+};
+pub use snapshot::{ComponentSnapshot, SimulationSnapshot};
+pub use steady_state::SteadyStateSettings;
+pub use sweep::{FrequencyResponse, SweepDescriptor};
 
 /// Represents an error in the simulation.
 #[derive(thiserror::Error, Debug)]
@@ -23,21 +76,68 @@ pub enum Error {
         input_length: usize,
         expected_length: usize,
     },
+    #[error("Analysis input {array_name} array does not have expected length \
+        ( {array_name} array length: {input_length}, \
+        expected length: {expected_length} )")]
+    BadAnalysisInput {
+        array_name: String,
+        input_length: usize,
+        expected_length: usize,
+    },
+    #[error("Invalid snapshot ( {0} )")]
+    BadSnapshot(String),
+    #[error("Invalid grid ( {0} )")]
+    BadGrid(String),
+    #[error("Invalid parameter ( {0} )")]
+    BadParameter(String),
     #[error("There was an error during computation")]
     ComputationError(i32),
+    #[error("Unstable time step ( Courant number: {courant}, which must be at least 1 )")]
+    UnstableTimeStep {
+        courant: Float,
+    },
+    #[error("Simulation diverged ( non-finite {quantity} at time step {step}, index {cell} )")]
+    Diverged {
+        quantity: String,
+        step: usize,
+        cell: usize,
+    },
+    #[cfg(feature = "gpu")]
+    #[error("GPU error ( {0} )")]
+    GpuError(String),
+    #[cfg(feature = "cuda")]
+    #[error("CUDA error ( {0} )")]
+    CudaError(String),
     #[error(transparent)]
     H5Error(#[from] hdf5::Error),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[cfg(feature = "parquet")]
+    #[error(transparent)]
+    ParquetError(#[from] parquet::errors::ParquetError),
 }
 
 /// Manages actual computations.
 pub trait Solver {
-    /// Generates voltage and current data for a set of times.
+    /// Generates voltage, current, and any other channel data for a set of times.
     fn compute(
         &mut self,
         desc: ComputeDescriptor,
-    ) -> Result<(ndarray::Array2<f32>, ndarray::Array2<f32>), Error>;
+    ) -> Result<ComputeOutput, Error>;
 
     fn npoints(&self) -> usize;
+
+    /// Gets the fastest phase velocity anywhere on the line, if the solver is limited by it.
+    ///
+    /// Simulations check their time step against this when created.
+    fn max_phase_velocity(&self) -> Option<Float> {
+        None
+    }
+
+    /// Captures the configuration of each component, by role.
+    fn snapshot(&self) -> std::collections::BTreeMap<String, ComponentSnapshot> {
+        std::collections::BTreeMap::new()
+    }
 }
 
 /// Describes how a `StandardSolver` should do computations.
@@ -47,3 +147,15 @@ pub struct ComputeDescriptor<'a> {
     pub nsteps: usize,
     pub bar: &'a Option<indicatif::ProgressBar>,
 }
+
+/// The data generated by a `Solver`, with one row for the initial state and each time step.
+pub struct ComputeOutput {
+    /// The voltages of each point along the line.
+    pub voltages: ndarray::Array2<Float>,
+    /// The currents of each point along the line.
+    pub currents: ndarray::Array2<Float>,
+    /// Any additional component defined channels, by name.
+    pub channels: std::collections::BTreeMap<String, ndarray::Array2<Float>>,
+    /// The SI units of each channel, by name.
+    pub channel_units: std::collections::BTreeMap<String, &'static str>,
+}
@@ -0,0 +1,281 @@
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{ComputeOutput, Error, Float};
+
+/// The NumPy type descriptor of little endian `Float` values.
+#[cfg(not(feature = "f64"))]
+const DESCR: &str = "<f4";
+/// The NumPy type descriptor of little endian `Float` values.
+#[cfg(feature = "f64")]
+const DESCR: &str = "<f8";
+
+/// The files of a NumPy save, with each array streamed to its own `.npy` file as the run goes.
+///
+/// The shapes of the arrays are fixed when they are created, so every file is complete once the
+/// run is, and runs that stop early rewrite them when closed. Archives are packed from the files
+/// when the save is closed.
+pub(crate) struct NpyOutput {
+    /// The name, path, and open file of each array.
+    arrays: Vec<(String, PathBuf, std::io::BufWriter<std::fs::File>)>,
+    /// The shape of each array, in the same order.
+    shapes: Vec<Vec<usize>>,
+    /// Where to pack the arrays, if into an archive.
+    archive: Option<PathBuf>,
+}
+
+impl NpyOutput {
+    /// Creates the files of a save of `nsteps` time steps, failing if they exist and `overwrite`
+    /// isn't set.
+    ///
+    /// Arrays are saved to `<stem>_<name>.npy` next to the file, or packed into the file itself
+    /// if `archive` is set. `full` sets whether the voltages and currents of every point are
+    /// saved, in that order.
+    pub(crate) fn create(
+        filename: &Path,
+        full: [bool; 2],
+        archive: bool,
+        overwrite: bool,
+        nsteps: usize,
+        total_points: usize,
+    ) -> Result<Self, Error> {
+        let mut shapes = vec![
+            ("time", vec![nsteps]),
+            ("start_voltages", vec![nsteps]),
+            ("start_currents", vec![nsteps]),
+            ("end_voltages", vec![nsteps]),
+            ("end_currents", vec![nsteps]),
+        ];
+        if full[0] {
+            shapes.push(("voltages", vec![nsteps, total_points + 1]));
+        }
+        if full[1] {
+            shapes.push(("currents", vec![nsteps, total_points]));
+        }
+
+        if archive {
+            check_overwrite(filename, overwrite)?;
+        }
+        let stem = filename.file_stem().unwrap_or_default().to_string_lossy();
+        let mut arrays = Vec::new();
+        for (name, shape) in &shapes {
+            let path = if archive {
+                let mut path = filename.as_os_str().to_owned();
+                path.push(format!(".{}.tmp", name));
+                PathBuf::from(path)
+            } else {
+                filename.with_file_name(format!("{}_{}.npy", stem, name))
+            };
+            check_overwrite(&path, overwrite)?;
+
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&path)?);
+            file.write_all(&npy_header(shape, 0))?;
+            arrays.push((name.to_string(), path, file));
+        }
+
+        Ok(Self {
+            arrays,
+            shapes: shapes.into_iter().map(|(_, shape)| shape).collect(),
+            archive: archive.then(|| filename.to_path_buf()),
+        })
+    }
+
+    /// Appends the `niters` time steps of a computed chunk, whose initial row is at `start_time`.
+    pub(crate) fn write_chunk(
+        &mut self,
+        data: &ComputeOutput,
+        niters: usize,
+        start_time: Float,
+        delta_t: Float,
+    ) -> Result<(), Error> {
+        let ComputeOutput { ref voltages, ref currents, .. } = *data;
+        let rows = 1..=niters;
+        let last = voltages.ncols() - 1;
+
+        let times = rows.clone()
+            .map(|row| start_time + (row as Float)*delta_t)
+            .collect::<ndarray::Array1<Float>>();
+        // the full arrays only exist when saving them
+        let chunks = [
+            ("time", times.view().into_dyn()),
+            ("start_voltages", voltages.slice(ndarray::s![rows.clone(), 0]).into_dyn()),
+            ("start_currents", currents.slice(ndarray::s![rows.clone(), 0]).into_dyn()),
+            ("end_voltages", voltages.slice(ndarray::s![rows.clone(), last]).into_dyn()),
+            ("end_currents", currents.slice(ndarray::s![rows.clone(), last - 1]).into_dyn()),
+            ("voltages", voltages.slice(ndarray::s![rows.clone(), ..]).into_dyn()),
+            ("currents", currents.slice(ndarray::s![rows.clone(), ..]).into_dyn()),
+        ];
+        for (name, _, file) in &mut self.arrays {
+            let (_, values) = chunks.iter()
+                .find(|(chunk_name, _)| chunk_name == name)
+                .expect("every array has a chunk");
+            for value in values.iter() {
+                file.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flushes the files, and packs them into the archive if there is one.
+    ///
+    /// The shapes of the arrays are cut down to `nsteps` time steps if fewer were saved than they
+    /// were created for.
+    pub(crate) fn close(self, nsteps: usize) -> Result<(), Error> {
+        let mut paths = Vec::new();
+        for ((name, path, mut file), shape) in self.arrays.into_iter().zip(self.shapes) {
+            file.flush()?;
+            if nsteps < shape[0] {
+                // the header is padded to its old length, so the data stays where it is
+                let old_len = npy_header(&shape, 0).len();
+                let shape = std::iter::once(nsteps).chain(shape[1..].iter().copied())
+                    .collect::<Vec<_>>();
+                let mut file = file.into_inner().map_err(|error| error.into_error())?;
+                file.rewind()?;
+                file.write_all(&npy_header(&shape, old_len))?;
+                let data_len = shape.iter().product::<usize>()*std::mem::size_of::<Float>();
+                file.set_len((old_len + data_len) as u64)?;
+            }
+            paths.push((name, path));
+        }
+
+        if let Some(archive) = self.archive {
+            write_archive(&archive, &paths)?;
+            for (_, path) in paths {
+                std::fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Fails if `path` exists and `overwrite` isn't set, since NumPy files can't be extended.
+fn check_overwrite(path: &Path, overwrite: bool) -> Result<(), Error> {
+    if path.exists() && !overwrite {
+        return Err(Error::IoError(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} exists and NumPy files can't be extended", path.display()),
+        )))
+    }
+
+    Ok(())
+}
+
+/// Gets the header of a version 1.0 `.npy` file holding a C ordered array of `shape`, padded to
+/// at least `min_len` bytes.
+fn npy_header(shape: &[usize], min_len: usize) -> Vec<u8> {
+    let shape = match shape {
+        [len] => format!("({},)", len),
+        _ => format!(
+            "({})",
+            shape.iter().map(|len| len.to_string()).collect::<Vec<_>>().join(", "),
+        ),
+    };
+    let mut dict = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        DESCR,
+        shape,
+    );
+    // the header is padded with spaces and a newline so the data is aligned to 64 bytes
+    let unpadded = 10 + dict.len() + 1;
+    let padded = unpadded.div_ceil(64)*64;
+    dict.push_str(&" ".repeat(padded.max(min_len) - unpadded));
+    dict.push('\n');
+
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend((dict.len() as u16).to_le_bytes());
+    header.extend(dict.as_bytes());
+    header
+}
+
+/// Packs `.npy` files into an uncompressed `.npz` archive, as an entry of each name.
+///
+/// The archive is a plain zip file, so each file must be smaller than 4 GiB.
+fn write_archive(archive: &Path, files: &[(String, PathBuf)]) -> Result<(), Error> {
+    let mut output = std::io::BufWriter::new(std::fs::File::create(archive)?);
+    let mut central_directory = Vec::new();
+    let mut offset = 0u64;
+
+    for (name, path) in files {
+        let size = std::fs::metadata(path)?.len();
+        let (size, local_offset) = match (u32::try_from(size), u32::try_from(offset)) {
+            (Ok(size), Ok(offset)) => (size, offset),
+            _ => return Err(Error::IoError(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "arrays of 4 GiB or more can't be packed into an archive",
+            ))),
+        };
+        let name = format!("{}.npy", name);
+        let crc = crc32(path)?;
+
+        // the fields shared by the local and central headers: version, flags, stored method,
+        // a 1980-01-01 timestamp, checksum, sizes, and name length
+        let mut fields = Vec::new();
+        for half in [20u16, 0, 0, 0, 0x21] {
+            fields.extend(half.to_le_bytes());
+        }
+        for word in [crc, size, size] {
+            fields.extend(word.to_le_bytes());
+        }
+        fields.extend((name.len() as u16).to_le_bytes());
+
+        output.write_all(&0x04034b50u32.to_le_bytes())?;
+        output.write_all(&fields)?;
+        output.write_all(&0u16.to_le_bytes())?;
+        output.write_all(name.as_bytes())?;
+        std::io::copy(&mut std::fs::File::open(path)?, &mut output)?;
+
+        central_directory.extend(0x02014b50u32.to_le_bytes());
+        central_directory.extend(20u16.to_le_bytes());
+        central_directory.extend(&fields);
+        // no extra field, comment, disk, or attributes
+        central_directory.extend([0u8; 12]);
+        central_directory.extend(local_offset.to_le_bytes());
+        central_directory.extend(name.as_bytes());
+
+        offset += 30 + name.len() as u64 + size as u64;
+    }
+
+    let directory_offset = u32::try_from(offset).map_err(|_| std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "archives of 4 GiB or more aren't supported",
+    ))?;
+    output.write_all(&central_directory)?;
+    output.write_all(&0x06054b50u32.to_le_bytes())?;
+    for half in [0u16, 0, files.len() as u16, files.len() as u16] {
+        output.write_all(&half.to_le_bytes())?;
+    }
+    output.write_all(&(central_directory.len() as u32).to_le_bytes())?;
+    output.write_all(&directory_offset.to_le_bytes())?;
+    output.write_all(&0u16.to_le_bytes())?;
+    output.flush()?;
+
+    Ok(())
+}
+
+/// Calculates the CRC-32 checksum of a file, as used by zip files.
+fn crc32(path: &Path) -> Result<u32, Error> {
+    let table = (0..256u32)
+        .map(|byte| {
+            (0..8).fold(byte, |crc, _| {
+                if crc & 1 == 1 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut buffer = vec![0u8; 1 << 16];
+    let mut crc = !0u32;
+    loop {
+        let len = file.read(&mut buffer)?;
+        if len == 0 {
+            break
+        }
+        for &byte in &buffer[..len] {
+            crc = table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+        }
+    }
+
+    Ok(!crc)
+}
@@ -0,0 +1,183 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parquet::data_type::Int32Type;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+
+use crate::{ComputeOutput, Error, Float};
+
+/// The Parquet type of `Float` values.
+#[cfg(not(feature = "f64"))]
+type FloatType = parquet::data_type::FloatType;
+/// The Parquet type of `Float` values.
+#[cfg(feature = "f64")]
+type FloatType = parquet::data_type::DoubleType;
+/// The name of the physical type of `Float` values in schemas.
+#[cfg(not(feature = "f64"))]
+const FLOAT_TYPE: &str = "FLOAT";
+/// The name of the physical type of `Float` values in schemas.
+#[cfg(feature = "f64")]
+const FLOAT_TYPE: &str = "DOUBLE";
+
+/// A column of a row group.
+enum Column {
+    Float(Vec<Float>),
+    Index(Vec<i32>),
+}
+
+/// The open files of a Parquet save, with a row group for each computed chunk.
+///
+/// Parquet files can't be extended, so they are written as the run goes and closed at its end.
+pub(crate) struct ParquetOutput {
+    end: SerializedFileWriter<std::fs::File>,
+    /// The writers of the voltages and currents of every point, when saving them.
+    full: [Option<SerializedFileWriter<std::fs::File>>; 2],
+    /// The paths of every open file.
+    paths: Vec<PathBuf>,
+}
+
+impl ParquetOutput {
+    /// Creates the files of a save, failing if they exist and `overwrite` isn't set.
+    ///
+    /// `full` sets whether the voltages and currents of every point are saved, in that order, to
+    /// `<stem>_voltages.parquet` and `<stem>_currents.parquet` next to the file. They are saved in
+    /// long form with a row for each point and time step.
+    pub(crate) fn create(filename: &Path, full: [bool; 2], overwrite: bool) -> Result<Self, Error> {
+        let end = writer(
+            filename,
+            "end",
+            &["time", "start_voltage", "start_current", "end_voltage", "end_current"],
+            overwrite,
+        )?;
+        let mut paths = vec![filename.to_path_buf()];
+        let stem = filename.file_stem().unwrap_or_default().to_string_lossy();
+        let mut full_writer = |quantity: &str, column: &str, saved: bool| -> Result<_, Error> {
+            if !saved {
+                return Ok(None)
+            }
+            let path = filename.with_file_name(format!("{}_{}.parquet", stem, quantity));
+            let writer = writer(&path, quantity, &["time", "index", column], overwrite)?;
+            paths.push(path);
+            Ok(Some(writer))
+        };
+        let full = [
+            full_writer("voltages", "voltage", full[0])?,
+            full_writer("currents", "current", full[1])?,
+        ];
+
+        Ok(Self { end, full, paths })
+    }
+
+    /// Writes the `niters` time steps of a computed chunk, whose initial row is at `start_time`.
+    pub(crate) fn write_chunk(
+        &mut self,
+        data: &ComputeOutput,
+        niters: usize,
+        start_time: Float,
+        delta_t: Float,
+    ) -> Result<(), Error> {
+        let ComputeOutput { ref voltages, ref currents, .. } = *data;
+        let rows = 1..=niters;
+        let times = rows.clone()
+            .map(|row| start_time + (row as Float)*delta_t)
+            .collect::<Vec<_>>();
+        let last = voltages.ncols() - 1;
+        let column = |values: ndarray::ArrayView1<Float>| Column::Float(values.to_vec());
+
+        write_row_group(&mut self.end, &[
+            Column::Float(times.clone()),
+            column(voltages.slice(ndarray::s![rows.clone(), 0])),
+            column(currents.slice(ndarray::s![rows.clone(), 0])),
+            column(voltages.slice(ndarray::s![rows.clone(), last])),
+            column(currents.slice(ndarray::s![rows.clone(), last - 1])),
+        ])?;
+
+        for (writer, values) in self.full.iter_mut().zip([voltages, currents]) {
+            if let Some(ref mut writer) = *writer {
+                let npoints = values.ncols();
+                let values = values.slice(ndarray::s![rows.clone(), ..]).to_owned();
+                write_row_group(writer, &[
+                    Column::Float(times.iter().flat_map(|&time| vec![time; npoints]).collect()),
+                    Column::Index((0..niters).flat_map(|_| 0..(npoints as i32)).collect()),
+                    Column::Float(values.into_raw_vec()),
+                ])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the footers of the files, without which they can't be read.
+    ///
+    /// If any file can't be finished, every file is deleted rather than left unreadable.
+    pub(crate) fn close(self) -> Result<(), Error> {
+        let Self { end, full, paths } = self;
+        let closed = std::iter::once(end)
+            .chain(full.into_iter().flatten())
+            .try_for_each(|writer| writer.close().map(|_| ()));
+        if closed.is_err() {
+            for path in &paths {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+
+        closed.map_err(Error::from)
+    }
+}
+
+/// Creates a file with a required column of each name, holding `Float` values except for an
+/// integer `index` column.
+fn writer(
+    path: &Path,
+    name: &str,
+    columns: &[&str],
+    overwrite: bool,
+) -> Result<SerializedFileWriter<std::fs::File>, Error> {
+    if path.exists() && !overwrite {
+        return Err(Error::IoError(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} exists and Parquet files can't be extended", path.display()),
+        )))
+    }
+
+    let fields = columns.iter()
+        .map(|&column| {
+            let column_type = if column == "index" { "INT32" } else { FLOAT_TYPE };
+            format!("REQUIRED {} {};", column_type, column)
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let schema = parse_message_type(&format!("message {} {{ {} }}", name, fields))?;
+    let file = std::fs::File::create(path)?;
+    Ok(SerializedFileWriter::new(
+        file,
+        Arc::new(schema),
+        Arc::new(WriterProperties::builder().build()),
+    )?)
+}
+
+/// Writes a row group with a value of each column per row, in the order of the schema.
+fn write_row_group(
+    writer: &mut SerializedFileWriter<std::fs::File>,
+    columns: &[Column],
+) -> Result<(), Error> {
+    let mut row_group = writer.next_row_group()?;
+    for column in columns {
+        let mut column_writer = row_group.next_column()?
+            .expect("the schema has a column for each value");
+        match *column {
+            Column::Float(ref values) => {
+                column_writer.typed::<FloatType>().write_batch(values, None, None)?;
+            },
+            Column::Index(ref values) => {
+                column_writer.typed::<Int32Type>().write_batch(values, None, None)?;
+            },
+        }
+        column_writer.close()?;
+    }
+    row_group.close()?;
+
+    Ok(())
+}
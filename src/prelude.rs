@@ -1,14 +1,25 @@
 //! Includes commonly used library components.
 
 pub use crate::{
+    Checkpoint,
+    CheckpointSettings,
+    ComponentSnapshot,
     ComputeDescriptor,
+    ComputeOutput,
+    Float,
+    Controller,
+    Parameter,
+    Probe,
     RunDescriptor,
+    SaveFormat,
     SaveSettings,
     SaveType,
     Simulation,
     SimulationDescriptor,
     SimulationParameters,
+    SimulationSnapshot,
     SimulationState,
     Solver,
+    SteadyStateSettings,
 };
-pub use crate::fdtd::TransmissionLine;
+pub use crate::fdtd::{LineParameters, TransmissionLine};
@@ -0,0 +1,619 @@
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use num_complex::Complex;
+
+use crate::analysis::spectrum;
+use crate::{diagnostics, units, ComponentSnapshot, Error, Float, Observation};
+
+/// Measures quantities online as a simulation runs, without storing full waveforms.
+pub trait Probe {
+    /// Accumulates the data of each time step of a computed chunk.
+    fn record(&mut self, observation: &Observation);
+
+    /// Gets the results accumulated so far, by dataset name.
+    fn results(&self) -> BTreeMap<String, ndarray::ArrayD<Float>>;
+
+    /// Gets the SI units of a dataset of the results, if it has any.
+    fn units(&self, _dataset: &str) -> Option<&'static str> {
+        None
+    }
+}
+
+/// A quantity measured by a probe.
+#[derive(Copy, Clone, Debug)]
+pub enum ProbeTarget {
+    /// The voltage at an index into the voltages of the state.
+    Voltage(usize),
+    /// The current at an index into the currents of the state.
+    Current(usize),
+}
+
+impl ProbeTarget {
+    /// Gets the SI units of the target.
+    pub fn units(&self) -> &'static str {
+        match *self {
+            ProbeTarget::Voltage(_) => "V",
+            ProbeTarget::Current(_) => "A",
+        }
+    }
+
+    /// Gets the SI units of the power density of the target.
+    fn density_units(&self) -> &'static str {
+        match *self {
+            ProbeTarget::Voltage(_) => "V^2/Hz",
+            ProbeTarget::Current(_) => "A^2/Hz",
+        }
+    }
+
+    /// Gets the values of the target at each time step of an observation.
+    ///
+    /// Panics if the index is out of range.
+    #[inline]
+    pub fn values<'a>(&self, observation: &Observation<'a>) -> ndarray::ArrayView1<'a, Float> {
+        match *self {
+            ProbeTarget::Voltage(index) => {
+                observation.voltages.index_axis_move(ndarray::Axis(1), index)
+            },
+            ProbeTarget::Current(index) => {
+                observation.currents.index_axis_move(ndarray::Axis(1), index)
+            },
+        }
+    }
+}
+
+/// Gets the time of the first time step of an observation.
+fn first_time(observation: &Observation) -> f64 {
+    let nrows = observation.voltages.nrows().max(1);
+    (observation.state.time as f64) - ((nrows - 1) as f64)*(observation.sim_params.delta_t as f64)
+}
+
+/// Describes a `LockInProbe`.
+pub struct LockInProbeDescriptor {
+    /// The quantity to measure.
+    pub target: ProbeTarget,
+    /// The frequencies to measure the amplitude and phase at.
+    pub frequencies: Vec<Float>,
+    /// The number of time steps in each measurement.
+    pub interval: usize,
+}
+
+/// Measures the amplitude and phase of a quantity at a set of frequencies over time.
+///
+/// A single bin DFT is accumulated for each frequency over each interval, so only one value per
+/// frequency is stored for each interval. Phases are relative to a cosine starting at time zero.
+pub struct LockInProbe {
+    target: ProbeTarget,
+    frequencies: Vec<Float>,
+    interval: usize,
+    sums: Vec<Complex<f64>>,
+    phasors: Vec<Complex<f64>>,
+    nsamples: usize,
+    times: Vec<Float>,
+    amplitudes: Vec<Float>,
+    phases: Vec<Float>,
+}
+
+impl LockInProbe {
+    /// Creates a new `LockInProbe` instance.
+    #[inline]
+    pub fn new(desc: LockInProbeDescriptor) -> Self {
+        let nfrequencies = desc.frequencies.len();
+        Self {
+            target: desc.target,
+            frequencies: desc.frequencies,
+            interval: desc.interval.max(1),
+            sums: vec![Complex::new(0.0, 0.0); nfrequencies],
+            phasors: vec![Complex::new(1.0, 0.0); nfrequencies],
+            nsamples: 0,
+            times: Vec::new(),
+            amplitudes: Vec::new(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+impl Probe for LockInProbe {
+    fn record(&mut self, observation: &Observation) {
+        let delta_t = observation.sim_params.delta_t as f64;
+        let mut time = first_time(observation);
+        let rotations = self.frequencies.iter()
+            .map(|&frequency| Complex::from_polar(1.0, -2.0*PI*(frequency as f64)*delta_t))
+            .collect::<Vec<_>>();
+
+        for &value in self.target.values(observation) {
+            if self.nsamples == 0 {
+                // start each interval from an exact phasor so rounding errors don't build up
+                for (phasor, &frequency) in self.phasors.iter_mut().zip(&self.frequencies) {
+                    *phasor = Complex::from_polar(1.0, -2.0*PI*(frequency as f64)*time);
+                }
+            }
+
+            let accumulators = self.sums.iter_mut().zip(&mut self.phasors).zip(&rotations);
+            for ((sum, phasor), rotation) in accumulators {
+                *sum += *phasor * (value as f64);
+                *phasor *= rotation;
+            }
+            self.nsamples += 1;
+
+            if self.nsamples == self.interval {
+                self.times.push(time as Float);
+                for sum in &mut self.sums {
+                    self.amplitudes.push((2.0*sum.norm() / (self.interval as f64)) as Float);
+                    self.phases.push(sum.arg() as Float);
+                    *sum = Complex::new(0.0, 0.0);
+                }
+                self.nsamples = 0;
+            }
+            time += delta_t;
+        }
+    }
+
+    /// Gets the `times` at the end of each interval, the measured `frequencies`, and the
+    /// `amplitudes` and `phases` of each interval and frequency.
+    fn results(&self) -> BTreeMap<String, ndarray::ArrayD<Float>> {
+        let shape = ndarray::IxDyn(&[self.times.len(), self.frequencies.len()]);
+        let mut results = BTreeMap::new();
+        results.insert("times".to_string(), ndarray::arr1(&self.times).into_dyn());
+        results.insert("frequencies".to_string(), ndarray::arr1(&self.frequencies).into_dyn());
+        results.insert(
+            "amplitudes".to_string(),
+            ndarray::ArrayD::from_shape_vec(shape.clone(), self.amplitudes.clone()).unwrap(),
+        );
+        results.insert(
+            "phases".to_string(),
+            ndarray::ArrayD::from_shape_vec(shape, self.phases.clone()).unwrap(),
+        );
+
+        results
+    }
+
+    fn units(&self, dataset: &str) -> Option<&'static str> {
+        match dataset {
+            "times" => Some("s"),
+            "frequencies" => Some("Hz"),
+            "amplitudes" => Some(self.target.units()),
+            "phases" => Some("rad"),
+            _ => None,
+        }
+    }
+}
+
+/// Describes a `StatisticsProbe`.
+pub struct StatisticsProbeDescriptor {
+    /// The quantities to measure.
+    pub targets: Vec<ProbeTarget>,
+}
+
+/// Measures the running mean, RMS, variance, and peak of quantities over every recorded time step.
+pub struct StatisticsProbe {
+    targets: Vec<ProbeTarget>,
+    nsamples: usize,
+    means: Vec<f64>,
+    squared_deviations: Vec<f64>,
+    peaks: Vec<Float>,
+}
+
+impl StatisticsProbe {
+    /// Creates a new `StatisticsProbe` instance.
+    #[inline]
+    pub fn new(desc: StatisticsProbeDescriptor) -> Self {
+        let ntargets = desc.targets.len();
+        Self {
+            targets: desc.targets,
+            nsamples: 0,
+            means: vec![0.0; ntargets],
+            squared_deviations: vec![0.0; ntargets],
+            peaks: vec![0.0; ntargets],
+        }
+    }
+}
+
+impl Probe for StatisticsProbe {
+    fn record(&mut self, observation: &Observation) {
+        let nsamples = self.nsamples;
+        for (k, target) in self.targets.iter().enumerate() {
+            // Welford's algorithm, which stays accurate over very long runs
+            let mut count = nsamples as f64;
+            for &value in target.values(observation) {
+                count += 1.0;
+                let delta = (value as f64) - self.means[k];
+                self.means[k] += delta / count;
+                self.squared_deviations[k] += delta * ((value as f64) - self.means[k]);
+                self.peaks[k] = self.peaks[k].max(value.abs());
+            }
+        }
+        self.nsamples += observation.voltages.nrows();
+    }
+
+    /// Gets the `mean`, `rms`, population `variance`, and absolute `peak` of each target.
+    fn results(&self) -> BTreeMap<String, ndarray::ArrayD<Float>> {
+        let count = self.nsamples.max(1) as f64;
+        let means = ndarray::Array1::from_vec(self.means.clone());
+        let variances = ndarray::Array1::from_vec(self.squared_deviations.clone()) / count;
+        let rms = (&means*&means + &variances).mapv(f64::sqrt);
+
+        let mut results = BTreeMap::new();
+        results.insert("mean".to_string(), means.mapv(|x| x as Float).into_dyn());
+        results.insert("rms".to_string(), rms.mapv(|x| x as Float).into_dyn());
+        results.insert("variance".to_string(), variances.mapv(|x| x as Float).into_dyn());
+        results.insert("peak".to_string(), ndarray::arr1(&self.peaks).into_dyn());
+
+        results
+    }
+
+    /// The units of the targets, when they are all voltages or all currents.
+    fn units(&self, dataset: &str) -> Option<&'static str> {
+        let units = self.targets.first()?.units();
+        if self.targets.iter().any(|target| target.units() != units) {
+            return None
+        }
+        match dataset {
+            "mean" | "rms" | "peak" => Some(units),
+            "variance" => Some(if units == "V" { "V^2" } else { "A^2" }),
+            _ => None,
+        }
+    }
+}
+
+/// A window applied to each segment of a `SpectrumProbe`.
+#[derive(Copy, Clone, Debug)]
+pub enum SpectralWindow {
+    /// Leaves the segment unchanged, giving the narrowest peaks but the most leakage.
+    Rectangular,
+    /// The Hann window.
+    Hann,
+    /// The Blackman window, with less leakage than the Hann window but wider peaks.
+    Blackman,
+}
+
+impl SpectralWindow {
+    /// Gets the weights of the window over a segment of `length` samples.
+    pub fn weights(&self, length: usize) -> Vec<f64> {
+        let denominator = length.max(1) as f64;
+        (0..length)
+            .map(|n| {
+                let x = 2.0*PI*(n as f64) / denominator;
+                match *self {
+                    SpectralWindow::Rectangular => 1.0,
+                    SpectralWindow::Hann => 0.5 - 0.5*x.cos(),
+                    SpectralWindow::Blackman => 0.42 - 0.5*x.cos() + 0.08*(2.0*x).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Describes a `SpectrumProbe`.
+pub struct SpectrumProbeDescriptor {
+    /// The quantity to measure, usually the voltage at the end of the line.
+    pub target: ProbeTarget,
+    /// The number of time steps in each transformed segment.
+    pub segment_length: usize,
+    /// The window applied to each segment.
+    pub window: SpectralWindow,
+    /// The number of time steps to skip before recording, so transients can settle.
+    pub skip: usize,
+}
+
+/// Measures the averaged spectrum of a quantity with Welch's method.
+///
+/// The recorded time steps are split into segments that overlap by half, and the power of each
+/// windowed segment's FFT is averaged, so only one segment of samples is ever stored.
+pub struct SpectrumProbe {
+    target: ProbeTarget,
+    weights: Vec<f64>,
+    skip: usize,
+    nskipped: usize,
+    buffer: Vec<Float>,
+    power: Vec<f64>,
+    nsegments: usize,
+    delta_t: f64,
+}
+
+impl SpectrumProbe {
+    /// Creates a new `SpectrumProbe` instance.
+    #[inline]
+    pub fn new(desc: SpectrumProbeDescriptor) -> Self {
+        let segment_length = desc.segment_length.max(2);
+        Self {
+            target: desc.target,
+            weights: desc.window.weights(segment_length),
+            skip: desc.skip,
+            nskipped: 0,
+            buffer: Vec::with_capacity(segment_length),
+            power: vec![0.0; segment_length/2 + 1],
+            nsegments: 0,
+            delta_t: 0.0,
+        }
+    }
+
+    /// Adds the power of the FFT of the buffered segment to the accumulated power.
+    fn transform_segment(&mut self) {
+        let segment = self.buffer.iter()
+            .zip(&self.weights)
+            .map(|(&value, &weight)| Complex::new(((value as f64)*weight) as Float, 0.0))
+            .collect();
+        for (power, value) in self.power.iter_mut().zip(spectrum::fft(segment)) {
+            *power += value.norm_sqr() as f64;
+        }
+        self.nsegments += 1;
+    }
+}
+
+impl Probe for SpectrumProbe {
+    fn record(&mut self, observation: &Observation) {
+        self.delta_t = observation.sim_params.delta_t as f64;
+        let segment_length = self.weights.len();
+
+        for &value in self.target.values(observation) {
+            if self.nskipped < self.skip {
+                self.nskipped += 1;
+                continue;
+            }
+
+            self.buffer.push(value);
+            if self.buffer.len() == segment_length {
+                self.transform_segment();
+                // keep the second half as the start of the next segment
+                self.buffer.drain(..segment_length/2);
+            }
+        }
+    }
+
+    /// Gets the `frequencies` of each bin, the averaged `amplitudes` scaled so a steady tone of
+    /// amplitude `A` gives `A`, the one-sided `power_density` in units squared per hertz, and the
+    /// number of averaged `segments`.
+    fn results(&self) -> BTreeMap<String, ndarray::ArrayD<Float>> {
+        let n = self.weights.len();
+        let count = self.nsegments.max(1) as f64;
+        let weight_sum = self.weights.iter().sum::<f64>();
+        let weight_squares = self.weights.iter().map(|w| w*w).sum::<f64>();
+        let sample_rate = if self.delta_t > 0.0 { 1.0 / self.delta_t } else { 0.0 };
+
+        let frequencies = (0..self.power.len())
+            .map(|k| ((k as f64) * sample_rate / (n as f64)) as Float)
+            .collect::<Vec<_>>();
+        let one_sided = |k: usize| if k == 0 || 2*k == n { 1.0 } else { 2.0 };
+        let amplitudes = self.power.iter()
+            .enumerate()
+            .map(|(k, &power)| (one_sided(k)*(power/count).sqrt() / weight_sum) as Float)
+            .collect::<Vec<_>>();
+        let power_density = self.power.iter()
+            .enumerate()
+            .map(|(k, &power)| {
+                let scale = if sample_rate > 0.0 { sample_rate*weight_squares } else { 1.0 };
+                (one_sided(k)*power / (count*scale)) as Float
+            })
+            .collect::<Vec<_>>();
+
+        let mut results = BTreeMap::new();
+        results.insert("frequencies".to_string(), ndarray::arr1(&frequencies).into_dyn());
+        results.insert("amplitudes".to_string(), ndarray::arr1(&amplitudes).into_dyn());
+        results.insert("power_density".to_string(), ndarray::arr1(&power_density).into_dyn());
+        results.insert(
+            "segments".to_string(),
+            ndarray::arr1(&[self.nsegments as Float]).into_dyn(),
+        );
+
+        results
+    }
+
+    fn units(&self, dataset: &str) -> Option<&'static str> {
+        match dataset {
+            "frequencies" => Some("Hz"),
+            "amplitudes" => Some(self.target.units()),
+            "power_density" => Some(self.target.density_units()),
+            _ => None,
+        }
+    }
+}
+
+/// Sums the electric and magnetic energy stored in the cells of a line.
+///
+/// Currents are half a time step ahead of the voltages of the same row, so the conserved magnetic
+/// energy pairs each current with that of the previous row. The voltages include the source and
+/// end nodes, which aren't part of the line, and the last current flows into the end node.
+pub(crate) fn stored_energy(
+    voltages: ndarray::ArrayView1<Float>,
+    currents: ndarray::ArrayView1<Float>,
+    previous_currents: ndarray::ArrayView1<Float>,
+    capacitance: &[Float],
+    inductance: &[Float],
+    delta_z: Float,
+) -> f64 {
+    let electric = capacitance.iter()
+        .zip(voltages.iter().skip(1))
+        .map(|(&cap, &volt)| (cap as f64)*(volt as f64).powi(2))
+        .sum::<f64>();
+    let magnetic = inductance.iter()
+        .zip(currents.iter().zip(&previous_currents))
+        .map(|(&ind, (&curr, &previous))| (ind as f64)*(curr as f64)*(previous as f64))
+        .sum::<f64>();
+    0.5*(delta_z as f64)*(electric + magnetic)
+}
+
+/// Describes an `EnergyProbe`.
+pub struct EnergyProbeDescriptor {
+    /// The capacitance per unit length of each interior voltage, as in the line's snapshot.
+    pub capacitance: Vec<Float>,
+    /// The inductance per unit length of each current, as in the line's snapshot.
+    pub inductance: Vec<Float>,
+    /// The number of time steps between stored measurements.
+    pub interval: usize,
+    /// The drift relative to the largest stored energy that raises a warning.
+    pub tolerance: Float,
+}
+
+impl EnergyProbeDescriptor {
+    /// Creates a descriptor from the snapshot of a line, such as the `tline` component.
+    pub fn from_snapshot(
+        line: &ComponentSnapshot,
+        interval: usize,
+        tolerance: Float,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            capacitance: line.get("capacitance")?.clone(),
+            inductance: line.get("inductance")?.clone(),
+            interval,
+            tolerance,
+        })
+    }
+}
+
+/// Checks that the energy stored on a lossless line is conserved.
+///
+/// The energy stored on the line should change only by the power flowing in through the source
+/// and out through the end, so any drift from that balance comes from the simulation itself, such
+/// as from an unstable time step or a buggy component. The balance is accumulated every time step,
+/// and a warning is raised once the drift exceeds the tolerance. The balance is exact up to
+/// rounding, but rounding makes long single precision runs drift slowly, so their tolerance
+/// should be loose.
+pub struct EnergyProbe {
+    capacitance: Vec<Float>,
+    inductance: Vec<Float>,
+    interval: usize,
+    tolerance: f64,
+    nsamples: usize,
+    previous: Option<(ndarray::Array1<Float>, ndarray::Array1<Float>)>,
+    initial_energy: Option<f64>,
+    transferred: f64,
+    peak_energy: f64,
+    max_drift: f64,
+    times: Vec<Float>,
+    energies: Vec<Float>,
+    drifts: Vec<Float>,
+}
+
+impl EnergyProbe {
+    /// Creates a new `EnergyProbe` instance.
+    #[inline]
+    pub fn new(desc: EnergyProbeDescriptor) -> Self {
+        Self {
+            capacitance: desc.capacitance,
+            inductance: desc.inductance,
+            interval: desc.interval.max(1),
+            tolerance: desc.tolerance as f64,
+            nsamples: 0,
+            previous: None,
+            initial_energy: None,
+            transferred: 0.0,
+            peak_energy: 0.0,
+            max_drift: 0.0,
+            times: Vec::new(),
+            energies: Vec::new(),
+            drifts: Vec::new(),
+        }
+    }
+}
+
+impl Probe for EnergyProbe {
+    fn record(&mut self, observation: &Observation) {
+        let delta_t = observation.sim_params.delta_t as f64;
+        let mut time = first_time(observation);
+        let voltages = observation.voltages.rows().into_iter();
+        let rows = voltages.zip(observation.currents.rows());
+
+        for (voltages, currents) in rows {
+            let previous = self.previous.replace((voltages.to_owned(), currents.to_owned()));
+            let Some((previous_voltages, previous_currents)) = previous else {
+                time += delta_t;
+                continue
+            };
+
+            let energy = stored_energy(
+                voltages,
+                currents,
+                previous_currents.view(),
+                &self.capacitance,
+                &self.inductance,
+                observation.sim_params.delta_z,
+            );
+
+            // the last current flows out of the last interior voltage, leaving the line
+            let port_power = |voltage: usize, current: usize| {
+                0.5*((voltages[voltage] + previous_voltages[voltage]) as f64)
+                    * (previous_currents[current] as f64)
+            };
+            let last = voltages.len() - 2;
+            match self.initial_energy {
+                Some(_) => {
+                    self.transferred += (port_power(0, 0) - port_power(last, last))*delta_t;
+                },
+                None => self.initial_energy = Some(energy),
+            }
+            let initial_energy = self.initial_energy.unwrap_or(energy);
+
+            let drift = energy - initial_energy - self.transferred;
+            self.peak_energy = self.peak_energy.max(energy);
+            self.max_drift = self.max_drift.max(drift.abs());
+            if drift.abs() > self.tolerance*self.peak_energy && self.peak_energy > 0.0 {
+                diagnostics::warn("EnergyProbe", "energy drift exceeded the tolerance");
+            }
+
+            self.nsamples += 1;
+            if self.nsamples == self.interval {
+                self.times.push(time as Float);
+                self.energies.push(energy as Float);
+                self.drifts.push(drift as Float);
+                self.nsamples = 0;
+            }
+            time += delta_t;
+        }
+    }
+
+    /// Gets the `times` at the end of each interval with the stored `energy` and the `drift` from
+    /// the energy balance, and the `max_drift` relative to the largest stored energy.
+    fn results(&self) -> BTreeMap<String, ndarray::ArrayD<Float>> {
+        let relative_drift = if self.peak_energy > 0.0 {
+            self.max_drift / self.peak_energy
+        } else {
+            0.0
+        };
+
+        let mut results = BTreeMap::new();
+        results.insert("times".to_string(), ndarray::arr1(&self.times).into_dyn());
+        results.insert("energy".to_string(), ndarray::arr1(&self.energies).into_dyn());
+        results.insert("drift".to_string(), ndarray::arr1(&self.drifts).into_dyn());
+        results.insert(
+            "max_drift".to_string(),
+            ndarray::arr1(&[relative_drift as Float]).into_dyn(),
+        );
+
+        results
+    }
+
+    fn units(&self, dataset: &str) -> Option<&'static str> {
+        match dataset {
+            "times" => Some("s"),
+            "energy" | "drift" => Some("J"),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the results of each probe to the `probes` group, replacing any previous results.
+pub(crate) fn write_probes(
+    file: &hdf5::File,
+    probes: &BTreeMap<String, Box<dyn Probe>>,
+) -> Result<(), Error> {
+    if file.link_exists("probes") {
+        file.unlink("probes")?;
+    }
+    let group = file.create_group("probes")?;
+
+    for (name, probe) in probes {
+        let probe_group = group.create_group(name)?;
+        for (name, values) in probe.results() {
+            let dataset = probe_group.new_dataset::<Float>()
+                .shape(values.shape())
+                .create(name.as_str())?;
+            dataset.write(&values)?;
+            if let Some(units) = probe.units(&name) {
+                units::write_string_attr(&dataset, "units", units)?;
+            }
+        }
+    }
+
+    Ok(())
+}
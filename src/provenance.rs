@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{units, ComponentSnapshot, Error, Float, SimulationParameters};
+
+/// Describes how the data of a run were produced.
+pub(crate) struct Provenance {
+    pub(crate) sim_params: SimulationParameters,
+    /// The number of cells of the line.
+    pub(crate) npoints: usize,
+    /// The simulated time at the start of the run.
+    pub(crate) start_time: Float,
+    /// The components of the solver at the start of the run, by role.
+    pub(crate) components: BTreeMap<String, ComponentSnapshot>,
+    pub(crate) started: SystemTime,
+    pub(crate) finished: SystemTime,
+}
+
+impl Provenance {
+    /// Writes the provenance to a new group of the `provenance` group of an HDF5 file, named by
+    /// the index of the run within the file.
+    pub(crate) fn write(&self, file: &hdf5::File) -> Result<(), Error> {
+        let runs = if file.link_exists("provenance") {
+            file.group("provenance")?
+        } else {
+            file.create_group("provenance")?
+        };
+        let group = runs.create_group(&runs.member_names()?.len().to_string())?;
+
+        units::write_string_attr(&group, "crate_version", env!("CARGO_PKG_VERSION"))?;
+        units::write_string_attr(&group, "run_started", &timestamp(self.started))?;
+        units::write_string_attr(&group, "run_finished", &timestamp(self.finished))?;
+
+        let SimulationParameters { delta_t, delta_z } = self.sim_params;
+        for (name, value, unit) in [
+            ("start_time", self.start_time, "s"),
+            ("time_step", delta_t, "s"),
+            ("length_step", delta_z, "m"),
+            ("line_length", (self.npoints as Float)*delta_z, "m"),
+        ] {
+            group.new_attr::<Float>()
+                .shape(hdf5::Extents::Scalar)
+                .create(name)?
+                .write_scalar(&value)?;
+            units::label_attr(&group, name, unit)?;
+        }
+        group.new_attr::<u64>()
+            .shape(hdf5::Extents::Scalar)
+            .create("npoints")?
+            .write_scalar(&(self.npoints as u64))?;
+
+        // the kind of each component is repeated as an attribute so it can be read at a glance
+        let components_group = group.create_group("components")?;
+        for (role, component) in &self.components {
+            units::write_string_attr(&group, &format!("{}_kind", role), &component.kind)?;
+            component.write(&components_group.create_group(role)?)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats a time as an ISO 8601 timestamp in UTC, to the second.
+fn timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+    let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+
+    // converts days since the epoch to a date of the proleptic Gregorian calendar, counting
+    // years from March so leap days fall at the end
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era/1460 + day_of_era/36_524 - day_of_era/146_096) / 365;
+    let day_of_year = day_of_era - (365*year_of_era + year_of_era/4 - year_of_era/100);
+    let month_index = (5*day_of_year + 2) / 153;
+    let day = day_of_year - (153*month_index + 2)/5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + 400*era + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+    )
+}
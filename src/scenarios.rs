@@ -0,0 +1,272 @@
+//! Ready-built canonical simulations, to serve as examples and as baselines for modification.
+//!
+//! Every line is lossless with a phase velocity of `PHASE_VELOCITY`, and is resolved with
+//! `POINTS_PER_WAVELENGTH` points per wavelength at the highest frequency of interest.
+
+use crate::{Error, Float, Simulation, SimulationDescriptor};
+use crate::consts::PI;
+use crate::fdtd::{components, FdtdSolver, FdtdSolverDescriptor, TransmissionLine};
+
+/// The phase velocity of every line, in meters per second.
+pub const PHASE_VELOCITY: Float = 5e7;
+/// The characteristic impedance of the feed lines, in ohms.
+pub const IMPEDANCE: Float = 50.0;
+/// The number of points per wavelength at the highest frequency of interest.
+pub const POINTS_PER_WAVELENGTH: usize = 100;
+
+/// Gets the inductance and capacitance per unit length of a lossless line with an `impedance`.
+fn line_parameters(impedance: Float) -> (Float, Float) {
+    (impedance / PHASE_VELOCITY, (impedance*PHASE_VELOCITY).recip())
+}
+
+/// Gets the number of points needed to resolve `length` at `frequency`.
+fn npoints(length: Float, frequency: Float) -> usize {
+    let wavelength = PHASE_VELOCITY / frequency;
+    ((length / wavelength) * (POINTS_PER_WAVELENGTH as Float)).ceil().max(1.0) as usize
+}
+
+/// A matched line of `length`, driven by a 1 V sine wave at `frequency` through its start.
+pub fn matched_line(
+    frequency: Float,
+    length: Float,
+) -> Result<Simulation<FdtdSolver<components::LinearLine>>, Error> {
+    let (inductance, capacitance) = line_parameters(IMPEDANCE);
+    let tline = components::LinearLine::new(components::LinearLineDescriptor {
+        npoints: npoints(length, frequency),
+        length,
+        capacitance_fn: |_| capacitance,
+        inductance_fn: |_| inductance,
+        resistance_fn: |_| 0.0,
+        conductance_fn: |_| 0.0,
+    });
+
+    Simulation::new(SimulationDescriptor {
+        sim_params: tline.calculate_simulation_parameters(2.0),
+        solver: FdtdSolver::new(FdtdSolverDescriptor {
+            tline,
+            source: Box::new(components::MatchedVSource {
+                source_fn: move |t: Float| Float::sin(2.0*PI * frequency * t),
+                inductance,
+                capacitance,
+                resistance: 0.0,
+                conductance: 0.0,
+            }),
+            terminator: Box::new(components::MatchedTerminator {
+                inductance,
+                capacitance,
+                resistance: 0.0,
+                conductance: 0.0,
+            }),
+        }),
+        init_state: None,
+    })
+}
+
+/// A quarter wave transformer matching the feed impedance to a `load_impedance` at `frequency`.
+///
+/// The line is a wavelength of feed line, the quarter wavelength transformer section, and then a
+/// wavelength of matched line with the load impedance, which acts as a resistive load. It is
+/// driven by a 1 V sine wave at `frequency`.
+pub fn quarter_wave_transformer(
+    frequency: Float,
+    load_impedance: Float,
+) -> Result<Simulation<FdtdSolver<components::LinearLine>>, Error> {
+    let wavelength = PHASE_VELOCITY / frequency;
+    let length = 2.25*wavelength;
+    let (feed_inductance, feed_capacitance) = line_parameters(IMPEDANCE);
+    let (load_inductance, load_capacitance) = line_parameters(load_impedance);
+    let (section_inductance, section_capacitance) = line_parameters(
+        Float::sqrt(IMPEDANCE*load_impedance)
+    );
+    let section = |z: Float, feed: Float, section: Float, load: Float| {
+        if z < wavelength {
+            feed
+        } else if z < 1.25*wavelength {
+            section
+        } else {
+            load
+        }
+    };
+    let tline = components::LinearLine::new(components::LinearLineDescriptor {
+        npoints: npoints(length, frequency),
+        length,
+        capacitance_fn: |z| section(z, feed_capacitance, section_capacitance, load_capacitance),
+        inductance_fn: |z| section(z, feed_inductance, section_inductance, load_inductance),
+        resistance_fn: |_| 0.0,
+        conductance_fn: |_| 0.0,
+    });
+
+    Simulation::new(SimulationDescriptor {
+        sim_params: tline.calculate_simulation_parameters(2.0),
+        solver: FdtdSolver::new(FdtdSolverDescriptor {
+            tline,
+            source: Box::new(components::MatchedVSource {
+                source_fn: move |t: Float| Float::sin(2.0*PI * frequency * t),
+                inductance: feed_inductance,
+                capacitance: feed_capacitance,
+                resistance: 0.0,
+                conductance: 0.0,
+            }),
+            terminator: Box::new(components::MatchedTerminator {
+                inductance: load_inductance,
+                capacitance: load_capacitance,
+                resistance: 0.0,
+                conductance: 0.0,
+            }),
+        }),
+        init_state: None,
+    })
+}
+
+/// A quarter wavelength shorted stub, resonating at odd multiples of `frequency`.
+///
+/// The stub is weakly coupled to a matched source through a series capacitor with a reactance of
+/// 1 kΩ at `frequency`, and the source excites it with a Gaussian pulse covering the first few
+/// resonances.
+pub fn shorted_stub_resonator(
+    frequency: Float,
+) -> Result<Simulation<FdtdSolver<components::LinearLine>>, Error> {
+    let length = PHASE_VELOCITY / (4.0*frequency);
+    let (inductance, capacitance) = line_parameters(IMPEDANCE);
+    let coupling_capacitance = (2.0*PI * frequency * 1000.0).recip();
+    let tline = components::LinearLine::new(components::LinearLineDescriptor {
+        // resolve the first three resonances
+        npoints: npoints(length, 5.0*frequency),
+        length,
+        capacitance_fn: |_| capacitance,
+        inductance_fn: |_| inductance,
+        resistance_fn: |_| 0.0,
+        conductance_fn: |_| 0.0,
+    });
+
+    let width = 0.1 / frequency;
+    let source = components::TheveninVSource::new(components::TheveninVSourceDescriptor {
+        source_fn: move |t: Float| Float::exp(-((t - 4.0*width) / width).powi(2)),
+        inductance,
+        capacitance,
+        resistance: 0.0,
+        conductance: 0.0,
+        source_resistance: IMPEDANCE,
+        source_inductance: 0.0,
+        source_capacitance: coupling_capacitance,
+    });
+
+    Simulation::new(SimulationDescriptor {
+        sim_params: tline.calculate_simulation_parameters(2.0),
+        solver: FdtdSolver::new(FdtdSolverDescriptor {
+            tline,
+            source: Box::new(source),
+            terminator: Box::new(components::ElectricWall {
+                inductance,
+                capacitance,
+                resistance: 0.0,
+                conductance: 0.0,
+            }),
+        }),
+        init_state: None,
+    })
+}
+
+/// A basic kinetic inductance traveling wave parametric amplifier, driven by a strong pump and a
+/// weak signal.
+///
+/// The line is 200 pump wavelengths long, with half of its inductance kinetic and a critical
+/// current of 0.2 A. The amplitudes are those of the source voltages.
+pub fn ki_twpa(
+    pump_frequency: Float,
+    pump_amplitude: Float,
+    signal_frequency: Float,
+    signal_amplitude: Float,
+) -> Result<Simulation<FdtdSolver<components::KiLine>>, Error> {
+    let length = 200.0*PHASE_VELOCITY / pump_frequency;
+    let (inductance, capacitance) = line_parameters(IMPEDANCE);
+    let tline = components::KiLine::new(components::KiLineDescriptor {
+        // resolve the idler and pump harmonics
+        npoints: npoints(length, 3.0*pump_frequency.max(signal_frequency)),
+        length,
+        capacitance_fn: |_| capacitance,
+        inductance_fn: |_| inductance / 2.0,
+        kinetic_inductance_fn: |_| inductance / 2.0,
+        critical_current_fn: |_| 0.2,
+        resistance_fn: |_| 0.0,
+        nonlinear_resistance_fn: |_| 0.0,
+        flux_flow_resistance_fn: |_| 0.0,
+        bias_current_fn: |_| 0.0,
+        max_iterations: 10,
+        tolerance: 1e-5,
+    })?;
+
+    Simulation::new(SimulationDescriptor {
+        sim_params: tline.calculate_simulation_parameters(2.0),
+        solver: FdtdSolver::new(FdtdSolverDescriptor {
+            tline,
+            source: Box::new(components::MatchedVSource {
+                source_fn: move |t: Float| {
+                    pump_amplitude*Float::sin(2.0*PI * pump_frequency * t)
+                        + signal_amplitude*Float::sin(2.0*PI * signal_frequency * t)
+                },
+                inductance,
+                capacitance,
+                resistance: 0.0,
+                conductance: 0.0,
+            }),
+            terminator: Box::new(components::MatchedTerminator {
+                inductance,
+                capacitance,
+                resistance: 0.0,
+                conductance: 0.0,
+            }),
+        }),
+        init_state: None,
+    })
+}
+
+/// An externally pumped traveling wave parametric amplifier, whose inductance is modulated by a
+/// pump wave rather than a propagating tone, and which is driven by a weak signal.
+///
+/// The inductance of the line is `L (1 + depth sin(ωp (t - z / v)))`, a modulation traveling
+/// along the line at its phase velocity, so the signal and idler are phase matched to it. The line
+/// is 200 pump wavelengths long, and the amplitude is that of the source voltage.
+pub fn pumped_line(
+    pump_frequency: Float,
+    modulation_depth: Float,
+    signal_frequency: Float,
+    signal_amplitude: Float,
+) -> Result<Simulation<FdtdSolver<impl TransmissionLine>>, Error> {
+    let length = 200.0*PHASE_VELOCITY / pump_frequency;
+    let (inductance, capacitance) = line_parameters(IMPEDANCE);
+    let tline = components::TimeVaryingLine::new(components::TimeVaryingLineDescriptor {
+        // resolve the idler and the modulation
+        npoints: npoints(length, pump_frequency.max(signal_frequency)),
+        length,
+        capacitance_fn: move |_, _| capacitance,
+        inductance_fn: move |z, t| {
+            let phase = 2.0*PI * pump_frequency * (t - z/PHASE_VELOCITY);
+            inductance * (1.0 + modulation_depth*Float::sin(phase))
+        },
+        resistance_fn: |_| 0.0,
+        conductance_fn: |_| 0.0,
+    });
+
+    Simulation::new(SimulationDescriptor {
+        // the modulation raises the phase velocity where it lowers the inductance
+        sim_params: tline.calculate_simulation_parameters(2.0),
+        solver: FdtdSolver::new(FdtdSolverDescriptor {
+            tline,
+            source: Box::new(components::MatchedVSource {
+                source_fn: move |t: Float| signal_amplitude*Float::sin(2.0*PI * signal_frequency * t),
+                inductance,
+                capacitance,
+                resistance: 0.0,
+                conductance: 0.0,
+            }),
+            terminator: Box::new(components::MatchedTerminator {
+                inductance,
+                capacitance,
+                resistance: 0.0,
+                conductance: 0.0,
+            }),
+        }),
+        init_state: None,
+    })
+}
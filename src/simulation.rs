@@ -1,27 +1,247 @@
 #![allow(clippy::reversed_empty_ranges)]
 
 use std::cmp::min;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use crate::{Error, Solver, ComputeDescriptor};
+use crate::{
+    ComponentSnapshot, ComputeDescriptor, ComputeOutput, Error, Float, SimulationSnapshot, Solver,
+};
+use crate::checkpoint::{Checkpoint, CheckpointSettings, Checkpointer, RunProgress, SaveOffsets};
+use crate::control::{Controller, Observation, RegisteredController};
+use crate::csv_output;
+use crate::diagnostics::{self, Warning};
+use crate::npy_output::NpyOutput;
+#[cfg(feature = "parquet")]
+use crate::parquet_output::ParquetOutput;
+use crate::probe::{self, Probe};
+use crate::provenance::Provenance;
+use crate::steady_state::{SteadyStateMonitor, SteadyStateSettings};
+use crate::units;
 
 /// Simulation specific parameters.
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SimulationParameters {
     /// The physical size of each spacial step along the transmission line.
-    pub delta_z: f32,
+    pub delta_z: Float,
     /// The length of each temperal step in the simulation.
-    pub delta_t: f32,
+    pub delta_t: Float,
+}
+
+impl SimulationParameters {
+    /// Reads the time and length steps stored in an HDF5 save.
+    pub fn from_hdf5<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = hdf5::File::open(path)?;
+        Ok(Self {
+            delta_z: file.attr("length_step")?.read_scalar::<Float>()?,
+            delta_t: file.attr("time_step")?.read_scalar::<Float>()?,
+        })
+    }
+
+    /// Gets the position along the line of a voltage, by its index into the state's voltages.
+    ///
+    /// Voltages sit at the centers of the cells, so the first, at the source node, is half a cell
+    /// before the start of the line.
+    #[inline]
+    pub fn voltage_position(&self, index: usize) -> Float {
+        ((index as Float) - 0.5)*self.delta_z
+    }
+
+    /// Gets the position along the line of a current, by its index into the state's currents.
+    ///
+    /// Currents sit on the boundaries between cells, so the first is at the start of the line.
+    #[inline]
+    pub fn current_position(&self, index: usize) -> Float {
+        (index as Float)*self.delta_z
+    }
+
+    /// Gets the index of the voltage nearest to `position`.
+    #[inline]
+    pub fn voltage_index(&self, position: Float) -> usize {
+        (position/self.delta_z + 0.5).round().max(0.0) as usize
+    }
+
+    /// Gets the index of the current nearest to `position`.
+    #[inline]
+    pub fn current_index(&self, position: Float) -> usize {
+        (position/self.delta_z).round().max(0.0) as usize
+    }
 }
 
 /// Describes the  transmission line state at the current time step.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SimulationState {
     /// The time of the last time step of the simulation.
-    pub time: f32,
+    pub time: Float,
     /// The voltages of each point along the transmission line at `time`.
-    pub voltages: ndarray::Array1<f32>,
+    ///
+    /// Voltage `k` is at `(k - 0.5) Δz`, including the source and end nodes.
+    pub voltages: ndarray::Array1<Float>,
     /// The currents of each point along the transmission line at `time`.
-    pub currents: ndarray::Array1<f32>,
+    ///
+    /// Current `n` is at `n Δz`, including the current into the end node.
+    pub currents: ndarray::Array1<Float>,
+}
+
+/// The largest differences in one quantity between two states.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct QuantityDiff {
+    /// The largest absolute difference of any point.
+    pub max_abs_error: Float,
+    /// The largest difference of any point, relative to the larger magnitude of the two values.
+    pub max_rel_error: Float,
+    /// The index of the point with the largest absolute difference.
+    pub worst_index: usize,
+}
+
+/// The differences between two states.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StateDiff {
+    /// The absolute difference in time.
+    pub time_error: Float,
+    /// The differences in voltage.
+    pub voltages: QuantityDiff,
+    /// The differences in current.
+    pub currents: QuantityDiff,
+}
+
+impl SimulationState {
+    /// Interpolates the voltages onto the positions of the currents.
+    pub fn voltages_at_currents(&self) -> ndarray::Array1<Float> {
+        self.voltages.windows(2)
+            .into_iter()
+            .map(|pair| (pair[0] + pair[1]) / 2.0)
+            .collect()
+    }
+
+    /// Interpolates the currents onto the positions of the voltages.
+    ///
+    /// The currents of the end nodes, which lie beyond the outer currents, are held at the
+    /// nearest value. States without currents give zeros.
+    pub fn currents_at_voltages(&self) -> ndarray::Array1<Float> {
+        let Some(last) = self.currents.len().checked_sub(1) else {
+            return ndarray::Array1::zeros(self.voltages.len())
+        };
+        (0..self.voltages.len())
+            .map(|k| {
+                let (before, after) = (k.saturating_sub(1).min(last), k.min(last));
+                (self.currents[before] + self.currents[after]) / 2.0
+            })
+            .collect()
+    }
+
+    /// Compares this state to `other`, point by point.
+    pub fn diff(&self, other: &SimulationState) -> Result<StateDiff, Error> {
+        Ok(StateDiff {
+            time_error: (self.time - other.time).abs(),
+            voltages: quantity_diff("Voltage", self.voltages.view(), other.voltages.view())?,
+            currents: quantity_diff("Current", self.currents.view(), other.currents.view())?,
+        })
+    }
+
+    /// Whether every value of this state is within `abs_tol + rel_tol*magnitude` of `other`.
+    ///
+    /// The magnitude is the larger of the two values being compared. States with different numbers
+    /// of points are never equal.
+    pub fn approx_eq(&self, other: &SimulationState, abs_tol: Float, rel_tol: Float) -> bool {
+        let close = |a: Float, b: Float| (a - b).abs() <= abs_tol + rel_tol*a.abs().max(b.abs());
+        self.voltages.len() == other.voltages.len()
+            && self.currents.len() == other.currents.len()
+            && close(self.time, other.time)
+            && ndarray::Zip::from(&self.voltages).and(&other.voltages).all(|&a, &b| close(a, b))
+            && ndarray::Zip::from(&self.currents).and(&other.currents).all(|&a, &b| close(a, b))
+    }
+
+    /// Reads the state at the end of a run saved to an HDF5 file, so a new simulation can continue
+    /// from it.
+    ///
+    /// The state is read from the `state` group of HDF5 saves and checkpoints. Files without one
+    /// are read from the last row of their full data, assuming the saved runs started at time
+    /// zero.
+    pub fn from_hdf5<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = hdf5::File::open(path)?;
+        if let Ok(group) = file.group("state") {
+            // checkpoints hold the time of their state at their root
+            let time = match group.attr("time") {
+                Ok(attr) => attr,
+                Err(_) => file.attr("time")?,
+            };
+            return Ok(Self {
+                time: time.read_scalar::<Float>()?,
+                voltages: group.dataset("voltages")?.read_1d::<Float>()?,
+                currents: group.dataset("currents")?.read_1d::<Float>()?,
+            })
+        }
+
+        let voltages = file.dataset("full/voltages")?;
+        let currents = file.dataset("full/currents")?;
+        let nrows = voltages.shape()[0];
+        if nrows == 0 {
+            return Err(Error::BadSnapshot("the file holds no saved time steps".to_string()))
+        }
+        Ok(Self {
+            time: (nrows as Float)*file.attr("time_step")?.read_scalar::<Float>()?,
+            voltages: voltages.read_slice_1d::<Float, _>(ndarray::s![nrows - 1, ..])?,
+            currents: currents.read_slice_1d::<Float, _>(ndarray::s![nrows - 1, ..])?,
+        })
+    }
+
+    /// Writes the state to an HDF5 group, with its time as an attribute.
+    pub(crate) fn write(&self, group: &hdf5::Group) -> Result<(), Error> {
+        group.new_attr::<Float>()
+            .shape(hdf5::Extents::Scalar)
+            .create("time")?
+            .write_scalar(&self.time)?;
+        units::label_attr(group, "time", "s")?;
+
+        let voltages = group.new_dataset::<Float>()
+            .shape(self.voltages.len())
+            .create("voltages")?;
+        voltages.write(&self.voltages)?;
+        units::label_dataset(&voltages, "V", "voltage", "position")?;
+        let currents = group.new_dataset::<Float>()
+            .shape(self.currents.len())
+            .create("currents")?;
+        currents.write(&self.currents)?;
+        units::label_dataset(&currents, "A", "current", "position")?;
+
+        Ok(())
+    }
+}
+
+/// Finds the largest differences between two arrays of the same quantity.
+fn quantity_diff(
+    array_name: &str,
+    values: ndarray::ArrayView1<Float>,
+    other: ndarray::ArrayView1<Float>,
+) -> Result<QuantityDiff, Error> {
+    if other.len() != values.len() {
+        return Err(Error::BadAnalysisInput {
+            array_name: array_name.to_string(),
+            input_length: other.len(),
+            expected_length: values.len(),
+        })
+    }
+
+    let mut diff = QuantityDiff {
+        max_abs_error: 0.0,
+        max_rel_error: 0.0,
+        worst_index: 0,
+    };
+    for (index, (&a, &b)) in values.iter().zip(&other).enumerate() {
+        let abs_error = (a - b).abs();
+        let magnitude = a.abs().max(b.abs());
+        if abs_error > diff.max_abs_error {
+            diff.max_abs_error = abs_error;
+            diff.worst_index = index;
+        }
+        if magnitude > 0.0 {
+            diff.max_rel_error = diff.max_rel_error.max(abs_error / magnitude);
+        }
+    }
+
+    Ok(diff)
 }
 
 /// Describes a simulation.
@@ -37,14 +257,22 @@ pub struct SimulationDescriptor<S: Solver> {
 /// Describes a simulation run.
 pub struct RunDescriptor<P: AsRef<Path>> {
     /// How long, in temperal units, the simulation should run.
-    pub time_duration: f32,
+    pub time_duration: Float,
     /// Whether or not to print information to the console.
     pub verbose: bool,
     /// What, if any, information to save to file.
     pub save_settings: Option<SaveSettings<P>>,
+    /// When, if ever, to write checkpoints.
+    pub checkpoint_settings: Option<CheckpointSettings<P>>,
+    /// When, if ever, to stop the run early once it reaches a steady state.
+    ///
+    /// Saves of runs that stop early only hold the time steps that were taken.
+    pub steady_state: Option<SteadyStateSettings>,
 }
 
 /// How data should be saved to file.
+///
+/// Any additional channels generated by the solver are saved in the `channels` group.
 #[derive(Debug)]
 pub struct SaveSettings<P: AsRef<Path>> {
     /// The path to the save file.
@@ -53,6 +281,33 @@ pub struct SaveSettings<P: AsRef<Path>> {
     pub save_type: SaveType,
     /// Whether or not to overwrite any possible saved data.
     pub overwrite: bool,
+    /// Whether or not to save the warnings raised during the run.
+    pub save_warnings: bool,
+    /// Whether or not to save the power into the line at each end, and the energy stored on the
+    /// line, at each time step.
+    ///
+    /// They are saved in the `energy` group. The stored energy is only saved for lines with
+    /// `capacitance` and `inductance` per unit length in their snapshots. Only HDF5 saves hold
+    /// them.
+    pub save_energy: bool,
+    /// Whether or not to save the voltages of full, tapped, and windowed data.
+    ///
+    /// The end points are always saved.
+    pub save_voltages: bool,
+    /// Whether or not to save the currents of full, tapped, and windowed data.
+    ///
+    /// The end points are always saved.
+    pub save_currents: bool,
+    /// Where to save the rest of the run if saving to `filename` fails.
+    pub fallback_filename: Option<P>,
+    /// The file format to save in.
+    pub format: SaveFormat,
+    /// Whether to save HDF5 files in single-writer multiple-reader mode, so other processes can
+    /// read them while the run goes.
+    ///
+    /// The datasets grow with each saved chunk instead of being sized for the whole run. Readers
+    /// must open the file in SWMR mode, and every channel must be in the first computed chunk.
+    pub swmr: bool,
 }
 
 /// Represents what data to save.
@@ -61,7 +316,53 @@ pub enum SaveType {
     /// Save voltage and current data for every point on the line.
     Full,
     /// Save voltage and current data for only the end points.
+    ///
+    /// Only the first and last columns of any additional channels are saved.
     End,
+    /// Save voltage and current data for the end points and the points nearest to each position,
+    /// in meters along the line.
+    ///
+    /// The tapped data is saved in the `taps` group, along with the positions of the tapped
+    /// voltages and currents. Only HDF5 saves hold tapped data.
+    Probes(Vec<Float>),
+    /// Save voltage and current data for the end points and every point between `start` and
+    /// `end`, in meters along the line.
+    ///
+    /// The windowed data is saved in the `window` group, along with the positions of the windowed
+    /// voltages and currents. Only HDF5 saves hold windowed data.
+    Window { start: Float, end: Float },
+}
+
+/// Represents the file format of saved data.
+#[derive(PartialEq, Debug)]
+pub enum SaveFormat {
+    /// Save to a single HDF5 file.
+    Hdf5,
+    /// Save the time series of the end points to a CSV file, with a row for each time step.
+    ///
+    /// Saving full data also writes every voltage and current to `<stem>_voltages.csv` and
+    /// `<stem>_currents.csv` next to the file. Channels, warnings, and probe results are not saved.
+    Csv,
+    /// Save the time series of the end points to a Parquet file, with a row for each time step.
+    ///
+    /// Saving full data also writes every voltage and current to `<stem>_voltages.parquet` and
+    /// `<stem>_currents.parquet` next to the file, with a row for each point and time step.
+    /// Parquet files can't be extended, so runs must overwrite them. Channels, warnings, and probe
+    /// results are not saved.
+    #[cfg(feature = "parquet")]
+    Parquet,
+    /// Save each array to a NumPy `<stem>_<name>.npy` file next to the file.
+    ///
+    /// The arrays are `time`, `start_voltages`, `start_currents`, `end_voltages`, and
+    /// `end_currents`, along with `voltages` and `currents` when saving full data. NumPy files
+    /// can't be extended, so runs must overwrite them. Channels, warnings, and probe results are
+    /// not saved.
+    Npy,
+    /// Save the arrays of `Npy` to a single uncompressed NumPy `.npz` archive.
+    ///
+    /// The arrays are packed into the archive at the end of the run, and each must be smaller
+    /// than 4 GiB.
+    Npz,
 }
 
 /// The main `struct` of the framework.
@@ -69,19 +370,34 @@ pub struct Simulation<S: Solver> {
     solver: S,
     sim_params: SimulationParameters,
     state: SimulationState,
+    controllers: Vec<RegisteredController>,
+    probes: std::collections::BTreeMap<String, Box<dyn Probe>>,
+    warnings: Vec<Warning>,
 }
 
 impl<S: Solver> Simulation<S> {
     /// Creates a new `Simulation` instance.
+    ///
+    /// Fails if the time step is too long for the line to be simulated stably. Time steps close to
+    /// the limit are warned about during runs instead.
     #[inline]
     pub fn new(desc: SimulationDescriptor<S>) -> Result<Self, Error> {
         let total_points: usize = 1 + desc.solver.npoints();
 
+        // catch unstable time steps before they blow up, with the Courant number defined as in
+        // `TransmissionLine::calculate_simulation_parameters`
+        if let Some(max_phase_velocity) = desc.solver.max_phase_velocity() {
+            let courant = desc.sim_params.delta_z / (max_phase_velocity*desc.sim_params.delta_t);
+            if courant < 1.0 {
+                return Err(Error::UnstableTimeStep { courant })
+            }
+        }
+
         // create arrays for initial data
         let state = desc.init_state.unwrap_or(SimulationState {
             time: 0.0,
-            voltages: ndarray::Array1::<f32>::zeros(total_points + 1),
-            currents: ndarray::Array1::<f32>::zeros(total_points),
+            voltages: ndarray::Array1::<Float>::zeros(total_points + 1),
+            currents: ndarray::Array1::<Float>::zeros(total_points),
         });
         if state.voltages.len() != (total_points + 1) {
             return Err(Error::BadInit {
@@ -102,193 +418,1202 @@ impl<S: Solver> Simulation<S> {
             state,
             solver: desc.solver,
             sim_params: desc.sim_params,
+            controllers: Vec::new(),
+            probes: std::collections::BTreeMap::new(),
+            warnings: Vec::new(),
         })
     }
 
+    /// Creates a new `Simulation` instance that continues from a snapshot.
+    ///
+    /// The `solver` should be built from the components of the snapshot.
+    #[inline]
+    pub fn from_snapshot(snapshot: SimulationSnapshot, solver: S) -> Result<Self, Error> {
+        Self::new(SimulationDescriptor {
+            solver,
+            sim_params: snapshot.sim_params,
+            init_state: Some(snapshot.state),
+        })
+    }
+
+    /// Registers a `Controller` to be updated every `interval` time steps during runs.
+    #[inline]
+    pub fn register_controller(&mut self, controller: Box<dyn Controller>, interval: usize) {
+        self.controllers.push(RegisteredController {
+            controller,
+            interval,
+            steps: 0,
+        });
+    }
+
+    /// Registers a `Probe` to record every time step during runs, replacing any with the same name.
+    ///
+    /// When a run is saved, the results of each probe are saved in the `probes/<name>` group.
+    #[inline]
+    pub fn register_probe(&mut self, name: &str, probe: Box<dyn Probe>) {
+        self.probes.insert(name.to_string(), probe);
+    }
+
+    /// Gets a registered `Probe` by name.
+    #[inline]
+    pub fn probe(&self, name: &str) -> Option<&dyn Probe> {
+        self.probes.get(name).map(|probe| probe.as_ref())
+    }
+
+    /// Gets the current state of the simulation.
+    #[inline]
+    pub fn state(&self) -> &SimulationState {
+        &self.state
+    }
+
+    /// Gets the parameters of the simulation.
+    #[inline]
+    pub fn sim_params(&self) -> &SimulationParameters {
+        &self.sim_params
+    }
+
+    /// Gets the warnings raised during the last run, and while stepping since.
+    #[inline]
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Captures the complete configuration and state of the simulation.
+    #[inline]
+    pub fn snapshot(&self) -> SimulationSnapshot {
+        SimulationSnapshot {
+            sim_params: self.sim_params,
+            state: self.state.clone(),
+            components: self.solver.snapshot(),
+        }
+    }
+
+    /// Advances the simulation by `nsteps` time steps without saving, in chunks that end whenever
+    /// a controller is due.
+    ///
+    /// The simulation, after the state is moved to the end of each computed chunk, is passed to
+    /// `on_chunk` along with the index of the first time step of the chunk and its voltages and
+    /// currents excluding the initial row.
+    pub(crate) fn advance<F>(&mut self, nsteps: usize, mut on_chunk: F) -> Result<(), Error>
+    where
+        F: FnMut(&mut Self, usize, ndarray::ArrayView2<Float>, ndarray::ArrayView2<Float>),
+    {
+        let total_points: usize = 1 + self.solver.npoints();
+        let store_size = min(nsteps + 1, (100_000_000 / total_points) + 1);
+
+        let mut start_index = 0;
+        while start_index < nsteps {
+            let mut niters = min(store_size - 1, nsteps - start_index);
+            for controller in &self.controllers {
+                niters = min(niters, controller.steps_remaining());
+            }
+
+            let ComputeOutput { voltages, currents, .. } = self.solver.compute(ComputeDescriptor {
+                state: &self.state,
+                sim_params: self.sim_params,
+                nsteps: niters,
+                bar: &None,
+            })?;
+            self.state.voltages.assign(&voltages.row(niters));
+            self.state.currents.assign(&currents.row(niters));
+            self.state.time += (niters as Float)*self.sim_params.delta_t;
+            on_chunk(
+                self,
+                start_index,
+                voltages.slice(ndarray::s![1..=niters, ..]),
+                currents.slice(ndarray::s![1..=niters, ..]),
+            );
+
+            start_index += niters;
+        }
+
+        Ok(())
+    }
+
+    /// Advances the simulation by a single time step.
+    ///
+    /// See `step_n`.
+    #[inline]
+    pub fn step(&mut self) -> Result<(), Error> {
+        self.step_n(1)
+    }
+
+    /// Advances the simulation by `nsteps` time steps, leaving the new state in `state`.
+    ///
+    /// Controllers and probes are updated as in a run, but nothing is saved. Warnings raised while
+    /// stepping are added to `warnings`.
+    #[inline]
+    pub fn step_n(&mut self, nsteps: usize) -> Result<(), Error> {
+        self.step_with(nsteps, |_, _, _| ())
+    }
+
+    /// Does a computational run without saving, returning the voltages and currents of each time
+    /// step.
+    ///
+    /// Each row holds a time step after the initial state, so the last row is the new `state`.
+    /// Controllers, probes, and warnings are handled as in `step_n`.
+    pub fn run_collect(
+        &mut self,
+        time_duration: Float,
+    ) -> Result<(ndarray::Array2<Float>, ndarray::Array2<Float>), Error> {
+        let nsteps = (time_duration / self.sim_params.delta_t).ceil() as usize;
+        let npoints = self.solver.npoints();
+        let mut all_voltages = ndarray::Array2::zeros((nsteps, npoints + 2));
+        let mut all_currents = ndarray::Array2::zeros((nsteps, npoints + 1));
+
+        self.step_with(nsteps, |start_index, voltages, currents| {
+            let rows = start_index..(start_index + voltages.nrows());
+            all_voltages.slice_mut(ndarray::s![rows.clone(), ..]).assign(&voltages);
+            all_currents.slice_mut(ndarray::s![rows, ..]).assign(&currents);
+        })?;
+
+        Ok((all_voltages, all_currents))
+    }
+
+    /// Advances the simulation by `nsteps` time steps, updating controllers and probes.
+    ///
+    /// The index of the first time step of each computed chunk is passed to `on_chunk`, along with
+    /// its voltages and currents excluding the initial row.
+    pub(crate) fn step_with<F>(&mut self, nsteps: usize, mut on_chunk: F) -> Result<(), Error>
+    where
+        F: FnMut(usize, ndarray::ArrayView2<Float>, ndarray::ArrayView2<Float>),
+    {
+        self.advance(nsteps, |simulation, start_index, voltages, currents| {
+            simulation.observe(voltages, currents);
+            on_chunk(start_index, voltages, currents);
+        })?;
+        self.warnings.extend(diagnostics::take_warnings());
+
+        Ok(())
+    }
+
+    /// Updates the controllers that are due and records every probe, with the voltages and
+    /// currents of a computed chunk.
+    fn observe(
+        &mut self,
+        voltages: ndarray::ArrayView2<Float>,
+        currents: ndarray::ArrayView2<Float>,
+    ) {
+        let niters = voltages.nrows();
+        for registered in &mut self.controllers {
+            registered.steps += niters;
+            if registered.steps_remaining() == 0 {
+                registered.controller.update(&Observation {
+                    state: &self.state,
+                    voltages: voltages.view(),
+                    currents: currents.view(),
+                    sim_params: &self.sim_params,
+                    elapsed: (registered.steps as Float)*self.sim_params.delta_t,
+                });
+                registered.steps = 0;
+            }
+        }
+
+        for probe in self.probes.values_mut() {
+            probe.record(&Observation {
+                state: &self.state,
+                voltages: voltages.view(),
+                currents: currents.view(),
+                sim_params: &self.sim_params,
+                elapsed: (niters as Float)*self.sim_params.delta_t,
+            });
+        }
+    }
+
     /// Does a computational run.
+    ///
+    /// If saving a chunk of data fails and a fallback file is set, the chunk and the rest of the
+    /// run are saved to the fallback file instead. Chunks are saved on a separate thread while the
+    /// next is computed.
     #[inline]
     pub fn run<P: AsRef<Path>>(
         &mut self,
         desc: RunDescriptor<P>,
+    ) -> Result<(), Error> {
+        self.run_from(desc, None)
+    }
+
+    /// Continues an interrupted run from a checkpoint written during it.
+    ///
+    /// `desc` should describe the interrupted run. The state is restored from the checkpoint, but
+    /// the solver must already match it, such as by creating the simulation with
+    /// `Simulation::from_snapshot`. HDF5 saves are continued in place, overwriting anything saved
+    /// after the checkpoint, while other formats save the rest of the run as a new run would.
+    ///
+    /// Controllers and probes aren't part of checkpoints, so simulations with any registered can't
+    /// resume, as they would continue from the wrong state.
+    pub fn resume<P: AsRef<Path>>(
+        &mut self,
+        checkpoint: &Checkpoint,
+        desc: RunDescriptor<P>,
+    ) -> Result<(), Error> {
+        if !self.controllers.is_empty() || !self.probes.is_empty() {
+            let message = "controllers and probes aren't part of checkpoints, so can't be resumed";
+            return Err(Error::BadSnapshot(message.to_string()))
+        }
+        let nsteps = (desc.time_duration / self.sim_params.delta_t).ceil() as usize;
+        if checkpoint.progress.nsteps != nsteps {
+            return Err(Error::BadSnapshot(format!(
+                "the checkpoint is of a run of {} time steps, not {}",
+                checkpoint.progress.nsteps,
+                nsteps,
+            )))
+        }
+
+        self.state = checkpoint.snapshot.state.clone();
+        self.run_from(desc, Some(&checkpoint.progress))
+    }
+
+    /// Does a computational run, continuing from `progress` if set.
+    fn run_from<P: AsRef<Path>>(
+        &mut self,
+        desc: RunDescriptor<P>,
+        progress: Option<&RunProgress>,
     ) -> Result<(), Error> {
         let nsteps = (desc.time_duration / self.sim_params.delta_t).ceil() as usize;
         let total_points: usize = 1 + self.solver.npoints();
         let store_size = min(nsteps + 1, (100_000_000 / total_points) + 1);
-        let mut full_offset = 0;
-        let mut end_offset = 0;
-
-        // optionally create file
-        if let Some(SaveSettings {
-            ref filename,
-            ref save_type,
-            overwrite,
-        }) = desc.save_settings {
-            let filename = filename.as_ref();
-            if filename.exists() && !overwrite {
-                let file = hdf5::File::append(filename)?;
-
-                let previous_end_size = file.dataset("end/voltages")?.shape()[0];
-                end_offset = previous_end_size;
-
-                // resize end datasets
-                file.dataset("end/voltages")?.resize(previous_end_size + nsteps)?;
-                file.dataset("end/currents")?.resize(previous_end_size + nsteps)?;
-                file.dataset("start/voltages")?.resize(previous_end_size + nsteps)?;
-                file.dataset("start/currents")?.resize(previous_end_size + nsteps)?;
-
-                if *save_type == SaveType::Full {
-                    if let Ok(full_group) = file.group("full") {
-                        let previous_full_size = file.dataset("full/voltages")?.shape()[0];
-                        full_offset = previous_full_size;
-                        // resize full datasets
-                        full_group.dataset("voltages")?.resize(
-                            (previous_full_size + nsteps, total_points + 1)
-                        )?;
-                        full_group.dataset("currents")?.resize(
-                            (previous_full_size + nsteps, total_points)
-                        )?;
-                    } else {
-                        // create full datasets
-                        let full_group = file.create_group("full")?;
-                        full_group.new_dataset::<f32>()
-                            .shape((hdf5::Extent::resizable(nsteps), total_points + 1))
-                            .create("voltages")?;
-                        full_group.new_dataset::<f32>()
-                            .shape((hdf5::Extent::resizable(nsteps), total_points))
-                            .create("currents")?;
-                    }
+        let first_index = progress.map_or(0, |progress| progress.completed);
+        let start_time = progress.map_or(self.state.time, |progress| progress.start_time);
+        let started = std::time::SystemTime::now();
+        // the components are described in HDF5 files, as they were when the run started
+        let components = match desc.save_settings {
+            Some(ref settings) if settings.format == SaveFormat::Hdf5 => self.solver.snapshot(),
+            _ => std::collections::BTreeMap::new(),
+        };
+        // discard any warnings raised outside of a run
+        diagnostics::take_warnings();
+
+        // optionally create file, or continue the file of a resumed run
+        let output = match desc.save_settings {
+            Some(ref settings) => {
+                let resume = progress
+                    .filter(|_| settings.format == SaveFormat::Hdf5)
+                    .and_then(|progress| progress.save_offsets.as_ref());
+                // files continued in place are indexed from the start of the run
+                let first_step = if resume.is_some() { 0 } else { first_index };
+                Some(Output::open(
+                    settings.filename.as_ref(),
+                    SaveOptions {
+                        save_type: &settings.save_type,
+                        format: &settings.format,
+                        overwrite: settings.overwrite,
+                        swmr: settings.swmr,
+                        voltages: settings.save_voltages,
+                        currents: settings.save_currents,
+                        resume,
+                        energy: settings.save_energy,
+                        line: components.get("tline"),
+                    },
+                    nsteps - first_step,
+                    first_step,
+                    start_time,
+                    total_points,
+                    &self.sim_params,
+                )?)
+            },
+            None => None,
+        };
+        // checkpoints record where the run saves in HDF5 files, so resumed runs can continue them
+        let save_offsets = output.as_ref()
+            .filter(|output| *output.options.format == SaveFormat::Hdf5)
+            .map(|output| SaveOffsets {
+                end: output.end_offset,
+                full: output.full_offset,
+                taps: output.taps_offset,
+                channels: output.channel_offsets.clone(),
+            });
+        let fallback = desc.save_settings.as_ref()
+            .and_then(|settings| settings.fallback_filename.as_ref())
+            .map(|fallback| fallback.as_ref().to_path_buf());
+        let sim_params = self.sim_params;
+
+        // setup output if verbose
+        let bar = if desc.verbose {
+            println!("# of time steps: {}", nsteps);
+            let bar = indicatif::ProgressBar::new(nsteps as u64);
+            bar.set_position(first_index as u64);
+            Some(bar)
+        } else {
+            None
+        };
+
+        let mut checkpointer = match desc.checkpoint_settings {
+            Some(ref settings) => Some(Checkpointer::new(settings)?),
+            None => None,
+        };
+        let mut monitor = desc.steady_state
+            .map(|settings| SteadyStateMonitor::new(&settings, &self.sim_params));
+
+        let saved = std::thread::scope(|scope| {
+            // chunks are written on their own thread while the next is computed, so up to two
+            // chunks are held at once
+            let (saved_sender, saved) = std::sync::mpsc::channel();
+            let (sender, writer) = match output {
+                Some(output) => {
+                    let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+                    let writer = scope.spawn(move || {
+                        write_chunks(
+                            output,
+                            receiver,
+                            saved_sender,
+                            fallback,
+                            nsteps,
+                            total_points,
+                            sim_params,
+                        )
+                    });
+                    (Some(sender), Some(writer))
+                },
+                None => (None, None),
+            };
+            let mut saved_index = first_index;
+
+            // separate calculations into sets of time steps per loop
+            let mut start_index = first_index;
+            while start_index < nsteps {
+                let mut end_index = min(start_index + store_size - 1, nsteps);
+                if let Some(ref checkpointer) = checkpointer {
+                    end_index = min(
+                        end_index,
+                        start_index.saturating_add(checkpointer.steps_remaining()),
+                    );
+                }
+                for controller in &self.controllers {
+                    end_index = min(end_index, start_index + controller.steps_remaining());
+                }
+                if let Some(ref monitor) = monitor {
+                    end_index = start_index + monitor.chunk_steps(end_index - start_index);
                 }
+                let niters = end_index - start_index;
 
-                file.close()?;
-            } else {
-                let file = hdf5::File::create(filename)?;
+                // do calculations
+                let output_data = self.solver.compute(ComputeDescriptor {
+                    state: &self.state,
+                    sim_params: self.sim_params,
+                    nsteps: niters,
+                    bar: &bar,
+                })?;
+                let (voltages, currents) = (&output_data.voltages, &output_data.currents);
 
-                // create end datasets
-                let end_group = file.create_group("end")?;
-                end_group.new_dataset::<f32>()
-                    .shape(hdf5::Extent::resizable(nsteps))
-                    .create("voltages")?;
-                end_group.new_dataset::<f32>()
-                    .shape(hdf5::Extent::resizable(nsteps))
-                    .create("currents")?;
-                let start_group = file.create_group("start")?;
-                start_group.new_dataset::<f32>()
-                    .shape(hdf5::Extent::resizable(nsteps))
-                    .create("voltages")?;
-                start_group.new_dataset::<f32>()
-                    .shape(hdf5::Extent::resizable(nsteps))
-                    .create("currents")?;
+                // update state before saving, so a failed write doesn't lose the computed chunk
+                self.state.voltages.assign(&voltages.row(niters));
+                self.state.currents.assign(&currents.row(niters));
+                self.state.time += (niters as Float)*self.sim_params.delta_t;
 
-                if *save_type == SaveType::Full {
-                    // create full datasets
-                    let full_group = file.create_group("full")?;
-                    full_group.new_dataset::<f32>()
-                        .shape((hdf5::Extent::resizable(nsteps), total_points + 1))
-                        .create("voltages")?;
-                    full_group.new_dataset::<f32>()
-                        .shape((hdf5::Extent::resizable(nsteps), total_points))
-                        .create("currents")?;
+                self.observe(
+                    voltages.slice(ndarray::s![1..=niters, ..]),
+                    currents.slice(ndarray::s![1..=niters, ..]),
+                );
+                if let Some(ref mut monitor) = monitor {
+                    monitor.record(voltages.slice(ndarray::s![1..=niters, -1]));
                 }
 
-                // save deltas as file attributes
-                let dt_attr = file.new_attr::<f32>()
-                    .shape(hdf5::Extents::Scalar)
-                    .create("time_step");
-                if let Ok(attr) = dt_attr {
-                    attr.write_scalar(&self.sim_params.delta_t)?;
+                // optionally hand the chunk to the writer, which only hangs up if saving failed
+                if let Some(ref sender) = sender {
+                    if sender.send((output_data, start_index)).is_err() {
+                        break
+                    }
                 }
-                let dz_attr = file.new_attr::<f32>()
-                    .shape(hdf5::Extents::Scalar)
-                    .create("length_step");
-                if let Ok(attr) = dz_attr {
-                    attr.write_scalar(&self.sim_params.delta_z)?;
+
+                // optionally write checkpoint, once everything before it is saved
+                if let Some(ref mut checkpointer) = checkpointer {
+                    if checkpointer.advance(niters) {
+                        while sender.is_some() && saved_index < end_index {
+                            match saved.recv() {
+                                Ok(index) => saved_index = index,
+                                // saving failed, which the next chunk finds
+                                Err(_) => break,
+                            }
+                        }
+                        checkpointer.write(&self.snapshot(), &RunProgress {
+                            nsteps,
+                            completed: end_index,
+                            start_time,
+                            save_offsets: save_offsets.clone(),
+                        })?;
+                    }
+                }
+
+                start_index = end_index;
+                if monitor.as_ref().is_some_and(SteadyStateMonitor::is_steady) {
+                    break
                 }
+            }
+
+            drop(sender);
+            match writer {
+                Some(writer) => writer.join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                    .map(Some),
+                None => Ok(None),
+            }
+        })?;
+
+        if let Some(ref bar) = bar {
+            bar.finish();
+        }
+
+        // collect warnings and optionally write them to file
+        self.warnings = diagnostics::take_warnings();
+        if let (Some((filename, warnings)), Some(ref settings)) = (saved, &desc.save_settings) {
+            self.warnings.extend(warnings);
 
+            // only HDF5 files hold provenance, warnings, and probe results
+            let hdf5 = settings.format == SaveFormat::Hdf5;
+            if hdf5 {
+                let file = hdf5::File::open_rw(&filename)?;
+                Provenance {
+                    sim_params: self.sim_params,
+                    npoints: self.solver.npoints(),
+                    start_time,
+                    components,
+                    started,
+                    finished: std::time::SystemTime::now(),
+                }.write(&file)?;
+
+                // save the final state, so later runs can continue from it
+                if file.link_exists("state") {
+                    file.unlink("state")?;
+                }
+                self.state.write(&file.create_group("state")?)?;
+                file.close()?;
+            }
+            if hdf5 && settings.save_warnings {
+                let file = hdf5::File::open_rw(&filename)?;
+                diagnostics::write_warnings(&file, &self.warnings)?;
+                file.close()?;
+            }
+
+            // write probe results
+            if hdf5 && !self.probes.is_empty() {
+                let file = hdf5::File::open_rw(&filename)?;
+                probe::write_probes(&file, &self.probes)?;
                 file.close()?;
             }
         }
 
-        // setup output if verbose
-        let bar = if desc.verbose {
-            println!("# of time steps: {}", nsteps);
-            Some(indicatif::ProgressBar::new(nsteps as u64))
-        } else {
-            None
+        Ok(())
+    }
+}
+
+/// Saves the chunks of a run as they are sent, moving to the fallback file if saving fails.
+///
+/// The end of each saved chunk is sent to `saved`. Gets the file the end of the run was saved to,
+/// and the warnings raised while saving.
+fn write_chunks(
+    mut output: Output,
+    chunks: std::sync::mpsc::Receiver<(ComputeOutput, usize)>,
+    saved: std::sync::mpsc::Sender<usize>,
+    fallback: Option<PathBuf>,
+    nsteps: usize,
+    total_points: usize,
+    sim_params: SimulationParameters,
+) -> Result<(PathBuf, Vec<Warning>), Error> {
+    for (data, start_index) in chunks {
+        let niters = data.voltages.nrows() - 1;
+        if let Err(error) = output.write_chunk(&data, start_index, niters) {
+            let fallback = match fallback {
+                Some(ref fallback) if *fallback != output.filename => fallback,
+                _ => {
+                    // finish the file, so whatever it holds stays readable
+                    let _ = output.close();
+                    return Err(error)
+                },
+            };
+            diagnostics::set_time(output.start_time + (start_index as Float)*sim_params.delta_t);
+            diagnostics::warn(
+                "Simulation",
+                "saving failed, so the rest of the run is saved to the fallback file",
+            );
+            // close the failed file, so whatever it holds stays readable
+            let _ = output.close();
+            output = Output::open(
+                fallback,
+                SaveOptions { resume: None, ..output.options },
+                nsteps - start_index,
+                start_index,
+                output.start_time,
+                total_points,
+                &sim_params,
+            )?;
+            output.write_chunk(&data, start_index, niters)?;
+        }
+        output.saved = start_index + niters - output.first_step;
+        // the run only waits for this before checkpoints, so it may have stopped listening
+        let _ = saved.send(start_index + niters);
+    }
+    output.close()?;
+
+    Ok((output.filename, diagnostics::take_warnings()))
+}
+
+/// What an `Output` saves, and how.
+#[derive(Copy, Clone)]
+struct SaveOptions<'a> {
+    save_type: &'a SaveType,
+    format: &'a SaveFormat,
+    overwrite: bool,
+    swmr: bool,
+    voltages: bool,
+    currents: bool,
+    /// Where a resumed run started saving, when continuing its file in place.
+    resume: Option<&'a SaveOffsets>,
+    energy: bool,
+    /// The snapshot of the line at the start of the run, if known.
+    line: Option<&'a ComponentSnapshot>,
+}
+
+impl<'a> SaveOptions<'a> {
+    /// Whether the voltages and currents of every point are saved, in that order.
+    fn full(&self) -> [bool; 2] {
+        let full = *self.save_type == SaveType::Full;
+        [full && self.voltages, full && self.currents]
+    }
+
+    /// Gets the columns saved of a channel `width` values wide.
+    fn channel_columns(&self, width: usize) -> Vec<usize> {
+        match *self.save_type {
+            SaveType::End if width > 1 => vec![0, width - 1],
+            SaveType::End => vec![0; width],
+            _ => (0..width).collect(),
+        }
+    }
+
+    /// Whether the voltages and currents of tapped or windowed points are saved, in that order.
+    fn tapped(&self) -> [bool; 2] {
+        [self.voltages, self.currents]
+    }
+
+    /// Gets the capacitance and inductance per unit length of each cell of the line, if the
+    /// energy stored on it is saved.
+    fn energy_coefficients(&self) -> Option<(&'a Vec<Float>, &'a Vec<Float>)> {
+        let line = self.line.filter(|_| self.energy)?;
+        Some((line.get("capacitance").ok()?, line.get("inductance").ok()?))
+    }
+}
+
+/// The file a run is being saved to.
+struct Output<'a> {
+    filename: PathBuf,
+    options: SaveOptions<'a>,
+    /// The number of time steps of the run saved in this file.
+    nsteps: usize,
+    /// The number of those time steps saved so far.
+    saved: usize,
+    /// The time step of the run this file starts at.
+    first_step: usize,
+    /// The time at the start of the run.
+    start_time: Float,
+    delta_t: Float,
+    delta_z: Float,
+    end_offset: usize,
+    full_offset: usize,
+    taps_offset: usize,
+    /// The indices of the tapped voltages and currents.
+    taps: Option<(Vec<usize>, Vec<usize>)>,
+    channel_offsets: std::collections::BTreeMap<String, usize>,
+    #[cfg(feature = "parquet")]
+    parquet: Option<ParquetOutput>,
+    npy: Option<NpyOutput>,
+    /// The open file of an HDF5 save, which is kept open until the save is closed.
+    file: Option<hdf5::File>,
+    /// The datasets of the open file written so far, by path.
+    datasets: std::collections::BTreeMap<String, hdf5::Dataset>,
+    /// Whether the open file is being written in SWMR mode yet.
+    swmr_started: bool,
+}
+
+impl<'a> Output<'a> {
+    /// Creates or extends a file to save `nsteps` time steps of a run, starting from `first_step`.
+    fn open(
+        filename: &Path,
+        options: SaveOptions<'a>,
+        nsteps: usize,
+        first_step: usize,
+        start_time: Float,
+        total_points: usize,
+        sim_params: &SimulationParameters,
+    ) -> Result<Self, Error> {
+        let mut output = Self {
+            filename: filename.to_path_buf(),
+            options,
+            nsteps,
+            saved: 0,
+            first_step,
+            start_time,
+            delta_t: sim_params.delta_t,
+            delta_z: sim_params.delta_z,
+            end_offset: 0,
+            full_offset: 0,
+            taps_offset: 0,
+            taps: None,
+            channel_offsets: std::collections::BTreeMap::new(),
+            #[cfg(feature = "parquet")]
+            parquet: None,
+            npy: None,
+            file: None,
+            datasets: std::collections::BTreeMap::new(),
+            swmr_started: false,
         };
 
-        // separate calculations into sets of time steps per loop
-        let nloops = ((nsteps-1) / (store_size-1)) + 1;
-        for i in 0..nloops {
-            let start_index = (store_size-1) * i;
-            let end_index = min((store_size-1)*(i+1), nsteps);
-            let niters = end_index - start_index;
+        if *options.format == SaveFormat::Csv {
+            csv_output::create(filename, options.full(), options.overwrite, total_points)?;
+            return Ok(output)
+        }
+        #[cfg(feature = "parquet")]
+        if *options.format == SaveFormat::Parquet {
+            output.parquet = Some(ParquetOutput::create(
+                filename,
+                options.full(),
+                options.overwrite,
+            )?);
+            return Ok(output)
+        }
+        if *options.format == SaveFormat::Npy || *options.format == SaveFormat::Npz {
+            output.npy = Some(NpyOutput::create(
+                filename,
+                options.full(),
+                *options.format == SaveFormat::Npz,
+                options.overwrite,
+                nsteps,
+                total_points,
+            )?);
+            return Ok(output)
+        }
 
-            // do calculations
-            let (voltages, currents) = self.solver.compute(ComputeDescriptor {
-                state: &self.state,
-                sim_params: self.sim_params,
-                nsteps: niters,
-                bar: &bar,
-            })?;
+        if let SaveType::Probes(ref positions) = *options.save_type {
+            output.taps = Some((
+                positions.iter()
+                    .map(|&position| sim_params.voltage_index(position).min(total_points))
+                    .collect(),
+                positions.iter()
+                    .map(|&position| sim_params.current_index(position).min(total_points - 1))
+                    .collect(),
+            ));
+        }
+        if let SaveType::Window { start, end } = *options.save_type {
+            let (start, end) = (start.min(end), start.max(end));
+            let volt_end = sim_params.voltage_index(end).min(total_points);
+            let curr_end = sim_params.current_index(end).min(total_points - 1);
+            output.taps = Some((
+                (sim_params.voltage_index(start).min(volt_end)..=volt_end).collect(),
+                (sim_params.current_index(start).min(curr_end)..=curr_end).collect(),
+            ));
+        }
 
-            // optionally write full data to file
-            if let Some(SaveSettings {
-                ref filename,
-                ref save_type,
-                ..
-            }) = desc.save_settings {
-                let file = hdf5::File::open_rw(filename)?;
+        // SWMR saves grow their datasets as chunks are saved, so nothing is allocated up front
+        let allocated = if options.swmr { 0 } else { nsteps };
+        // SWMR needs the file format of HDF5 1.10
+        let mut builder = hdf5::File::with_options();
+        if options.swmr {
+            builder.with_fapl(|fapl| fapl.libver_v110());
+        }
 
-                // save end data
-                file.dataset("end/voltages")?
-                    .write_slice(
-                        voltages.slice(ndarray::s![1..=niters, -1]).to_owned().view(),
-                        ndarray::s![(start_index+end_offset)..(end_index+end_offset)],
-                    )?;
-                file.dataset("end/currents")?
-                    .write_slice(
-                        currents.slice(ndarray::s![1..=niters, -1]).to_owned().view(),
-                        ndarray::s![(start_index+end_offset)..(end_index+end_offset)],
-                    )?;
-                file.dataset("start/voltages")?
-                    .write_slice(
-                        voltages.slice(ndarray::s![1..=niters, 0]).to_owned().view(),
-                        ndarray::s![(start_index+end_offset)..(end_index+end_offset)],
-                    )?;
-                file.dataset("start/currents")?
+        if let Some(offsets) = options.resume {
+            // the datasets were already sized or created when the run started
+            output.end_offset = offsets.end;
+            output.full_offset = offsets.full;
+            output.taps_offset = offsets.taps;
+            output.channel_offsets = offsets.channels.clone();
+            output.file = Some(builder.append(filename)?);
+        } else if filename.exists() && !options.overwrite {
+            let file = builder.append(filename)?;
+
+            let previous_end_size = file.dataset("end/voltages")?.shape()[0];
+            output.end_offset = previous_end_size;
+
+            // resize end datasets
+            file.dataset("end/voltages")?.resize(previous_end_size + allocated)?;
+            file.dataset("end/currents")?.resize(previous_end_size + allocated)?;
+            file.dataset("start/voltages")?.resize(previous_end_size + allocated)?;
+            file.dataset("start/currents")?.resize(previous_end_size + allocated)?;
+            if options.energy {
+                extend_energy_datasets(&file, options, previous_end_size + allocated)?;
+            }
+
+            if *options.save_type == SaveType::Full {
+                // resize or create full datasets
+                output.full_offset = extend_full_datasets(
+                    &file,
+                    options.full(),
+                    allocated,
+                    total_points,
+                )?;
+            }
+            if let Some((ref volt_taps, ref curr_taps)) = output.taps {
+                output.taps_offset = extend_tap_datasets(
+                    &file,
+                    taps_group(options.save_type),
+                    options.tapped(),
+                    allocated,
+                    [volt_taps, curr_taps],
+                    sim_params,
+                )?;
+            }
+
+            // channels are resized when first saved, since they may not be computed
+            if let Ok(channel_group) = file.group("channels") {
+                for name in channel_group.member_names()? {
+                    let previous_size = channel_group.dataset(&name)?.shape()[0];
+                    output.channel_offsets.insert(name, previous_size);
+                }
+            }
+
+            output.file = Some(file);
+        } else {
+            let file = builder.create(filename)?;
+
+            // create end datasets
+            for end in ["end", "start"] {
+                let end_group = file.create_group(end)?;
+                let voltages = end_group.new_dataset::<Float>()
+                    .shape(hdf5::Extent::resizable(allocated))
+                    .create("voltages")?;
+                units::label_dataset(&voltages, "V", &format!("{} voltage", end), "time")?;
+                let currents = end_group.new_dataset::<Float>()
+                    .shape(hdf5::Extent::resizable(allocated))
+                    .create("currents")?;
+                units::label_dataset(&currents, "A", &format!("{} current", end), "time")?;
+            }
+            if options.energy {
+                extend_energy_datasets(&file, options, allocated)?;
+            }
+
+            if *options.save_type == SaveType::Full {
+                // create full datasets
+                extend_full_datasets(&file, options.full(), allocated, total_points)?;
+            }
+            if let Some((ref volt_taps, ref curr_taps)) = output.taps {
+                extend_tap_datasets(
+                    &file,
+                    taps_group(options.save_type),
+                    options.tapped(),
+                    allocated,
+                    [volt_taps, curr_taps],
+                    sim_params,
+                )?;
+            }
+
+            // save deltas as file attributes
+            let dt_attr = file.new_attr::<Float>()
+                .shape(hdf5::Extents::Scalar)
+                .create("time_step");
+            if let Ok(attr) = dt_attr {
+                attr.write_scalar(&sim_params.delta_t)?;
+                units::label_attr(&file, "time_step", "s")?;
+            }
+            let dz_attr = file.new_attr::<Float>()
+                .shape(hdf5::Extents::Scalar)
+                .create("length_step");
+            if let Ok(attr) = dz_attr {
+                attr.write_scalar(&sim_params.delta_z)?;
+                units::label_attr(&file, "length_step", "m")?;
+            }
+
+            output.file = Some(file);
+        }
+
+        Ok(output)
+    }
+
+    /// Saves the `niters` time steps of a computed chunk that starts at the run's `start_index`.
+    fn write_chunk(
+        &mut self,
+        data: &ComputeOutput,
+        start_index: usize,
+        niters: usize,
+    ) -> Result<(), Error> {
+        if *self.options.format == SaveFormat::Csv {
+            return csv_output::write_chunk(
+                &self.filename,
+                data,
+                niters,
+                self.options.full(),
+                self.start_time + (start_index as Float)*self.delta_t,
+                self.delta_t,
+            )
+        }
+        #[cfg(feature = "parquet")]
+        if let Some(ref mut parquet) = self.parquet {
+            return parquet.write_chunk(
+                data,
+                niters,
+                self.start_time + (start_index as Float)*self.delta_t,
+                self.delta_t,
+            )
+        }
+        if let Some(ref mut npy) = self.npy {
+            return npy.write_chunk(
+                data,
+                niters,
+                self.start_time + (start_index as Float)*self.delta_t,
+                self.delta_t,
+            )
+        }
+
+        let ComputeOutput { ref voltages, ref currents, ref channels, ref channel_units } = *data;
+        let start = start_index - self.first_step;
+        let end = start + niters;
+        let (end_offset, full_offset) = (self.end_offset, self.full_offset);
+        let file = self.file.as_ref().expect("HDF5 saves keep their file open");
+        let datasets = &mut self.datasets;
+        let options = self.options;
+        let swmr = options.swmr;
+        let allocated = if swmr { 0 } else { self.nsteps };
+
+        // save end data
+        cached_dataset(datasets, file, "end/voltages", swmr.then_some(end+end_offset))?
+            .write_slice(
+                voltages.slice(ndarray::s![1..=niters, -1]).to_owned().view(),
+                ndarray::s![(start+end_offset)..(end+end_offset)],
+            )?;
+        cached_dataset(datasets, file, "end/currents", swmr.then_some(end+end_offset))?
+            .write_slice(
+                currents.slice(ndarray::s![1..=niters, -1]).to_owned().view(),
+                ndarray::s![(start+end_offset)..(end+end_offset)],
+            )?;
+        cached_dataset(datasets, file, "start/voltages", swmr.then_some(end+end_offset))?
+            .write_slice(
+                voltages.slice(ndarray::s![1..=niters, 0]).to_owned().view(),
+                ndarray::s![(start+end_offset)..(end+end_offset)],
+            )?;
+        cached_dataset(datasets, file, "start/currents", swmr.then_some(end+end_offset))?
+            .write_slice(
+                currents.slice(ndarray::s![1..=niters, 0]).to_owned().view(),
+                ndarray::s![(start+end_offset)..(end+end_offset)],
+            )?;
+
+        // optionally save the power into each end and the energy stored on the line
+        if options.energy {
+            let last = voltages.ncols() - 1;
+            let rows = 1..=niters;
+            let power = |volt_index: usize, curr_index: usize| {
+                rows.clone()
+                    .map(|row| voltages[[row, volt_index]]*currents[[row, curr_index]])
+                    .collect::<ndarray::Array1<Float>>()
+            };
+            let mut values = vec![
+                ("energy/source_power", power(0, 0)),
+                // power flowing out of the line into the end node
+                ("energy/load_power", power(last, last - 1)),
+            ];
+            if let Some((capacitance, inductance)) = options.energy_coefficients() {
+                let stored = rows.clone()
+                    .map(|row| {
+                        probe::stored_energy(
+                            voltages.row(row),
+                            currents.row(row),
+                            currents.row(row - 1),
+                            capacitance,
+                            inductance,
+                            self.delta_z,
+                        ) as Float
+                    })
+                    .collect();
+                values.push(("energy/stored", stored));
+            }
+
+            for (path, values) in values {
+                cached_dataset(datasets, file, path, swmr.then_some(end+end_offset))?
+                    .write_slice(values.view(), ndarray::s![(start+end_offset)..(end+end_offset)])?;
+            }
+        }
+
+        // optionally save full data
+        let quantities = [("voltages", voltages), ("currents", currents)];
+        for ((quantity, values), saved) in quantities.into_iter().zip(options.full()) {
+            if !saved {
+                continue
+            }
+            let path = format!("full/{}", quantity);
+            cached_dataset(datasets, file, &path, swmr.then_some(end+full_offset))?
+                .write_slice(
+                    values.slice(ndarray::s![1..=niters, ..]),
+                    ndarray::s![(start+full_offset)..(end+full_offset), ..],
+                )?;
+        }
+
+        // optionally save tapped data
+        if let Some((ref volt_taps, ref curr_taps)) = self.taps {
+            let offset = self.taps_offset;
+            let quantities = [("voltages", voltages, volt_taps), ("currents", currents, curr_taps)];
+            for ((quantity, values, taps), saved) in quantities.into_iter().zip(options.tapped()) {
+                if !saved {
+                    continue
+                }
+                let tapped = values.slice(ndarray::s![1..=niters, ..])
+                    .select(ndarray::Axis(1), taps);
+                let path = format!("{}/{}", taps_group(options.save_type), quantity);
+                cached_dataset(datasets, file, &path, swmr.then_some(end+offset))?
                     .write_slice(
-                        currents.slice(ndarray::s![1..=niters, 0]).to_owned().view(),
-                        ndarray::s![(start_index+end_offset)..(end_index+end_offset)],
+                        tapped.view(),
+                        ndarray::s![(start+offset)..(end+offset), ..],
                     )?;
+            }
+        }
 
-                // optionally save full data
-                if *save_type == SaveType::Full {
-                    // save full data
-                    file.dataset("full/voltages")?
-                        .write_slice(
-                            voltages.slice(ndarray::s![1..=niters, ..]),
-                            ndarray::s![(start_index+full_offset)..(end_index+full_offset), ..],
-                        )?;
-                    file.dataset("full/currents")?
-                        .write_slice(
-                            currents.slice(ndarray::s![1..=niters, ..]),
-                            ndarray::s![(start_index+full_offset)..(end_index+full_offset), ..],
-                        )?;
+        // save any additional channels, extending those of a previous run
+        for (name, channel) in channels {
+            let path = format!("channels/{}", name);
+            let columns = options.channel_columns(channel.ncols());
+            if !datasets.contains_key(&path) {
+                // resumed runs sized or created their channels before they were interrupted,
+                // unless the channel first appeared after the checkpoint
+                let resumed = options.resume.is_some() && file.group("channels")
+                    .is_ok_and(|channel_group| channel_group.link_exists(name));
+                match self.channel_offsets.get(name) {
+                    _ if resumed => {},
+                    Some(&offset) => {
+                        file.dataset(&path)?.resize((offset + allocated, columns.len()))?;
+                    },
+                    None => {
+                        let channel_group = match file.group("channels") {
+                            Ok(group) => group,
+                            Err(_) => file.create_group("channels")?,
+                        };
+                        let dataset = channel_group.new_dataset::<Float>()
+                            .shape((hdf5::Extent::resizable(allocated), columns.len()))
+                            .create(name.as_str())?;
+                        let units = channel_units.get(name).copied().unwrap_or_default();
+                        units::label_dataset(&dataset, units, name, "time,index")?;
+                        self.channel_offsets.insert(name.clone(), 0);
+                    },
                 }
-
-                file.close()?;
             }
+            let offset = self.channel_offsets.get(name).copied().unwrap_or(0);
+            let saved = channel.slice(ndarray::s![1..=niters, ..])
+                .select(ndarray::Axis(1), &columns);
+            cached_dataset(datasets, file, &path, swmr.then_some(end+offset))?
+                .write_slice(
+                    saved.view(),
+                    ndarray::s![(start+offset)..(end+offset), ..],
+                )?;
+        }
 
-            // update state
-            self.state.voltages.assign(&voltages.row(niters));
-            self.state.currents.assign(&currents.row(niters));
-            self.state.time += (niters as f32)*self.sim_params.delta_t;
+        // objects can't be created in SWMR mode, so it starts once the first chunk has created
+        // the channels
+        if swmr && !self.swmr_started {
+            start_swmr_write(file)?;
+            self.swmr_started = true;
         }
 
-        if let Some(ref bar) = bar {
-            bar.finish();
+        // flush each chunk, so the file is complete up to the last saved chunk
+        file.flush()?;
+
+        Ok(())
+    }
+
+    /// Finishes and closes the files of the save.
+    ///
+    /// Saves of runs that stopped early are cut down to the time steps that were saved.
+    fn close(&mut self) -> Result<(), Error> {
+        if let Some(file) = self.file.take() {
+            // every written dataset had rows allocated for the whole run, unless saving in SWMR
+            let unused = if self.options.swmr { 0 } else { self.nsteps - self.saved };
+            if unused > 0 {
+                for dataset in self.datasets.values() {
+                    let mut shape = dataset.shape();
+                    shape[0] = shape[0].saturating_sub(unused);
+                    dataset.resize(shape)?;
+                }
+            }
+            self.datasets.clear();
+            file.close()?;
+        }
+        #[cfg(feature = "parquet")]
+        if let Some(parquet) = self.parquet.take() {
+            parquet.close()?;
+        }
+        if let Some(npy) = self.npy.take() {
+            npy.close(self.saved)?;
         }
 
         Ok(())
     }
 }
+
+/// Gets a dataset of an open file, only opening it the first time, and grows it to at least
+/// `rows` rows if set.
+fn cached_dataset<'d>(
+    datasets: &'d mut std::collections::BTreeMap<String, hdf5::Dataset>,
+    file: &hdf5::File,
+    path: &str,
+    rows: Option<usize>,
+) -> Result<&'d hdf5::Dataset, Error> {
+    if !datasets.contains_key(path) {
+        datasets.insert(path.to_string(), file.dataset(path)?);
+    }
+    let dataset = &datasets[path];
+
+    if let Some(rows) = rows {
+        let mut shape = dataset.shape();
+        if shape[0] < rows {
+            shape[0] = rows;
+            dataset.resize(shape)?;
+        }
+    }
+    Ok(dataset)
+}
+
+/// Switches an open file to single-writer multiple-reader mode.
+fn start_swmr_write(file: &hdf5::File) -> Result<(), Error> {
+    // hdf5 doesn't wrap SWMR, so the call holds its lock like its own calls do
+    hdf5::sync::sync(|| {
+        hdf5::h5check(unsafe { hdf5_sys::h5f::H5Fstart_swmr_write(file.id()) })
+    })?;
+    Ok(())
+}
+
+/// Resizes or creates the datasets for saving tapped points in `group` to hold `nsteps` more
+/// rows, along with their positions, and gets the number of rows they held before.
+///
+/// Only the voltages and currents set in `quantities` are saved. Datasets added to an existing
+/// group are padded to line up with the rest.
+fn extend_tap_datasets(
+    file: &hdf5::File,
+    group: &str,
+    quantities: [bool; 2],
+    nsteps: usize,
+    taps: [&[usize]; 2],
+    sim_params: &SimulationParameters,
+) -> Result<usize, Error> {
+    let taps_group = match file.group(group) {
+        Ok(taps_group) => taps_group,
+        Err(_) => {
+            let taps_group = file.create_group(group)?;
+            let volt_positions = taps[0].iter()
+                .map(|&index| sim_params.voltage_position(index))
+                .collect::<ndarray::Array1<Float>>();
+            let positions = taps_group.new_dataset::<Float>()
+                .shape(taps[0].len())
+                .create("voltage_positions")?;
+            positions.write(&volt_positions)?;
+            units::label_dataset(&positions, "m", "tapped voltage position", "tap")?;
+            let curr_positions = taps[1].iter()
+                .map(|&index| sim_params.current_position(index))
+                .collect::<ndarray::Array1<Float>>();
+            let positions = taps_group.new_dataset::<Float>()
+                .shape(taps[1].len())
+                .create("current_positions")?;
+            positions.write(&curr_positions)?;
+            units::label_dataset(&positions, "m", "tapped current position", "tap")?;
+            taps_group
+        },
+    };
+
+    let previous_size = previous_rows(&taps_group)?;
+    let labels = [("voltages", "V", "tapped voltage"), ("currents", "A", "tapped current")];
+    for (((name, unit, long_name), saved), taps) in labels.into_iter().zip(quantities).zip(taps) {
+        if saved {
+            let shape = (previous_size + nsteps, taps.len());
+            extend_dataset(&taps_group, name, shape, [unit, long_name, "time,tap"])?;
+        }
+    }
+
+    Ok(previous_size)
+}
+
+/// Resizes or creates the datasets of the `energy` group to hold `rows` rows in total.
+fn extend_energy_datasets(
+    file: &hdf5::File,
+    options: SaveOptions,
+    rows: usize,
+) -> Result<(), Error> {
+    let energy_group = match file.group("energy") {
+        Ok(energy_group) => energy_group,
+        Err(_) => file.create_group("energy")?,
+    };
+
+    let mut labels = vec![
+        ("source_power", "W", "power into the line from the source"),
+        ("load_power", "W", "power out of the line into the load"),
+    ];
+    if options.energy_coefficients().is_some() {
+        labels.push(("stored", "J", "energy stored on the line"));
+    }
+    for (name, unit, long_name) in labels {
+        if energy_group.link_exists(name) {
+            energy_group.dataset(name)?.resize(rows)?;
+        } else {
+            let dataset = energy_group.new_dataset::<Float>()
+                .shape(hdf5::Extent::resizable(rows))
+                .create(name)?;
+            units::label_dataset(&dataset, unit, long_name, "time")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Gets the group that tapped points are saved in.
+fn taps_group(save_type: &SaveType) -> &'static str {
+    match *save_type {
+        SaveType::Window { .. } => "window",
+        _ => "taps",
+    }
+}
+
+/// Resizes or creates the datasets for saving every point along the line to hold `nsteps` more
+/// rows, and gets the number of rows they held before.
+///
+/// Only the voltages and currents set in `quantities` are saved. Datasets added to an existing
+/// group are padded to line up with the rest.
+fn extend_full_datasets(
+    file: &hdf5::File,
+    quantities: [bool; 2],
+    nsteps: usize,
+    total_points: usize,
+) -> Result<usize, Error> {
+    let full_group = match file.group("full") {
+        Ok(full_group) => full_group,
+        Err(_) => file.create_group("full")?,
+    };
+
+    let previous_size = previous_rows(&full_group)?;
+    let labels = [
+        ("voltages", "V", "voltage", total_points + 1),
+        ("currents", "A", "current", total_points),
+    ];
+    for ((name, unit, long_name, width), saved) in labels.into_iter().zip(quantities) {
+        if saved {
+            let shape = (previous_size + nsteps, width);
+            extend_dataset(&full_group, name, shape, [unit, long_name, "time,position"])?;
+        }
+    }
+
+    Ok(previous_size)
+}
+
+/// Gets the number of rows of the saved voltages or currents of a group, or zero if it has
+/// neither.
+fn previous_rows(group: &hdf5::Group) -> Result<usize, Error> {
+    for name in ["voltages", "currents"] {
+        if group.link_exists(name) {
+            return Ok(group.dataset(name)?.shape()[0])
+        }
+    }
+
+    Ok(0)
+}
+
+/// Resizes a resizable dataset of time steps to `shape`, creating it with the units, long name,
+/// and axes of `label` if it doesn't exist.
+fn extend_dataset(
+    group: &hdf5::Group,
+    name: &str,
+    shape: (usize, usize),
+    label: [&str; 3],
+) -> Result<(), Error> {
+    if group.link_exists(name) {
+        group.dataset(name)?.resize(shape)?;
+    } else {
+        let dataset = group.new_dataset::<Float>()
+            .shape((hdf5::Extent::resizable(shape.0), shape.1))
+            .create(name)?;
+        let [unit, long_name, axes] = label;
+        units::label_dataset(&dataset, unit, long_name, axes)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+
+use hdf5::types::VarLenUnicode;
+
+use crate::{units, Error, Float, SimulationParameters, SimulationState};
+
+/// The archived configuration of a single component.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComponentSnapshot {
+    /// A name identifying the kind of component.
+    pub kind: String,
+    /// The named scalar or per-cell parameters of the component.
+    pub parameters: BTreeMap<String, Vec<Float>>,
+}
+
+impl ComponentSnapshot {
+    /// Creates a new `ComponentSnapshot` with no parameters.
+    #[inline]
+    pub fn new(kind: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            parameters: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a named parameter to the snapshot.
+    #[inline]
+    pub fn with(mut self, name: &str, values: Vec<Float>) -> Self {
+        self.parameters.insert(name.to_string(), values);
+        self
+    }
+
+    /// Adds the parameters of a nested component, with the names prefixed by `<prefix>_`.
+    ///
+    /// The kind of the nested component is not captured.
+    pub fn with_nested(mut self, prefix: &str, nested: ComponentSnapshot) -> Self {
+        for (name, values) in nested.parameters {
+            self.parameters.insert(format!("{}_{}", prefix, name), values);
+        }
+        self
+    }
+
+    /// Gets the snapshot of a component nested under `prefix`, which is of the given kind.
+    pub fn nested(&self, prefix: &str, kind: &str) -> Self {
+        let prefix = format!("{}_", prefix);
+        let mut nested = Self::new(kind);
+        for (name, values) in &self.parameters {
+            if let Some(name) = name.strip_prefix(&prefix) {
+                nested.parameters.insert(name.to_string(), values.clone());
+            }
+        }
+        nested
+    }
+
+    /// Checks that the snapshot describes a component of the expected kind.
+    pub fn expect_kind(&self, kind: &str) -> Result<(), Error> {
+        if self.kind != kind {
+            return Err(Error::BadSnapshot(
+                format!("expected a {} but found a {}", kind, self.kind)
+            ))
+        }
+        Ok(())
+    }
+
+    /// Gets a per-cell parameter.
+    pub fn get(&self, name: &str) -> Result<&Vec<Float>, Error> {
+        self.parameters.get(name)
+            .ok_or_else(|| Error::BadSnapshot(format!("{} is missing {}", self.kind, name)))
+    }
+
+    /// Gets a scalar parameter.
+    pub fn get_scalar(&self, name: &str) -> Result<Float, Error> {
+        self.get(name)?
+            .first()
+            .copied()
+            .ok_or_else(|| Error::BadSnapshot(format!("{} has an empty {}", self.kind, name)))
+    }
+
+    /// Writes the snapshot to an HDF5 group.
+    pub fn write(&self, group: &hdf5::Group) -> Result<(), Error> {
+        let kind = self.kind.parse::<VarLenUnicode>()
+            .map_err(|e| Error::BadSnapshot(e.to_string()))?;
+        group.new_attr::<VarLenUnicode>()
+            .shape(hdf5::Extents::Scalar)
+            .create("kind")?
+            .write_scalar(&kind)?;
+
+        for (name, values) in &self.parameters {
+            group.new_dataset::<Float>()
+                .shape(values.len())
+                .create(name.as_str())?
+                .write_raw(values.as_slice())?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a snapshot from an HDF5 group.
+    pub fn read(group: &hdf5::Group) -> Result<Self, Error> {
+        let mut snapshot = Self::new(group.attr("kind")?.read_scalar::<VarLenUnicode>()?.as_str());
+        for name in group.member_names()? {
+            let values = group.dataset(&name)?.read_raw::<Float>()?;
+            snapshot.parameters.insert(name, values);
+        }
+
+        Ok(snapshot)
+    }
+}
+
+/// The complete archived configuration and state of a simulation.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SimulationSnapshot {
+    /// The parameters of the simulation.
+    pub sim_params: SimulationParameters,
+    /// The state of the simulation.
+    pub state: SimulationState,
+    /// The components of the solver, by role.
+    pub components: BTreeMap<String, ComponentSnapshot>,
+}
+
+impl SimulationSnapshot {
+    /// Writes the snapshot to an HDF5 group.
+    pub fn write(&self, group: &hdf5::Group) -> Result<(), Error> {
+        write_scalar_attr(group, "time_step", self.sim_params.delta_t)?;
+        units::label_attr(group, "time_step", "s")?;
+        write_scalar_attr(group, "length_step", self.sim_params.delta_z)?;
+        units::label_attr(group, "length_step", "m")?;
+        write_scalar_attr(group, "time", self.state.time)?;
+        units::label_attr(group, "time", "s")?;
+
+        let state_group = group.create_group("state")?;
+        let voltages = state_group.new_dataset::<Float>()
+            .shape(self.state.voltages.len())
+            .create("voltages")?;
+        voltages.write(&self.state.voltages)?;
+        units::label_dataset(&voltages, "V", "voltage", "position")?;
+        let currents = state_group.new_dataset::<Float>()
+            .shape(self.state.currents.len())
+            .create("currents")?;
+        currents.write(&self.state.currents)?;
+        units::label_dataset(&currents, "A", "current", "position")?;
+
+        let components_group = group.create_group("components")?;
+        for (role, component) in &self.components {
+            component.write(&components_group.create_group(role)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a snapshot from an HDF5 group.
+    pub fn read(group: &hdf5::Group) -> Result<Self, Error> {
+        let state_group = group.group("state")?;
+        let components_group = group.group("components")?;
+
+        let mut components = BTreeMap::new();
+        for role in components_group.member_names()? {
+            let component = ComponentSnapshot::read(&components_group.group(&role)?)?;
+            components.insert(role, component);
+        }
+
+        Ok(Self {
+            sim_params: SimulationParameters {
+                delta_t: group.attr("time_step")?.read_scalar::<Float>()?,
+                delta_z: group.attr("length_step")?.read_scalar::<Float>()?,
+            },
+            state: SimulationState {
+                time: group.attr("time")?.read_scalar::<Float>()?,
+                voltages: state_group.dataset("voltages")?.read_1d::<Float>()?,
+                currents: state_group.dataset("currents")?.read_1d::<Float>()?,
+            },
+            components,
+        })
+    }
+
+    /// Gets the snapshot of the component with the given role.
+    pub fn component(&self, role: &str) -> Result<&ComponentSnapshot, Error> {
+        self.components.get(role)
+            .ok_or_else(|| Error::BadSnapshot(format!("no {} component", role)))
+    }
+}
+
+fn write_scalar_attr(group: &hdf5::Group, name: &str, value: Float) -> Result<(), Error> {
+    group.new_attr::<Float>()
+        .shape(hdf5::Extents::Scalar)
+        .create(name)?
+        .write_scalar(&value)?;
+    Ok(())
+}
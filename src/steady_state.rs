@@ -0,0 +1,91 @@
+use crate::{Float, SimulationParameters};
+
+/// When to stop a run early, once the end port voltage has settled into a steady state.
+///
+/// Each period of the end voltage is compared to the one before, and the run stops at the end of a
+/// period once enough periods in a row meet the tolerance. Periods with no voltage at the end,
+/// such as before the wave arrives, never count as settled. Periods are rounded to a whole number
+/// of time steps, so the tolerance shouldn't be much tighter than the rounding allows.
+#[derive(Copy, Clone, Debug)]
+pub struct SteadyStateSettings {
+    /// The period of the source, which the end voltage repeats with once settled.
+    pub period: Float,
+    /// The RMS change of the end voltage between consecutive periods, relative to its RMS, that
+    /// counts as settled.
+    pub tolerance: Float,
+    /// How many consecutive periods must be settled before the run stops.
+    pub periods: usize,
+}
+
+/// Watches the end port voltage of a run for a steady state.
+pub(crate) struct SteadyStateMonitor {
+    period_steps: usize,
+    tolerance: Float,
+    required: usize,
+    previous: Vec<Float>,
+    current: Vec<Float>,
+    settled: usize,
+}
+
+impl SteadyStateMonitor {
+    /// Creates a new `SteadyStateMonitor` instance.
+    pub(crate) fn new(settings: &SteadyStateSettings, sim_params: &SimulationParameters) -> Self {
+        let period_steps = (settings.period / sim_params.delta_t).round().max(1.0) as usize;
+        Self {
+            period_steps,
+            tolerance: settings.tolerance,
+            required: settings.periods.max(1),
+            previous: Vec::with_capacity(period_steps),
+            current: Vec::with_capacity(period_steps),
+            settled: 0,
+        }
+    }
+
+    /// Shortens a chunk of `steps` time steps to end on the last period boundary within it, if
+    /// any, so the run can stop there.
+    #[inline]
+    pub(crate) fn chunk_steps(&self, steps: usize) -> usize {
+        let remaining = self.period_steps - self.current.len();
+        if steps < remaining {
+            steps
+        } else {
+            remaining + (steps - remaining) / self.period_steps * self.period_steps
+        }
+    }
+
+    /// Records the end voltages of each time step of a computed chunk.
+    ///
+    /// Once steady, later periods are ignored.
+    pub(crate) fn record(&mut self, end_voltages: ndarray::ArrayView1<Float>) {
+        for &voltage in end_voltages {
+            if self.is_steady() {
+                return
+            }
+            self.current.push(voltage);
+            if self.current.len() < self.period_steps {
+                continue
+            }
+
+            if self.previous.len() == self.period_steps {
+                let (change, total) = self.current.iter()
+                    .zip(&self.previous)
+                    .fold((0.0, 0.0), |(change, total), (&current, &previous)| {
+                        (change + (current - previous).powi(2), total + current*current)
+                    });
+                if total > 0.0 && change <= self.tolerance*self.tolerance*total {
+                    self.settled += 1;
+                } else {
+                    self.settled = 0;
+                }
+            }
+            std::mem::swap(&mut self.previous, &mut self.current);
+            self.current.clear();
+        }
+    }
+
+    /// Whether enough consecutive periods have settled for the run to stop.
+    #[inline]
+    pub(crate) fn is_steady(&self) -> bool {
+        self.settled >= self.required
+    }
+}
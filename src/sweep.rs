@@ -0,0 +1,83 @@
+use num_complex::Complex;
+
+use crate::{Error, Float, Parameter, Simulation, Solver};
+use crate::analysis::spectrum::phasor;
+
+/// Describes a frequency sweep.
+///
+/// The source of the simulation should read its frequency from `frequency`, for example with a
+/// source function `move |t| Float::sin(2.0*PI * frequency.get() * t)`.
+pub struct SweepDescriptor {
+    /// The frequencies to measure, in order.
+    pub frequencies: Vec<Float>,
+    /// The parameter the source reads its frequency from.
+    pub frequency: Parameter,
+    /// How long to run at each frequency before measuring, to reach a steady state.
+    pub settle_duration: Float,
+    /// How many periods to measure at each frequency.
+    pub measure_periods: usize,
+    /// The impedance the incident and outgoing waves are referenced to.
+    pub reference_impedance: Float,
+}
+
+/// The measured response of a line to a frequency sweep.
+#[derive(Clone, Debug)]
+pub struct FrequencyResponse {
+    /// The measured frequencies.
+    pub frequencies: Vec<Float>,
+    /// The wave leaving the end of the line relative to the wave incident on its start.
+    pub transmission: Vec<Complex<Float>>,
+    /// The wave reflected from the start of the line relative to the incident wave.
+    pub reflection: Vec<Complex<Float>>,
+}
+
+impl FrequencyResponse {
+    /// Gets the magnitude of the transmission at each frequency, in decibels.
+    pub fn transmission_db(&self) -> Vec<Float> {
+        self.transmission.iter().map(|t| 20.0 * t.norm().log10()).collect()
+    }
+
+    /// Gets the phase of the transmission at each frequency, in radians.
+    pub fn transmission_phase(&self) -> Vec<Float> {
+        self.transmission.iter().map(|t| t.arg()).collect()
+    }
+}
+
+impl<S: Solver> Simulation<S> {
+    /// Measures the transmission and reflection of the line at each frequency of a sweep.
+    ///
+    /// Each frequency starts from the final state of the previous one. Nothing is saved, but
+    /// controllers and probes are updated as in `step_n`.
+    pub fn sweep(&mut self, desc: SweepDescriptor) -> Result<FrequencyResponse, Error> {
+        let delta_t = self.sim_params().delta_t;
+        let mut response = FrequencyResponse {
+            frequencies: desc.frequencies.clone(),
+            transmission: Vec::with_capacity(desc.frequencies.len()),
+            reflection: Vec::with_capacity(desc.frequencies.len()),
+        };
+
+        for &frequency in &desc.frequencies {
+            desc.frequency.set(frequency);
+            self.step_with((desc.settle_duration / delta_t).ceil() as usize, |_, _, _| ())?;
+
+            // record the port voltages and currents over whole periods
+            let nsteps = ((desc.measure_periods.max(1) as Float) / (frequency * delta_t)).round() as usize;
+            let mut ports = [(); 4].map(|_| Vec::with_capacity(nsteps));
+            self.step_with(nsteps, |_, voltages, currents| {
+                ports[0].extend(voltages.column(0));
+                ports[1].extend(currents.column(0));
+                ports[2].extend(voltages.column(voltages.ncols() - 1));
+                ports[3].extend(currents.column(currents.ncols() - 1));
+            })?;
+            let [start_volt, start_curr, end_volt, end_curr] = ports
+                .map(|port| phasor(ndarray::ArrayView1::from(&port), frequency, self.sim_params()));
+
+            let z0 = desc.reference_impedance;
+            let incident = (start_volt + start_curr*z0) / 2.0;
+            response.reflection.push((start_volt - start_curr*z0) / 2.0 / incident);
+            response.transmission.push((end_volt + end_curr*z0) / 2.0 / incident);
+        }
+
+        Ok(response)
+    }
+}
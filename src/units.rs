@@ -0,0 +1,35 @@
+use hdf5::types::VarLenUnicode;
+
+use crate::Error;
+
+/// Labels a dataset with its SI units, description, and axes, following the NeXus convention.
+///
+/// `axes` names the dimensions of the dataset in order, separated by commas.
+pub(crate) fn label_dataset(
+    dataset: &hdf5::Dataset,
+    units: &str,
+    long_name: &str,
+    axes: &str,
+) -> Result<(), Error> {
+    write_string_attr(dataset, "units", units)?;
+    write_string_attr(dataset, "long_name", long_name)?;
+    write_string_attr(dataset, "axes", axes)
+}
+
+/// Labels the attribute `name` of a location with its SI units.
+///
+/// Attributes can't have attributes of their own, so the units are written to `<name>_units`.
+pub(crate) fn label_attr(location: &hdf5::Location, name: &str, units: &str) -> Result<(), Error> {
+    write_string_attr(location, &format!("{}_units", name), units)
+}
+
+/// Writes a scalar string attribute to a location.
+pub(crate) fn write_string_attr(location: &hdf5::Location, name: &str, value: &str) -> Result<(), Error> {
+    // the labels are plain ASCII, so parsing can't fail
+    let value = value.parse::<VarLenUnicode>().unwrap_or_default();
+    location.new_attr::<VarLenUnicode>()
+        .shape(hdf5::Extents::Scalar)
+        .create(name)?
+        .write_scalar(&value)?;
+    Ok(())
+}